@@ -1,144 +1,1902 @@
 use eframe::CreationContext;
-use egui::{FontData, FontDefinitions, Key};
+use egui::{Key, Modifiers};
 use reqwest::header::HeaderName;
-use twitch_message::builders::{PrivmsgBuilder, TagsBuilder};
+use twitch_message::{
+    builders::{PrivmsgBuilder, TagsBuilder},
+    messages::Privmsg,
+    Tags,
+};
+use uuid::Uuid;
+
+use crate::{
+    automod::Automod,
+    db::{self, InsertMessage, Integrity},
+    helix, notification,
+    repaint::{Idle, IdleRepaint, Pending},
+    resolver::Wakeup,
+    runtime::{
+        Action, EmoteMap, FileWatcher, GameMap, Highlighter, ImageCache, StreamCheck, StreamStatus,
+        TaskExit, UserMap,
+    },
+    state::{
+        actions_for, Autosave, Channel, ChannelSearch, MessageOpts, MessageSearch, NotifyAction,
+        NotifyEvent, Permalink, SavedState, Screen, State, Suggestions, TabOrder, Toasts, Trigger,
+        ViewState,
+    },
+    task_error::TaskErrors,
+    twitch::{self, ChannelName},
+    url_handler::LinkListener,
+    views::{
+        BadgeBrowserView, BookmarkAction, BookmarkList, BookmarksView, DiagnosticsView,
+        EmoteBrowser, EmoteBrowserView, FollowStatus, FrameStatsView, HistorySearch,
+        HistorySearchView, IdentityChip, InitialView, MainView, NotificationsView, NotifyRulesView,
+        QuickSwitch, QuickSwitcher, QuickSwitcherView, RawMessage, RawMessageView, SettingsView,
+        SplitLayout, StartView, ToastsView, TokenCheck, UserCard, UserCardAction, UserCardView,
+        UserSearch, UserSearchView,
+    },
+};
+
+mod db_repair;
+pub use db_repair::DbRepair;
+
+mod fonts;
+pub use fonts::{FontManager, SystemFont};
+
+mod frame_stats;
+pub use frame_stats::FrameStats;
+
+mod settings_export;
+pub use settings_export::{ImportMode, SettingsExport};
+
+mod startup;
+pub use startup::Startup;
+
+mod theme;
+pub use theme::{LiveShape, Palette, Theme};
+
+mod token_refresh;
+pub use token_refresh::TokenRefresh;
+
+mod watchdog;
+pub use watchdog::{Task, Watchdog};
+
+pub struct App {
+    pub state: State,
+    pub screen: Screen,
+    pub helix: helix::Client,
+    pub twitch: twitch::Client,
+    pub stream_check: StreamCheck,
+    pub cache: ImageCache,
+    pub emote_map: EmoteMap,
+    pub user_map: UserMap,
+    pub game_map: GameMap,
+    /// Our sent messages, waiting on the server to tell us their ids
+    pub last: std::collections::VecDeque<(ChannelName, PrivmsgBuilder, TagsBuilder)>,
+    pub conn: db::Connection,
+    db_repair: Option<DbRepair>,
+    pub autosave: Autosave,
+    pub crash_report: Option<std::path::PathBuf>,
+    pub theme: Theme,
+    pub fonts: FontManager,
+    pub watcher: FileWatcher,
+    pub automod: Automod,
+    pub highlighter: Highlighter,
+    /// How the chat area is split between channels
+    pub layout: SplitLayout,
+    pub token_check: TokenCheck,
+    token_refresh: TokenRefresh,
+    watchdog: Watchdog,
+    /// Errors and confirmations, shown for a bit and kept for the notification center
+    pub toasts: Toasts,
+    /// Read before the runtime started, it can't be read safely once other threads are running
+    pub local_offset: time::UtcOffset,
+    /// The ctrl+f search over the active channel
+    pub message_search: MessageSearch,
+    pending_link: Option<Permalink>,
+    links: Option<LinkListener>,
+    // the message a link in the input points at, so the history isn't asked every frame
+    quoted: Option<(Uuid, Option<db::Message>)>,
+    user_search: Option<UserSearch>,
+    pub history_search: Option<HistorySearch>,
+    user_card: Option<UserCard>,
+    pub emote_browser: Option<EmoteBrowser>,
+    pub quick_switcher: Option<QuickSwitcher>,
+    /// The ids of the bookmarked messages, so the context menu doesn't have to ask the history
+    pub bookmarked: hashbrown::HashSet<Uuid>,
+    bookmarks: Option<BookmarkList>,
+    bookmark_writer: db::BookmarkWriter,
+    raw_message: Option<RawMessage>,
+    idle: Idle,
+    repaint_pending: Pending,
+    last_input: std::time::Instant,
+    focused: bool,
+    // the task bar keeps flashing on its own, so this is only asked for once until we're focused
+    wants_attention: bool,
+    // it's asked for through the frame, which is only around during the update
+    attention_pending: bool,
+    last_housekeeping: std::time::Instant,
+    // set by the background work the runtime maps are waiting on
+    wakeup: Wakeup,
+    // what the background tasks failed at, shown as toasts
+    errors: TaskErrors,
+    native_pixels_per_point: Option<f32>,
+    pub join_search: ChannelSearch,
+    pub completer: crate::input::Completer,
+    unsent: Vec<(String, String)>,
+    stay_passive: bool,
+    pub show_rules: bool,
+    pub show_diagnostics: bool,
+    pub show_badges: bool,
+    pub startup: Startup,
+    pub show_frame_stats: bool,
+    pub show_watchlist: bool,
+    pub show_notifications: bool,
+    pub show_settings: bool,
+    pub frame_stats: FrameStats,
+    /// Where messages go instead of the active channel
+    pub send_to: Option<ChannelName>,
+    /// Why the input didn't go through, shown until the input is changed
+    pub input_error: Option<(String, String)>,
+    // tabs from joins that haven't been checked against helix yet
+    unverified: Vec<(ChannelName, std::time::Instant)>,
+    // whether each stream was live the last time we heard about it
+    live: hashbrown::HashMap<String, bool>,
+}
+
+impl App {
+    pub const USER_AGENT: &str = concat!(env!("CARGO_PKG_NAME"), "/", env!("CARGO_PKG_VERSION"));
+
+    const SHUTDOWN_TIMEOUT: std::time::Duration = std::time::Duration::from_secs(2);
+    const PART_ON_EXIT: bool = false;
+    const STATE_PATH: &str = "vohiyo.toml";
+    pub(crate) const HISTORY_PATH: &str = "history.db";
+    pub(crate) const THEME_PATH: &str = "theme.toml";
+    // this is relative to the monitor the window is on
+    const UI_SCALE: f32 = 1.5;
+    const IDLE_AFTER: std::time::Duration = std::time::Duration::from_secs(5 * 60);
+    const HOUSEKEEPING: std::time::Duration = std::time::Duration::from_secs(1);
+    // how long to wait on helix before assuming a new channel exists
+    const VERIFY_TIMEOUT: std::time::Duration = std::time::Duration::from_secs(30);
+
+    pub fn create(
+        cc: &CreationContext,
+        config: twitch::Config,
+        link: Option<Permalink>,
+        local_offset: time::UtcOffset,
+    ) -> Box<dyn eframe::App> {
+        let mut startup = Startup::begin();
+        let native_pixels_per_point = cc.integration_info.native_pixels_per_point;
+        let pixels_per_point = native_pixels_per_point.unwrap_or(1.0) * Self::UI_SCALE;
+        cc.egui_ctx.set_pixels_per_point(pixels_per_point);
+
+        let theme = Theme::load(Self::THEME_PATH).unwrap_or_default();
+        theme.apply(&cc.egui_ctx);
+        startup.phase("theme");
+
+        let mut state = SavedState::load(Self::STATE_PATH).unwrap_or_default();
+        startup.phase("saved state");
+
+        let mut fonts = FontManager::default();
+        let preferences = &state.preferences;
+        if let Err(err) = fonts.apply(
+            &cc.egui_ctx,
+            preferences.font_path.as_deref(),
+            preferences.font_size,
+        ) {
+            log!("{err}");
+        }
+        startup.phase("fonts");
+
+        let http = reqwest::ClientBuilder::new()
+            .default_headers(
+                std::iter::once((
+                    HeaderName::from_static("user-agent"),
+                    Self::USER_AGENT.parse().unwrap(),
+                ))
+                .collect(),
+            )
+            .build()
+            .expect("valid client configuration");
+
+        let idle = Idle::default();
+        let repaint_pending = Pending::default();
+        let repaint = IdleRepaint {
+            ctx: cc.egui_ctx.clone(),
+            idle: idle.clone(),
+            pending: repaint_pending.clone(),
+        };
+
+        let wakeup = Wakeup::default();
+        let errors = TaskErrors::default();
+        let helix = helix::Client::create(repaint.clone(), wakeup.clone(), errors.clone());
+        let emote_map = EmoteMap::create(
+            helix.clone(),
+            repaint.clone(),
+            wakeup.clone(),
+            errors.clone(),
+            http.clone(),
+            state.preferences.image_scale.resolve(pixels_per_point),
+        );
+        startup.phase("clients");
+
+        // the history and the bookmarks are loaded once the check is done
+        let (conn, db_repair) = DbRepair::open(Self::HISTORY_PATH);
+        let bookmarked = hashbrown::HashSet::new();
+        startup.phase("open history");
+
+        let twitch = twitch::Client::create(config, repaint.clone(), wakeup.clone());
+
+        let mut user_map = UserMap::create(helix.clone());
+
+        for channel in state.channels.iter().map(|c| &c.name) {
+            if let Err(err) = twitch.writer().join(channel) {
+                log!("cannot join {channel}: {err}");
+            }
+        }
+
+        // the watchlist is ordered by viewers, so those are needed right away too
+        let channels = state.channels.iter().map(|c| c.name.as_str());
+        user_map.prefetch(channels.chain(state.watchlist.iter().map(|name| name.as_str())));
+
+        // these show up as tabs once the join goes through
+        for favorite in &state.favorites {
+            if state.channels.iter().any(|c| &c.name == favorite) {
+                continue;
+            }
+            if let Err(err) = twitch.writer().join(favorite) {
+                log!("cannot join {favorite}: {err}");
+            }
+        }
+        startup.phase("queue joins");
+
+        let mut this = Self {
+            screen: Screen::default(),
+            stream_check: StreamCheck::create(
+                helix.clone(),
+                repaint,
+                wakeup.clone(),
+                idle.clone(),
+                state.preferences.stream_check_interval(),
+            ),
+            cache: ImageCache::new(http, cc.egui_ctx.clone(), wakeup.clone(), errors.clone()),
+            emote_map,
+            game_map: GameMap::create(helix.clone()),
+            user_map,
+            highlighter: Highlighter::new(&state.highlight_keywords),
+            layout: SplitLayout::default(),
+            token_check: TokenCheck::Checking(helix.validate_token(twitch.token())),
+            token_refresh: TokenRefresh::default(),
+            watchdog: Watchdog::default(),
+            toasts: Toasts::new(cc.egui_ctx.clone(), local_offset),
+            local_offset,
+            message_search: MessageSearch::default(),
+
+            state,
+            twitch,
+            helix,
+
+            last: std::collections::VecDeque::new(),
+
+            conn,
+            db_repair: Some(db_repair),
+            autosave: Autosave::default(),
+            crash_report: crate::crash::take_pending(),
+            theme,
+            fonts,
+            watcher: FileWatcher::create(
+                &[Self::STATE_PATH, Self::THEME_PATH, Automod::PATH],
+                cc.egui_ctx.clone(),
+                &errors,
+            ),
+            automod: Automod::load(Automod::PATH, local_offset),
+            pending_link: None,
+            links: LinkListener::bind(cc.egui_ctx.clone())
+                .map_err(|err| log!("cannot take links from other instances: {err}"))
+                .ok(),
+            quoted: None,
+            user_search: None,
+            history_search: None,
+            user_card: None,
+            emote_browser: None,
+            quick_switcher: None,
+            bookmarked,
+            bookmarks: None,
+            bookmark_writer: db::BookmarkWriter::spawn(),
+            raw_message: None,
+            idle,
+            repaint_pending,
+            last_input: std::time::Instant::now(),
+            focused: true,
+            wants_attention: false,
+            attention_pending: false,
+            last_housekeeping: std::time::Instant::now(),
+            wakeup,
+            errors,
+            native_pixels_per_point,
+            join_search: ChannelSearch::default(),
+            completer: crate::input::Completer::default(),
+            unsent: vec![],
+            stay_passive: false,
+            show_rules: false,
+            show_diagnostics: false,
+            show_badges: false,
+            startup,
+            show_frame_stats: false,
+            show_watchlist: true,
+            show_notifications: false,
+            show_settings: false,
+            frame_stats: FrameStats::default(),
+            send_to: None,
+            input_error: None,
+            unverified: vec![],
+            live: hashbrown::HashMap::new(),
+        };
+
+        if let Some(link) = link {
+            this.open_link(link);
+        }
+
+        Box::new(this)
+    }
+
+    // the identity is replaced on every (re)connect and updated by USERSTATE,
+    // either may come with new emote sets
+    fn sync_emote_sets(&mut self) {
+        let sets = self
+            .state
+            .identity
+            .as_ref()
+            .into_iter()
+            .flat_map(|s| &s.emote_sets)
+            .map(String::as_str);
+        self.emote_map.sync_emote_sets(sets)
+    }
+
+    /// Re-read the credentials from the files and reconnect with them, keeping the open channels
+    ///
+    /// This is also how the account is switched, by putting another one's credentials in the files
+    pub fn reload_credentials(&mut self, ctx: &egui::Context) {
+        let (config, helix_config) = match twitch::Config::reload()
+            .and_then(|config| helix::HelixConfig::reload().map(|helix| (config, helix)))
+        {
+            Ok(configs) => configs,
+            Err(err) => {
+                self.toasts.error(format!("cannot reload credentials: {err}"));
+                return;
+            }
+        };
+
+        log!("reloading credentials for {name}", name = config.name);
+        if config.name != self.twitch.user_name() {
+            self.toasts
+                .info(format!("switching to {name}", name = config.name));
+        }
+        self.helix.reload(helix_config);
+        self.reconnect_with(ctx, config);
+    }
+
+    // the open channels are kept around, they're joined again on the new connection
+    fn reconnect_with(&mut self, ctx: &egui::Context, config: twitch::Config) {
+        self.replace_client(ctx, config);
+        self.twitch.connect();
+
+        if matches!(self.screen, Screen::InvalidCredentials) {
+            self.screen = Screen::Disconnected;
+        }
+    }
+
+    // the refresh is scheduled off of what the id server says about the token
+    fn poll_token(&mut self, ctx: &egui::Context) {
+        let checking = matches!(self.token_check, TokenCheck::Checking(..));
+        self.token_check.poll(self.chat_read_only());
+        match &self.token_check {
+            TokenCheck::Checked(info) if checking => {
+                let expires_in = std::time::Duration::from_secs(info.expires_in);
+                self.token_refresh.schedule(expires_in);
+            }
+            TokenCheck::Failed(..) if checking => self.token_refresh.refresh_now(),
+            _ => {}
+        }
+
+        let refresh_token = self.twitch.config().refresh_token.as_deref();
+        let Some(token) = self.token_refresh.poll(&self.helix, refresh_token) else {
+            return;
+        };
+
+        // keep the prefix the same as the token that's being replaced
+        let oauth = if self.twitch.token().starts_with("oauth:") {
+            format!("oauth:{}", token.access_token)
+        } else {
+            token.access_token
+        };
+
+        let secrets = [
+            ("TWITCH_OAUTH", oauth.as_str()),
+            ("TWITCH_REFRESH_TOKEN", token.refresh_token.as_str()),
+        ];
+        if let Err(err) = twitch::Config::store_secrets(secrets) {
+            self.toasts
+                .error(format!("cannot store the refreshed token: {err}"));
+        }
+
+        // the environment isn't touched, other threads could be reading it
+        log!("the chat token was refreshed, reconnecting with it");
+        let config = twitch::Config {
+            token: oauth,
+            refresh_token: Some(token.refresh_token),
+            ..self.twitch.config().clone()
+        };
+        self.reconnect_with(ctx, config);
+    }
+
+    /// Disconnect and go back to the start screen, the channels are joined again on connect
+    pub fn disconnect(&mut self, ctx: &egui::Context) {
+        log!("disconnecting {name}", name = self.twitch.user_name());
+        self.replace_client(ctx, self.twitch.config().clone());
+        self.state.identity.take();
+        self.screen = Screen::Disconnected;
+    }
+
+    fn replace_client(&mut self, ctx: &egui::Context, config: twitch::Config) {
+        let _ = self.twitch.writer().shutdown(false);
+        let repaint = IdleRepaint {
+            ctx: ctx.clone(),
+            idle: self.idle.clone(),
+            pending: self.repaint_pending.clone(),
+        };
+        let twitch = twitch::Client::create(config, repaint, self.wakeup.clone());
+        for channel in self.state.channels.iter().filter(|c| !c.is_whispers()) {
+            if let Err(err) = twitch.writer().join(&channel.name) {
+                log!("cannot join {}: {err}", channel.name);
+            }
+        }
+
+        self.token_check = TokenCheck::Checking(self.helix.validate_token(twitch.token()));
+        self.token_refresh = TokenRefresh::default();
+        self.twitch = twitch;
+        self.last.clear();
+    }
+
+    /// Whisper to a user, it's echoed into the whispers buffer since the server won't
+    ///
+    /// These go through helix, a failure shows up as a toast once it comes back
+    pub fn whisper(&mut self, user: &str, data: &str) -> Result<(), twitch::WriteError> {
+        let Some(identity) = &self.state.identity else { return Ok(()) };
+        let user = user.trim_start_matches('@').to_lowercase();
+        twitch::Writer::check_whisper(&user, data)?;
+
+        let sent = self
+            .helix
+            .send_whisper(self.twitch.token(), &identity.user_id, &user, data);
+        let (toasts, to) = (self.toasts.sender(), user.clone());
+        tokio::spawn(async move {
+            if let Some(Err(err)) = sent.wait().await {
+                toasts.error(format!("cannot whisper {to}: {err}"));
+            }
+        });
+
+        let mut tags = Tags::builder()
+            .add("color", identity.color.unwrap_or_default().to_string())
+            .add("user-id", &identity.user_id);
+        if let Some(display_name) = &identity.display_name {
+            tags = tags.add("display-name", display_name);
+        }
+
+        let pm = Privmsg::builder()
+            .sender(&identity.name)
+            .channel(&user)
+            .data(format!("@{user} {data}"))
+            .tags(tags.finish())
+            .finish_privmsg()
+            .expect("valid privmsg");
+
+        let message = crate::state::Message::from_pm(
+            &pm,
+            &mut self.emote_map,
+            MessageOpts {
+                old: false,
+                local: true,
+            },
+        );
+        self.state.whispers().push(message);
+        self.state.last_whisper.replace(user);
+        Ok(())
+    }
+
+    /// Change our chat color to one of the named ones, it's shown right away
+    pub fn change_color(&mut self, name: &'static str, color: egui::Color32) {
+        let Some(identity) = &mut self.state.identity else { return };
+        let update = self
+            .helix
+            .update_chat_color(self.twitch.token(), &identity.user_id, name);
+        identity
+            .color
+            .replace(twitch_message::Color(color.r(), color.g(), color.b()));
+
+        let toasts = self.toasts.sender();
+        tokio::spawn(async move {
+            match update.wait().await {
+                Some(Ok(())) => toasts.info(format!("changed color to {name}")),
+                Some(Err(err)) => toasts.error(format!("cannot change color to {name}: {err}")),
+                None => {}
+            }
+        });
+    }
+
+    // the topic bar only subscribes to the active channel, but ordering by viewers needs all of them
+    fn subscribe_streams(&mut self) {
+        // the watchlist is only there for this, so it doesn't need a reason
+        for name in &self.state.watchlist {
+            if let Some(user) = self.user_map.get(name) {
+                self.stream_check.get_or_subscribe(&user.id);
+            }
+        }
+
+        let go_live = self
+            .state
+            .notify_rules
+            .iter()
+            .any(|rule| rule.enabled && rule.trigger == Trigger::GoLive);
+
+        if self.state.tab_order != TabOrder::Live && !go_live {
+            return;
+        }
+
+        for channel in &self.state.channels {
+            if let Some(user) = self.user_map.try_get(&channel.name) {
+                self.stream_check.get_or_subscribe(&user.id);
+            }
+        }
+    }
+
+    /// The indices of the channels, in the order their tabs should be shown
+    pub fn tab_order(&self) -> Vec<usize> {
+        use std::cmp::Reverse;
+
+        let channels = &self.state.channels;
+        let mut order = (0..channels.len()).collect::<Vec<_>>();
+
+        // these are stable sorts, so ties keep the manual order
+        match self.state.tab_order {
+            TabOrder::Manual => {}
+            TabOrder::Live => order.sort_by_key(|&i| {
+                let viewers = self
+                    .user_map
+                    .try_get(&channels[i].name)
+                    .and_then(|user| self.stream_check.try_get(&user.id))
+                    .map(|stream| stream.viewer_count);
+                Reverse(viewers)
+            }),
+            TabOrder::Activity => order.sort_by_key(|&i| Reverse(channels[i].last_activity)),
+        }
+
+        order
+    }
+
+    /// Leave a channel and remove its tab
+    pub fn close_channel(&mut self, name: &ChannelName) {
+        if !self.state.channels.iter().any(|c| c.name == *name) {
+            return;
+        }
+
+        // there's nothing to part for the whispers tab
+        if *name != Channel::WHISPERS {
+            if let Err(err) = self.twitch.writer().part(name) {
+                log!("cannot part {name}: {err}");
+            }
+        }
+        self.remove_channel(name);
+    }
+
+    /// Move a tab to another position, the same channel stays active
+    pub fn move_channel(&mut self, from: usize, to: usize) {
+        let len = self.state.channels.len();
+        if from == to || from >= len || to >= len {
+            return;
+        }
+
+        let active = self.state.channels[self.state.active].name.clone();
+        let channel = self.state.channels.remove(from);
+        self.state.channels.insert(to, channel);
+        if let Some(pos) = self.state.channels.iter().position(|c| c.name == active) {
+            self.state.active = pos;
+        }
+        self.autosave.mark_changed();
+    }
+
+    /// Remove the tab for a channel that has already been parted
+    pub fn remove_channel(&mut self, name: &ChannelName) {
+        let Some(pos) = self.state.channels.iter().position(|c| c.name == *name) else {
+            return;
+        };
+
+        self.release_channel(name);
+        // the watchlist still wants to know when it goes live
+        if !self.state.watchlist.contains(name) {
+            if let Some(user) = self.user_map.try_get(name) {
+                self.stream_check.unsubscribe(&user.id);
+            }
+        }
+
+        self.state.channels.remove(pos);
+        if self.state.active > pos || self.state.active == self.state.channels.len() {
+            self.state.active = self.state.active.saturating_sub(1);
+        }
+        self.autosave.mark_changed();
+    }
+
+    /// Free everything that was only being kept around for this channel
+    pub fn release_channel(&mut self, name: &ChannelName) {
+        if let Some(room_id) = self
+            .state
+            .channels
+            .iter()
+            .find(|c| c.name == *name)
+            .and_then(|c| c.room_id.as_deref())
+        {
+            self.emote_map.release_channel(room_id);
+        }
+        self.cache.release_channel(name);
+    }
+
+    /// Keep track of when a channel goes live, without joining it
+    pub fn watch(&mut self, name: ChannelName) {
+        if self.state.watchlist.insert(name) {
+            self.autosave.mark_changed();
+        }
+    }
+
+    pub fn unwatch(&mut self, name: &ChannelName) {
+        if self.state.watchlist.shift_remove(name) {
+            self.autosave.mark_changed();
+        }
+    }
+
+    /// Focus the channel for the link and scroll to the message, joining the channel first if needed
+    pub fn open_link(&mut self, link: Permalink) {
+        if !self.state.channels.iter().any(|c| c.name == link.channel) {
+            if let Err(err) = self.twitch.writer().join(&link.channel) {
+                log!("cannot join {}: {err}", link.channel);
+                return;
+            }
+        }
+        self.pending_link.replace(link);
+    }
+
+    /// The channel typed messages are sent to, this falls back to the active one
+    pub fn send_target(&self) -> usize {
+        self.send_to
+            .as_ref()
+            .and_then(|name| self.state.channels.iter().position(|c| &c.name == name))
+            .unwrap_or(self.state.active)
+    }
+
+    /// Whether we can moderate the channel, a read-only token can't even if we're a moderator
+    pub fn is_moderator(&self, channel: &ChannelName) -> bool {
+        !self.chat_read_only()
+            && self
+                .state
+                .identity
+                .as_ref()
+                .map_or(false, |identity| identity.is_moderator(channel))
+    }
+
+    /// Nothing can be sent, either by choice or because the token wasn't granted `chat:edit`
+    pub fn chat_read_only(&self) -> bool {
+        self.state.read_only_token || self.token_check.is_read_only()
+    }
+
+    /// Put one of our messages back into the input to fix it up
+    ///
+    /// Moderators can delete the original, otherwise the correction is marked with a `*`
+    pub fn edit_message(&mut self, index: usize, id: Uuid) {
+        let moderator = self.is_moderator(&self.state.channels[index].name);
+        let channel = &mut self.state.channels[index];
+        let Some(msg) = channel.messages.iter().find(|msg| msg.id == Some(id)) else { return };
+        let data = msg.data.clone();
+
+        let delete = self
+            .state
+            .identity
+            .as_ref()
+            .filter(|_| moderator)
+            .zip(channel.room_id.as_deref());
+
+        channel.buffer = match delete {
+            Some((identity, broadcaster_id)) => {
+                let delete = self.helix.delete_message(
+                    self.twitch.token(),
+                    broadcaster_id,
+                    &identity.user_id,
+                    &id.to_string(),
+                );
+                let toasts = self.toasts.sender();
+                tokio::spawn(async move {
+                    if let Some(Err(err)) = delete.wait().await {
+                        toasts.error(format!("cannot delete the message: {err}"))
+                    }
+                });
+
+                channel.messages.retain(|msg| msg.id != Some(id));
+                data
+            }
+            None => format!("*{data}"),
+        };
+
+        self.state.active = index;
+        self.send_to.take();
+    }
+
+    /// Time out a user in the active channel, how it went is shown as a toast
+    pub fn timeout_user(&self, login: &str, duration: std::time::Duration, reason: &str) {
+        let channel = &self.state.channels[self.state.active].name;
+        self.moderate_user(channel, login, Some(duration), reason);
+    }
+
+    // without a duration this is a ban
+    fn moderate_user(
+        &self,
+        channel: &ChannelName,
+        login: &str,
+        duration: Option<std::time::Duration>,
+        reason: &str,
+    ) {
+        let Some(channel) = self.state.channels.iter().find(|c| c.name == *channel) else { return };
+        let (Some(identity), Some(broadcaster_id)) = (&self.state.identity, &channel.room_id)
+        else {
+            let err = format!("cannot moderate {login}, #{} isn't known yet", channel.name);
+            self.toasts.sender().error(err);
+            return;
+        };
+
+        let helix = self.helix.clone();
+        let token = self.twitch.token().to_string();
+        let (broadcaster_id, moderator_id) = (broadcaster_id.clone(), identity.user_id.clone());
+        let (login, reason) = (login.to_string(), reason.to_string());
+        let toasts = self.toasts.sender();
+
+        tokio::spawn(async move {
+            let Some(Some((_, user))) = helix.get_user(&login).wait().await else {
+                toasts.error(format!("cannot moderate {login}, there's nobody by that name"));
+                return;
+            };
+
+            let ban = helix.ban_user(
+                &token,
+                &broadcaster_id,
+                &moderator_id,
+                &user.id,
+                duration.map(|duration| duration.as_secs() as u32),
+                &reason,
+            );
+            match (ban.wait().await, duration) {
+                (Some(Ok(())), Some(duration)) => {
+                    toasts.info(format!("timed out {login} for {duration:?}"))
+                }
+                (Some(Ok(())), None) => toasts.info(format!("banned {login}")),
+                (Some(Err(err)), _) => toasts.error(format!("cannot moderate {login}: {err}")),
+                (None, _) => {}
+            }
+        });
+    }
+
+    /// Show what's known about a user, along with what they said in the active channel
+    pub fn open_user_card(&mut self, login: &str, user_id: &str) {
+        let channel = &self.state.channels[self.state.active];
+        let moderator = self.is_moderator(&channel.name);
+
+        let (pending_messages, follow) = match &channel.room_id {
+            Some(room_id) => (
+                Some(db::Connection::messages_for_user(
+                    self.history_path(),
+                    room_id.clone(),
+                    user_id.to_string(),
+                    UserCard::RECENT_MESSAGES,
+                )),
+                if moderator {
+                    FollowStatus::Loading(self.helix.get_follow(
+                        self.twitch.token(),
+                        room_id,
+                        user_id,
+                    ))
+                } else {
+                    FollowStatus::Unknown
+                },
+            ),
+            None => (None, FollowStatus::Unknown),
+        };
+
+        self.user_card.replace(UserCard {
+            login: login.to_string(),
+            user_id: user_id.to_string(),
+            channel: channel.name.clone(),
+            moderator,
+            follow,
+            messages: pending_messages.is_none().then(Vec::new),
+            pending_messages,
+        });
+    }
+
+    /// List every channel the user has spoken in, according to the history
+    pub fn find_user(&mut self, login: &str, user_id: &str) {
+        let db = self.history_path();
+        self.user_search.replace(UserSearch::new(db, login, user_id));
+    }
+
+    /// Show the line a message came in as, every message goes into the history first
+    pub fn inspect_message(&mut self, id: Uuid) {
+        let db = self.history_path();
+        self.raw_message.replace(RawMessage::new(db, id));
+    }
+
+    /// Always join the channel on startup, or stop doing that if it already was
+    ///
+    /// A channel that isn't open is joined when it becomes a favorite
+    pub fn toggle_favorite(&mut self, name: &ChannelName) {
+        self.autosave.mark_changed();
+        if self.state.favorites.shift_remove(name) {
+            return;
+        }
+        self.state.favorites.insert(name.clone());
+        if self.state.channels.iter().any(|c| &c.name == name) {
+            return;
+        }
+        if let Err(err) = self.twitch.writer().join(name) {
+            log!("cannot join {name}: {err}");
+        }
+    }
+
+    /// Bookmark a message, or remove the bookmark if it has one
+    pub fn toggle_bookmark(&mut self, id: Uuid) {
+        let bookmarked = !self.bookmarked.remove(&id);
+        if bookmarked {
+            self.bookmarked.insert(id);
+        }
+        self.bookmark_writer
+            .set(self.history_path(), id, bookmarked);
+
+        if let Some(list) = &mut self.bookmarks {
+            list.retain(|msg| self.bookmarked.contains(&msg.msg_id));
+        }
+    }
+
+    /// List the bookmarked messages across every channel
+    pub fn show_bookmarks(&mut self) {
+        let db = self.history_path();
+        self.bookmarks.replace(BookmarkList::new(db));
+    }
+
+    /// The message a link points at, if it's in the history
+    pub fn quoted_message(&mut self, id: Uuid) -> Option<&db::Message> {
+        if self.quoted.as_ref().map(|&(quoted, _)| quoted) != Some(id) {
+            self.quoted = Some((id, self.conn.history().get_by_msg_id(id)));
+        }
+        self.quoted.as_ref()?.1.as_ref()
+    }
+
+    /// Add an emote to the end of the channel's input, and switch over to it
+    pub fn insert_emote(&mut self, channel: &ChannelName, name: &str) {
+        let Some(pos) = self.state.channels.iter().position(|c| c.name == *channel) else { return };
+
+        let buffer = &mut self.state.channels[pos].buffer;
+        if !buffer.is_empty() && !buffer.ends_with(' ') {
+            buffer.push(' ');
+        }
+        buffer.push_str(name);
+        buffer.push(' ');
+
+        self.state.active = pos;
+        self.send_to.take();
+    }
+
+    /// Start loading the history for a channel if that hasn't happened yet
+    ///
+    /// This waits on the check of the history, the stand-in wouldn't have anything in it yet
+    pub fn ensure_history(&mut self, index: usize) {
+        if self.is_history_checking() {
+            return;
+        }
+        let (db, limit) = (self.history_path(), self.state.preferences.history_limit);
+        self.state.channels[index].request_history(db, limit);
+    }
+
+    fn poll_history(&mut self) {
+        for channel in &mut self.state.channels {
+            channel.poll_older_history(&mut self.emote_map);
+            if let Some(elapsed) = channel.poll_history(&mut self.emote_map) {
+                self.startup
+                    .record(format!("history for #{}", channel.name), elapsed);
+            }
+        }
+    }
+
+    // this waits until the channel shows up, then pulls the message out of the history if it was evicted
+    fn resolve_pending_link(&mut self) {
+        let Some(link) = &self.pending_link else { return };
+        let Some(pos) = self.state.channels.iter().position(|c| c.name == link.channel) else { return };
+
+        self.state.active = pos;
+        self.ensure_history(pos);
+        // the history could have the message too, so it has to be in first
+        if self.is_history_checking() || self.state.channels[pos].is_loading_history() {
+            return;
+        }
+
+        let link = self.pending_link.take().expect("pending link");
+        let channel = &mut self.state.channels[pos];
+
+        if !channel.messages.iter().any(|msg| msg.id == Some(link.id)) {
+            let Some(msg) = self
+                .conn
+                .history()
+                .get_by_msg_id(link.id)
+                .filter(|msg| msg.channel == link.channel)
+            else {
+                log!("cannot find message for {link}");
+                return;
+            };
+
+            // notices only have the parsed form, their raw line isn't a privmsg
+            let opts = MessageOpts {
+                old: true,
+                local: false,
+            };
+            let Some(msg) = crate::state::Message::from_history(&msg, opts) else {
+                log!("invalid message in history for {link}");
+                return;
+            };
+            msg.register_emotes(&mut self.emote_map);
+            channel.messages.push_front(msg);
+        }
+
+        channel.jump_to.replace(link.id);
+    }
+
+    fn display_crash_report(&mut self, ctx: &egui::Context) {
+        let Some(path) = &self.crash_report else { return };
+
+        let mut dismissed = false;
+        egui::Window::new("VoHiYo crashed last time")
+            .collapsible(false)
+            .resizable(false)
+            .anchor(egui::Align2::CENTER_CENTER, egui::Vec2::ZERO)
+            .show(ctx, |ui| {
+                ui.label("A crash report was written to:");
+                // the full path usually has the user name in it
+                if self.state.streamer_mode {
+                    ui.monospace("(hidden in streamer mode)");
+                } else {
+                    ui.monospace(path.display().to_string());
+                }
+                ui.horizontal(|ui| {
+                    if ui.button("Open report").clicked() {
+                        ctx.output_mut(|o| o.open_url(format!("file://{}", path.display())));
+                        dismissed = true;
+                    }
+                    if ui.button("Dismiss").clicked() {
+                        dismissed = true;
+                    }
+                });
+            });
+
+        if dismissed {
+            self.crash_report.take();
+        }
+    }
+
+    fn display_db_repair(&mut self, ctx: &egui::Context) {
+        let Some(repair) = &mut self.db_repair else {
+            return;
+        };
+        if let Some(integrity) = repair.poll_check() {
+            match integrity {
+                Integrity::Ok => self.reopen_history(None),
+                Integrity::Damaged(problems) => {
+                    log!("the history is damaged: {problems:?}");
+                    self.db_repair.replace(DbRepair::Damaged(problems));
+                }
+            }
+            return;
+        }
+        if let Some(result) = repair.poll() {
+            match result {
+                Ok(repaired) => self.reopen_history(Some(repaired)),
+                Err(err) => {
+                    self.toasts
+                        .error(format!("cannot rebuild the history: {err}"));
+                    self.db_repair
+                        .replace(DbRepair::Postponed(vec![err.to_string()]));
+                }
+            }
+            return;
+        }
+        if matches!(repair, DbRepair::Checking(..) | DbRepair::Postponed(..)) {
+            return;
+        }
+
+        let mut rebuild = false;
+        let mut dismissed = false;
+        egui::Window::new("The history is damaged")
+            .collapsible(false)
+            .resizable(false)
+            .anchor(egui::Align2::CENTER_CENTER, egui::Vec2::ZERO)
+            .show(ctx, |ui| {
+                let DbRepair::Damaged(problems) = &*repair else {
+                    ui.horizontal(|ui| {
+                        ui.add(egui::Spinner::new());
+                        ui.label("Rebuilding the history");
+                    });
+                    return;
+                };
+
+                ui.label("This usually happens after a crash. The check found:");
+                for problem in problems.iter().take(5) {
+                    ui.monospace(problem);
+                }
+                if problems.len() > 5 {
+                    ui.label(
+                        egui::RichText::new(format!("and {} more", problems.len() - 5)).weak(),
+                    );
+                }
+                ui.label("Nothing is saved to the history until it's rebuilt.");
+                ui.horizontal(|ui| {
+                    rebuild = ui.button("Back up and rebuild").clicked();
+                    dismissed = ui.button("Not now").clicked();
+                });
+            });
+
+        if rebuild {
+            // the stand-in keeps taking messages, the damaged file has to be left alone
+            self.db_repair.replace(DbRepair::repair(Self::HISTORY_PATH));
+        } else if dismissed {
+            if let Some(DbRepair::Damaged(problems)) = self.db_repair.take() {
+                self.db_repair.replace(DbRepair::Postponed(problems));
+            }
+        }
+    }
+
+    /// Where the history is read from, this is the stand-in while the file is damaged
+    pub fn history_path(&self) -> &'static str {
+        match self.db_repair {
+            Some(..) => DbRepair::STAND_IN,
+            None => Self::HISTORY_PATH,
+        }
+    }
+
+    /// Whether the history is still being checked, nothing has been read from it yet
+    pub fn is_history_checking(&self) -> bool {
+        matches!(self.db_repair, Some(DbRepair::Checking(..)))
+    }
+
+    /// Whether the user put off rebuilding the damaged history, so nothing is being kept
+    pub fn is_history_postponed(&self) -> bool {
+        matches!(self.db_repair, Some(DbRepair::Postponed(..)))
+    }
+
+    /// Ask about rebuilding the damaged history again
+    pub fn review_history_damage(&mut self) {
+        if let Some(DbRepair::Postponed(problems)) = self.db_repair.take() {
+            self.db_repair.replace(DbRepair::Damaged(problems));
+        }
+    }
+
+    // this is after the check passed, or after the rebuild when there's `repaired`
+    fn reopen_history(&mut self, repaired: Option<db::Repaired>) {
+        let conn = match db::Connection::open(Self::HISTORY_PATH) {
+            Ok(conn) => conn,
+            Err(err) => {
+                self.toasts.error(format!("cannot open the history: {err}"));
+                self.db_repair
+                    .replace(DbRepair::Postponed(vec![err.to_string()]));
+                return;
+            }
+        };
+
+        // what came in while it was being checked or rebuilt went to the stand-in
+        match conn.merge_from(DbRepair::STAND_IN) {
+            Ok(copied) => log!("kept {copied} messages from before the history was opened"),
+            Err(err) => self.toasts.error(format!(
+                "cannot keep the messages from before the history was opened: {err}"
+            )),
+        }
+        self.conn = conn;
+        self.db_repair.take();
+        self.bookmarked = self.conn.bookmarks().get_ids().into_iter().collect();
+        if let Some(repaired) = repaired {
+            self.toasts.info(format!(
+                "rebuilt the history with {} messages, the old one is at {}",
+                repaired.messages,
+                repaired.backup.display()
+            ));
+        }
+
+        // the open tab waited on the check
+        if !self.state.channels.is_empty() {
+            self.ensure_history(self.state.active);
+        }
+
+        // the channels that couldn't read it before get another try
+        let limit = self.state.preferences.history_limit;
+        for channel in &mut self.state.channels {
+            if channel.history_loaded
+                && !channel.is_loading_history()
+                && channel.messages.iter().next().is_none()
+            {
+                channel.history_loaded = false;
+                channel.request_history(Self::HISTORY_PATH, limit);
+            }
+        }
+    }
+
+    fn display_unsent(&mut self, ctx: &egui::Context) {
+        if self.unsent.is_empty() {
+            return;
+        }
+
+        let (mut send, mut discard) = (false, false);
+        egui::Window::new("Send messages from before the reconnect?")
+            .collapsible(false)
+            .resizable(false)
+            .anchor(egui::Align2::CENTER_CENTER, egui::Vec2::ZERO)
+            .show(ctx, |ui| {
+                for (target, data) in &self.unsent {
+                    ui.horizontal(|ui| {
+                        ui.label(egui::RichText::new(target).weak());
+                        ui.label(data);
+                    });
+                }
+                ui.horizontal(|ui| {
+                    send = ui.button("Send").clicked();
+                    discard = ui.button("Discard").clicked();
+                });
+            });
+
+        if send {
+            // anything that still can't be sent stays in the list
+            let unsent = std::mem::take(&mut self.unsent);
+            for (target, data) in unsent {
+                if let Err(err) = self.twitch.writer().privmsg(&target, &data) {
+                    log!("cannot resend to {target}: {err}");
+                    self.unsent.push((target, data));
+                }
+            }
+        }
+        if discard {
+            self.unsent.clear();
+        }
+    }
+
+    fn display_contested(&mut self, ctx: &egui::Context) {
+        if !matches!(self.twitch.status(), twitch::Status::Contested) || self.stay_passive {
+            return;
+        }
+
+        let (mut take_over, mut passive) = (false, false);
+        egui::Window::new("Is this account connected somewhere else?")
+            .collapsible(false)
+            .resizable(false)
+            .anchor(egui::Align2::CENTER_CENTER, egui::Vec2::ZERO)
+            .show(ctx, |ui| {
+                ui.label("The server keeps closing the connection shortly after it opens.");
+                ui.label("Another instance using the same account can cause this.");
+                ui.horizontal(|ui| {
+                    take_over = ui
+                        .button("Take over")
+                        .on_hover_text("reconnect, which may disconnect the other instance")
+                        .clicked();
+                    passive = ui
+                        .button("Stay passive")
+                        .on_hover_text("stay disconnected until you take over")
+                        .clicked();
+                });
+            });
+
+        if take_over {
+            self.take_over();
+        }
+        self.stay_passive |= passive;
+    }
+
+    // the image fetches are short lived, so those count as down once any of them crashed
+    fn check_tasks(&mut self) {
+        let images = (self.cache.crashed_fetches() > 0).then_some(TaskExit::Panicked);
+        let down = self.watchdog.update([
+            (Task::Chat, self.twitch.exit()),
+            (Task::StreamCheck, self.stream_check.exit()),
+            (Task::Emotes, self.emote_map.fetcher_exit()),
+            (Task::SevenTv, self.emote_map.seven_tv_exit()),
+            (Task::Images, images),
+        ]);
+        for task in down {
+            self.toasts
+                .error(format!("{} stopped working", task.label()));
+        }
+    }
+
+    fn display_watchdog(&mut self, ctx: &egui::Context) {
+        self.check_tasks();
+        if self.watchdog.down().is_empty() {
+            return;
+        }
+
+        let mut restart = None;
+        egui::Window::new("Something stopped working")
+            .collapsible(false)
+            .resizable(false)
+            .anchor(egui::Align2::CENTER_TOP, egui::vec2(0.0, 8.0))
+            .show(ctx, |ui| {
+                for &(task, exit) in self.watchdog.down() {
+                    ui.horizontal(|ui| {
+                        ui.label(format!("{} {}", task.label(), exit.label()));
+                        if ui.button("Restart").clicked() {
+                            restart.replace(task);
+                        }
+                    });
+                }
+            });
+
+        if let Some(task) = restart {
+            self.restart_task(ctx, task);
+        }
+    }
+
+    /// Start a background task again, with what the old one knew about
+    pub fn restart_task(&mut self, ctx: &egui::Context, task: Task) {
+        log!("restarting {}", task.label());
+        match task {
+            Task::Chat => {
+                self.replace_client(ctx, self.twitch.config().clone());
+                self.twitch.connect();
+            }
+            // subscriptions are made again as the streams are asked for
+            Task::StreamCheck => {
+                let repaint = IdleRepaint {
+                    ctx: ctx.clone(),
+                    idle: self.idle.clone(),
+                    pending: self.repaint_pending.clone(),
+                };
+                self.stream_check = StreamCheck::create(
+                    self.helix.clone(),
+                    repaint,
+                    self.wakeup.clone(),
+                    self.idle.clone(),
+                    self.state.preferences.stream_check_interval(),
+                );
+            }
+            Task::Emotes => self.emote_map.restart_fetcher(),
+            Task::SevenTv => self.emote_map.restart_seven_tv(),
+            Task::Images => self.cache.restart_fetches(),
+        }
+    }
+
+    fn display_invalid_credentials(&mut self, ctx: &egui::Context) {
+        let (mut reload, mut disconnect) = (false, false);
+        egui::CentralPanel::default().show(ctx, |ui| {
+            ui.vertical_centered(|ui| {
+                ui.add_space(ui.available_height() * 0.3);
+                ui.heading("Twitch didn't accept the login");
+                ui.label(format!(
+                    "The token for {name} is invalid or has expired.",
+                    name = self.twitch.user_name()
+                ));
+                ui.label(format!(
+                    "Update it in {files} and reload the credentials.",
+                    files = twitch::Config::ENV_FILES.join(" or ")
+                ));
+                ui.add_space(ui.spacing().item_spacing.y * 2.0);
+                ui.horizontal(|ui| {
+                    reload = ui.button("Reload credentials").clicked();
+                    disconnect = ui.button("Disconnect").clicked();
+                });
+            });
+        });
+
+        if reload {
+            self.reload_credentials(ctx);
+        }
+        if disconnect {
+            self.disconnect(ctx);
+        }
+    }
+
+    pub fn take_over(&mut self) {
+        log!("taking over the connection");
+        self.stay_passive = false;
+        self.twitch.writer().take_over();
+    }
+
+    fn handle_file_changes(&mut self, ctx: &egui::Context) {
+        for path in self.watcher.poll() {
+            match path.file_name().and_then(|name| name.to_str()) {
+                Some(Self::THEME_PATH) => {
+                    log!("reloading theme");
+                    self.theme = Theme::load(Self::THEME_PATH).unwrap_or_default();
+                    self.theme.apply(ctx);
+                }
+                Some(Self::STATE_PATH) => self.reload_state(),
+                Some(Automod::PATH) => {
+                    log!("reloading automod rules");
+                    self.automod = Automod::load(Automod::PATH, self.local_offset);
+                }
+                _ => {}
+            }
+        }
+    }
+
+    /// Write the settings to a single file, the credentials are left out unless asked for
+    pub fn export_settings(&self, path: &str, include_secrets: bool) -> anyhow::Result<()> {
+        SettingsExport::new(
+            &self.state,
+            &self.theme,
+            &self.automod.config,
+            include_secrets,
+        )?
+        .save(path)?;
+        log!("exported settings to {path}");
+        Ok(())
+    }
+
+    /// Read settings from a file written by [`App::export_settings`] and start using them
+    pub fn import_settings(
+        &mut self,
+        ctx: &egui::Context,
+        path: &str,
+        mode: ImportMode,
+    ) -> anyhow::Result<()> {
+        let current = SettingsExport::new(&self.state, &self.theme, &self.automod.config, false)?;
+        let imported = SettingsExport::load(path)?.combine(current, mode)?;
+
+        // everything is checked before anything is changed
+        let state = SavedState::parse(&imported.state_toml()?)
+            .ok_or_else(|| anyhow::anyhow!("invalid state in {path}"))?;
+        let theme = imported.theme()?;
+        let automod = imported.automod()?;
+        imported.write_secrets()?;
+
+        self.theme = theme;
+        self.theme.apply(ctx);
+        self.theme.save(Self::THEME_PATH);
+
+        self.automod = Automod::new(automod, self.local_offset);
+        self.automod.save(Automod::PATH);
 
-use crate::{
-    db, helix,
-    runtime::{EmoteMap, GameMap, ImageCache, StreamCheck, UserMap},
-    state::{Channel, MessageOpts, SavedState, Screen, State, ViewState},
-    twitch,
-    views::{InitialView, MainView, StartView},
-};
+        self.apply_state(state, mode);
+        self.apply_fonts(ctx);
+        self.autosave.mark_changed();
 
-pub struct App {
-    pub state: State,
-    pub screen: Screen,
-    pub helix: helix::Client,
-    pub twitch: twitch::Client,
-    pub stream_check: StreamCheck,
-    pub cache: ImageCache,
-    pub emote_map: EmoteMap,
-    pub user_map: UserMap,
-    pub game_map: GameMap,
-    pub last: Option<(PrivmsgBuilder, TagsBuilder)>,
-    pub conn: db::Connection,
-}
+        log!("imported settings from {path} ({mode:?})");
+        Ok(())
+    }
 
-impl App {
-    pub const USER_AGENT: &str = concat!(env!("CARGO_PKG_NAME"), "/", env!("CARGO_PKG_VERSION"));
+    /// Add Chatterino's highlights, ignored users and nicknames to ours, returns what was added
+    pub fn import_chatterino(
+        &mut self,
+        path: impl AsRef<std::path::Path>,
+    ) -> anyhow::Result<String> {
+        let import = crate::state::ChatterinoImport::load(path)?;
+        let state = &mut self.state;
 
-    pub fn create(cc: &CreationContext, config: twitch::Config) -> Box<dyn eframe::App> {
-        cc.egui_ctx.set_pixels_per_point(1.5);
-        Self::load_fonts(&cc.egui_ctx);
+        let keywords = import
+            .keywords
+            .into_iter()
+            .filter(|keyword| !state.highlight_keywords.contains(keyword))
+            .collect::<Vec<_>>();
+        let added = (
+            keywords.len(),
+            import.ignored_users.len(),
+            import.nicknames.len(),
+        );
 
-        let mut state = SavedState::load("vohiyo.toml").unwrap_or_default();
+        state.highlight_keywords.extend(keywords);
+        state.ignored_users.extend(import.ignored_users);
+        state.nicknames.extend(import.nicknames);
 
-        let http = reqwest::ClientBuilder::new()
-            .default_headers(
-                std::iter::once((
-                    HeaderName::from_static("user-agent"),
-                    Self::USER_AGENT.parse().unwrap(),
-                ))
-                .collect(),
-            )
-            .build()
-            .expect("valid client configuration");
+        self.highlighter.set_keywords(&state.highlight_keywords);
+        self.autosave.mark_changed();
+
+        let (keywords, users, nicknames) = added;
+        let mut summary =
+            format!("{keywords} highlights, {users} ignored users and {nicknames} nicknames");
+        if import.skipped > 0 {
+            summary.push_str(&format!(
+                ", skipped {} regex or empty entries",
+                import.skipped
+            ));
+        }
+        log!("imported from chatterino: {summary}");
+        Ok(summary)
+    }
+
+    // open channels are kept unless they're being replaced, anything new is joined and shows up
+    // once the join goes through
+    fn apply_state(&mut self, loaded: State, mode: ImportMode) {
+        if mode == ImportMode::Replace {
+            let closed = self
+                .state
+                .channels
+                .iter()
+                .map(|c| c.name.clone())
+                .filter(|name| *name != Channel::WHISPERS)
+                .filter(|name| !loaded.channels.iter().any(|c| c.name == *name))
+                .collect::<Vec<_>>();
+            for name in closed {
+                self.close_channel(&name);
+            }
+        }
 
-        let helix = helix::Client::create(cc.egui_ctx.clone());
-        let mut emote_map = EmoteMap::create(helix.clone(), cc.egui_ctx.clone(), http.clone());
+        for channel in loaded.channels {
+            match self
+                .state
+                .channels
+                .iter_mut()
+                .find(|c| c.name == channel.name)
+            {
+                Some(existing) => {
+                    existing.read_only = channel.read_only;
+                    existing.messages.set_capacity(channel.messages.capacity());
+                }
+                None => {
+                    if let Err(err) = self.twitch.writer().join(&channel.name) {
+                        log!("cannot join {}: {err}", channel.name);
+                    }
+                }
+            }
+        }
 
-        let conn = db::Connection::create("history.db");
-        let history = conn.history();
-        for channel in &mut state.channels {
-            let messages = history.get_channel_messages(&channel.name, 250);
-            if let Some(msg) = messages.last() {
-                channel.mark_end_of_history(msg.msg_id);
+        for favorite in &loaded.favorites {
+            if self.state.favorites.contains(favorite)
+                || self.state.channels.iter().any(|c| &c.name == favorite)
+            {
+                continue;
+            }
+            if let Err(err) = self.twitch.writer().join(favorite) {
+                log!("cannot join {favorite}: {err}");
             }
-            channel.messages.populate(messages, &mut emote_map);
         }
 
-        let twitch = twitch::Client::create(config, cc.egui_ctx.clone());
+        self.state.streamer_mode = loaded.streamer_mode;
+        self.state.quiet_taskbar = loaded.quiet_taskbar;
+        self.state.read_only_token = loaded.read_only_token;
+        self.state.favorites = loaded.favorites;
+        self.state.watchlist = loaded.watchlist;
+        self.state.highlight_keywords = loaded.highlight_keywords;
+        self.state.ignored_users = loaded.ignored_users;
+        self.state.nicknames = loaded.nicknames;
+        self.state.tab_order = loaded.tab_order;
+        self.state.input_position = loaded.input_position;
+        self.state.tab_bar_position = loaded.tab_bar_position;
+        self.state.do_not_disturb = loaded.do_not_disturb;
+        self.state.notify_rules = loaded.notify_rules;
+        self.state.preferences = loaded.preferences;
 
-        let mut user_map = UserMap::create(helix.clone());
+        self.highlighter
+            .set_keywords(&self.state.highlight_keywords);
+        self.apply_image_scale();
+        self.stream_check
+            .set_interval(self.state.preferences.stream_check_interval());
+    }
 
-        for channel in state.channels.iter().map(|c| &c.name) {
-            twitch.writer().join(channel);
-            user_map.get(channel);
+    // only new channels are picked up, closing them is left to the user
+    fn reload_state(&mut self) {
+        let Ok(data) = std::fs::read_to_string(Self::STATE_PATH) else { return };
+        if self.autosave.is_ours(&data) {
+            return;
         }
 
-        Box::new(Self {
-            screen: Screen::default(),
-            stream_check: StreamCheck::create(helix.clone(), cc.egui_ctx.clone()),
-            cache: ImageCache::new(http, cc.egui_ctx.clone()),
-            emote_map,
-            game_map: GameMap::create(helix.clone()),
-            user_map,
+        let Some(state) = SavedState::parse(&data) else {
+            log!("invalid state in {path}", path = Self::STATE_PATH);
+            return;
+        };
 
-            state,
-            twitch,
-            helix,
+        log!("reloading state");
+        for channel in state.channels {
+            if self.state.channels.iter().any(|c| c.name == channel.name) {
+                continue;
+            }
+            if let Err(err) = self.twitch.writer().join(&channel.name) {
+                log!("cannot join {}: {err}", channel.name);
+            }
+        }
+    }
 
-            last: None,
+    // moving to a monitor with a different scale factor resets egui to the native scale
+    fn handle_scale_change(&mut self, ctx: &egui::Context, frame: &eframe::Frame) {
+        let native = frame.info().native_pixels_per_point;
+        if native == self.native_pixels_per_point {
+            return;
+        }
+        self.native_pixels_per_point = native;
 
-            conn,
-        })
+        let pixels_per_point = native.unwrap_or(1.0) * Self::UI_SCALE;
+        log!("scale factor changed, using {pixels_per_point} pixels per point");
+        ctx.set_pixels_per_point(pixels_per_point);
+        self.apply_image_scale();
     }
 
-    fn load_fonts(ctx: &egui::Context) {
-        let mut fonts = FontDefinitions::empty();
+    fn apply_fonts(&mut self, ctx: &egui::Context) {
+        let preferences = &self.state.preferences;
+        let path = preferences.font_path.as_deref();
+        if let Err(err) = self.fonts.apply(ctx, path, preferences.font_size) {
+            self.toasts.error(err.to_string());
+        }
+    }
 
-        macro_rules! load_font {
-        ($($font:expr => $entry:expr),*) => {
-            $(
-                fonts.font_data.insert($font.into(), FontData::from_static(
-                    include_bytes!(concat!(env!("CARGO_MANIFEST_DIR"), "/fonts/", $font, ".ttf")))
-                );
-                fonts.families.entry($entry).or_default().push($font.into());
-            )*
-            ctx.set_fonts(fonts)
-        };
+    // the images at the old scale are dropped, they're fetched again as they're drawn
+    fn apply_image_scale(&mut self) {
+        let pixels_per_point = self.native_pixels_per_point.unwrap_or(1.0) * Self::UI_SCALE;
+        let scale = self.state.preferences.image_scale.resolve(pixels_per_point);
+        if self.emote_map.set_scale(scale) {
+            self.cache.clear();
+        }
+    }
+
+    // any input wakes us back up in the same frame, so there's nothing to catch up on
+    // the maps only change once something finished in the background, their timers are checked anyway
+    fn poll_runtime(&mut self) {
+        if !self.wakeup.take() && self.last_housekeeping.elapsed() < Self::HOUSEKEEPING {
+            return;
+        }
+        self.last_housekeeping = std::time::Instant::now();
+
+        self.stream_check.poll();
+        self.game_map.poll();
+        self.user_map.poll();
+        self.emote_map.poll();
+        self.cache.poll();
+
+        self.verify_channels();
+    }
+
+    // the server lets us join channels that don't exist, so they're closed once helix says so
+    fn verify_channels(&mut self) {
+        let mut missing = vec![];
+        self.unverified.retain(|(name, joined)| {
+            if self.user_map.is_missing(name) {
+                missing.push(name.clone());
+                return false;
+            }
+            self.user_map.try_get(name).is_none() && joined.elapsed() < Self::VERIFY_TIMEOUT
+        });
+
+        for name in missing {
+            log!("closing {name}, there's no such channel");
+            self.close_channel(&name);
+            let buffer = self
+                .state
+                .channels
+                .get(self.state.active)
+                .map(|c| c.buffer.clone())
+                .unwrap_or_default();
+            self.input_error
+                .replace((format!("there's no channel named {name}"), buffer));
+        }
     }
 
-        load_font! {
-            "Roboto-Regular"     => egui::FontFamily::Proportional,
-            "RobotoMono-Regular" => egui::FontFamily::Monospace,
-            "RobotoMono-Bold"    => egui::FontFamily::Name("bold".into())
+    fn update_idle(&mut self, ctx: &egui::Context) {
+        let (focused, active) = ctx.input(|i| {
+            (
+                i.raw.has_focus,
+                !i.events.is_empty() || i.pointer.is_moving(),
+            )
+        });
+
+        if active {
+            self.last_input = std::time::Instant::now();
+        }
+
+        self.focused = focused;
+        if focused {
+            self.wants_attention = false;
+        }
+
+        let idle = !focused || self.last_input.elapsed() >= Self::IDLE_AFTER;
+        if idle != self.idle.get() {
+            log!("{}", if idle { "going idle" } else { "resuming from idle" });
+            self.idle.set(idle);
+            self.cache.set_idle(idle);
         }
     }
 
-    fn fetch_initial_emotes(&mut self) {
-        for set in self
+    // the first status for a stream is just where it's at, so only changes after that count
+    fn handle_stream_event(&mut self, event: Action<StreamStatus>) {
+        let (user_id, live) = match event {
+            Action::Added(status) => (status.user_id, true),
+            Action::Removed(status) => (status.user_id, false),
+        };
+
+        let Some(was_live) = self.live.insert(user_id.clone(), live) else { return };
+        if was_live || !live {
+            return;
+        }
+
+        let Some(channel) = self
             .state
-            .identity
-            .as_ref()
-            .into_iter()
-            .flat_map(|s| &s.emote_sets)
+            .channels
+            .iter()
+            .map(|channel| &channel.name)
+            .chain(&self.state.watchlist)
+            .find(|name| {
+                self.user_map
+                    .try_get(name)
+                    .filter(|user| user.id == user_id)
+                    .is_some()
+            })
+            .cloned() else { return };
+
+        // watched channels always say when they go live, even without a rule for it
+        let event = NotifyEvent::GoLive { channel: &channel };
+        if self.state.watchlist.contains(&channel)
+            && actions_for(&self.state.notify_rules, &event).is_empty()
+        {
+            let summary = format!("{channel} is live");
+            if self.state.do_not_disturb.is_active(self.local_offset) {
+                self.toasts.record(summary);
+            } else {
+                notification::desktop(&summary, "");
+            }
+            return;
+        }
+        self.notify(event);
+    }
+
+    // sounds and desktop notifications are held back by do not disturb, the tab still flashes and
+    // the notification center still gets them
+    fn notify(&mut self, event: NotifyEvent<'_>) {
+        let actions = actions_for(&self.state.notify_rules, &event);
+        if actions.is_empty() {
+            return;
+        }
+
+        let (channel, summary, body) = match event {
+            NotifyEvent::Message {
+                channel,
+                sender,
+                data,
+                ..
+            } => (channel, format!("{sender} in #{channel}"), data),
+            NotifyEvent::GoLive { channel } => (channel, format!("{channel} is live"), ""),
+        };
+        let body = if self.state.streamer_mode { "" } else { body };
+
+        let quiet = self.state.do_not_disturb.is_active(self.local_offset);
+        let mut held_back = false;
+        for action in actions {
+            match action {
+                NotifyAction::Sound | NotifyAction::Desktop if quiet => held_back = true,
+                NotifyAction::Sound => notification::sound(),
+                NotifyAction::Desktop => notification::desktop(&summary, body),
+                NotifyAction::FlashTab => {
+                    let active = self.state.active;
+                    if let Some((_, channel)) = self
+                        .state
+                        .channels
+                        .iter_mut()
+                        .enumerate()
+                        .find(|(i, c)| *i != active && c.name == channel)
+                    {
+                        channel.attention = true;
+                    }
+                }
+            }
+        }
+
+        // it's still kept, so what was missed can be looked at later
+        if held_back {
+            self.toasts.record(if body.is_empty() {
+                summary
+            } else {
+                format!("{summary}: {body}")
+            });
+        }
+    }
+
+    // mentions and whispers are the only things worth pulling someone back to the window for
+    fn request_attention(&mut self) {
+        if self.focused
+            || self.wants_attention
+            || self.state.quiet_taskbar
+            || self.state.do_not_disturb.is_active(self.local_offset)
         {
-            self.emote_map.populate_emote_set(set)
+            return;
         }
+        self.wants_attention = true;
+        self.attention_pending = true;
     }
 
     fn handle_keyboard_input(&mut self, ctx: &egui::Context) {
         if ctx.input(|i| i.key_released(Key::F12)) {
             ctx.set_debug_on_hover(!ctx.debug_on_hover())
         }
+
+        if ctx.input_mut(|i| i.consume_key(Modifiers::COMMAND, Key::F)) {
+            self.message_search.open();
+        }
+
+        if ctx.input_mut(|i| i.consume_key(Modifiers::COMMAND, Key::K)) {
+            self.quick_switcher.get_or_insert_with(Default::default);
+        }
     }
 
     fn handle_message(&mut self, message: twitch::Message) {
         match message {
+            twitch::Message::Unsent { messages } => {
+                log!("{} messages were queued for too long", messages.len());
+                self.unsent.extend(messages);
+            }
+
+            twitch::Message::EmoteOnly {
+                channel: name,
+                enabled,
+            } => {
+                if let Some(channel) = self.state.channels.iter_mut().find(|c| c.name == name) {
+                    log!("emote only mode in #{name}: {enabled}");
+                    channel.emote_only = enabled;
+                }
+            }
+
+            twitch::Message::ChannelId {
+                channel: name,
+                room_id,
+            } => {
+                self.emote_map.populate_channel(&room_id);
+
+                let db = self.history_path();
+                if let Some(channel) = self
+                    .state
+                    .channels
+                    .iter_mut()
+                    .find(|c| c.name == name && c.room_id.as_deref() != Some(&*room_id))
+                {
+                    channel.room_id.replace(room_id);
+                    self.autosave.mark_changed();
+
+                    // a renamed channel has its history under the old name
+                    if channel.history_loaded
+                        && !channel.is_loading_history()
+                        && channel.messages.iter().next().is_none()
+                    {
+                        channel.history_loaded = false;
+                        let limit = self.state.preferences.history_limit;
+                        channel.request_history(db, limit);
+                    }
+                }
+            }
+
+            // these aren't stored, they're between us and the sender
+            twitch::Message::Whisper { msg } => {
+                let message = crate::state::Message::from_pm(
+                    &msg,
+                    &mut self.emote_map,
+                    MessageOpts {
+                        old: false,
+                        local: false,
+                    },
+                );
+
+                if self.state.ignored_users.contains(&*msg.sender) {
+                    return;
+                }
+
+                let seen = (self.state.channels.get(self.state.active))
+                    .map_or(false, Channel::is_whispers);
+                let whispers = self.state.whispers();
+                whispers.attention |= !seen;
+                // a whisper is always for us, so it counts as a mention too
+                if !seen {
+                    whispers.unread += 1;
+                    whispers.unread_highlights += 1;
+                }
+                whispers.push(message);
+                self.state.last_whisper.replace(msg.sender.to_string());
+                self.request_attention();
+
+                let our_name = self.state.identity.as_ref().map(|i| i.name.clone());
+                self.notify(NotifyEvent::Message {
+                    channel: Channel::WHISPERS,
+                    sender: &msg.sender,
+                    data: &msg.data,
+                    our_name: our_name.as_deref(),
+                });
+            }
+
+            twitch::Message::UserTimedOut {
+                channel: name,
+                user_id,
+                duration,
+            } => {
+                let Some(channel) = self.state.channels.iter_mut().find(|c| c.name == name)
+                else { return };
+
+                match duration {
+                    Some(duration) => log!("{user_id} timed out in #{name} for {duration:?}"),
+                    None => log!("{user_id} banned in #{name}"),
+                }
+
+                // a timeout only clears what's loaded, what they said before stays searchable
+                let mut cleared = vec![];
+                for msg in channel.messages.iter_mut() {
+                    if msg.user_id.as_deref() == Some(&*user_id) && !msg.deleted {
+                        msg.deleted = true;
+                        cleared.extend(msg.id);
+                    }
+                }
+
+                // a ban is for good, so everything they said goes
+                let history = self.conn.history();
+                if let (Some(room_id), None) = (&channel.room_id, duration) {
+                    history.delete_for_user(room_id, &user_id);
+                } else {
+                    history.delete_many(&cleared);
+                }
+            }
+
+            twitch::Message::MessageDeleted { channel, msg_id } => {
+                let Ok(id) = Uuid::parse_str(&msg_id) else { return };
+                if let Some(msg) = (self.state.channels.iter_mut())
+                    .filter(|c| c.name == channel)
+                    .find_map(|c| c.messages.iter_mut().find(|msg| msg.id == Some(id)))
+                {
+                    msg.deleted = true;
+                }
+                self.conn.history().delete(id);
+            }
+
+            twitch::Message::UserNotice {
+                msg,
+                kind,
+                system,
+                raw,
+            } => {
+                let notice = crate::state::Message::parse(
+                    &msg,
+                    MessageOpts {
+                        old: false,
+                        local: false,
+                    },
+                )
+                .with_notice(&kind, system);
+
+                // the stored line is the notice, not the message that was made out of it
+                match InsertMessage::try_from(&msg) {
+                    Ok(insert) => {
+                        let insert = InsertMessage { raw: &raw, ..insert };
+                        self.conn
+                            .history()
+                            .insert(insert.with_parsed(notice.to_stored()));
+                    }
+                    Err(err) => log!("not storing notice: {err}"),
+                }
+
+                let name = &*msg.channel;
+                if let Some(channel) = self.state.channels.iter_mut().find(|c| c.name == name) {
+                    notice.register_emotes(&mut self.emote_map);
+                    channel.push(notice);
+                }
+            }
+
+            // these are usually why a message didn't go through, like slow mode or being banned
+            twitch::Message::Notice {
+                channel: name,
+                kind,
+                text,
+            } => {
+                log!("notice in #{name} ({kind:?}): {text}");
+                if let Some(channel) = self.state.channels.iter_mut().find(|c| c.name == name) {
+                    channel.push(crate::state::Message::server_notice(text));
+                }
+            }
+
             twitch::Message::Join { channel } => {
-                if let Some(pos) = self.state.channels.iter().position(|p| {
-                    p.name.strip_prefix('#').unwrap_or(&p.name)
-                        == channel.strip_prefix('#').unwrap_or(&channel)
-                }) {
+                if let Some(pos) = self.state.channels.iter().position(|p| p.name == channel) {
                     self.state.active = pos;
                 } else {
                     let pos = self.state.channels.len();
                     self.state.channels.push(Channel::new(&channel));
                     self.state.active = pos;
-                    self.user_map
-                        .get(channel.strip_prefix('#').unwrap_or(&channel));
+                    self.autosave.mark_changed();
+
+                    self.user_map.get(&channel);
+                    self.unverified.push((channel, std::time::Instant::now()));
                 }
             }
 
@@ -148,52 +1906,138 @@ impl App {
                 | twitch::Message::Privmsg { msg }) = this
                 else { unreachable!() };
 
-                self.conn.history().insert(&msg);
+                let mut parsed =
+                    crate::state::Message::parse(&msg, MessageOpts { old: false, local });
+                match InsertMessage::try_from(&msg) {
+                    Ok(insert) => {
+                        self.conn
+                            .history()
+                            .insert(insert.with_parsed(parsed.to_stored()));
+                    }
+                    Err(err) => log!("not storing message: {err}"),
+                }
+
+                let is_active = self
+                    .state
+                    .channels
+                    .get(self.state.active)
+                    .map_or(false, |c| c.name == *msg.channel);
 
-                let channel = self
+                // this can happen if a message was in flight while we were parting
+                let Some(channel) = self
                     .state
                     .channels
                     .iter_mut()
-                    .find(|c| c.name == msg.channel.strip_prefix('#').unwrap_or(&msg.channel))
-                    .unwrap_or_else(|| {
-                        panic!(
-                            "we should be on this channel: {channel}",
-                            channel = msg.channel
-                        )
-                    });
+                    .find(|c| c.name == *msg.channel)
+                else {
+                    log!(
+                        "dropping message for a channel we're not on: {}",
+                        msg.channel
+                    );
+                    return;
+                };
+
+                // the echo went in without an id, the server only tells us it now
+                if local {
+                    let id = msg
+                        .msg_id()
+                        .and_then(|id| Uuid::parse_str(id.as_str()).ok());
+                    if let Some(echo) =
+                        channel.messages.iter_mut().rev().find(|echo| {
+                            echo.opts.local && echo.id.is_none() && echo.data == *msg.data
+                        })
+                    {
+                        echo.id = id;
+                    }
+                    return;
+                }
+
+                // they're in the history, but that's as far as they get
+                if self.state.ignored_users.contains(&*msg.sender) {
+                    return;
+                }
+
+                if let Some(identity) = &self.state.identity {
+                    parsed.highlighted = self.highlighter.is_highlight(&parsed, &identity.name);
+                }
+                let highlighted = parsed.highlighted;
+                if !is_active {
+                    channel.unread += 1;
+                    channel.unread_highlights += usize::from(highlighted);
+                }
+
+                parsed.register_emotes(&mut self.emote_map);
+                channel.push(parsed);
 
-                if !local {
-                    channel.push(crate::state::Message::from_pm(
+                // automod acts as us, so it's off when we can't
+                let read_only = self.chat_read_only();
+                if let (Some(identity), Some(broadcaster_id), false) =
+                    (&self.state.identity, &channel.room_id, read_only)
+                {
+                    self.automod.check(
                         &msg,
-                        &mut self.emote_map,
-                        MessageOpts { old: false, local },
-                    ));
+                        broadcaster_id,
+                        identity,
+                        &self.helix,
+                        self.twitch.token(),
+                    );
                 }
+
+                if highlighted {
+                    self.request_attention();
+                }
+
+                let name = ChannelName::new(&msg.channel);
+                let our_name = self.state.identity.as_ref().map(|i| i.name.clone());
+                self.notify(NotifyEvent::Message {
+                    channel: &name,
+                    sender: &msg.sender,
+                    data: &msg.data,
+                    our_name: our_name.as_deref(),
+                });
             }
         }
     }
 }
 
 impl eframe::App for App {
-    fn update(&mut self, ctx: &egui::Context, _frame: &mut eframe::Frame) {
-        // TODO make this optional (its only needed for smooth image animations)
-        ctx.request_repaint_after(std::time::Duration::from_secs_f32(1.0 / 60.0));
-
+    fn update(&mut self, ctx: &egui::Context, frame: &mut eframe::Frame) {
+        self.repaint_pending.frame_started();
+        self.frame_stats.begin_frame();
+        self.handle_scale_change(ctx, frame);
+        self.update_idle(ctx);
         self.handle_keyboard_input(ctx);
+        self.handle_file_changes(ctx);
+        self.display_crash_report(ctx);
+        self.display_db_repair(ctx);
+        self.display_unsent(ctx);
+        self.display_contested(ctx);
+        self.display_watchdog(ctx);
 
         while let Some(event) = self.twitch.poll(&mut self.state.identity, &mut self.last) {
             self.handle_message(event);
         }
-
-        self.stream_check.poll();
-        while let Some(_event) = self.stream_check.poll_event() {
-            //
+        if std::mem::take(&mut self.attention_pending) {
+            frame.request_user_attention(egui::UserAttentionType::Informational);
+        }
+        self.poll_token(ctx);
+        while let Some(link) = self.links.as_mut().and_then(LinkListener::poll) {
+            self.open_link(link);
+        }
+        for err in self.errors.drain() {
+            self.toasts.error(err);
         }
+        if matches!(self.twitch.status(), twitch::Status::InvalidCredentials) {
+            self.screen = Screen::InvalidCredentials;
+        }
+        self.sync_emote_sets();
+        self.poll_history();
 
-        self.game_map.poll();
-        self.user_map.poll();
-        self.emote_map.poll();
-        self.cache.poll();
+        self.subscribe_streams();
+        self.poll_runtime();
+        while let Some(event) = self.stream_check.poll_event() {
+            self.handle_stream_event(event);
+        }
 
         match &mut self.screen {
             Screen::Disconnected => {
@@ -203,36 +2047,243 @@ impl eframe::App for App {
                 }
                 .display(ctx);
 
-                if matches!(self.screen, Screen::Connected { .. }) {
-                    self.fetch_initial_emotes();
-                }
+                egui::Area::new("identity-inlay")
+                    .anchor(egui::Align2::RIGHT_TOP, egui::vec2(-10.0, 0.0))
+                    .movable(false)
+                    .show(ctx, |ui| {
+                        ui.horizontal(|ui| IdentityChip { app: self }.display(ui))
+                    });
             }
 
             Screen::Connected { state } => {
                 if matches!(state, ViewState::MainView) && self.state.channels.is_empty() {
-                    eprintln!("changing view state: empty");
+                    log!("changing view state: empty");
                     *state = ViewState::Empty {
                         buffer: String::new(),
+                        suggestions: Suggestions::new(
+                            self.conn.history(),
+                            &self.helix,
+                            self.state
+                                .identity
+                                .as_ref()
+                                .map(|identity| (self.twitch.token(), &*identity.user_id)),
+                        ),
                     }
                 } else if !matches!(state, ViewState::MainView) && !self.state.channels.is_empty() {
-                    eprintln!("changing view state: mainview");
+                    log!("changing view state: mainview");
                     *state = ViewState::MainView
                 };
 
                 match state {
-                    ViewState::Empty { buffer } => InitialView {
+                    ViewState::Empty {
                         buffer,
+                        suggestions,
+                    } => InitialView {
+                        buffer,
+                        suggestions,
                         twitch: &self.twitch,
+                        helix: &self.helix,
+                        theme: &self.theme,
                     }
                     .display(ctx),
-                    ViewState::MainView => MainView { app: self }.display(ctx),
+                    ViewState::MainView => {
+                        self.resolve_pending_link();
+                        MainView { app: self }.display(ctx)
+                    }
+                }
+            }
+
+            Screen::InvalidCredentials => self.display_invalid_credentials(ctx),
+        }
+
+        let db = self.history_path();
+        if let Some(query) = (UserSearchView {
+            search: &mut self.user_search,
+        })
+        .display(ctx)
+        {
+            self.history_search.replace(HistorySearch::with_query(db, query));
+        }
+
+        if let Some(link) = (HistorySearchView {
+            db,
+            search: &mut self.history_search,
+            emote_map: &mut self.emote_map,
+            cache: &mut self.cache,
+        })
+        .display(ctx)
+        {
+            self.open_link(link);
+        }
+
+        match (UserCardView {
+            card: &mut self.user_card,
+            user_map: &mut self.user_map,
+            cache: &mut self.cache,
+        })
+        .display(ctx)
+        {
+            Some(UserCardAction::Timeout {
+                login,
+                channel,
+                duration,
+            }) => self.moderate_user(&channel, &login, Some(duration), ""),
+            Some(UserCardAction::Ban { login, channel }) => {
+                self.moderate_user(&channel, &login, None, "")
+            }
+            None => {}
+        }
+
+        let room_id = self.emote_browser.as_ref().and_then(|browser| {
+            let channel = self
+                .state
+                .channels
+                .iter()
+                .find(|c| c.name == browser.channel)?;
+            channel.room_id.as_deref()
+        });
+        if let Some((channel, name)) = (EmoteBrowserView {
+            browser: &mut self.emote_browser,
+            room_id,
+            emote_map: &self.emote_map,
+            cache: &mut self.cache,
+        })
+        .display(ctx)
+        {
+            self.insert_emote(&channel, &name);
+        }
+
+        match (QuickSwitcherView {
+            switcher: &mut self.quick_switcher,
+            channels: &self.state.channels,
+            favorites: &self.state.favorites,
+            helix: &self.helix,
+            live: self.theme.live(),
+        })
+        .display(ctx)
+        {
+            Some(QuickSwitch::Show(i)) => {
+                self.state.active = i;
+                self.send_to.take();
+            }
+            Some(QuickSwitch::Join(name)) => {
+                if let Err(err) = self.twitch.writer().join(&name) {
+                    log!("cannot join {name}: {err}");
                 }
             }
+            Some(QuickSwitch::ToggleFavorite(name)) => self.toggle_favorite(&name),
+            None => {}
+        }
+
+        match (BookmarksView {
+            bookmarks: &mut self.bookmarks,
+        })
+        .display(ctx)
+        {
+            Some(BookmarkAction::Jump(link)) => self.open_link(link),
+            Some(BookmarkAction::Remove(id)) => self.toggle_bookmark(id),
+            None => {}
+        }
+
+        RawMessageView {
+            inspect: &mut self.raw_message,
+        }
+        .display(ctx);
+
+        DiagnosticsView {
+            open: &mut self.show_diagnostics,
+            startup: &self.startup,
+        }
+        .display(ctx);
+
+        if let Some(channel) = self.state.channels.get(self.state.active) {
+            BadgeBrowserView {
+                open: &mut self.show_badges,
+                channel: &channel.name,
+                room_id: channel.room_id.as_deref(),
+                emote_map: &self.emote_map,
+                cache: &mut self.cache,
+            }
+            .display(ctx);
+        }
+
+        NotificationsView {
+            open: &mut self.show_notifications,
+            toasts: &mut self.toasts,
+        }
+        .display(ctx);
+
+        ToastsView {
+            toasts: &mut self.toasts,
+        }
+        .display(ctx);
+
+        if self.show_frame_stats {
+            FrameStatsView {
+                stats: &self.frame_stats,
+            }
+            .display(ctx);
+        }
+
+        let theme = self.theme.clone();
+        if (SettingsView {
+            open: &mut self.show_settings,
+            preferences: &mut self.state.preferences,
+            theme: &mut self.theme,
+            fonts: &mut self.fonts,
+            show_rules: &mut self.show_rules,
+        })
+        .display(ctx)
+        {
+            self.apply_image_scale();
+            self.apply_fonts(ctx);
+            self.stream_check
+                .set_interval(self.state.preferences.stream_check_interval());
+            self.autosave.mark_changed();
+        }
+        if self.theme != theme {
+            self.theme.apply(ctx);
+            self.theme.save(Self::THEME_PATH);
+        }
+
+        if (NotifyRulesView {
+            open: &mut self.show_rules,
+            rules: &mut self.state.notify_rules,
+            channels: self.state.channels.iter().map(|c| &*c.name).collect(),
+        })
+        .display(ctx)
+        {
+            self.autosave.mark_changed();
+        }
+
+        self.autosave.maybe_save(&self.state, Self::STATE_PATH);
+
+        // only keep ticking while there is an animation on screen
+        if self.cache.take_animating() {
+            ctx.request_repaint_after(self.state.preferences.animation_rate.frame_time());
+        }
+
+        let channel = self.state.channels.get(self.state.active);
+        self.frame_stats
+            .end_frame(ctx, channel.map(|c| c.name.as_str()), self.local_offset);
+
+        // these aren't needed to draw anything at first, so they wait until something is up
+        if self.startup.first_frame() {
+            self.emote_map.populate_globals();
         }
     }
 
     fn save(&mut self, _storage: &mut dyn eframe::Storage) {
-        SavedState { state: &self.state }.save("vohiyo.toml");
+        self.autosave.save(&self.state, Self::STATE_PATH);
+    }
+
+    fn on_exit(&mut self, _gl: Option<&eframe::glow::Context>) {
+        let done = self.twitch.writer().shutdown(Self::PART_ON_EXIT);
+        if done.recv_timeout(Self::SHUTDOWN_TIMEOUT).is_err() {
+            log!("connection did not shut down in time")
+        }
+
+        self.autosave.save(&self.state, Self::STATE_PATH);
     }
 
     fn persist_egui_memory(&self) -> bool {
@@ -0,0 +1,69 @@
+use crate::{
+    db::{self, Integrity, Repaired},
+    resolver::Fut,
+};
+
+/// A history that hasn't been checked yet, or that failed its check
+pub enum DbRepair {
+    /// The check is running off the ui thread, the stand-in is used until it's done
+    Checking(Fut<Integrity>),
+    /// What the check found, waiting on the user to decide
+    Damaged(Vec<String>),
+    /// The user put it off, the stand-in is used until they come back to it
+    Postponed(Vec<String>),
+    Repairing(Fut<anyhow::Result<Repaired>>),
+}
+
+impl DbRepair {
+    /// The in-memory history used while the file is checked, or while it's damaged
+    ///
+    /// The cache is shared, so the readers off the ui thread see what the ui thread wrote to it
+    pub const STAND_IN: &'static str = "file:vohiyo-stand-in?mode=memory&cache=shared";
+
+    /// Start checking the history, this hands back the stand-in to write to meanwhile
+    ///
+    /// A large history can take a while to check and to migrate, so this doesn't wait on it.
+    /// The migrations run here once, after that the history is only opened
+    pub fn open(db: &'static str) -> (db::Connection, Self) {
+        let fut = Fut::spawn(async move {
+            let check = move || match db::Connection::check(db) {
+                Integrity::Ok => match db::Connection::create(db) {
+                    Ok(..) => Integrity::Ok,
+                    Err(err) => Integrity::Damaged(vec![err.to_string()]),
+                },
+                damaged => damaged,
+            };
+            tokio::task::spawn_blocking(check)
+                .await
+                .unwrap_or_else(|err| Integrity::Damaged(vec![err.to_string()]))
+        });
+        // nothing touches the disk, so there's nothing for this to fail on
+        let conn = db::Connection::create(Self::STAND_IN).expect("in-memory history");
+        (conn, Self::Checking(fut))
+    }
+
+    /// Back up and rebuild the history off the ui thread
+    pub fn repair(db: &'static str) -> Self {
+        Self::Repairing(Fut::spawn(async move {
+            tokio::task::spawn_blocking(move || db::Connection::repair(db))
+                .await
+                .unwrap_or_else(|err| Err(anyhow::anyhow!("the rebuild crashed: {err}")))
+        }))
+    }
+
+    /// The result of the rebuild, once it's done
+    pub fn poll(&mut self) -> Option<anyhow::Result<Repaired>> {
+        match self {
+            Self::Checking(..) | Self::Damaged(..) | Self::Postponed(..) => None,
+            Self::Repairing(fut) => fut.try_resolve(),
+        }
+    }
+
+    /// What the check found, once it's done
+    pub fn poll_check(&mut self) -> Option<Integrity> {
+        match self {
+            Self::Checking(fut) => fut.try_resolve(),
+            _ => None,
+        }
+    }
+}
@@ -0,0 +1,185 @@
+use std::path::{Path, PathBuf};
+
+use egui::{FontData, FontDefinitions, FontFamily};
+
+use crate::{resolver::Fut, state::Preferences};
+
+/// A font file found in one of the system's font directories
+#[derive(Clone, Debug)]
+pub struct SystemFont {
+    pub name: String,
+    pub path: PathBuf,
+}
+
+/// Which font and size the text is drawn with
+#[derive(Clone, Debug, PartialEq)]
+struct Applied {
+    path: Option<PathBuf>,
+    size: f32,
+}
+
+/// Builds the font definitions from the preferences, and finds the fonts that can be picked
+#[derive(Default)]
+pub struct FontManager {
+    discovery: Option<Fut<Vec<SystemFont>>>,
+    system: Option<Vec<SystemFont>>,
+    applied: Option<Applied>,
+}
+
+impl FontManager {
+    const PICKED: &'static str = "picked";
+
+    /// The fonts found on the system, this starts looking for them on the first call
+    pub fn system_fonts(&mut self) -> Option<&[SystemFont]> {
+        if self.system.is_none() && self.discovery.is_none() {
+            self.discovery.replace(Fut::spawn(async {
+                tokio::task::spawn_blocking(Self::discover)
+                    .await
+                    .unwrap_or_default()
+            }));
+        }
+
+        if let Some(fonts) = self.discovery.as_mut().and_then(|fut| fut.try_resolve()) {
+            log!("found {} system fonts", fonts.len());
+            self.system.replace(fonts);
+            self.discovery.take();
+        }
+
+        self.system.as_deref()
+    }
+
+    /// Swap the fonts in, unless they're already in use
+    pub fn apply(
+        &mut self,
+        ctx: &egui::Context,
+        path: Option<&Path>,
+        size: f32,
+    ) -> anyhow::Result<()> {
+        let next = Applied {
+            path: path.map(ToOwned::to_owned),
+            size,
+        };
+        if self.applied.as_ref() == Some(&next) {
+            return Ok(());
+        }
+
+        // even when the picked font can't be read the size is still applied
+        let (picked, error) = match path.map(std::fs::read).transpose() {
+            Ok(picked) => (picked, None),
+            Err(err) => (None, Some(err)),
+        };
+        ctx.set_fonts(Self::definitions(picked));
+
+        let scale = size / Preferences::DEFAULT_FONT_SIZE;
+        let mut style = (*ctx.style()).clone();
+        style.text_styles = egui::Style::default()
+            .text_styles
+            .into_iter()
+            .map(|(style, mut font)| {
+                font.size *= scale;
+                (style, font)
+            })
+            .collect();
+        ctx.set_style(style);
+
+        self.applied.replace(next);
+        match (error, path) {
+            (Some(err), Some(path)) => {
+                anyhow::bail!("cannot load the font at {}: {err}", path.display())
+            }
+            _ => Ok(()),
+        }
+    }
+
+    fn definitions(picked: Option<Vec<u8>>) -> FontDefinitions {
+        let mut fonts = FontDefinitions::empty();
+
+        macro_rules! load_font {
+            ($($font:expr => $entry:expr),*) => {
+                $(
+                    fonts.font_data.insert($font.into(), FontData::from_static(
+                        include_bytes!(concat!(env!("CARGO_MANIFEST_DIR"), "/fonts/", $font, ".ttf")))
+                    );
+                    fonts.families.entry($entry).or_default().push($font.into());
+                )*
+            };
+        }
+
+        load_font! {
+            "Roboto-Regular"     => FontFamily::Proportional,
+            "RobotoMono-Regular" => FontFamily::Monospace,
+            "RobotoMono-Bold"    => FontFamily::Name("bold".into())
+        }
+
+        // the bundled font stays behind it, for the glyphs the picked font doesn't have
+        if let Some(data) = picked {
+            fonts
+                .font_data
+                .insert(Self::PICKED.into(), FontData::from_owned(data));
+            fonts
+                .families
+                .entry(FontFamily::Proportional)
+                .or_default()
+                .insert(0, Self::PICKED.into());
+        }
+
+        fonts
+    }
+
+    fn font_dirs() -> Vec<PathBuf> {
+        let home = std::env::var_os("HOME").map(PathBuf::from);
+        if cfg!(target_os = "windows") {
+            let windir = std::env::var_os("WINDIR").unwrap_or_else(|| "C:\\Windows".into());
+            let mut dirs = vec![PathBuf::from(windir).join("Fonts")];
+            if let Some(local) = std::env::var_os("LOCALAPPDATA") {
+                dirs.push(PathBuf::from(local).join("Microsoft/Windows/Fonts"));
+            }
+            dirs
+        } else if cfg!(target_os = "macos") {
+            let mut dirs = vec!["/System/Library/Fonts".into(), "/Library/Fonts".into()];
+            dirs.extend(home.map(|home| home.join("Library/Fonts")));
+            dirs
+        } else {
+            let mut dirs = vec!["/usr/share/fonts".into(), "/usr/local/share/fonts".into()];
+            dirs.extend(home.iter().map(|home| home.join(".local/share/fonts")));
+            dirs.extend(home.map(|home| home.join(".fonts")));
+            dirs
+        }
+    }
+
+    // this goes by the file names, so a family with several files shows up once per file
+    fn discover() -> Vec<SystemFont> {
+        let mut fonts = vec![];
+        let mut queue = Self::font_dirs();
+        while let Some(dir) = queue.pop() {
+            let Ok(entries) = std::fs::read_dir(&dir) else {
+                continue;
+            };
+            for path in entries.flatten().map(|entry| entry.path()) {
+                if path.is_dir() {
+                    queue.push(path);
+                    continue;
+                }
+
+                // egui can only read truetype and opentype fonts
+                let supported = path
+                    .extension()
+                    .and_then(|ext| ext.to_str())
+                    .map_or(false, |ext| {
+                        matches!(&*ext.to_ascii_lowercase(), "ttf" | "otf")
+                    });
+                let name = path.file_stem().and_then(|name| name.to_str());
+                if let (true, Some(name)) = (supported, name) {
+                    fonts.push(SystemFont {
+                        name: name.to_string(),
+                        path,
+                    });
+                }
+            }
+        }
+
+        fonts.sort_by_cached_key(|font| font.name.to_lowercase());
+        fonts.dedup_by(|left, right| left.name == right.name);
+        fonts
+    }
+}
@@ -0,0 +1,113 @@
+use std::{
+    collections::VecDeque,
+    time::{Duration, Instant},
+};
+
+/// Timings for the last few hundred frames, for the frame time overlay
+pub struct FrameStats {
+    times: VecDeque<Duration>,
+    started: Option<Instant>,
+    rows: usize,
+    last_rows: usize,
+    textures: usize,
+    uploads: usize,
+    long_frames: VecDeque<LongFrame>,
+}
+
+pub struct LongFrame {
+    pub when: time::OffsetDateTime,
+    pub channel: Option<String>,
+    pub took: Duration,
+}
+
+impl Default for FrameStats {
+    fn default() -> Self {
+        Self {
+            times: VecDeque::with_capacity(Self::HISTORY),
+            started: None,
+            rows: 0,
+            last_rows: 0,
+            textures: 0,
+            uploads: 0,
+            long_frames: VecDeque::with_capacity(Self::LONG_FRAMES),
+        }
+    }
+}
+
+impl FrameStats {
+    const HISTORY: usize = 300;
+    const LONG_FRAMES: usize = 50;
+    // a couple of frames at 60hz
+    pub const LONG_FRAME: Duration = Duration::from_millis(33);
+
+    pub fn begin_frame(&mut self) {
+        self.started.replace(Instant::now());
+        self.rows = 0;
+    }
+
+    /// Count the message rows that were laid out, only the ones in view are
+    pub fn add_rows(&mut self, rows: usize) {
+        self.rows += rows;
+    }
+
+    /// `offset` is the local one, for when the long frames happened
+    pub fn end_frame(
+        &mut self,
+        ctx: &egui::Context,
+        channel: Option<&str>,
+        offset: time::UtcOffset,
+    ) {
+        let Some(started) = self.started.take() else { return };
+        let took = started.elapsed();
+
+        if self.times.len() == Self::HISTORY {
+            self.times.pop_front();
+        }
+        self.times.push_back(took);
+        self.last_rows = self.rows;
+
+        // anything that grew the texture count was uploaded this frame
+        let textures = ctx.tex_manager().read().num_allocated();
+        self.uploads = textures.saturating_sub(self.textures);
+        self.textures = textures;
+
+        if took >= Self::LONG_FRAME {
+            log!("long frame: {took:.2?} in {}", channel.unwrap_or("-"));
+            if self.long_frames.len() == Self::LONG_FRAMES {
+                self.long_frames.pop_front();
+            }
+            self.long_frames.push_back(LongFrame {
+                when: time::OffsetDateTime::now_utc().to_offset(offset),
+                channel: channel.map(ToString::to_string),
+                took,
+            });
+        }
+    }
+
+    /// The frame time that `p` (0.0 to 1.0) of the recent frames were under
+    pub fn percentile(&self, p: f32) -> Option<Duration> {
+        if self.times.is_empty() {
+            return None;
+        }
+        let mut times = self.times.iter().copied().collect::<Vec<_>>();
+        times.sort_unstable();
+        let index = ((times.len() - 1) as f32 * p.clamp(0.0, 1.0)).round() as usize;
+        Some(times[index])
+    }
+
+    pub const fn rows(&self) -> usize {
+        self.last_rows
+    }
+
+    pub const fn textures(&self) -> usize {
+        self.textures
+    }
+
+    pub const fn uploads(&self) -> usize {
+        self.uploads
+    }
+
+    pub fn long_frames(&self) -> impl DoubleEndedIterator<Item = &LongFrame> {
+        self.long_frames.iter()
+    }
+}
@@ -0,0 +1,315 @@
+use std::path::Path;
+
+use indexmap::{IndexMap, IndexSet};
+use serde_json::Value;
+
+use crate::{
+    automod::{AutomodConfig, AutomodRule},
+    state::{Rule, State},
+    twitch::{self, ChannelName},
+};
+
+use super::Theme;
+
+/// How an imported file is combined with what's already here
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum ImportMode {
+    /// Lists are combined, and settings in the file win
+    Merge,
+    /// Only what's in the file is kept, open channels that aren't in it are closed
+    Replace,
+}
+
+/// Everything that makes up a setup, in one file for moving it to another machine
+///
+/// The saved state, theme and automod rules are kept in the same shape as their own files
+#[derive(Debug, Default, serde::Serialize, serde::Deserialize)]
+pub struct SettingsExport {
+    pub version: u32,
+    #[serde(default)]
+    pub state: Value,
+    #[serde(default)]
+    pub theme: Value,
+    #[serde(default)]
+    pub automod: Value,
+    /// The credentials from the environment, these are only written out when asked for
+    #[serde(default, skip_serializing_if = "IndexMap::is_empty")]
+    pub secrets: IndexMap<String, String>,
+}
+
+impl SettingsExport {
+    const VERSION: u32 = 1;
+    const SECRETS: [&str; 5] = [
+        "TWITCH_NAME",
+        "TWITCH_OAUTH",
+        "TWITCH_REFRESH_TOKEN",
+        "TWITCH_CLIENT_ID",
+        "TWITCH_CLIENT_SECRET",
+    ];
+
+    pub fn new(
+        state: &State,
+        theme: &Theme,
+        automod: &AutomodConfig,
+        include_secrets: bool,
+    ) -> anyhow::Result<Self> {
+        let state = crate::state::SavedState { state }.serialize();
+        let state = toml::from_str::<toml::Value>(&state)?;
+
+        let secrets = Self::SECRETS
+            .into_iter()
+            .filter(|_| include_secrets)
+            .filter_map(|key| Some((key.to_string(), std::env::var(key).ok()?)))
+            .collect();
+
+        Ok(Self {
+            version: Self::VERSION,
+            state: serde_json::to_value(state)?,
+            theme: serde_json::to_value(theme)?,
+            automod: serde_json::to_value(automod)?,
+            secrets,
+        })
+    }
+
+    pub fn save(&self, path: impl AsRef<Path>) -> anyhow::Result<()> {
+        let data = serde_json::to_string_pretty(self)?;
+        std::fs::write(path, data)?;
+        Ok(())
+    }
+
+    pub fn load(path: impl AsRef<Path>) -> anyhow::Result<Self> {
+        let data = std::fs::read_to_string(path)?;
+        let this = serde_json::from_str::<Self>(&data)?;
+        anyhow::ensure!(
+            this.version <= Self::VERSION,
+            "settings are from a newer version ({})",
+            this.version
+        );
+        Ok(this)
+    }
+
+    /// Combine the file with the current settings, sections missing from the file are left alone
+    pub fn combine(mut self, current: Self, mode: ImportMode) -> anyhow::Result<Self> {
+        if mode == ImportMode::Merge {
+            StateLists::merge(&current.state, &mut self.state)?;
+            AutomodLists::merge(&current.automod, &mut self.automod)?;
+        }
+
+        for (imported, current) in [
+            (&mut self.state, current.state),
+            (&mut self.theme, current.theme),
+            (&mut self.automod, current.automod),
+        ] {
+            match (imported.is_null(), mode) {
+                (true, _) => *imported = current,
+                (false, ImportMode::Merge) => *imported = merge(current, std::mem::take(imported)),
+                (false, ImportMode::Replace) => {}
+            }
+        }
+        Ok(self)
+    }
+
+    /// The state section, as the state file would have it
+    pub fn state_toml(&self) -> anyhow::Result<String> {
+        let value = toml::Value::try_from(&self.state)?;
+        Ok(toml::to_string_pretty(&value)?)
+    }
+
+    pub fn theme(&self) -> anyhow::Result<Theme> {
+        Ok(serde_json::from_value(self.theme.clone())?)
+    }
+
+    pub fn automod(&self) -> anyhow::Result<AutomodConfig> {
+        Ok(serde_json::from_value(self.automod.clone())?)
+    }
+
+    /// Write the credentials into the secrets file, keeping any other lines that are in it
+    ///
+    /// They're picked up the next time the credentials are reloaded. Only the keys this would
+    /// export are written, anything else in the file is skipped
+    pub fn write_secrets(&self) -> anyhow::Result<()> {
+        let mut secrets = vec![];
+        for (key, value) in &self.secrets {
+            if !Self::SECRETS.contains(&key.as_str()) {
+                log!("not importing unknown secret: {key}");
+                continue;
+            }
+            secrets.push((key.as_str(), value.as_str()));
+        }
+        twitch::Config::store_secrets(secrets)
+    }
+}
+
+// the lists in a section are merged as what they hold rather than as json, so entries that only
+// differ in how they were written (the case of a channel, a field left at its default) are
+// still the same entry
+trait Lists: Default + serde::Serialize + for<'de> serde::Deserialize<'de> {
+    // the imported entries that aren't here yet go after the current ones
+    fn extend(&mut self, imported: Self);
+
+    // the imported section gets the combined lists, the rest of it is merged after this
+    fn merge(current: &Value, imported: &mut Value) -> anyhow::Result<()> {
+        if imported.is_null() {
+            return Ok(());
+        }
+
+        let mut lists = match current {
+            Value::Null => Self::default(),
+            current => serde_json::from_value(current.clone())?,
+        };
+        lists.extend(serde_json::from_value(imported.clone())?);
+
+        let (Value::Object(imported), Value::Object(lists)) =
+            (imported, serde_json::to_value(lists)?)
+        else {
+            anyhow::bail!("expected a table")
+        };
+        imported.extend(lists);
+        Ok(())
+    }
+}
+
+#[derive(Default, serde::Serialize, serde::Deserialize)]
+struct StateLists {
+    #[serde(default)]
+    channels: IndexSet<ChannelName>,
+    #[serde(default)]
+    read_only: IndexSet<ChannelName>,
+    #[serde(default)]
+    favorites: IndexSet<ChannelName>,
+    #[serde(default)]
+    watchlist: IndexSet<ChannelName>,
+    #[serde(default)]
+    highlight_keywords: IndexSet<String>,
+    #[serde(default)]
+    ignored_users: IndexSet<String>,
+    #[serde(default)]
+    notify_rules: Vec<Rule>,
+}
+
+impl Lists for StateLists {
+    fn extend(&mut self, imported: Self) {
+        self.channels.extend(imported.channels);
+        self.read_only.extend(imported.read_only);
+        self.favorites.extend(imported.favorites);
+        self.watchlist.extend(imported.watchlist);
+        self.highlight_keywords.extend(imported.highlight_keywords);
+        self.ignored_users.extend(imported.ignored_users);
+        extend_unique(&mut self.notify_rules, imported.notify_rules);
+    }
+}
+
+#[derive(Default, serde::Serialize, serde::Deserialize)]
+struct AutomodLists {
+    #[serde(default, rename = "rule")]
+    rules: Vec<AutomodRule>,
+}
+
+impl Lists for AutomodLists {
+    fn extend(&mut self, imported: Self) {
+        extend_unique(&mut self.rules, imported.rules);
+    }
+}
+
+fn extend_unique<T: PartialEq>(current: &mut Vec<T>, imported: Vec<T>) {
+    for item in imported {
+        if !current.contains(&item) {
+            current.push(item);
+        }
+    }
+}
+
+// tables are merged key by key. the lists were already combined by `Lists`, so they're taken as
+// they are
+fn merge(current: Value, imported: Value) -> Value {
+    match (current, imported) {
+        (Value::Object(mut current), Value::Object(imported)) => {
+            for (key, value) in imported {
+                let merged = match current.remove(&key) {
+                    Some(existing) => merge(existing, value),
+                    None => value,
+                };
+                current.insert(key, merged);
+            }
+            Value::Object(current)
+        }
+        (_, imported) => imported,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn export(state: &str, automod: &str) -> SettingsExport {
+        let section = |data| {
+            let value = toml::from_str::<toml::Value>(data).unwrap();
+            serde_json::to_value(value).unwrap()
+        };
+        SettingsExport {
+            version: SettingsExport::VERSION,
+            state: section(state),
+            automod: section(automod),
+            ..SettingsExport::default()
+        }
+    }
+
+    #[test]
+    fn merging_twice_adds_nothing() {
+        let current = export(
+            r#"
+            channels = ["museun"]
+            favorites = ["museun"]
+            highlight_keywords = ["hello"]
+
+            [[notify_rules]]
+            trigger = "mention"
+            action = "sound"
+            "#,
+            r#"
+            dry_run = true
+
+            [[rule]]
+            pattern = "spam"
+            action = "delete"
+            "#,
+        );
+
+        // the same entries written differently, and one that's new
+        let file = || {
+            export(
+                r#"
+                channels = ["Museun", "shaken_bot"]
+                favorites = ["MUSEUN"]
+                highlight_keywords = ["hello", "world"]
+
+                [[notify_rules]]
+                enabled = true
+                trigger = "mention"
+                action = "sound"
+                "#,
+                r#"
+                [[rule]]
+                pattern = "spam"
+                unless_badges = []
+                action = "delete"
+                "#,
+            )
+        };
+
+        let once = file().combine(current, ImportMode::Merge).unwrap();
+        let state = once.state.clone();
+        let automod = once.automod.clone();
+        let twice = file().combine(once, ImportMode::Merge).unwrap();
+
+        assert_eq!(twice.state, state);
+        assert_eq!(twice.automod, automod);
+
+        let lists = serde_json::from_value::<StateLists>(twice.state).unwrap();
+        assert_eq!(lists.channels.len(), 2);
+        assert_eq!(lists.favorites.len(), 1);
+        assert_eq!(lists.highlight_keywords.len(), 2);
+        assert_eq!(lists.notify_rules.len(), 1);
+        assert_eq!(twice.automod().unwrap().rules.len(), 1);
+    }
+}
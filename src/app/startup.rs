@@ -0,0 +1,52 @@
+use std::time::{Duration, Instant};
+
+/// How long each part of starting up took, for the diagnostics window
+pub struct Startup {
+    started: Instant,
+    last: Instant,
+    phases: Vec<(String, Duration)>,
+    first_frame: Option<Duration>,
+}
+
+impl Startup {
+    pub fn begin() -> Self {
+        let now = Instant::now();
+        Self {
+            started: now,
+            last: now,
+            phases: Vec::new(),
+            first_frame: None,
+        }
+    }
+
+    /// Record everything since the last phase under this name
+    pub fn phase(&mut self, name: impl ToString) {
+        let now = Instant::now();
+        self.phases.push((name.to_string(), now - self.last));
+        self.last = now;
+    }
+
+    /// Record something that finished in the background
+    pub fn record(&mut self, name: impl ToString, elapsed: Duration) {
+        self.phases.push((name.to_string(), elapsed));
+    }
+
+    /// Returns true only the first time it's called
+    pub fn first_frame(&mut self) -> bool {
+        if self.first_frame.is_some() {
+            return false;
+        }
+        let elapsed = self.started.elapsed();
+        log!("first frame after {elapsed:.2?}");
+        self.first_frame.replace(elapsed);
+        true
+    }
+
+    pub fn time_to_first_frame(&self) -> Option<Duration> {
+        self.first_frame
+    }
+
+    pub fn phases(&self) -> impl Iterator<Item = (&str, Duration)> {
+        self.phases.iter().map(|(name, dt)| (name.as_str(), *dt))
+    }
+}
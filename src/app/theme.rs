@@ -0,0 +1,266 @@
+use std::path::Path;
+
+use egui::{pos2, Color32, Painter, Rect, Shape, Stroke, Visuals};
+
+#[derive(Clone, Debug, PartialEq, serde::Serialize, serde::Deserialize)]
+#[serde(default)]
+pub struct Theme {
+    /// Which preset the colors below start from, unset roles use the preset's
+    pub dark: bool,
+    #[serde(with = "hex_color")]
+    pub accent: Color32,
+    #[serde(with = "hex_color")]
+    pub marker: Color32,
+    #[serde(with = "hex_color")]
+    pub live: Color32,
+    #[serde(with = "hex_color::option", skip_serializing_if = "Option::is_none")]
+    pub background: Option<Color32>,
+    /// The color of message text
+    #[serde(with = "hex_color::option", skip_serializing_if = "Option::is_none")]
+    pub text: Option<Color32>,
+    /// Behind messages that mention us, this defaults to a faded marker color
+    #[serde(with = "hex_color::option", skip_serializing_if = "Option::is_none")]
+    pub mention: Option<Color32>,
+    /// Behind the messages we sent, these aren't set apart when it's unset
+    #[serde(with = "hex_color::option", skip_serializing_if = "Option::is_none")]
+    pub own_message: Option<Color32>,
+    /// Replaces the colors above with ones that can be told apart with color blindness
+    pub palette: Palette,
+    pub live_shape: LiveShape,
+}
+
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Palette {
+    #[default]
+    Custom,
+    RedGreen,
+    BlueYellow,
+}
+
+impl Palette {
+    pub const ALL: [Self; 3] = [Self::Custom, Self::RedGreen, Self::BlueYellow];
+
+    pub const fn label(&self) -> &'static str {
+        match self {
+            Self::Custom => "theme colors",
+            Self::RedGreen => "red-green safe",
+            Self::BlueYellow => "blue-yellow safe",
+        }
+    }
+
+    // these are from the Okabe-Ito palette
+    const fn colors(&self) -> Option<[Color32; 3]> {
+        const BLUE: Color32 = Color32::from_rgb(0x00, 0x72, 0xB2);
+        const ORANGE: Color32 = Color32::from_rgb(0xE6, 0x9F, 0x00);
+        const YELLOW: Color32 = Color32::from_rgb(0xF0, 0xE4, 0x42);
+        const GREEN: Color32 = Color32::from_rgb(0x00, 0x9E, 0x73);
+        const VERMILLION: Color32 = Color32::from_rgb(0xD5, 0x5E, 0x00);
+        const PURPLE: Color32 = Color32::from_rgb(0xCC, 0x79, 0xA7);
+
+        match self {
+            Self::Custom => None,
+            Self::RedGreen => Some([BLUE, YELLOW, ORANGE]),
+            Self::BlueYellow => Some([GREEN, PURPLE, VERMILLION]),
+        }
+    }
+}
+
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum LiveShape {
+    #[default]
+    Dot,
+    Square,
+    Triangle,
+    Diamond,
+}
+
+impl LiveShape {
+    pub const ALL: [Self; 4] = [Self::Dot, Self::Square, Self::Triangle, Self::Diamond];
+
+    pub const fn label(&self) -> &'static str {
+        match self {
+            Self::Dot => "dot",
+            Self::Square => "square",
+            Self::Triangle => "triangle",
+            Self::Diamond => "diamond",
+        }
+    }
+
+    pub fn paint(&self, painter: &Painter, rect: Rect, fill: Color32, stroke: impl Into<Stroke>) {
+        let (c, r) = (rect.center(), rect.width() * 0.5);
+        let points = match self {
+            Self::Dot => {
+                painter.circle(c, r, fill, stroke);
+                return;
+            }
+            Self::Square => {
+                painter.rect(rect.shrink(r * 0.15), 0.0, fill, stroke);
+                return;
+            }
+            Self::Triangle => vec![
+                pos2(c.x, c.y - r),
+                pos2(c.x + r, c.y + r * 0.8),
+                pos2(c.x - r, c.y + r * 0.8),
+            ],
+            Self::Diamond => vec![
+                pos2(c.x, c.y - r),
+                pos2(c.x + r, c.y),
+                pos2(c.x, c.y + r),
+                pos2(c.x - r, c.y),
+            ],
+        };
+        painter.add(Shape::convex_polygon(points, fill, stroke));
+    }
+}
+
+impl Default for Theme {
+    fn default() -> Self {
+        Self {
+            dark: true,
+            accent: Color32::BLUE,
+            marker: Color32::RED,
+            live: Color32::RED,
+            background: None,
+            text: None,
+            mention: None,
+            own_message: None,
+            palette: Palette::default(),
+            live_shape: LiveShape::default(),
+        }
+    }
+}
+
+impl Theme {
+    /// The colors of a preset, keeping the accessibility options
+    pub fn preset(&self, dark: bool) -> Self {
+        Self {
+            dark,
+            palette: self.palette,
+            live_shape: self.live_shape,
+            ..Self::default()
+        }
+    }
+
+    pub fn load(path: impl AsRef<Path>) -> Option<Self> {
+        let data = std::fs::read_to_string(path).ok()?;
+        toml::from_str(&data)
+            .map_err(|err| log!("invalid theme: {err}"))
+            .ok()
+    }
+
+    pub fn save(&self, path: impl AsRef<Path>) {
+        let path = path.as_ref();
+        let data = toml::to_string_pretty(self).expect("valid serialization");
+        if let Err(err) = std::fs::write(path, data) {
+            log!("cannot save theme to {}: {err}", path.display())
+        }
+    }
+
+    pub fn accent(&self) -> Color32 {
+        self.palette
+            .colors()
+            .map_or(self.accent, |[accent, ..]| accent)
+    }
+
+    pub fn marker(&self) -> Color32 {
+        self.palette
+            .colors()
+            .map_or(self.marker, |[_, marker, _]| marker)
+    }
+
+    pub fn live(&self) -> Color32 {
+        self.palette.colors().map_or(self.live, |[.., live]| live)
+    }
+
+    pub fn mention(&self) -> Color32 {
+        self.mention
+            .unwrap_or_else(|| self.marker().linear_multiply(0.15))
+    }
+
+    fn visuals(&self) -> Visuals {
+        if self.dark {
+            Visuals::dark()
+        } else {
+            Visuals::light()
+        }
+    }
+
+    pub fn background(&self) -> Color32 {
+        self.background.unwrap_or_else(|| self.visuals().panel_fill)
+    }
+
+    pub fn text(&self) -> Color32 {
+        self.text
+            .unwrap_or_else(|| self.visuals().widgets.noninteractive.fg_stroke.color)
+    }
+
+    pub fn apply(&self, ctx: &egui::Context) {
+        let mut visuals = self.visuals();
+        if let Some(background) = self.background {
+            visuals.panel_fill = background;
+            visuals.window_fill = background;
+        }
+        if let Some(text) = self.text {
+            visuals.widgets.noninteractive.fg_stroke.color = text;
+        }
+        ctx.set_visuals(visuals);
+    }
+}
+
+mod hex_color {
+    use egui::Color32;
+
+    pub fn serialize<S>(color: &Color32, ser: S) -> Result<S::Ok, S::Error>
+    where
+        S: serde::Serializer,
+    {
+        let [r, g, b, _] = color.to_array();
+        ser.serialize_str(&format!("#{r:02X}{g:02X}{b:02X}"))
+    }
+
+    pub fn deserialize<'de, D>(de: D) -> Result<Color32, D::Error>
+    where
+        D: serde::Deserializer<'de>,
+    {
+        use serde::Deserialize as _;
+        parse(&String::deserialize(de)?)
+    }
+
+    fn parse<E: serde::de::Error>(s: &str) -> Result<Color32, E> {
+        let s = s.strip_prefix('#').unwrap_or(s);
+        let [r, g, b] = match u32::from_str_radix(s, 16) {
+            Ok(color) if s.len() == 6 => {
+                let [_, r, g, b] = color.to_be_bytes();
+                [r, g, b]
+            }
+            _ => return Err(E::custom(format!("invalid color: {s}"))),
+        };
+        Ok(Color32::from_rgb(r, g, b))
+    }
+
+    pub mod option {
+        use egui::Color32;
+
+        pub fn serialize<S>(color: &Option<Color32>, ser: S) -> Result<S::Ok, S::Error>
+        where
+            S: serde::Serializer,
+        {
+            match color {
+                Some(color) => super::serialize(color, ser),
+                None => ser.serialize_none(),
+            }
+        }
+
+        pub fn deserialize<'de, D>(de: D) -> Result<Option<Color32>, D::Error>
+        where
+            D: serde::Deserializer<'de>,
+        {
+            use serde::Deserialize as _;
+            Option::<String>::deserialize(de)?
+                .map(|s| super::parse(&s))
+                .transpose()
+        }
+    }
+}
@@ -0,0 +1,85 @@
+use std::time::{Duration, Instant};
+
+use crate::{
+    helix::{self, data::RefreshedToken},
+    resolver::Fut,
+};
+
+/// Gets a new chat token before the current one expires, when there's a refresh token for it
+#[derive(Default)]
+pub struct TokenRefresh {
+    due: Option<Instant>,
+    pending: Option<Fut<anyhow::Result<RefreshedToken>>>,
+    // a refresh token that was turned down won't work the next time either
+    rejected: bool,
+    // refreshes in a row that failed for some other reason, e.g. the network being down
+    failures: u32,
+}
+
+impl TokenRefresh {
+    // early enough that nothing gets to use the old token after it expires
+    const MARGIN: Duration = Duration::from_secs(5 * 60);
+    // doubled for each failure in a row, up to the max
+    const RETRY: Duration = Duration::from_secs(10);
+    const MAX_RETRY: Duration = Duration::from_secs(10 * 60);
+
+    /// Refresh a while before the token expires, tokens that don't expire are left alone
+    pub fn schedule(&mut self, expires_in: Duration) {
+        self.due = (!expires_in.is_zero())
+            .then(|| Instant::now() + expires_in.saturating_sub(Self::MARGIN));
+    }
+
+    /// Refresh on the next poll, e.g. once the token has been turned down
+    pub fn refresh_now(&mut self) {
+        self.due.replace(Instant::now());
+    }
+
+    /// Start a refresh when it's due, and hand back the new token once it arrives
+    pub fn poll(
+        &mut self,
+        helix: &helix::Client,
+        refresh_token: Option<&str>,
+    ) -> Option<RefreshedToken> {
+        if let Some(fut) = &mut self.pending {
+            let result = fut.try_resolve()?;
+            self.pending.take();
+            return match result {
+                Ok(token) => {
+                    self.failures = 0;
+                    Some(token)
+                }
+                Err(err) if Self::is_rejection(&err) => {
+                    log!("the refresh token was turned down: {err}");
+                    self.rejected = true;
+                    None
+                }
+                Err(err) => {
+                    let delay = Self::RETRY
+                        .saturating_mul(1 << self.failures.min(16))
+                        .min(Self::MAX_RETRY);
+                    self.failures += 1;
+                    log!("cannot refresh the chat token, trying again in {delay:?}: {err}");
+                    self.due.replace(Instant::now() + delay);
+                    None
+                }
+            };
+        }
+
+        let refresh_token = refresh_token.filter(|_| !self.rejected)?;
+        if self.due.map_or(true, |due| due > Instant::now()) {
+            return None;
+        }
+
+        log!("refreshing the chat token");
+        self.due.take();
+        self.pending.replace(helix.refresh_user_token(refresh_token));
+        None
+    }
+
+    // the id server answers a bad or revoked refresh token with one of these
+    fn is_rejection(err: &anyhow::Error) -> bool {
+        err.downcast_ref::<reqwest::Error>()
+            .and_then(reqwest::Error::status)
+            .map_or(false, |status| matches!(status.as_u16(), 400 | 401))
+    }
+}
@@ -0,0 +1,58 @@
+use crate::runtime::TaskExit;
+
+/// The background tasks that are meant to keep running for as long as we do
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Task {
+    Chat,
+    StreamCheck,
+    Emotes,
+    SevenTv,
+    Images,
+}
+
+impl Task {
+    pub const fn label(&self) -> &'static str {
+        match self {
+            Self::Chat => "the chat connection",
+            Self::StreamCheck => "checking which streams are live",
+            Self::Emotes => "looking up emotes",
+            Self::SevenTv => "following 7TV emote changes",
+            Self::Images => "fetching images",
+        }
+    }
+}
+
+/// Which of the background tasks are gone, so that's shown rather than features quietly dying
+#[derive(Default)]
+pub struct Watchdog {
+    down: Vec<(Task, TaskExit)>,
+}
+
+impl Watchdog {
+    /// Update what's down, this returns the tasks that just went down
+    pub fn update(
+        &mut self,
+        tasks: impl IntoIterator<Item = (Task, Option<TaskExit>)>,
+    ) -> Vec<Task> {
+        let mut new = vec![];
+        for (task, exit) in tasks {
+            let pos = self.down.iter().position(|&(t, _)| t == task);
+            match (exit, pos) {
+                (Some(exit), None) => {
+                    log!("watchdog: {} {}", task.label(), exit.label());
+                    self.down.push((task, exit));
+                    new.push(task);
+                }
+                (None, Some(pos)) => {
+                    self.down.remove(pos);
+                }
+                _ => {}
+            }
+        }
+        new
+    }
+
+    pub fn down(&self) -> &[(Task, TaskExit)] {
+        &self.down
+    }
+}
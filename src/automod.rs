@@ -0,0 +1,286 @@
+use std::{
+    io::Write as _,
+    path::{Path, PathBuf},
+};
+
+use regex::Regex;
+use tokio::sync::mpsc::{unbounded_channel, UnboundedSender};
+use twitch_message::messages::Privmsg;
+
+use crate::{
+    helix,
+    twitch::{ChannelName, Identity},
+};
+
+/// Rules that are applied to every message in channels we moderate
+///
+/// ```toml
+/// dry_run = false
+///
+/// [[rule]]
+/// channel = "museun"
+/// pattern = "(?i)buy followers"
+/// unless_badges = ["subscriber", "vip"]
+/// action = { timeout = 600 }
+/// reason = "spam"
+/// ```
+#[derive(Clone, Debug, serde::Serialize, serde::Deserialize)]
+pub struct AutomodConfig {
+    /// Only write what would've happened to the audit log
+    #[serde(default = "dry_run")]
+    pub dry_run: bool,
+    #[serde(default, rename = "rule")]
+    pub rules: Vec<AutomodRule>,
+}
+
+const fn dry_run() -> bool {
+    true
+}
+
+impl Default for AutomodConfig {
+    fn default() -> Self {
+        Self {
+            dry_run: dry_run(),
+            rules: Vec::new(),
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+pub struct AutomodRule {
+    /// Only for this channel, otherwise every channel we moderate
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub channel: Option<String>,
+    pub pattern: String,
+    /// Senders with any of these badges are left alone
+    #[serde(default)]
+    pub unless_badges: Vec<String>,
+    pub action: Penalty,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub reason: Option<String>,
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Penalty {
+    Delete,
+    Timeout(u32),
+    Ban,
+}
+
+impl std::fmt::Display for Penalty {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Delete => f.write_str("delete"),
+            Self::Timeout(seconds) => write!(f, "timeout {seconds}s"),
+            Self::Ban => f.write_str("ban"),
+        }
+    }
+}
+
+pub struct Automod {
+    pub config: AutomodConfig,
+    // these line up with the rules, a rule with an invalid pattern never matches
+    patterns: Vec<Option<Regex>>,
+    // the audit log is in local time
+    offset: time::UtcOffset,
+    writer: Writer,
+}
+
+enum Write {
+    Audit(String),
+    Rules(PathBuf, String),
+}
+
+// the files are written on a task of their own, in the order they were asked for
+#[derive(Clone)]
+struct Writer(UnboundedSender<Write>);
+
+impl Writer {
+    fn spawn() -> Self {
+        let (tx, mut rx) = unbounded_channel();
+        tokio::spawn(async move {
+            while let Some(write) = rx.recv().await {
+                let _ = tokio::task::spawn_blocking(move || Self::write(write)).await;
+            }
+        });
+        Self(tx)
+    }
+
+    fn send(&self, write: Write) {
+        let _ = self.0.send(write);
+    }
+
+    fn write(write: Write) {
+        match write {
+            Write::Audit(line) => {
+                let result = std::fs::OpenOptions::new()
+                    .create(true)
+                    .append(true)
+                    .open(Automod::AUDIT_LOG_PATH)
+                    .and_then(|mut file| writeln!(file, "{line}"));
+                if let Err(err) = result {
+                    log!("cannot write to {}: {err}", Automod::AUDIT_LOG_PATH)
+                }
+            }
+            Write::Rules(path, data) => {
+                if let Err(err) = std::fs::write(&path, data) {
+                    log!("cannot save automod rules to {}: {err}", path.display())
+                }
+            }
+        }
+    }
+}
+
+impl Automod {
+    pub const PATH: &str = "automod.toml";
+    const AUDIT_LOG_PATH: &str = "automod.log";
+
+    pub fn load(path: impl AsRef<Path>, offset: time::UtcOffset) -> Self {
+        let config = std::fs::read_to_string(path)
+            .ok()
+            .and_then(|data| {
+                toml::from_str(&data)
+                    .map_err(|err| log!("invalid automod rules: {err}"))
+                    .ok()
+            })
+            .unwrap_or_default();
+        Self::new(config, offset)
+    }
+
+    pub fn new(config: AutomodConfig, offset: time::UtcOffset) -> Self {
+        let patterns = config
+            .rules
+            .iter()
+            .map(|rule| {
+                Regex::new(&rule.pattern)
+                    .map_err(|err| log!("invalid automod pattern '{}': {err}", rule.pattern))
+                    .ok()
+            })
+            .collect();
+        Self {
+            config,
+            patterns,
+            offset,
+            writer: Writer::spawn(),
+        }
+    }
+
+    /// The rules are written off the ui thread
+    pub fn save(&self, path: impl AsRef<Path>) {
+        let data = toml::to_string_pretty(&self.config).expect("valid serialization");
+        self.writer.send(Write::Rules(path.as_ref().to_path_buf(), data));
+    }
+
+    /// Apply the first matching rule to the message, if we're a moderator in its channel
+    ///
+    /// Moderation has to be done with the chat token, so it needs the
+    /// `moderator:manage:banned_users` and `moderator:manage:chat_messages` scopes
+    pub fn check(
+        &self,
+        msg: &Privmsg<'_>,
+        broadcaster_id: &str,
+        identity: &Identity,
+        helix: &helix::Client,
+        token: &str,
+    ) {
+        fn is_moderator<'a>(mut badges: impl Iterator<Item = &'a str>) -> bool {
+            badges.any(|badge| matches!(badge, "moderator" | "broadcaster"))
+        }
+
+        let channel = ChannelName::new(&msg.channel);
+        if self.config.rules.is_empty() || !identity.is_moderator(&channel) {
+            return;
+        }
+
+        let badges = msg.badges().collect::<Vec<_>>();
+        let badges = || badges.iter().map(|badge| badge.name.as_str());
+        // other moderators are never acted on
+        if is_moderator(badges()) {
+            return;
+        }
+
+        let Some((index, rule)) = self
+            .config
+            .rules
+            .iter()
+            .enumerate()
+            .filter(|(_, rule)| rule.channel.as_deref().map_or(true, |c| channel == c))
+            .filter(|(_, rule)| !badges().any(|b| rule.unless_badges.iter().any(|u| u == b)))
+            .find(|&(i, _)| {
+                self.patterns[i]
+                    .as_ref()
+                    .filter(|re| re.is_match(&msg.data))
+                    .is_some()
+            })
+        else {
+            return;
+        };
+
+        let entry = format!(
+            "#{channel} {sender}: {action} (rule {index}) {data:?}",
+            sender = msg.sender,
+            action = rule.action,
+            data = &*msg.data,
+        );
+
+        if self.config.dry_run {
+            self.audit(&format!("dry run: {entry}"));
+            return;
+        }
+
+        let (Some(user_id), Some(msg_id)) = (msg.user_id(), msg.msg_id()) else {
+            self.audit(&format!("skipped, missing ids: {entry}"));
+            return;
+        };
+
+        let reason = rule.reason.as_deref().unwrap_or("automod");
+        let fut = match rule.action {
+            Penalty::Delete => {
+                helix.delete_message(token, broadcaster_id, &identity.user_id, msg_id.as_str())
+            }
+            Penalty::Timeout(seconds) => helix.ban_user(
+                token,
+                broadcaster_id,
+                &identity.user_id,
+                user_id.as_str(),
+                Some(seconds),
+                reason,
+            ),
+            Penalty::Ban => helix.ban_user(
+                token,
+                broadcaster_id,
+                &identity.user_id,
+                user_id.as_str(),
+                None,
+                reason,
+            ),
+        };
+
+        // the outcome only matters for the audit log
+        let (offset, writer) = (self.offset, self.writer.clone());
+        tokio::spawn(async move {
+            match fut.wait().await {
+                Some(Ok(())) => Self::audit_to(&writer, offset, &entry),
+                Some(Err(err)) => {
+                    Self::audit_to(&writer, offset, &format!("failed ({err}): {entry}"))
+                }
+                None => {}
+            }
+        });
+    }
+
+    fn audit(&self, entry: &str) {
+        Self::audit_to(&self.writer, self.offset, entry)
+    }
+
+    fn audit_to(writer: &Writer, offset: time::UtcOffset, entry: &str) {
+        log!("automod: {entry}");
+
+        let now = time::OffsetDateTime::now_utc()
+            .to_offset(offset)
+            .format(&time::format_description::well_known::Rfc3339)
+            .unwrap_or_default();
+        writer.send(Write::Audit(format!("{now} {entry}")));
+    }
+}
@@ -1,15 +1,31 @@
-#[tokio::main]
-async fn main() {
-    simple_env_load::load_env_from([".dev.env", ".secrets.env"]);
-    let config = vohiyo::twitch::Config {
-        name: std::env::var("TWITCH_NAME").expect("'TWITCH_NAME' must be set'"),
-        token: std::env::var("TWITCH_OAUTH").expect("'TWITCH_OAUTH' must be set'"),
-    };
+fn main() {
+    // so a panic anywhere below still leaves a report
+    vohiyo::crash::install();
+
+    // this can't be read once there are other threads, so it's read before the runtime starts
+    let local_offset = time::UtcOffset::current_local_offset().unwrap_or(time::UtcOffset::UTC);
+    let runtime = tokio::runtime::Runtime::new().expect("tokio runtime");
+    let _runtime = runtime.enter();
+
+    let link = std::env::args()
+        .skip(1)
+        .find_map(|arg| vohiyo::Permalink::parse(&arg));
+    if let Some(link) = &link {
+        if let Err(err) = vohiyo::url_handler::enter_state_dir() {
+            eprintln!("cannot find the state directory: {err}");
+        }
+        // another client on the same account would fight this one for the connection
+        if vohiyo::url_handler::forward(link) {
+            return;
+        }
+    }
+
+    let config = vohiyo::twitch::Config::load().unwrap_or_else(|err| panic!("{err}"));
 
     eframe::run_native(
         &format!("VoHiYo - {name}", name = config.name,),
         eframe::NativeOptions::default(),
-        Box::new(|cc| vohiyo::App::create(cc, config)),
+        Box::new(move |cc| vohiyo::App::create(cc, config, link, local_offset)),
     )
     .unwrap();
 }
@@ -0,0 +1,71 @@
+use std::{
+    panic::PanicInfo,
+    path::{Path, PathBuf},
+};
+
+const CRASH_DIR: &str = "crashes";
+const PENDING: &str = "pending";
+
+/// Write a crash report whenever anything panics, including background tasks
+///
+/// This has to be called from the main thread, only its panics are offered on the next launch.
+/// A background task panicking doesn't take the app down with it, so those are only written out
+pub fn install() {
+    let main = std::thread::current().id();
+    let previous = std::panic::take_hook();
+    std::panic::set_hook(Box::new(move |info| {
+        match write_report(info, std::thread::current().id() == main) {
+            Some(path) => eprintln!("wrote crash report to {}", path.display()),
+            None => eprintln!("cannot write crash report"),
+        }
+        previous(info)
+    }));
+}
+
+/// The report from a previous crash the user hasn't seen yet
+pub fn take_pending() -> Option<PathBuf> {
+    let pending = Path::new(CRASH_DIR).join(PENDING);
+    let path = std::fs::read_to_string(&pending).ok()?;
+    let _ = std::fs::remove_file(pending);
+    std::fs::canonicalize(path.trim()).ok()
+}
+
+fn write_report(info: &PanicInfo<'_>, pending: bool) -> Option<PathBuf> {
+    use std::fmt::Write as _;
+
+    std::fs::create_dir_all(CRASH_DIR).ok()?;
+
+    let now = time::OffsetDateTime::now_utc();
+    let path = Path::new(CRASH_DIR).join(format!("crash-{}.txt", now.unix_timestamp()));
+
+    let mut report = String::new();
+    let _ = writeln!(
+        report,
+        "{name} {version}",
+        name = env!("CARGO_PKG_NAME"),
+        version = env!("CARGO_PKG_VERSION")
+    );
+    let _ = writeln!(report, "{now}\n");
+    let _ = writeln!(
+        report,
+        "thread '{thread}' {info}\n",
+        thread = std::thread::current().name().unwrap_or("<unnamed>")
+    );
+    let _ = writeln!(report, "{}\n", std::backtrace::Backtrace::force_capture());
+
+    let _ = writeln!(report, "recent log:");
+    for line in crate::logger::recent() {
+        let _ = writeln!(report, "{line}");
+    }
+
+    std::fs::write(&path, report).ok()?;
+    if !pending {
+        return Some(path);
+    }
+    std::fs::write(
+        Path::new(CRASH_DIR).join(PENDING),
+        path.to_string_lossy().as_bytes(),
+    )
+    .ok()?;
+    Some(path)
+}
@@ -0,0 +1,114 @@
+use tokio::sync::mpsc::{unbounded_channel, UnboundedSender};
+use uuid::Uuid;
+
+use super::{Connection, History, Message};
+
+/// Messages saved by the user, these point into the history
+pub struct Bookmarks<'a> {
+    conn: &'a Connection,
+}
+
+impl<'a> Bookmarks<'a> {
+    pub(in crate::db) const fn new(conn: &'a Connection) -> Self {
+        Self { conn }
+    }
+
+    pub fn add(&self, msg_id: Uuid) -> bool {
+        let Connection { conn, .. } = self.conn;
+
+        let mut stmt = conn
+            .prepare(
+                "insert or ignore into bookmarks(msg_id, timestamp)
+                values (:msg_id, :timestamp);",
+            )
+            .expect("valid sql");
+
+        match stmt.execute(rusqlite::named_params! {
+            ":msg_id": msg_id,
+            ":timestamp": time::OffsetDateTime::now_utc(),
+        }) {
+            Ok(n) => n == 1,
+            Err(err) => {
+                log!("cannot bookmark {msg_id}: {err}");
+                false
+            }
+        }
+    }
+
+    pub fn remove(&self, msg_id: Uuid) -> bool {
+        let Connection { conn, .. } = self.conn;
+
+        let mut stmt = conn
+            .prepare("delete from bookmarks where msg_id = :msg_id;")
+            .expect("valid sql");
+
+        match stmt.execute(rusqlite::named_params! {":msg_id": msg_id}) {
+            Ok(n) => n == 1,
+            Err(err) => {
+                log!("cannot remove the bookmark for {msg_id}: {err}");
+                false
+            }
+        }
+    }
+
+    pub fn get_ids(&self) -> Vec<Uuid> {
+        let Connection { conn, .. } = self.conn;
+
+        let mut stmt = conn
+            .prepare("select msg_id from bookmarks;")
+            .expect("valid sql");
+        let Ok(iter) = stmt.query_map([], |row| row.get("msg_id")) else {
+            return vec![];
+        };
+        iter.flatten().collect()
+    }
+
+    /// The bookmarked messages, newest bookmark first
+    ///
+    /// Bookmarks for messages that aren't in the history anymore are left out
+    pub fn get_messages(&self) -> Vec<Message> {
+        let Connection { conn, .. } = self.conn;
+
+        let mut stmt = conn
+            .prepare(
+                "select history.* from bookmarks
+                inner join history on history.msg_id = bookmarks.msg_id
+                order by bookmarks.rowid desc;",
+            )
+            .expect("valid sql");
+        let Ok(iter) = stmt.query_map([], History::message_from_row) else {
+            return vec![];
+        };
+        iter.flatten().collect()
+    }
+}
+
+/// Adds and removes bookmarks off the ui thread, in the order they were toggled
+#[derive(Clone)]
+pub struct BookmarkWriter {
+    tx: UnboundedSender<(&'static str, Uuid, bool)>,
+}
+
+impl BookmarkWriter {
+    pub fn spawn() -> Self {
+        let (tx, mut rx) = unbounded_channel::<(&'static str, Uuid, bool)>();
+        tokio::spawn(async move {
+            while let Some((db, msg_id, bookmarked)) = rx.recv().await {
+                let write = move || match Connection::open(db) {
+                    // these log what went wrong themselves
+                    Ok(conn) if bookmarked => _ = conn.bookmarks().add(msg_id),
+                    Ok(conn) => _ = conn.bookmarks().remove(msg_id),
+                    Err(err) => log!("cannot open the history: {err}"),
+                };
+                // one at a time, so a quick add and remove can't land the other way around
+                let _ = tokio::task::spawn_blocking(write).await;
+            }
+        });
+        Self { tx }
+    }
+
+    /// Bookmark the message in `db`, or remove its bookmark
+    pub fn set(&self, db: &'static str, msg_id: Uuid, bookmarked: bool) {
+        let _ = self.tx.send((db, msg_id, bookmarked));
+    }
+}
@@ -1,38 +1,172 @@
-use super::History;
+use std::time::Duration;
+
+use rusqlite::OpenFlags;
+use uuid::Uuid;
+
+use crate::{resolver::Fut, twitch::ChannelName};
+
+use super::{migrations, Bookmarks, History, HistoryQuery, Message, UserChannel};
 
 pub struct Connection {
     pub(in crate::db) conn: rusqlite::Connection,
 }
 
 impl Connection {
-    const SCHEMA: &str = "
-        create table if not exists history(
-            room_id     text not null,
-            channel     text not null,
-            user_id     text not null,
-            msg_id      blob unique not null,
-            timestamp   blob not null,
-            data        text not null,
-            login       text not null,
-            raw         text not null,
-            deleted     bool
-        );
-    ";
-
-    pub fn create(db: &str) -> Self {
-        let conn = rusqlite::Connection::open(db).expect("open db");
-        let this = Self { conn };
-        this.ensure_table();
-        this
-    }
-
-    fn ensure_table(&self) {
-        let Self { conn, .. } = self;
-        conn.execute_batch(Self::SCHEMA)
-            .expect("ensure table schema is valid");
+    // the readers off the ui thread wait this long on a write before giving up
+    const BUSY_TIMEOUT: Duration = Duration::from_secs(5);
+
+    /// Open the database and bring its schema up to date
+    ///
+    /// The migrations can read the whole history, so this should be done once and off the ui
+    /// thread, everything after that uses [`Connection::open`]
+    pub fn create(db: &str) -> anyhow::Result<Self> {
+        let mut conn = rusqlite::Connection::open(db)?;
+        // readers and the writer don't block each other with a write-ahead log
+        conn.pragma_update_and_check(None, "journal_mode", "wal", |row| row.get::<_, String>(0))?;
+        conn.busy_timeout(Self::BUSY_TIMEOUT)?;
+        migrations::run(&mut conn)?;
+        Ok(Self { conn })
+    }
+
+    /// Open a database that [`Connection::create`] already migrated
+    pub fn open(db: &str) -> anyhow::Result<Self> {
+        let conn = rusqlite::Connection::open(db)?;
+        conn.busy_timeout(Self::BUSY_TIMEOUT)?;
+        Ok(Self { conn })
+    }
+
+    // the readers never write, so they don't need the migrations or the log to be set up
+    fn open_read_only(db: &str) -> anyhow::Result<Self> {
+        // the stand-in for a damaged history is a uri
+        let flags = OpenFlags::SQLITE_OPEN_READ_ONLY
+            | OpenFlags::SQLITE_OPEN_URI
+            | OpenFlags::SQLITE_OPEN_NO_MUTEX;
+        let conn = rusqlite::Connection::open_with_flags(db, flags)?;
+        conn.busy_timeout(Self::BUSY_TIMEOUT)?;
+        Ok(Self { conn })
+    }
+
+    // a connection of its own, on a blocking task
+    fn read<T>(db: &'static str, read: impl FnOnce(&Self) -> T + Send + 'static) -> Fut<Option<T>>
+    where
+        T: Send + 'static,
+    {
+        Fut::spawn(async move {
+            let task = tokio::task::spawn_blocking(move || match Self::open_read_only(db) {
+                Ok(this) => Some(read(&this)),
+                Err(err) => {
+                    log!("cannot open the history: {err}");
+                    None
+                }
+            });
+            task.await.ok().flatten()
+        })
     }
 
     pub const fn history(&self) -> History<'_> {
         History::new(self)
     }
+
+    pub const fn bookmarks(&self) -> Bookmarks<'_> {
+        Bookmarks::new(self)
+    }
+
+    /// The most recent messages for a channel, read on its own connection off the ui thread
+    ///
+    /// This goes by room id when it's known, so renamed channels keep their history.
+    /// `map` runs on the same blocking task, so anything slow done to the messages can go there
+    pub fn recent_messages<T>(
+        db: &'static str,
+        room_id: Option<String>,
+        channel: ChannelName,
+        limit: usize,
+        map: impl FnOnce(Vec<Message>) -> T + Send + 'static,
+    ) -> Fut<Option<T>>
+    where
+        T: Send + 'static,
+    {
+        Self::read(db, move |this| {
+            let history = this.history();
+            map(match room_id {
+                Some(room_id) => history.get_room_id_messages(&room_id, limit),
+                None => history.get_channel_messages(&channel, limit),
+            })
+        })
+    }
+
+    /// The messages for a channel from before `before`, like [`Connection::recent_messages`]
+    pub fn messages_before<T>(
+        db: &'static str,
+        room_id: Option<String>,
+        channel: ChannelName,
+        before: Uuid,
+        limit: usize,
+        map: impl FnOnce(Vec<Message>) -> T + Send + 'static,
+    ) -> Fut<Option<T>>
+    where
+        T: Send + 'static,
+    {
+        Self::read(db, move |this| {
+            map(this
+                .history()
+                .get_messages_before(room_id.as_deref(), &channel, before, limit))
+        })
+    }
+
+    /// Search a channel's history on its own connection off the ui thread, see
+    /// [`History::search_channel`]
+    pub fn search_messages(
+        db: &'static str,
+        room_id: Option<String>,
+        channel: ChannelName,
+        query: String,
+        limit: usize,
+    ) -> Fut<Option<Vec<Message>>> {
+        Self::read(db, move |this| {
+            this.history()
+                .search_channel(room_id.as_deref(), &channel, &query, limit)
+        })
+    }
+
+    /// Search all of the history on its own connection off the ui thread, see [`History::search`]
+    pub fn search_history(
+        db: &'static str,
+        query: HistoryQuery,
+        page: usize,
+        per_page: usize,
+    ) -> Fut<Option<Vec<Message>>> {
+        Self::read(db, move |this| {
+            this.history().search(&query, page, per_page)
+        })
+    }
+
+    /// A message from the history, off the ui thread, see [`History::get_by_msg_id`]
+    pub fn message_by_id(db: &'static str, id: Uuid) -> Fut<Option<Option<Message>>> {
+        Self::read(db, move |this| this.history().get_by_msg_id(id))
+    }
+
+    /// The bookmarked messages, off the ui thread, see [`Bookmarks::get_messages`]
+    pub fn bookmarked_messages(db: &'static str) -> Fut<Option<Vec<Message>>> {
+        Self::read(db, |this| this.bookmarks().get_messages())
+    }
+
+    /// What a user said in a channel, off the ui thread, see [`History::get_messages_for_user`]
+    pub fn messages_for_user(
+        db: &'static str,
+        room_id: String,
+        user_id: String,
+        limit: usize,
+    ) -> Fut<Option<Vec<Message>>> {
+        Self::read(db, move |this| {
+            this.history()
+                .get_messages_for_user(&room_id, &user_id, limit)
+        })
+    }
+
+    /// The channels a user has spoken in, off the ui thread, see [`History::get_channels_for_user`]
+    pub fn channels_for_user(db: &'static str, user_id: String) -> Fut<Option<Vec<UserChannel>>> {
+        Self::read(db, move |this| {
+            this.history().get_channels_for_user(&user_id)
+        })
+    }
 }
@@ -1,6 +1,40 @@
 use uuid::Uuid;
 
-use super::{Connection, InsertMessage, Message};
+use crate::twitch::ChannelName;
+
+use super::{Connection, InsertMessage, Message, RecentChannel, UserChannel};
+
+/// What to look for across all of the history, the empty parts match anything
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct HistoryQuery {
+    pub text: String,
+    pub login: String,
+    pub channel: String,
+}
+
+impl HistoryQuery {
+    pub fn is_empty(&self) -> bool {
+        [&self.text, &self.login, &self.channel]
+            .iter()
+            .all(|s| s.trim().is_empty())
+    }
+
+    // each word is quoted, so the user's text isn't read as the fts query syntax
+    fn match_expr(&self) -> Option<String> {
+        let words = self
+            .text
+            .split_whitespace()
+            .map(|word| format!("\"{}\"", word.replace('"', "\"\"")))
+            .collect::<Vec<_>>();
+        (!words.is_empty()).then(|| words.join(" "))
+    }
+
+    // older rows can have the channel with a `#` in front, so it's looked for both ways
+    fn optional(s: &str) -> Option<String> {
+        let s = s.trim().trim_start_matches('#');
+        (!s.is_empty()).then(|| s.to_lowercase())
+    }
+}
 
 pub struct History<'a> {
     conn: &'a Connection,
@@ -11,22 +45,33 @@ impl<'a> History<'a> {
         Self { conn }
     }
 
-    pub fn insert<'t>(&self, msg: impl Into<InsertMessage<'t>>) {
+    pub fn insert<'t, M>(&self, msg: M) -> bool
+    where
+        M: TryInto<InsertMessage<'t>>,
+        M::Error: std::fmt::Display,
+    {
+        let msg = match msg.try_into() {
+            Ok(msg) => msg,
+            Err(err) => {
+                log!("not storing message: {err}");
+                return false;
+            }
+        };
+
         let Connection { conn, .. } = self.conn;
 
         let mut stmt = conn
             .prepare(
                 r#"
                     insert into history(
-                        room_id, channel, user_id, msg_id, timestamp, data, login, raw, deleted
+                        room_id, channel, user_id, msg_id, timestamp, data, login, raw, deleted, parsed
                     ) values (
-                        :room_id, :channel, :user_id, :msg_id, :timestamp, :data, :login, :raw, :deleted
+                        :room_id, :channel, :user_id, :msg_id, :timestamp, :data, :login, :raw, :deleted, :parsed
                     );
                 "#,
             )
             .expect("valid sql");
 
-        let msg = msg.into();
         let res = stmt.execute(rusqlite::named_params! {
             ":room_id": msg.room_id,
             ":channel": msg.channel,
@@ -37,9 +82,20 @@ impl<'a> History<'a> {
             ":login": msg.login,
             ":raw": msg.raw,
             ":deleted": false,
+            ":parsed": msg.parsed,
         });
 
-        assert!(matches!(res, Ok(1)), "invalid database state")
+        match res {
+            Ok(1) => true,
+            Ok(n) => {
+                log!("expected to insert 1 message, inserted {n}");
+                false
+            }
+            Err(err) => {
+                log!("cannot insert message: {err}");
+                false
+            }
+        }
     }
 
     pub fn delete(&self, msg_id: Uuid) -> bool {
@@ -49,9 +105,41 @@ impl<'a> History<'a> {
             .prepare("update history set deleted = true where msg_id = :msg_id")
             .expect("valid sql");
 
-        1 == stmt
-            .execute(rusqlite::named_params! {":msg_id": msg_id})
-            .expect("valid query")
+        match stmt.execute(rusqlite::named_params! {":msg_id": msg_id}) {
+            Ok(n) => n == 1,
+            Err(err) => {
+                log!("cannot delete {msg_id}: {err}");
+                false
+            }
+        }
+    }
+
+    /// Mark these messages as deleted, like [`History::delete`]
+    ///
+    /// This returns how many were marked
+    pub fn delete_many(&self, msg_ids: &[Uuid]) -> usize {
+        msg_ids
+            .iter()
+            .filter(|&&msg_id| self.delete(msg_id))
+            .count()
+    }
+
+    /// Mark everything a user said in a channel as deleted, for when they're banned
+    pub fn delete_for_user(&self, room_id: &str, user_id: &str) -> usize {
+        let Connection { conn, .. } = self.conn;
+
+        let mut stmt = conn
+            .prepare(
+                "update history set deleted = true
+                where room_id = :room_id and user_id = :user_id and not deleted",
+            )
+            .expect("valid sql");
+
+        stmt.execute(rusqlite::named_params! {":room_id": room_id, ":user_id": user_id})
+            .unwrap_or_else(|err| {
+                log!("cannot delete the messages from {user_id}: {err}");
+                0
+            })
     }
 
     pub fn get_by_msg_id(&self, msg_id: Uuid) -> Option<Message> {
@@ -88,6 +176,44 @@ impl<'a> History<'a> {
         )
     }
 
+    // sqlite takes the bare msg_id from the same row as the max(rowid)
+    pub fn get_channels_for_user(&self, user_id: &str) -> Vec<UserChannel> {
+        self.get_many(
+            "select channel, count(*) as count, msg_id, max(rowid)
+                from history
+                where user_id = :user_id
+                group by channel
+                order by count desc;",
+            rusqlite::named_params! {":user_id": user_id},
+            |row| {
+                Ok(UserChannel {
+                    channel: row.get("channel")?,
+                    count: row.get::<_, i64>("count")? as usize,
+                    last_msg_id: row.get("msg_id")?,
+                })
+            },
+        )
+    }
+
+    pub fn get_recent_channels(&self, limit: usize) -> Vec<RecentChannel> {
+        self.get_many(
+            &format!(
+                "select channel, room_id, max(rowid) as last
+                    from history
+                    group by channel
+                    order by last desc
+                    limit {limit};"
+            ),
+            [],
+            |row| {
+                Ok(RecentChannel {
+                    channel: row.get("channel")?,
+                    room_id: row.get("room_id")?,
+                })
+            },
+        )
+    }
+
     pub fn get_room_id_messages(&self, room_id: &str, limit: usize) -> Vec<Message> {
         self.get_many(
             &format!(
@@ -103,9 +229,7 @@ impl<'a> History<'a> {
         )
     }
 
-    pub fn get_channel_messages(&self, channel: &str, limit: usize) -> Vec<Message> {
-        let channel = channel.strip_prefix('#').unwrap_or(channel);
-
+    pub fn get_channel_messages(&self, channel: &ChannelName, limit: usize) -> Vec<Message> {
         self.get_many(
             &format!(
                 "select * from(
@@ -120,6 +244,106 @@ impl<'a> History<'a> {
         )
     }
 
+    /// The `limit` messages in a channel stored before `before`, for scrolling further back
+    ///
+    /// This goes by room id when it's known, like the most recent messages do
+    pub fn get_messages_before(
+        &self,
+        room_id: Option<&str>,
+        channel: &ChannelName,
+        before: Uuid,
+        limit: usize,
+    ) -> Vec<Message> {
+        self.get_many(
+            &format!(
+                "select * from(
+                        select rowid, * from history
+                        where (room_id = :room_id or (:room_id is null and channel = :channel))
+                            and rowid < (select rowid from history where msg_id = :before)
+                        order by rowid desc
+                        limit {limit}
+                    ) order by rowid asc;"
+            ),
+            rusqlite::named_params! {
+                ":room_id": room_id,
+                ":channel": channel,
+                ":before": before,
+            },
+            Self::message_from_row,
+        )
+    }
+
+    /// Messages in a channel with `query` in them, ignoring case, the newest `limit` of them
+    pub fn search_channel(
+        &self,
+        room_id: Option<&str>,
+        channel: &ChannelName,
+        query: &str,
+        limit: usize,
+    ) -> Vec<Message> {
+        // % and _ are wildcards to `like`, they're looked for as they are
+        let pattern = format!(
+            "%{}%",
+            query
+                .replace('\\', "\\\\")
+                .replace('%', "\\%")
+                .replace('_', "\\_")
+        );
+        self.get_many(
+            &format!(
+                "select * from(
+                        select rowid, * from history
+                        where (room_id = :room_id or (:room_id is null and channel = :channel))
+                            and data like :pattern escape '\\'
+                        order by rowid desc
+                        limit {limit}
+                    ) order by rowid asc;"
+            ),
+            rusqlite::named_params! {
+                ":room_id": room_id,
+                ":channel": channel,
+                ":pattern": pattern,
+            },
+            Self::message_from_row,
+        )
+    }
+
+    /// A page of the messages matching the query, newest first
+    pub fn search(&self, query: &HistoryQuery, page: usize, per_page: usize) -> Vec<Message> {
+        let offset = page * per_page;
+        let login = HistoryQuery::optional(&query.login);
+        let channel = HistoryQuery::optional(&query.channel);
+
+        // going through the index is only needed when there's text to look for
+        let Some(text) = query.match_expr() else {
+            return self.get_many(
+                &format!(
+                    "select rowid, * from history
+                        where (:login is null or login = :login)
+                            and (:channel is null or channel in (:channel, '#' || :channel))
+                        order by rowid desc
+                        limit {per_page} offset {offset};"
+                ),
+                rusqlite::named_params! {":login": login, ":channel": channel},
+                Self::message_from_row,
+            );
+        };
+
+        self.get_many(
+            &format!(
+                "select history.rowid, history.* from history_fts
+                    join history on history.rowid = history_fts.rowid
+                    where history_fts match :text
+                        and (:login is null or history.login = :login)
+                        and (:channel is null or history.channel in (:channel, '#' || :channel))
+                    order by history.rowid desc
+                    limit {per_page} offset {offset};"
+            ),
+            rusqlite::named_params! {":text": text, ":login": login, ":channel": channel},
+            Self::message_from_row,
+        )
+    }
+
     fn get_many<T>(
         &self,
         sql: &str,
@@ -134,7 +358,7 @@ impl<'a> History<'a> {
         iter.flatten().collect()
     }
 
-    fn message_from_row(row: &rusqlite::Row<'_>) -> rusqlite::Result<Message> {
+    pub(in crate::db) fn message_from_row(row: &rusqlite::Row<'_>) -> rusqlite::Result<Message> {
         Ok(Message {
             timestamp: row.get("timestamp")?,
             channel: row.get("channel")?,
@@ -145,6 +369,50 @@ impl<'a> History<'a> {
             data: row.get("data")?,
             raw: row.get("raw")?,
             deleted: row.get("deleted")?,
+            parsed: row.get("parsed")?,
         })
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn insert(conn: &Connection, room_id: &str, channel: &str, data: &str) {
+        let msg = InsertMessage {
+            msg_id: Uuid::new_v4(),
+            channel: ChannelName::new(channel),
+            user_id: "1",
+            room_id,
+            login: "someone",
+            data,
+            raw: "raw",
+            parsed: None,
+        };
+        assert!(conn.history().insert(msg));
+    }
+
+    #[test]
+    fn search_channel_keeps_to_the_room() {
+        let conn = Connection::create(":memory:").unwrap();
+        // the channel was renamed, and someone else took the old name
+        insert(&conn, "1", "museun", "hello from the first room");
+        insert(&conn, "2", "museun", "hello from the second room");
+        insert(&conn, "1", "renamed", "hello after the rename");
+
+        let found =
+            conn.history()
+                .search_channel(Some("1"), &ChannelName::new("renamed"), "hello", 10);
+        let found = found.iter().map(|msg| &*msg.data).collect::<Vec<_>>();
+        assert_eq!(
+            found,
+            ["hello from the first room", "hello after the rename"]
+        );
+
+        // without a room id it can only go by the name
+        let found = conn
+            .history()
+            .search_channel(None, &ChannelName::new("museun"), "hello", 10);
+        assert_eq!(found.len(), 2);
+    }
+}
@@ -1,35 +1,48 @@
 use twitch_message::messages::Privmsg;
 use uuid::Uuid;
 
+use crate::twitch::ChannelName;
+
 pub struct InsertMessage<'a> {
     pub msg_id: Uuid,
-    pub channel: &'a str,
+    pub channel: ChannelName,
     pub user_id: &'a str,
     pub room_id: &'a str,
     pub login: &'a str,
     pub data: &'a str,
     pub raw: &'a str,
+    pub parsed: Option<String>,
 }
 
-impl<'a> From<&'a Privmsg<'static>> for InsertMessage<'a> {
-    fn from(value: &'a Privmsg<'static>) -> Self {
+impl<'a> InsertMessage<'a> {
+    /// Store the parsed form too, so it doesn't have to be parsed again when it's restored
+    pub fn with_parsed(self, parsed: String) -> Self {
         Self {
+            parsed: Some(parsed),
+            ..self
+        }
+    }
+}
+
+impl<'a> TryFrom<&'a Privmsg<'static>> for InsertMessage<'a> {
+    type Error = &'static str;
+
+    fn try_from(value: &'a Privmsg<'static>) -> Result<Self, Self::Error> {
+        Ok(Self {
             msg_id: value
                 .msg_id()
-                .map(|id| Uuid::parse_str(id.as_str()))
-                .transpose()
-                .ok()
-                .flatten()
-                .expect("msg-id"),
-            channel: value.channel.strip_prefix('#').unwrap_or(&*value.channel),
+                .and_then(|id| Uuid::parse_str(id.as_str()).ok())
+                .ok_or("missing or invalid msg-id")?,
+            channel: ChannelName::new(&value.channel),
             user_id: value
                 .user_id()
                 .map(<twitch_message::messages::UserIdRef>::as_str)
-                .expect("user-id"),
-            room_id: value.room_id().expect("room-id"),
+                .ok_or("missing user-id")?,
+            room_id: value.room_id().ok_or("missing room-id")?,
             login: value.sender.as_str(),
             data: &*value.data,
             raw: &*value.raw,
-        }
+            parsed: None,
+        })
     }
 }
@@ -0,0 +1,166 @@
+use std::path::{Path, PathBuf};
+
+use rusqlite::{types::Value, OpenFlags};
+
+use super::Connection;
+
+/// What `pragma quick_check` had to say about a database
+#[derive(Debug)]
+pub enum Integrity {
+    Ok,
+    /// The problems it found, or why it couldn't be read at all
+    Damaged(Vec<String>),
+}
+
+/// Where the damaged database was copied to, and how much was kept from it
+#[derive(Debug)]
+pub struct Repaired {
+    pub backup: PathBuf,
+    pub messages: usize,
+    pub bookmarks: usize,
+}
+
+impl Connection {
+    const HISTORY_COLUMNS: &str =
+        "room_id, channel, user_id, msg_id, timestamp, data, login, raw, deleted, parsed";
+    const BOOKMARK_COLUMNS: &str = "msg_id, timestamp";
+
+    /// Check the database without changing it, a missing file is fine
+    pub fn check(db: &str) -> Integrity {
+        if !Path::new(db).exists() {
+            return Integrity::Ok;
+        }
+
+        let problems = rusqlite::Connection::open_with_flags(db, OpenFlags::SQLITE_OPEN_READ_ONLY)
+            .and_then(|conn| {
+                let mut stmt = conn.prepare("pragma quick_check")?;
+                let rows = stmt.query_map([], |row| row.get::<_, String>(0))?;
+                rows.collect::<Result<Vec<_>, _>>()
+            });
+
+        match problems {
+            Ok(problems) if problems.iter().all(|p| p == "ok") => Integrity::Ok,
+            Ok(problems) => Integrity::Damaged(problems),
+            Err(err) => Integrity::Damaged(vec![err.to_string()]),
+        }
+    }
+
+    /// Copy the database aside, then move every row that can still be read into a new one
+    ///
+    /// Nothing else should have the database open while this runs
+    pub fn repair(db: &str) -> anyhow::Result<Repaired> {
+        let now = time::OffsetDateTime::now_utc().unix_timestamp();
+        let backup = PathBuf::from(format!("{db}.{now}.bak"));
+        // what hasn't been checkpointed yet is only in the log, and sqlite replays the log it
+        // finds next to the copy when the copy is opened
+        for suffix in ["", "-wal", "-shm"] {
+            let from = format!("{db}{suffix}");
+            if suffix.is_empty() || Path::new(&from).exists() {
+                std::fs::copy(&from, format!("{}{suffix}", backup.display()))?;
+            }
+        }
+        log!("backed up the history to {}", backup.display());
+
+        let rebuild = format!("{db}.rebuild");
+        if Path::new(&rebuild).exists() {
+            std::fs::remove_file(&rebuild)?;
+        }
+
+        let (messages, bookmarks) = {
+            let damaged =
+                rusqlite::Connection::open_with_flags(db, OpenFlags::SQLITE_OPEN_READ_ONLY)?;
+            let mut fresh = Self::create(&rebuild)?;
+            let tx = fresh.conn.transaction()?;
+            let messages = Self::salvage(&damaged, &tx, "history", Self::HISTORY_COLUMNS);
+            let bookmarks = Self::salvage(&damaged, &tx, "bookmarks", Self::BOOKMARK_COLUMNS);
+            tx.commit()?;
+            (messages, bookmarks)
+        };
+
+        // what's left of the old log would be replayed on top of the new database
+        for suffix in ["-wal", "-shm"] {
+            let path = format!("{db}{suffix}");
+            if Path::new(&path).exists() {
+                std::fs::remove_file(path)?;
+            }
+        }
+        std::fs::rename(&rebuild, db)?;
+        log!("rebuilt the history with {messages} messages and {bookmarks} bookmarks");
+        Ok(Repaired {
+            backup,
+            messages,
+            bookmarks,
+        })
+    }
+
+    /// Copy what's in another database into this one, skipping the messages already here
+    ///
+    /// This returns how many messages were copied
+    pub fn merge_from(&self, other: &str) -> anyhow::Result<usize> {
+        self.conn.execute("attach database ?1 as other", [other])?;
+        let copied = (|| {
+            let (history, bookmarks) = (Self::HISTORY_COLUMNS, Self::BOOKMARK_COLUMNS);
+            let copied = self.conn.execute(
+                &format!(
+                    "insert or ignore into main.history({history})
+                    select {history} from other.history order by rowid"
+                ),
+                [],
+            )?;
+            self.conn.execute(
+                &format!(
+                    "insert or ignore into main.bookmarks({bookmarks})
+                    select {bookmarks} from other.bookmarks order by rowid"
+                ),
+                [],
+            )?;
+            rusqlite::Result::Ok(copied)
+        })();
+        let _ = self.conn.execute("detach database other", []);
+        Ok(copied?)
+    }
+
+    // a damaged page ends the read, everything before it is kept
+    fn salvage(
+        from: &rusqlite::Connection,
+        to: &rusqlite::Connection,
+        table: &str,
+        columns: &str,
+    ) -> usize {
+        let count = columns.split(',').count();
+        let Ok(mut select) = from.prepare(&format!("select {columns} from {table} order by rowid"))
+        else {
+            log!("cannot read {table} from the damaged history");
+            return 0;
+        };
+        let params = ["?"].repeat(count).join(", ");
+        let Ok(mut insert) = to.prepare(&format!(
+            "insert or ignore into {table}({columns}) values ({params})"
+        )) else {
+            return 0;
+        };
+
+        let Ok(rows) = select.query_map([], |row| {
+            (0..count)
+                .map(|i| row.get::<_, Value>(i))
+                .collect::<Result<Vec<_>, _>>()
+        }) else {
+            return 0;
+        };
+
+        let mut kept = 0;
+        for row in rows {
+            let row = match row {
+                Ok(row) => row,
+                Err(err) => {
+                    log!("stopped reading {table} after {kept} rows: {err}");
+                    break;
+                }
+            };
+            if insert.execute(rusqlite::params_from_iter(row)).is_ok() {
+                kept += 1;
+            }
+        }
+        kept
+    }
+}
@@ -1,14 +1,31 @@
 use uuid::Uuid;
 
+use crate::twitch::ChannelName;
+
 #[derive(Clone, Debug)]
 pub struct Message {
     pub timestamp: time::OffsetDateTime,
     pub msg_id: Uuid,
-    pub channel: Box<str>,
+    pub channel: ChannelName,
     pub user_id: Box<str>,
     pub room_id: Box<str>,
     pub login: Box<str>,
     pub data: Box<str>,
     pub raw: Box<str>,
     pub deleted: bool,
+    /// The spans, color and badges as json, see `state::Message::to_stored`
+    pub parsed: Option<Box<str>>,
+}
+
+#[derive(Clone, Debug)]
+pub struct RecentChannel {
+    pub channel: ChannelName,
+    pub room_id: Box<str>,
+}
+
+#[derive(Clone, Debug)]
+pub struct UserChannel {
+    pub channel: ChannelName,
+    pub count: usize,
+    pub last_msg_id: Uuid,
 }
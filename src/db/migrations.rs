@@ -0,0 +1,265 @@
+//! The schema, as the steps it took to get here
+//!
+//! The version a database is at is kept in its `user_version`. A change to the schema is a new
+//! step at the end of [`MIGRATIONS`], the ones already there can't change since databases out
+//! there have already run them.
+//!
+//! Databases from before this were at version 0, with some or all of the first steps done, so
+//! those steps have to be fine to run again.
+
+use rusqlite::Connection;
+
+struct Migration {
+    name: &'static str,
+    run: fn(&Connection) -> rusqlite::Result<()>,
+}
+
+const MIGRATIONS: &[Migration] = &[
+    Migration {
+        name: "create the history and bookmarks",
+        run: create_tables,
+    },
+    Migration {
+        name: "keep the parsed message",
+        run: add_parsed,
+    },
+    Migration {
+        name: "index the history for searching",
+        run: add_search_index,
+    },
+    Migration {
+        name: "index the history by user",
+        run: add_user_index,
+    },
+];
+
+/// The version the schema is at once every migration has run
+pub const LATEST: i64 = MIGRATIONS.len() as i64;
+
+/// The version the database is at
+pub fn version(conn: &Connection) -> rusqlite::Result<i64> {
+    conn.query_row("pragma user_version", [], |row| row.get(0))
+}
+
+/// Run the migrations the database hasn't had yet, each one on its own transaction
+///
+/// This returns the version the database ended up at
+pub fn run(conn: &mut Connection) -> rusqlite::Result<i64> {
+    let current = version(conn)?;
+    // a newer build was used on it, what it added is left alone
+    if current > LATEST {
+        log!("the history is at version {current}, newer than {LATEST}");
+        return Ok(current);
+    }
+
+    for (version, migration) in (1..).zip(MIGRATIONS).skip(current as usize) {
+        log!("migrating the history to {version}: {}", migration.name);
+        let tx = conn.transaction()?;
+        (migration.run)(&tx)?;
+        // pragmas can't take parameters
+        tx.execute_batch(&format!("pragma user_version = {version}"))?;
+        tx.commit()?;
+    }
+
+    Ok(LATEST)
+}
+
+fn create_tables(conn: &Connection) -> rusqlite::Result<()> {
+    conn.execute_batch(
+        "
+        create table if not exists history(
+            room_id     text not null,
+            channel     text not null,
+            user_id     text not null,
+            msg_id      blob unique not null,
+            timestamp   blob not null,
+            data        text not null,
+            login       text not null,
+            raw         text not null,
+            deleted     bool
+        );
+
+        create table if not exists bookmarks(
+            msg_id      blob unique not null,
+            timestamp   blob not null
+        );
+        ",
+    )
+}
+
+fn add_parsed(conn: &Connection) -> rusqlite::Result<()> {
+    if has_column(conn, "history", "parsed")? {
+        return Ok(());
+    }
+    conn.execute("alter table history add column parsed text", [])
+        .map(drop)
+}
+
+// the index only points at the rows in history, the triggers keep it in step
+fn add_search_index(conn: &Connection) -> rusqlite::Result<()> {
+    let indexed = conn.query_row(
+        "select count(*) from sqlite_master where name = 'history_fts'",
+        [],
+        |row| row.get::<_, i64>(0),
+    )? > 0;
+
+    conn.execute_batch(
+        "
+        create virtual table if not exists history_fts using fts5(
+            data, login, channel,
+            content = 'history',
+            content_rowid = 'rowid'
+        );
+
+        create trigger if not exists history_fts_insert after insert on history begin
+            insert into history_fts(rowid, data, login, channel)
+                values (new.rowid, new.data, new.login, new.channel);
+        end;
+
+        create trigger if not exists history_fts_delete after delete on history begin
+            insert into history_fts(history_fts, rowid, data, login, channel)
+                values ('delete', old.rowid, old.data, old.login, old.channel);
+        end;
+
+        create trigger if not exists history_fts_update
+        after update of data, login, channel on history begin
+            insert into history_fts(history_fts, rowid, data, login, channel)
+                values ('delete', old.rowid, old.data, old.login, old.channel);
+            insert into history_fts(rowid, data, login, channel)
+                values (new.rowid, new.data, new.login, new.channel);
+        end;
+        ",
+    )?;
+
+    // the messages from before the index existed are put in it once
+    if !indexed {
+        conn.execute(
+            "insert into history_fts(history_fts) values ('rebuild')",
+            [],
+        )?;
+    }
+    Ok(())
+}
+
+// looking up a user's channels and messages would read the whole table without these
+fn add_user_index(conn: &Connection) -> rusqlite::Result<()> {
+    conn.execute_batch(
+        "
+        create index if not exists history_user_id on history(user_id, channel);
+        create index if not exists history_login on history(login, channel);
+        ",
+    )
+}
+
+fn has_column(conn: &Connection, table: &str, column: &str) -> rusqlite::Result<bool> {
+    let mut stmt = conn.prepare(&format!("pragma table_info({table})"))?;
+    let mut names = stmt.query_map([], |row| row.get::<_, String>("name"))?;
+    names.try_fold(false, |found, name| Ok(found || name? == column))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // the tables as they were before the schema had a version
+    const V0_TABLES: &str = "
+        create table history(
+            room_id     text not null,
+            channel     text not null,
+            user_id     text not null,
+            msg_id      blob unique not null,
+            timestamp   blob not null,
+            data        text not null,
+            login       text not null,
+            raw         text not null,
+            deleted     bool
+        );
+
+        create table bookmarks(
+            msg_id      blob unique not null,
+            timestamp   blob not null
+        );
+
+        insert into history(room_id, channel, user_id, msg_id, timestamp, data, login, raw, deleted)
+            values ('1', 'museun', '2', x'01', x'00', 'hello world', 'someone', 'raw', false);
+        insert into bookmarks(msg_id, timestamp) values (x'01', x'00');
+    ";
+
+    fn v0(parsed: bool, indexed: bool) -> Connection {
+        let conn = Connection::open_in_memory().unwrap();
+        conn.execute_batch(V0_TABLES).unwrap();
+        if parsed {
+            conn.execute("alter table history add column parsed text", [])
+                .unwrap();
+        }
+        // the index was made the same way before it was a migration
+        if indexed {
+            add_search_index(&conn).unwrap();
+        }
+        assert_eq!(version(&conn).unwrap(), 0);
+        conn
+    }
+
+    fn has_index(conn: &Connection, name: &str) -> bool {
+        count(
+            conn,
+            &format!("select count(*) from sqlite_master where type = 'index' and name = '{name}'"),
+        ) > 0
+    }
+
+    fn count(conn: &Connection, sql: &str) -> i64 {
+        conn.query_row(sql, [], |row| row.get(0)).unwrap()
+    }
+
+    fn assert_migrated(mut conn: Connection) {
+        assert_eq!(run(&mut conn).unwrap(), LATEST);
+        assert_eq!(version(&conn).unwrap(), LATEST);
+
+        assert_eq!(count(&conn, "select count(*) from history"), 1);
+        assert_eq!(count(&conn, "select count(*) from bookmarks"), 1);
+        assert!(has_column(&conn, "history", "parsed").unwrap());
+        assert_eq!(
+            count(
+                &conn,
+                "select count(*) from history_fts where history_fts match 'hello'"
+            ),
+            1
+        );
+        assert!(has_index(&conn, "history_user_id"));
+        assert!(has_index(&conn, "history_login"));
+
+        // running it again doesn't do anything
+        assert_eq!(run(&mut conn).unwrap(), LATEST);
+        assert_eq!(count(&conn, "select count(*) from history"), 1);
+    }
+
+    #[test]
+    fn migrate_v0_without_parsed_or_index() {
+        assert_migrated(v0(false, false));
+    }
+
+    #[test]
+    fn migrate_v0_with_parsed_and_index() {
+        assert_migrated(v0(true, true));
+    }
+
+    #[test]
+    fn migrate_v0_with_parsed_without_index() {
+        assert_migrated(v0(true, false));
+    }
+
+    #[test]
+    fn migrate_new_database() {
+        let mut conn = Connection::open_in_memory().unwrap();
+        assert_eq!(run(&mut conn).unwrap(), LATEST);
+        assert_eq!(count(&conn, "select count(*) from history"), 0);
+    }
+
+    #[test]
+    fn newer_database_is_left_alone() {
+        let mut conn = Connection::open_in_memory().unwrap();
+        conn.execute_batch(&format!("pragma user_version = {}", LATEST + 1))
+            .unwrap();
+        assert_eq!(run(&mut conn).unwrap(), LATEST + 1);
+    }
+}
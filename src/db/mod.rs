@@ -3,11 +3,21 @@
 mod connection;
 pub use connection::Connection;
 
+mod migrations;
+
+mod integrity;
+pub use integrity::{Integrity, Repaired};
+
 mod history;
-pub use history::History;
+pub use history::{History, HistoryQuery};
+
+mod bookmarks;
+pub use bookmarks::{BookmarkWriter, Bookmarks};
 
 mod message;
-pub use message::Message;
+pub use message::{Message, RecentChannel, UserChannel};
 
 mod insert_message;
 pub use insert_message::InsertMessage;
+
+mod channel_name;
@@ -0,0 +1,147 @@
+use std::{
+    path::{Path, PathBuf},
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+
+use hashbrown::HashMap;
+use tokio::sync::mpsc;
+
+use crate::task_error::{Source, TaskErrors};
+
+#[derive(serde::Serialize, serde::Deserialize)]
+struct Entry {
+    #[serde(with = "time::serde::rfc3339")]
+    fetched: time::OffsetDateTime,
+    data: serde_json::Value,
+}
+
+pub struct Cache {
+    entries: Arc<Mutex<HashMap<String, Entry>>>,
+    // wakes up the writer, a full channel means a write is already coming
+    dirty: mpsc::Sender<()>,
+}
+
+impl Cache {
+    // a burst of responses (e.g. joining many channels) is written out once
+    const WRITE_DELAY: Duration = Duration::from_secs(5);
+    // the oldest responses are dropped past this, so the file doesn't grow without bound
+    const MAX_ENTRIES: usize = 2000;
+
+    /// This starts the task that writes the cache out, so it has to be called on the runtime
+    pub fn load(path: impl AsRef<Path>, errors: TaskErrors) -> Self {
+        let path = path.as_ref().to_path_buf();
+        let entries = std::fs::read_to_string(&path)
+            .ok()
+            .and_then(|data| serde_json::from_str::<HashMap<String, Entry>>(&data).ok())
+            .unwrap_or_default();
+
+        let entries = Arc::new(Mutex::new(entries));
+        let (dirty, rx) = mpsc::channel(1);
+        tokio::spawn(Self::write_changes(path, entries.clone(), errors, rx));
+
+        let this = Self { entries, dirty };
+        this.evict_expired();
+        this
+    }
+
+    // streams are the only thing we poll for changes, so they are never cached
+    pub fn ttl_for(ep: &str) -> Option<Duration> {
+        const HOUR: u64 = 60 * 60;
+        let ttl = match ep.strip_prefix("https://api.twitch.tv/helix/")? {
+            "games" => HOUR * 24 * 7,
+            "users" => HOUR * 6,
+            "chat/badges/global" | "chat/emotes/global" => HOUR * 24,
+            "chat/badges" | "chat/emotes" | "chat/emotes/set" => HOUR * 6,
+            _ => return None,
+        };
+        Some(Duration::from_secs(ttl))
+    }
+
+    pub fn get<T>(&self, ep: &str, key: &str) -> Option<Vec<T>>
+    where
+        T: for<'de> serde::Deserialize<'de>,
+    {
+        let ttl = Self::ttl_for(ep)?;
+        let entries = self.entries.lock().unwrap();
+        let entry = entries.get(key)?;
+        if Self::is_expired(entry, ttl) {
+            return None;
+        }
+        serde_json::from_value(entry.data.clone()).ok()
+    }
+
+    pub fn insert(&self, ep: &str, key: &str, data: serde_json::Value) {
+        if Self::ttl_for(ep).is_none() {
+            return;
+        }
+
+        let mut entries = self.entries.lock().unwrap();
+        entries.insert(
+            key.to_string(),
+            Entry {
+                fetched: time::OffsetDateTime::now_utc(),
+                data,
+            },
+        );
+        Self::trim(&mut entries);
+        drop(entries);
+
+        let _ = self.dirty.try_send(());
+    }
+
+    // this ends once the cache is dropped, after writing out what was left
+    async fn write_changes(
+        path: PathBuf,
+        entries: Arc<Mutex<HashMap<String, Entry>>>,
+        errors: TaskErrors,
+        mut dirty: mpsc::Receiver<()>,
+    ) {
+        while dirty.recv().await.is_some() {
+            tokio::time::sleep(Self::WRITE_DELAY).await;
+
+            let Ok(data) = serde_json::to_string(&*entries.lock().unwrap()) else { continue };
+            let written = tokio::task::spawn_blocking({
+                let path = path.clone();
+                move || std::fs::write(path, data)
+            })
+            .await;
+            if let Ok(Err(err)) = written {
+                let context = format!("cannot write helix cache to {}", path.display());
+                errors.report(Source::Cache, context, err)
+            }
+        }
+    }
+
+    fn evict_expired(&self) {
+        let mut entries = self.entries.lock().unwrap();
+        entries.retain(|key, entry| {
+            let ep = key.split_once('?').map_or(&**key, |(ep, _)| ep);
+            Self::ttl_for(ep)
+                .filter(|&ttl| !Self::is_expired(entry, ttl))
+                .is_some()
+        });
+        Self::trim(&mut entries);
+    }
+
+    // the oldest responses go first
+    fn trim(entries: &mut HashMap<String, Entry>) {
+        let over = entries.len().saturating_sub(Self::MAX_ENTRIES);
+        if over == 0 {
+            return;
+        }
+
+        let mut oldest = entries
+            .iter()
+            .map(|(key, entry)| (entry.fetched, key.clone()))
+            .collect::<Vec<_>>();
+        oldest.sort_unstable();
+        for (_, key) in oldest.into_iter().take(over) {
+            entries.remove(&key);
+        }
+    }
+
+    fn is_expired(entry: &Entry, ttl: Duration) -> bool {
+        time::OffsetDateTime::now_utc() - entry.fetched > ttl
+    }
+}
@@ -44,6 +44,16 @@ pub struct Stream {
     pub viewer_count: i64,
 }
 
+#[derive(Clone, Debug, serde::Serialize, serde::Deserialize)]
+pub struct SearchChannel {
+    pub broadcaster_login: String,
+    pub display_name: String,
+    pub id: String,
+    pub is_live: bool,
+    pub game_name: String,
+    pub title: String,
+}
+
 #[derive(Clone, Debug, serde::Serialize, serde::Deserialize)]
 pub struct User {
     pub display_name: String,
@@ -62,3 +72,28 @@ pub struct Game {
     pub igdb_id: String,
     pub name: String,
 }
+
+/// What the id server knows about a user token
+#[derive(Clone, Debug, serde::Serialize, serde::Deserialize)]
+pub struct TokenInfo {
+    pub client_id: String,
+    pub login: String,
+    pub user_id: String,
+    /// This is `null` rather than empty for tokens without any
+    #[serde(default)]
+    pub scopes: Option<Vec<String>>,
+    /// Seconds until it expires, tokens that don't expire have 0
+    pub expires_in: u64,
+}
+
+/// A new user token, from trading in a refresh token
+#[derive(Clone, Debug, serde::Serialize, serde::Deserialize)]
+pub struct RefreshedToken {
+    pub access_token: String,
+    /// This can change with every refresh, so the old one shouldn't be used again
+    pub refresh_token: String,
+    #[serde(default)]
+    pub expires_in: u64,
+    #[serde(default)]
+    pub scope: Vec<String>,
+}
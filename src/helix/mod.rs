@@ -4,52 +4,72 @@ use std::sync::Arc;
 use reqwest::{header::HeaderName, StatusCode};
 use tokio::{sync::Mutex, task::JoinSet};
 
-use crate::{repaint::ErasedRepaint, repaint::Repaint, resolver::Fut};
+use crate::{
+    repaint::ErasedRepaint,
+    repaint::Repaint,
+    resolver::{Fut, Wakeup},
+    task_error::{Source, TaskErrors},
+};
 
 pub mod data;
 
+mod cache;
+use cache::Cache;
+
 pub struct HelixConfig {
     pub client_id: String,
     pub client_secret: String,
 }
 
 impl HelixConfig {
-    fn load() -> Self {
-        fn get(key: &str) -> String {
-            std::env::var(key).unwrap_or_else(|_| panic!("'{key}' is not set"))
-        }
+    pub fn load() -> anyhow::Result<Self> {
+        Self::from_lookup(|key| std::env::var(key).ok())
+    }
 
-        Self {
-            client_id: get("TWITCH_CLIENT_ID"),
-            client_secret: get("TWITCH_CLIENT_SECRET"),
-        }
+    /// Like [`crate::twitch::Config::reload`], what's in the environment files wins
+    pub fn reload() -> anyhow::Result<Self> {
+        let files = crate::twitch::Config::read_files();
+        Self::from_lookup(|key| files.get(key).cloned().or_else(|| std::env::var(key).ok()))
     }
-}
 
-pub static HELIX_CONFIG: once_cell::sync::Lazy<HelixConfig> =
-    once_cell::sync::Lazy::new(HelixConfig::load);
+    fn from_lookup(get: impl Fn(&str) -> Option<String>) -> anyhow::Result<Self> {
+        let required = |key| get(key).ok_or_else(|| anyhow::anyhow!("'{key}' is not set"));
+        Ok(Self {
+            client_id: required("TWITCH_CLIENT_ID")?,
+            client_secret: required("TWITCH_CLIENT_SECRET")?,
+        })
+    }
+}
 
 #[derive(Clone)]
 pub struct Client {
     client: reqwest::Client,
     repaint: ErasedRepaint,
+    // what the runtime maps poll wakes them up
+    wakeup: Wakeup,
+    errors: TaskErrors,
     bearer_token: Arc<Mutex<Option<Arc<String>>>>,
+    cache: Arc<Cache>,
+    use_cache: bool,
+    // this is shared so every clone sees reloaded credentials
+    config: Arc<std::sync::RwLock<Arc<HelixConfig>>>,
 }
 
 impl Client {
-    pub fn create(repaint: impl Repaint) -> Self {
-        let headers = [
-            ("user-agent", crate::app::App::USER_AGENT),
-            ("client-id", &*HELIX_CONFIG.client_id),
-        ]
-        .into_iter()
-        .map(|(k, v)| {
-            (
-                HeaderName::from_static(k),
-                v.parse().expect("valid header name"),
-            )
-        })
-        .collect();
+    const CACHE_PATH: &str = "helix_cache.json";
+
+    pub fn create(repaint: impl Repaint, wakeup: Wakeup, errors: TaskErrors) -> Self {
+        let config = HelixConfig::load().unwrap_or_else(|err| panic!("{err}"));
+
+        let headers = [("user-agent", crate::app::App::USER_AGENT)]
+            .into_iter()
+            .map(|(k, v)| {
+                (
+                    HeaderName::from_static(k),
+                    v.parse().expect("valid header name"),
+                )
+            })
+            .collect();
 
         let client = reqwest::ClientBuilder::new()
             .default_headers(headers)
@@ -60,6 +80,30 @@ impl Client {
             client,
             bearer_token: Arc::default(),
             repaint: repaint.erased(),
+            wakeup,
+            cache: Arc::new(Cache::load(Self::CACHE_PATH, errors.clone())),
+            errors,
+            use_cache: true,
+            config: Arc::new(std::sync::RwLock::new(Arc::new(config))),
+        }
+    }
+
+    /// Use new credentials for every request from now on
+    pub fn reload(&self, config: HelixConfig) {
+        *self.config.write().unwrap() = Arc::new(config);
+        let bearer_token = self.bearer_token.clone();
+        tokio::spawn(async move { bearer_token.lock().await.take() });
+    }
+
+    fn config(&self) -> Arc<HelixConfig> {
+        Arc::clone(&self.config.read().unwrap())
+    }
+
+    /// A client that skips cached responses, but still updates the cache with what it fetches
+    pub fn uncached(&self) -> Self {
+        Self {
+            use_cache: false,
+            ..self.clone()
         }
     }
 
@@ -135,7 +179,12 @@ impl Client {
         )
     }
 
-    pub fn get_many_users<T>(&self, logins: impl IntoIterator<Item = T>) -> Fut<Vec<data::User>>
+    /// Unlike the other batches, this fails if any part of it does, so the logins can be tried
+    /// again instead of looking like they don't exist
+    pub fn get_many_users<T>(
+        &self,
+        logins: impl IntoIterator<Item = T>,
+    ) -> Fut<anyhow::Result<Vec<data::User>>>
     where
         T: ToString,
     {
@@ -144,7 +193,7 @@ impl Client {
             .map(|s| ("login", s.to_string()))
             .collect::<Vec<_>>();
 
-        self.get_many_inner("https://api.twitch.tv/helix/users", logins)
+        self.try_get_many_inner("https://api.twitch.tv/helix/users", logins)
     }
 
     pub fn get_many_streams<T>(&self, ids: impl IntoIterator<Item = T>) -> Fut<Vec<data::Stream>>
@@ -158,6 +207,234 @@ impl Client {
         self.get_many_inner("https://api.twitch.tv/helix/streams", ids)
     }
 
+    pub fn search_channels(&self, query: &str) -> Fut<Vec<data::SearchChannel>> {
+        self.get_response_fut(
+            "https://api.twitch.tv/helix/search/channels",
+            [("query", query.to_string()), ("first", String::from("10"))],
+            Self::flatten_result_vec,
+        )
+    }
+
+    /// Ban a user, or time them out when there's a duration in seconds
+    ///
+    /// This is done as us, so it takes the chat token rather than the app token
+    pub fn ban_user(
+        &self,
+        user_token: &str,
+        broadcaster_id: &str,
+        moderator_id: &str,
+        user_id: &str,
+        duration: Option<u32>,
+        reason: &str,
+    ) -> Fut<anyhow::Result<()>> {
+        #[derive(serde::Serialize)]
+        struct Ban {
+            user_id: String,
+            #[serde(skip_serializing_if = "Option::is_none")]
+            duration: Option<u32>,
+            reason: String,
+        }
+
+        #[derive(serde::Serialize)]
+        struct Body {
+            data: Ban,
+        }
+
+        let req = self
+            .client
+            .post("https://api.twitch.tv/helix/moderation/bans")
+            .query(&[
+                ("broadcaster_id", broadcaster_id),
+                ("moderator_id", moderator_id),
+            ])
+            .json(&Body {
+                data: Ban {
+                    user_id: user_id.to_string(),
+                    duration,
+                    reason: reason.to_string(),
+                },
+            });
+        self.moderate(req, user_token)
+    }
+
+    /// When the user followed the channel, this takes the chat token like [`Self::ban_user`]
+    ///
+    /// Only the broadcaster and their moderators can see this
+    pub fn get_follow(
+        &self,
+        user_token: &str,
+        broadcaster_id: &str,
+        user_id: &str,
+    ) -> Fut<anyhow::Result<Option<time::OffsetDateTime>>> {
+        #[derive(serde::Deserialize)]
+        struct Follow {
+            #[serde(with = "time::serde::rfc3339")]
+            followed_at: time::OffsetDateTime,
+        }
+
+        #[derive(serde::Deserialize)]
+        struct Resp {
+            data: Vec<Follow>,
+        }
+
+        let token = user_token.strip_prefix("oauth:").unwrap_or(user_token);
+        let req = self
+            .client
+            .get("https://api.twitch.tv/helix/channels/followers")
+            .query(&[("broadcaster_id", broadcaster_id), ("user_id", user_id)])
+            .bearer_auth(token)
+            .header("client-id", &self.config().client_id);
+
+        Fut::spawn(async move {
+            let Resp { data } = req.send().await?.error_for_status()?.json().await?;
+            Ok(data.into_iter().next().map(|follow| follow.followed_at))
+        })
+    }
+
+    /// The live channels we follow, this takes the chat token like [`Self::ban_user`]
+    ///
+    /// The token needs the `user:read:follows` scope
+    pub fn get_followed_streams(
+        &self,
+        user_token: &str,
+        user_id: &str,
+    ) -> Fut<anyhow::Result<Vec<data::Stream>>> {
+        #[derive(serde::Deserialize)]
+        struct Resp {
+            data: Vec<data::Stream>,
+        }
+
+        let token = user_token.strip_prefix("oauth:").unwrap_or(user_token);
+        let req = self
+            .client
+            .get("https://api.twitch.tv/helix/streams/followed")
+            .query(&[("user_id", user_id), ("first", "100")])
+            .bearer_auth(token)
+            .header("client-id", &self.config().client_id);
+
+        Fut::spawn(async move {
+            let Resp { data } = req.send().await?.error_for_status()?.json().await?;
+            Ok(data)
+        })
+    }
+
+    /// Delete a single message, this takes the chat token like [`Self::ban_user`]
+    pub fn delete_message(
+        &self,
+        user_token: &str,
+        broadcaster_id: &str,
+        moderator_id: &str,
+        message_id: &str,
+    ) -> Fut<anyhow::Result<()>> {
+        let req = self
+            .client
+            .delete("https://api.twitch.tv/helix/moderation/chat")
+            .query(&[
+                ("broadcaster_id", broadcaster_id),
+                ("moderator_id", moderator_id),
+                ("message_id", message_id),
+            ]);
+        self.moderate(req, user_token)
+    }
+
+    /// Ask the id server what the chat token is for, and what it's allowed to do
+    pub fn validate_token(&self, user_token: &str) -> Fut<anyhow::Result<data::TokenInfo>> {
+        let token = user_token.strip_prefix("oauth:").unwrap_or(user_token);
+        let req = self
+            .client
+            .get("https://id.twitch.tv/oauth2/validate")
+            .header("authorization", format!("OAuth {token}"));
+
+        Fut::spawn(async move {
+            let resp = req.send().await?;
+            anyhow::ensure!(
+                resp.status() != StatusCode::UNAUTHORIZED,
+                "the token is invalid or has expired"
+            );
+            Ok(resp.error_for_status()?.json().await?)
+        })
+    }
+
+    /// Trade a refresh token for a new chat token
+    ///
+    /// This is only possible for tokens made with our client id and secret
+    pub fn refresh_user_token(
+        &self,
+        refresh_token: &str,
+    ) -> Fut<anyhow::Result<data::RefreshedToken>> {
+        let config = self.config();
+        let req = self
+            .client
+            .post("https://id.twitch.tv/oauth2/token")
+            .form(&[
+                ("grant_type", "refresh_token"),
+                ("refresh_token", refresh_token),
+                ("client_id", &config.client_id),
+                ("client_secret", &config.client_secret),
+            ]);
+
+        Fut::spawn(async move { Ok(req.send().await?.error_for_status()?.json().await?) })
+    }
+
+    /// Change our chat color, this takes the chat token like [`Self::ban_user`]
+    ///
+    /// Without turbo or prime only the named colors (e.g. `blue_violet`) are allowed
+    pub fn update_chat_color(
+        &self,
+        user_token: &str,
+        user_id: &str,
+        color: &str,
+    ) -> Fut<anyhow::Result<()>> {
+        let req = self
+            .client
+            .put("https://api.twitch.tv/helix/chat/color")
+            .query(&[("user_id", user_id), ("color", color)]);
+        self.moderate(req, user_token)
+    }
+
+    /// Whisper to a user as us, this takes the chat token like [`Self::ban_user`]
+    ///
+    /// The token needs the `user:manage:whispers` scope, and the account a verified phone number
+    pub fn send_whisper(
+        &self,
+        user_token: &str,
+        from_user_id: &str,
+        to_login: &str,
+        message: &str,
+    ) -> Fut<anyhow::Result<()>> {
+        let user = self.get_user(to_login);
+        let (this, token) = (self.clone(), user_token.to_string());
+        let (from, to) = (from_user_id.to_string(), to_login.to_string());
+        let body = serde_json::json!({ "message": message });
+
+        Fut::spawn(async move {
+            let Some((_, user)) = user.wait().await.flatten() else {
+                anyhow::bail!("there's no user named {to}")
+            };
+            let req = this
+                .client
+                .post("https://api.twitch.tv/helix/whispers")
+                .query(&[("from_user_id", &*from), ("to_user_id", &*user.id)])
+                .json(&body);
+            this.moderate(req, &token)
+                .wait()
+                .await
+                .unwrap_or_else(|| Err(anyhow::anyhow!("the whisper was dropped")))
+        })
+    }
+
+    fn moderate(&self, req: reqwest::RequestBuilder, user_token: &str) -> Fut<anyhow::Result<()>> {
+        let token = user_token.strip_prefix("oauth:").unwrap_or(user_token);
+        let req = req
+            .bearer_auth(token)
+            .header("client-id", &self.config().client_id);
+
+        Fut::spawn(async move {
+            req.send().await?.error_for_status()?;
+            Ok(())
+        })
+    }
+
     fn flatten_result_vec<T>(result: anyhow::Result<Vec<T>>) -> Vec<T> {
         Result::unwrap_or_default(result)
     }
@@ -184,7 +461,7 @@ impl Client {
                 set.spawn(async move {
                     this.get_response::<T>(ep, query)
                         .await
-                        .ok()
+                        .map_err(|err| this.errors.report(Source::Helix, ep, err))
                         .unwrap_or_default()
                 });
             }
@@ -197,7 +474,42 @@ impl Client {
             out
         };
 
-        Fut::spawn(fut)
+        Fut::spawn_notify(&self.wakeup, fut)
+    }
+
+    fn try_get_many_inner<T>(
+        &self,
+        ep: &'static str,
+        query: Vec<impl serde::Serialize + Clone + Send + Sync + 'static>,
+    ) -> Fut<anyhow::Result<Vec<T>>>
+    where
+        for<'de> T: serde::Deserialize<'de> + Send + Sync + 'static,
+    {
+        let this = self.clone();
+        let fut = async move {
+            let mut set = JoinSet::new();
+
+            for chunk in query.chunks(100) {
+                let query = chunk.to_vec();
+                let this = this.clone();
+                set.spawn(async move { this.get_response::<T>(ep, query).await });
+            }
+
+            let mut out = Vec::with_capacity(query.len());
+            while let Some(item) = set.join_next().await {
+                match item.map_err(anyhow::Error::from).and_then(|item| item) {
+                    Ok(item) => out.extend(item),
+                    Err(err) => {
+                        this.errors.report(Source::Helix, ep, &err);
+                        return Err(err);
+                    }
+                }
+            }
+            out.shrink_to_fit();
+            Ok(out)
+        };
+
+        Fut::spawn_notify(&self.wakeup, fut)
     }
 
     fn get_response_fut<T, U>(
@@ -213,10 +525,13 @@ impl Client {
         let this = self.clone();
         let fut = async move {
             let result = this.get_response(ep, query).await;
+            if let Err(err) = &result {
+                this.errors.report(Source::Helix, ep, err);
+            }
             map(result)
         };
 
-        Fut::spawn(fut)
+        Fut::spawn_notify(&self.wakeup, fut)
     }
 
     async fn get_response<T>(
@@ -227,13 +542,19 @@ impl Client {
     where
         T: for<'de> serde::Deserialize<'de>,
     {
+        let key = self.client.get(ep).query(&query).build()?.url().to_string();
+        if let Some(data) = self.cache.get(ep, &key).filter(|_| self.use_cache) {
+            return Ok(data);
+        }
+
         // TODO exponential backoff (or atleast add some jitter)
         let resp = loop {
-            let token = self.fetch_bearer_token().await;
+            let token = self.fetch_bearer_token().await?;
             let req = self
                 .client
                 .get(ep)
                 .header("authorization", &*token)
+                .header("client-id", &self.config().client_id)
                 .query(&query)
                 .build()?;
 
@@ -242,7 +563,7 @@ impl Client {
                 break resp;
             }
 
-            eprintln!("fetching a new OAuth token");
+            log!("fetching a new OAuth token");
             let _ = self.bearer_token.lock().await.take();
         };
 
@@ -251,28 +572,36 @@ impl Client {
             data: Vec<T>,
         }
 
-        let Resp { data } = resp.json().await?;
+        let Resp { data } = resp.json::<Resp<serde_json::Value>>().await?;
+        let empty = data.is_empty();
+        let data = serde_json::Value::Array(data);
+        let out = serde_json::from_value(data.clone())?;
+        // don't remember misses, the thing may exist later
+        if !empty {
+            self.cache.insert(ep, &key, data);
+        }
+
         (self.repaint)();
-        Ok(data)
+        Ok(out)
     }
 
-    async fn fetch_bearer_token(&self) -> Arc<String> {
+    async fn fetch_bearer_token(&self) -> anyhow::Result<Arc<String>> {
         let mut token = self.bearer_token.lock().await;
         if let Some(token) = &mut *token {
-            return Arc::clone(token);
+            return Ok(Arc::clone(token));
         }
 
+        let config = self.config();
         let HelixConfig {
             client_id,
             client_secret,
-        } = &*HELIX_CONFIG;
+        } = &*config;
 
         let bearer_token = Self::get_oauth(client_id, client_secret)
             .await
-            // TODO make this fallible
-            .unwrap_or_else(|err| panic!("cannot update bearer token: {err}"));
+            .map_err(|err| anyhow::anyhow!("cannot update bearer token: {err}"))?;
 
-        Arc::clone(token.insert(Arc::from(bearer_token)))
+        Ok(Arc::clone(token.insert(Arc::from(bearer_token))))
     }
 
     async fn get_oauth(client_id: &str, client_secret: &str) -> anyhow::Result<String> {
@@ -1,20 +1,50 @@
 use std::{
-    cell::Cell,
+    cell::{Cell, RefCell},
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc,
+    },
     time::{Duration, Instant},
 };
 
 use egui::{TextureHandle, TextureOptions, Vec2};
 
+use crate::resolver::Fut;
+
 pub enum Image {
     Static(TextureHandle),
     Animated(Animated),
 }
 
 impl Image {
-    pub fn as_egui_image(&self, size: Vec2, dt: f32) -> egui::Image {
+    /// The image to draw on egui's frame `frame`, see [`egui::Context::frame_nr`]
+    ///
+    /// Animations advance once per frame, however many times they're drawn in it
+    pub fn as_egui_image(&self, size: Vec2, frame: u64) -> egui::Image {
+        match self {
+            Self::Static(image) => egui::Image::new(image, size),
+            Self::Animated(animated) => animated.get_frame(size, frame),
+        }
+    }
+
+    /// Like [`Self::as_egui_image`] but without advancing the animation
+    pub fn as_paused_egui_image(&self, size: Vec2) -> egui::Image {
         match self {
             Self::Static(image) => egui::Image::new(image, size),
-            Self::Animated(animated) => animated.get_frame(dt, size),
+            Self::Animated(animated) => animated.current_frame(size),
+        }
+    }
+
+    pub const fn is_animated(&self) -> bool {
+        matches!(self, Self::Animated(..))
+    }
+
+    /// Drop the frames of an animation that hasn't been drawn in a while
+    ///
+    /// They're decoded again once it's back on screen
+    pub fn unload_hidden(&self) {
+        if let Self::Animated(animated) = self {
+            animated.unload_hidden()
         }
     }
 
@@ -40,7 +70,10 @@ impl Image {
                     Self::load_texture_handle(ctx, name, data)
                 }
             }
-            ::image::ImageFormat::Jpeg => Self::load_texture_handle(ctx, name, data),
+            // these are only used when there's nothing else, so only the first frame is shown
+            ::image::ImageFormat::Jpeg | ::image::ImageFormat::WebP => {
+                Self::load_texture_handle(ctx, name, data)
+            }
             ::image::ImageFormat::Gif => Self::load_gif(ctx, name, data),
             fmt => {
                 anyhow::bail!("unsupported format for '{name}': {fmt:?}")
@@ -60,64 +93,251 @@ impl Image {
     fn load_apng(ctx: &egui::Context, name: &str, data: &[u8]) -> anyhow::Result<Self> {
         let dec = ::image::codecs::png::PngDecoder::new(data)?;
         anyhow::ensure!(dec.is_apng(), "expected an animated png");
-        Animated::load_frames(ctx, name, dec.apng()).map(Self::Animated)
+        Animated::load_first_frame(ctx, name, data, Encoding::Apng, dec.apng())
     }
 
     fn load_gif(ctx: &egui::Context, name: &str, data: &[u8]) -> anyhow::Result<Self> {
         let dec = ::image::codecs::gif::GifDecoder::new(data)?;
-        Animated::load_frames(ctx, name, dec).map(Self::Animated)
+        Animated::load_first_frame(ctx, name, data, Encoding::Gif, dec)
+    }
+}
+
+#[derive(Copy, Clone)]
+enum Encoding {
+    Gif,
+    Apng,
+}
+
+enum Frames {
+    /// Only the first frame is around, the rest are decoded when it's drawn
+    Unloaded,
+    /// The budget was used up, this tries again after a while
+    OverBudget(Instant),
+    Decoding(Fut<anyhow::Result<Option<Decoded>>>),
+    Decoded(Decoded),
+    /// Only the first frame could be decoded
+    Broken,
+}
+
+struct Decoded {
+    // delays are in seconds
+    frames: Vec<(f32, TextureHandle)>,
+    total: f32,
+    // how much of the animation budget we're holding on to
+    reserved: usize,
+}
+
+impl Drop for Decoded {
+    fn drop(&mut self) {
+        Animated::release(self.reserved)
     }
 }
 
 pub struct Animated {
-    // TODO use an f32 here so we can compenstate for render lag
-    frames: Vec<(Duration, TextureHandle)>,
-    // TODO use an f32 here
-    last: Cell<Option<Instant>>,
+    ctx: egui::Context,
+    name: String,
+    // the rest of the frames are decoded from this while it's on screen
+    data: Arc<[u8]>,
+    encoding: Encoding,
+    // shown until the rest of the frames are decoded
+    first: TextureHandle,
+    frames: RefCell<Frames>,
+    // the egui frame it last advanced on, and when that was
+    last: Cell<Option<(u64, Instant)>>,
+    elapsed: Cell<f32>,
     pos: Cell<usize>,
 }
 
+// decoded frames of every animated image on screen, shared across all of them
+static ANIMATION_MEMORY: AtomicUsize = AtomicUsize::new(0);
+
 impl Animated {
-    fn get_frame(&self, dt: f32, size: Vec2) -> egui::Image {
-        let pos = self.pos.get();
-
-        let (delay, frame) = &self.frames[pos];
-        match self.last.get() {
-            Some(last) if last.elapsed().as_secs_f32() >= delay.as_secs_f32() - dt => {
-                self.pos.set((pos + 1) % self.frames.len());
-                self.last.set(Some(Instant::now()))
-            }
-            Some(..) => {}
-            None => {
-                self.last.set(Some(Instant::now()));
+    const ANIMATION_BUDGET: usize = 256 * 1024 * 1024;
+
+    fn reserve(bytes: usize) -> bool {
+        ANIMATION_MEMORY
+            .fetch_update(Ordering::SeqCst, Ordering::SeqCst, |used| {
+                used.checked_add(bytes)
+                    .filter(|&used| used <= Self::ANIMATION_BUDGET)
+            })
+            .is_ok()
+    }
+
+    fn release(bytes: usize) {
+        ANIMATION_MEMORY.fetch_sub(bytes, Ordering::SeqCst);
+    }
+
+    // browsers treat really short delays as 'unspecified' and use 100ms instead
+    const MIN_DELAY: f32 = 0.02;
+    const DEFAULT_DELAY: f32 = 0.1;
+
+    // how long the frames are kept once it's off screen
+    const HIDDEN_FOR: Duration = Duration::from_secs(10);
+    // how long to wait for the budget to free up before trying again
+    const BUDGET_RETRY: Duration = Duration::from_secs(5);
+
+    fn get_frame(&self, size: Vec2, frame: u64) -> egui::Image {
+        let now = Instant::now();
+        let since = match self.last.get() {
+            // other copies of it were already drawn this frame
+            Some((last, _)) if last == frame => None,
+            Some((last, at)) if last + 1 == frame => Some((now - at).as_secs_f32()),
+            // it wasn't on screen, so it picks up where it stopped instead of skipping ahead
+            _ => Some(0.0),
+        };
+
+        let mut frames = self.frames.borrow_mut();
+        self.load(&mut frames);
+        let Frames::Decoded(decoded) = &*frames else {
+            self.last.set(Some((frame, now)));
+            return egui::Image::new(&self.first, size);
+        };
+        let Some(since) = since else {
+            return egui::Image::new(&decoded.frames[self.pos.get()].1, size);
+        };
+        self.last.set(Some((frame, now)));
+
+        // wrapping around the whole animation lands on the same frame, so skip those loops
+        let mut elapsed = (self.elapsed.get() + since) % decoded.total;
+        let mut pos = self.pos.get();
+        while elapsed >= decoded.frames[pos].0 {
+            elapsed -= decoded.frames[pos].0;
+            pos = (pos + 1) % decoded.frames.len();
+        }
+
+        self.elapsed.set(elapsed);
+        self.pos.set(pos);
+
+        egui::Image::new(&decoded.frames[pos].1, size)
+    }
+
+    fn current_frame(&self, size: Vec2) -> egui::Image {
+        match &*self.frames.borrow() {
+            Frames::Decoded(decoded) => egui::Image::new(&decoded.frames[self.pos.get()].1, size),
+            _ => egui::Image::new(&self.first, size),
+        }
+    }
+
+    // start decoding the frames if they aren't around, or pick them up once they're decoded
+    fn load(&self, frames: &mut Frames) {
+        match frames {
+            Frames::Unloaded => {}
+            Frames::OverBudget(at) if at.elapsed() >= Self::BUDGET_RETRY => {}
+            Frames::Decoding(fut) => {
+                let Some(decoded) = fut.try_resolve() else { return };
+                *frames = match decoded {
+                    Ok(Some(decoded)) => Frames::Decoded(decoded),
+                    Ok(None) => Frames::OverBudget(Instant::now()),
+                    Err(err) => {
+                        log!("only using the first frame of '{}': {err}", self.name);
+                        Frames::Broken
+                    }
+                };
+                return;
             }
+            _ => return,
         }
 
-        egui::Image::new(frame, size)
+        let (ctx, name, data) = (self.ctx.clone(), self.name.clone(), self.data.clone());
+        let encoding = self.encoding;
+        *frames = Frames::Decoding(Fut::spawn(async move {
+            let decode = move || match encoding {
+                Encoding::Gif => {
+                    let dec = ::image::codecs::gif::GifDecoder::new(&*data)?;
+                    Self::decode_frames(&ctx, &name, dec)
+                }
+                Encoding::Apng => {
+                    let dec = ::image::codecs::png::PngDecoder::new(&*data)?;
+                    Self::decode_frames(&ctx, &name, dec.apng())
+                }
+            };
+            tokio::task::spawn_blocking(decode)
+                .await
+                .unwrap_or_else(|err| Err(anyhow::anyhow!("the decoder crashed: {err}")))
+        }));
+    }
+
+    fn unload_hidden(&self) {
+        let hidden = self
+            .last
+            .get()
+            .map_or(true, |(_, at)| at.elapsed() >= Self::HIDDEN_FOR);
+        if hidden && matches!(&*self.frames.borrow(), Frames::Decoded(..)) {
+            // dropping the frames gives back their part of the budget
+            self.frames.replace(Frames::Unloaded);
+            self.elapsed.set(0.0);
+            self.pos.set(0);
+        }
     }
 
-    fn load_frames<'a>(
+    // only the first frame is decoded up front, the rest wait until it's drawn
+    fn load_first_frame<'a>(
         ctx: &egui::Context,
         name: &str,
+        data: &[u8],
+        encoding: Encoding,
         decoder: impl ::image::AnimationDecoder<'a>,
-    ) -> anyhow::Result<Self> {
-        decoder
-            .into_frames()
-            .map(|frame| {
-                let frame = frame?;
-                let delay = Duration::from(frame.delay());
-                let data = frame.into_buffer();
-                let (width, height) = data.dimensions();
-                let image =
-                    egui::ColorImage::from_rgba_unmultiplied([width as _, height as _], &data);
-                let handle = ctx.load_texture(name, image, TextureOptions::default());
-                Ok((delay, handle))
-            })
-            .collect::<anyhow::Result<_>>()
-            .map(|frames| Self {
-                frames,
-                last: Cell::default(),
-                pos: Cell::default(),
-            })
+    ) -> anyhow::Result<Image> {
+        let Some(first) = decoder.into_frames().next() else {
+            anyhow::bail!("animation for '{name}' has no frames")
+        };
+        let first = Self::upload(ctx, name, &first?.into_buffer());
+
+        Ok(Image::Animated(Self {
+            ctx: ctx.clone(),
+            name: name.to_string(),
+            data: data.into(),
+            encoding,
+            first,
+            frames: RefCell::new(Frames::Unloaded),
+            last: Cell::default(),
+            elapsed: Cell::default(),
+            pos: Cell::default(),
+        }))
+    }
+
+    // this runs off the ui thread. if the animation would go over the budget, what was decoded
+    // is dropped and `None` is returned
+    fn decode_frames<'a>(
+        ctx: &egui::Context,
+        name: &str,
+        decoder: impl ::image::AnimationDecoder<'a>,
+    ) -> anyhow::Result<Option<Decoded>> {
+        let mut decoded = Decoded {
+            frames: vec![],
+            total: 0.0,
+            reserved: 0,
+        };
+
+        for frame in decoder.into_frames() {
+            let frame = frame?;
+            let delay = Duration::from(frame.delay()).as_secs_f32();
+            let delay = if delay < Self::MIN_DELAY {
+                Self::DEFAULT_DELAY
+            } else {
+                delay
+            };
+            let data = frame.into_buffer();
+            let bytes = data.as_raw().len();
+            if !Self::reserve(bytes) {
+                return Ok(None);
+            }
+
+            decoded.reserved += bytes;
+            decoded.total += delay;
+            decoded.frames.push((delay, Self::upload(ctx, name, &data)));
+        }
+
+        anyhow::ensure!(
+            !decoded.frames.is_empty(),
+            "animation for '{name}' has no frames"
+        );
+        Ok(Some(decoded))
+    }
+
+    fn upload(ctx: &egui::Context, name: &str, data: &::image::RgbaImage) -> TextureHandle {
+        let (width, height) = data.dimensions();
+        let image = egui::ColorImage::from_rgba_unmultiplied([width as _, height as _], data);
+        ctx.load_texture(name, image, TextureOptions::default())
     }
 }
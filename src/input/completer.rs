@@ -0,0 +1,101 @@
+use hashbrown::HashSet;
+
+use crate::{runtime::EmoteMap, state::Channel};
+
+/// Completes the last word of the input, each Tab moves on to the next candidate
+#[derive(Default)]
+pub struct Completer {
+    // everything before the word being completed
+    head: String,
+    candidates: Vec<String>,
+    index: usize,
+    // the buffer after the last completion, if it changed since then this starts over
+    completed: String,
+}
+
+impl Completer {
+    const MAX_CANDIDATES: usize = 10;
+
+    /// Complete the last word in `buffer`, or move on to the next candidate
+    ///
+    /// Returns whether the buffer was changed
+    pub fn complete(
+        &mut self,
+        buffer: &mut String,
+        emote_map: &EmoteMap,
+        channel: &Channel,
+    ) -> bool {
+        if self.is_cycling(buffer) {
+            self.index = (self.index + 1) % self.candidates.len();
+        } else {
+            let (head, word) = match buffer.rfind(' ') {
+                Some(pos) => buffer.split_at(pos + 1),
+                None => ("", buffer.as_str()),
+            };
+            self.candidates = Self::gather(word, emote_map, channel);
+            if self.candidates.is_empty() {
+                return false;
+            }
+            self.head = head.to_string();
+            self.index = 0;
+        }
+
+        self.apply(buffer);
+        true
+    }
+
+    /// Use a specific candidate, like one that was clicked on
+    pub fn pick(&mut self, index: usize, buffer: &mut String) {
+        if index < self.candidates.len() {
+            self.index = index;
+            self.apply(buffer);
+        }
+    }
+
+    /// The candidates and which one is used, while Tab is cycling through them
+    pub fn candidates(&self, buffer: &str) -> Option<(&[String], usize)> {
+        self.is_cycling(buffer)
+            .then_some((self.candidates.as_slice(), self.index))
+    }
+
+    fn is_cycling(&self, buffer: &str) -> bool {
+        !self.candidates.is_empty() && self.completed == buffer
+    }
+
+    fn apply(&mut self, buffer: &mut String) {
+        *buffer = format!("{}{} ", self.head, self.candidates[self.index]);
+        self.completed.clone_from(buffer);
+    }
+
+    // chatters come first with the most recent one first, then the emotes in order.
+    // a word starting with @ is always a chatter
+    fn gather(word: &str, emote_map: &EmoteMap, channel: &Channel) -> Vec<String> {
+        if word.is_empty() {
+            return vec![];
+        }
+
+        let (at, name) = match word.strip_prefix('@') {
+            Some(name) => ("@", name),
+            None => ("", word),
+        };
+        let prefix = name.to_lowercase();
+
+        let mut seen = HashSet::new();
+        let chatters = channel
+            .messages
+            .iter()
+            .rev()
+            .map(|msg| msg.sender.as_str())
+            .filter(|sender| sender.to_lowercase().starts_with(&prefix))
+            .filter(|sender| seen.insert(*sender))
+            .map(|sender| format!("{at}{sender}"));
+
+        let mut emotes = vec![];
+        if at.is_empty() {
+            emotes.extend(emote_map.names_starting_with(word).map(String::from));
+            emotes.sort_unstable();
+        }
+
+        chatters.chain(emotes).take(Self::MAX_CANDIDATES).collect()
+    }
+}
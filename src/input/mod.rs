@@ -0,0 +1,219 @@
+use std::time::Duration;
+
+pub enum Input<'a> {
+    Join {
+        channels: Vec<&'a str>,
+    },
+    Part {
+        channel: &'a str,
+    },
+    Watch {
+        channel: &'a str,
+    },
+    Unwatch {
+        channel: &'a str,
+    },
+    Timeout {
+        user: &'a str,
+        duration: Duration,
+        reason: &'a str,
+    },
+    Whisper {
+        user: &'a str,
+        data: &'a str,
+    },
+    Send {
+        data: &'a str,
+    },
+    /// The command was recognized, but the arguments weren't right
+    Usage {
+        cmd: &'static Command,
+        error: Option<&'static str>,
+    },
+    Unknown {
+        data: &'a str,
+    },
+}
+
+/// A slash command that can be typed into the input
+pub struct Command {
+    pub name: &'static str,
+    pub aliases: &'static [&'static str],
+    pub args: &'static str,
+    pub description: &'static str,
+}
+
+impl Command {
+    pub const ALL: &[Self] = &[
+        Self {
+            name: "join",
+            aliases: &["enter"],
+            args: "channel [channel...]",
+            description: "join channels, or switch to one",
+        },
+        Self {
+            name: "part",
+            aliases: &["leave"],
+            args: "channel",
+            description: "leave a channel",
+        },
+        Self {
+            name: "watch",
+            aliases: &[],
+            args: "channel",
+            description: "get told when a channel goes live, without joining it",
+        },
+        Self {
+            name: "unwatch",
+            aliases: &[],
+            args: "channel",
+            description: "stop watching a channel",
+        },
+        Self {
+            name: "timeout",
+            aliases: &[],
+            args: "user duration [reason]",
+            description: "time out a user, the duration is like 90s, 10m or 1h30m",
+        },
+        Self {
+            name: "w",
+            aliases: &["whisper"],
+            args: "user message",
+            description: "whisper to a user, it shows up in the whispers tab",
+        },
+    ];
+
+    // what twitch allows for timeouts
+    const MAX_TIMEOUT: Duration = Duration::from_secs(14 * 24 * 60 * 60);
+
+    pub fn find(name: &str) -> Option<&'static Self> {
+        Self::ALL.iter().find(|cmd| cmd.names().any(|n| n == name))
+    }
+
+    /// Commands where the name or one of the aliases starts with `partial`
+    pub fn matching(partial: &str) -> impl Iterator<Item = &'static Self> + '_ {
+        Self::ALL
+            .iter()
+            .filter(move |cmd| cmd.names().any(|n| n.starts_with(partial)))
+    }
+
+    pub fn names(&self) -> impl Iterator<Item = &'static str> {
+        std::iter::once(self.name).chain(self.aliases.iter().copied())
+    }
+
+    pub fn syntax(&self) -> String {
+        format!("/{} {}", self.name, self.args)
+    }
+}
+
+impl<'a> Input<'a> {
+    pub fn parse(input: &'a str) -> Self {
+        let Some(tail) = input.strip_prefix('/') else {
+            return Self::Send { data: input };
+        };
+
+        let (head, tail) = tail.split_once(' ').unwrap_or((tail, ""));
+        let tail = tail.trim();
+
+        let Some(cmd) = Command::find(head) else {
+            return Self::Unknown { data: input };
+        };
+
+        if tail.is_empty() && !cmd.args.is_empty() {
+            return Self::Usage { cmd, error: None };
+        }
+
+        let usage = |error| Self::Usage {
+            cmd,
+            error: Some(error),
+        };
+
+        match cmd.name {
+            "join" => {
+                // these can be separated by commas, spaces or both
+                let channels = tail
+                    .split([',', ' '])
+                    .filter(|channel| !channel.is_empty())
+                    .collect::<Vec<_>>();
+                if !channels.iter().all(|channel| is_login(channel)) {
+                    return usage("that isn't a valid channel name");
+                }
+                Self::Join { channels }
+            }
+            "part" if !is_login(tail) => usage("that isn't a valid channel name"),
+            "part" => Self::Part { channel: tail },
+            "watch" | "unwatch" if !is_login(tail) => usage("that isn't a valid channel name"),
+            "watch" => Self::Watch { channel: tail },
+            "unwatch" => Self::Unwatch { channel: tail },
+            "timeout" => {
+                let mut args = tail.splitn(3, ' ');
+                let user = args.next().unwrap_or_default();
+                let Some(duration) = args.next() else {
+                    return usage("a duration is needed");
+                };
+                if !is_login(user) {
+                    return usage("that isn't a valid user name");
+                }
+                match parse_duration(duration) {
+                    None => usage("durations look like 90s, 10m or 1h30m"),
+                    Some(duration) if duration > Command::MAX_TIMEOUT => {
+                        usage("timeouts can be 2 weeks at most")
+                    }
+                    Some(duration) => Self::Timeout {
+                        user: user.strip_prefix('@').unwrap_or(user),
+                        duration,
+                        reason: args.next().unwrap_or_default().trim(),
+                    },
+                }
+            }
+            "w" => {
+                let (user, data) = tail.split_once(' ').unwrap_or((tail, ""));
+                if !is_login(user) {
+                    return usage("that isn't a valid user name");
+                }
+                match data.trim() {
+                    "" => usage("a message is needed"),
+                    data => Self::Whisper {
+                        user: user.strip_prefix('@').unwrap_or(user),
+                        data,
+                    },
+                }
+            }
+            _ => Self::Unknown { data: input },
+        }
+    }
+}
+
+// the `#` or `@` in front is optional
+fn is_login(input: &str) -> bool {
+    crate::twitch::is_login(input.trim_start_matches(['#', '@']))
+}
+
+// either plain seconds, or a sequence of numbers with a `d`, `h`, `m` or `s` after each
+fn parse_duration(input: &str) -> Option<Duration> {
+    if let Ok(secs) = input.parse::<u64>() {
+        return (secs > 0).then_some(Duration::from_secs(secs));
+    }
+
+    let (mut total, mut number) = (0u64, None::<u64>);
+    for c in input.chars() {
+        if let Some(digit) = c.to_digit(10) {
+            number = Some(number.unwrap_or(0).checked_mul(10)? + u64::from(digit));
+            continue;
+        }
+
+        let unit = match c {
+            'd' => 24 * 60 * 60,
+            'h' => 60 * 60,
+            'm' => 60,
+            's' => 1,
+            _ => return None,
+        };
+        total = total.checked_add(number.take()?.checked_mul(unit)?)?;
+    }
+
+    (number.is_none() && total > 0).then_some(Duration::from_secs(total))
+}
+
+mod completer;
+pub use completer::Completer;
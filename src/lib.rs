@@ -1,12 +1,20 @@
+#[macro_use]
+mod logger;
+
+mod automod;
+pub mod crash;
 mod db;
 mod helix;
 mod image;
 mod input;
+mod notification;
 mod queue;
 mod repaint;
 mod resolver;
 mod runtime;
 mod state;
+mod task_error;
+pub mod url_handler;
 mod util;
 mod views;
 mod widgets;
@@ -15,3 +23,4 @@ pub mod twitch;
 
 mod app;
 pub use app::App;
+pub use state::Permalink;
@@ -0,0 +1,28 @@
+use std::{
+    collections::VecDeque,
+    sync::{Mutex, PoisonError},
+};
+
+// this is kept around so crash reports have some context
+static RECENT: Mutex<VecDeque<String>> = Mutex::new(VecDeque::new());
+const MAX_RECENT: usize = 200;
+
+macro_rules! log {
+    ($($arg:tt)*) => {
+        $crate::logger::record(format!($($arg)*))
+    };
+}
+
+pub fn record(line: String) {
+    eprintln!("{line}");
+    let mut recent = RECENT.lock().unwrap_or_else(PoisonError::into_inner);
+    while recent.len() >= MAX_RECENT {
+        recent.pop_front();
+    }
+    recent.push_back(line);
+}
+
+pub fn recent() -> Vec<String> {
+    let recent = RECENT.lock().unwrap_or_else(PoisonError::into_inner);
+    recent.iter().cloned().collect()
+}
@@ -0,0 +1,82 @@
+/// Show a desktop notification, this doesn't wait for it to be shown
+pub fn desktop(summary: &str, body: &str) {
+    if let Err(err) = show(summary, body) {
+        log!("cannot show a notification: {err}")
+    }
+}
+
+/// Play the platform's message sound, this doesn't wait for it to finish
+pub fn sound() {
+    if let Err(err) = play() {
+        log!("cannot play a sound: {err}")
+    }
+}
+
+// the child is waited on from its own thread, so it doesn't linger once it exits
+fn spawn(command: &mut std::process::Command) -> anyhow::Result<()> {
+    let mut child = command.spawn()?;
+    std::thread::spawn(move || child.wait());
+    Ok(())
+}
+
+#[cfg(target_os = "linux")]
+fn show(summary: &str, body: &str) -> anyhow::Result<()> {
+    spawn(std::process::Command::new("notify-send").args(["--app-name", "VoHiYo", summary, body]))
+}
+
+#[cfg(target_os = "linux")]
+fn play() -> anyhow::Result<()> {
+    spawn(std::process::Command::new("canberra-gtk-play").args(["--id", "message-new-instant"]))
+}
+
+// the text is passed as arguments to the script, so none of it is read as applescript
+#[cfg(target_os = "macos")]
+fn show(summary: &str, body: &str) -> anyhow::Result<()> {
+    const SCRIPT: &str = "on run argv\n\
+        display notification (item 2 of argv) with title \"VoHiYo\" subtitle (item 1 of argv)\n\
+        end run";
+    spawn(std::process::Command::new("osascript").args(["-e", SCRIPT, summary, body]))
+}
+
+#[cfg(target_os = "macos")]
+fn play() -> anyhow::Result<()> {
+    spawn(std::process::Command::new("afplay").arg("/System/Library/Sounds/Glass.aiff"))
+}
+
+#[cfg(windows)]
+fn show(summary: &str, body: &str) -> anyhow::Result<()> {
+    // there's no toast without a registered app id, a balloon tip works without one
+    //
+    // the text is read from the environment, nothing from chat is ever part of the script
+    const SCRIPT: &str = "Add-Type -AssemblyName System.Windows.Forms; \
+        $n = New-Object System.Windows.Forms.NotifyIcon; \
+        $n.Icon = [System.Drawing.SystemIcons]::Information; \
+        $n.Visible = $true; \
+        $n.ShowBalloonTip(5000, $env:VOHIYO_SUMMARY, $env:VOHIYO_BODY, 'None'); \
+        Start-Sleep -Seconds 5; $n.Dispose()";
+    spawn(
+        std::process::Command::new("powershell")
+            .args(["-NoProfile", "-WindowStyle", "Hidden", "-Command", SCRIPT])
+            .env("VOHIYO_SUMMARY", summary)
+            .env("VOHIYO_BODY", body),
+    )
+}
+
+#[cfg(windows)]
+fn play() -> anyhow::Result<()> {
+    spawn(std::process::Command::new("powershell").args([
+        "-NoProfile",
+        "-Command",
+        "[System.Media.SystemSounds]::Asterisk.Play()",
+    ]))
+}
+
+#[cfg(not(any(target_os = "linux", target_os = "macos", windows)))]
+fn show(_summary: &str, _body: &str) -> anyhow::Result<()> {
+    anyhow::bail!("notifications aren't supported on this platform")
+}
+
+#[cfg(not(any(target_os = "linux", target_os = "macos", windows)))]
+fn play() -> anyhow::Result<()> {
+    anyhow::bail!("sounds aren't supported on this platform")
+}
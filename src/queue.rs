@@ -1,9 +1,5 @@
 use std::collections::VecDeque;
 
-use twitch_message::messages::Privmsg;
-
-use crate::{runtime::EmoteMap, state::MessageOpts};
-
 pub struct Queue<T> {
     inner: VecDeque<T>,
     max: usize,
@@ -25,32 +21,46 @@ impl<T> Queue<T> {
         self.inner.push_back(item);
     }
 
-    pub fn iter(&self) -> impl Iterator<Item = &T> + ExactSizeIterator {
-        self.inner.iter()
+    pub const fn capacity(&self) -> usize {
+        self.max
+    }
+
+    /// Shrinking this drops the oldest items
+    pub fn set_capacity(&mut self, max: usize) {
+        assert!(max > 0, "max cannot be zero");
+        self.max = max;
+        let len = self.inner.len();
+        if len > max {
+            self.inner.drain(..len - max);
+        }
     }
-}
 
-impl Queue<crate::state::Message> {
-    pub fn populate(
-        &mut self,
-        iter: impl IntoIterator<Item = crate::db::Message>,
-        emote_map: &mut EmoteMap,
-    ) {
-        self.inner.extend(iter.into_iter().map(|msg| {
-            let msg = twitch_message::parse_as::<Privmsg>(&msg.raw).unwrap();
-            crate::state::Message::from_pm(
-                &msg,
-                emote_map,
-                MessageOpts {
-                    old: true,
-                    local: false,
-                },
-            )
-        }));
+    // this can go over the limit, but the front is the first thing to go on the next push
+    pub fn push_front(&mut self, item: T) {
+        self.inner.push_front(item);
+    }
+
+    /// Put these in front of everything, the front is what goes first when this is over the limit
+    pub fn prepend(&mut self, items: Vec<T>) {
+        for item in items.into_iter().rev() {
+            self.inner.push_front(item);
+        }
 
         let len = self.inner.len();
-        if len >= self.max {
+        if len > self.max {
             self.inner.drain(..len - self.max);
         }
     }
+
+    pub fn retain(&mut self, keep: impl FnMut(&T) -> bool) {
+        self.inner.retain(keep)
+    }
+
+    pub fn iter(&self) -> impl DoubleEndedIterator<Item = &T> + ExactSizeIterator {
+        self.inner.iter()
+    }
+
+    pub fn iter_mut(&mut self) -> impl DoubleEndedIterator<Item = &mut T> + ExactSizeIterator {
+        self.inner.iter_mut()
+    }
 }
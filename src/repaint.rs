@@ -1,8 +1,19 @@
-use std::sync::Arc;
+use std::{
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
+    time::Duration,
+};
 
 pub trait Repaint: Sized + Send + Sync + 'static {
     fn repaint(&self) {}
 
+    /// For things the user is waiting on, this skips any batching
+    fn repaint_now(&self) {
+        self.repaint()
+    }
+
     fn erased(self) -> ErasedRepaint {
         let this = self;
         Arc::new(move || this.repaint())
@@ -17,4 +28,66 @@ impl Repaint for egui::Context {
     }
 }
 
+/// Whether the window is unfocused or hasn't been touched in a while, shared with the background tasks
+#[derive(Clone, Default)]
+pub struct Idle(Arc<AtomicBool>);
+
+impl Idle {
+    pub fn get(&self) -> bool {
+        self.0.load(Ordering::Relaxed)
+    }
+
+    pub fn set(&self, idle: bool) {
+        self.0.store(idle, Ordering::Relaxed)
+    }
+}
+
+/// Set once a repaint was asked for, until the next frame starts
+///
+/// Background tasks ask for a repaint per message, so a burst of those only wakes the ui once
+#[derive(Clone, Default)]
+pub struct Pending(Arc<AtomicBool>);
+
+impl Pending {
+    // whether this is the first request since the last frame started
+    fn request(&self) -> bool {
+        !self.0.swap(true, Ordering::AcqRel)
+    }
+
+    pub fn frame_started(&self) {
+        self.0.store(false, Ordering::Release)
+    }
+}
+
+/// Batches repaints together while idle, and to one per frame otherwise
+#[derive(Clone)]
+pub struct IdleRepaint {
+    pub ctx: egui::Context,
+    pub idle: Idle,
+    pub pending: Pending,
+}
+
+impl IdleRepaint {
+    const BATCH_WINDOW: Duration = Duration::from_secs(1);
+}
+
+impl Repaint for IdleRepaint {
+    fn repaint(&self) {
+        if !self.pending.request() {
+            return;
+        }
+
+        if self.idle.get() {
+            self.ctx.request_repaint_after(Self::BATCH_WINDOW)
+        } else {
+            self.ctx.request_repaint()
+        }
+    }
+
+    fn repaint_now(&self) {
+        self.pending.request();
+        self.ctx.request_repaint()
+    }
+}
+
 pub type ErasedRepaint = Arc<dyn Fn() + Send + Sync + 'static>;
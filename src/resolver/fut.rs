@@ -2,35 +2,74 @@ use std::future::Future;
 
 use tokio::sync::oneshot;
 
+use super::Wakeup;
+
 pub struct Fut<T> {
     recv: oneshot::Receiver<T>,
+    // what this is wrapped in wakes up the same polling
+    wakeup: Option<Wakeup>,
+}
+
+/// The sending half of [`Fut::channel`], this wakes up the polling when it's used
+pub struct Completer<T> {
+    send: oneshot::Sender<T>,
+    wakeup: Option<Wakeup>,
+}
+
+impl<T> Completer<T> {
+    pub fn send(self, value: T) -> Result<(), T> {
+        let result = self.send.send(value);
+        if let Some(wakeup) = &self.wakeup {
+            wakeup.notify();
+        }
+        result
+    }
 }
 
 impl<T> Fut<T>
 where
     T: Send + 'static,
 {
-    pub const fn new(recv: oneshot::Receiver<T>) -> Self {
-        Self { recv }
+    pub fn channel(wakeup: &Wakeup) -> (Completer<T>, Self) {
+        Self::channel_inner(Some(wakeup.clone()))
+    }
+
+    fn channel_inner(wakeup: Option<Wakeup>) -> (Completer<T>, Self) {
+        let (send, recv) = oneshot::channel();
+        let completer = Completer {
+            send,
+            wakeup: wakeup.clone(),
+        };
+        (completer, Self { recv, wakeup })
     }
 
     pub fn wrap<E>(self, wrap: impl FnOnce(T) -> E + Send + Sync + 'static) -> Fut<E>
     where
         E: Send + 'static,
     {
-        <Fut<E>>::spawn(async { wrap(self.wait().await.expect("resolver future shouldn't panic")) })
+        let wakeup = self.wakeup.clone();
+        <Fut<E>>::spawn_inner(wakeup, async {
+            wrap(self.wait().await.expect("resolver future shouldn't panic"))
+        })
     }
 
-    pub fn spawn(fut: impl Future<Output = T> + Send + 'static) -> Self
-    where
-        T: Send + 'static,
-    {
-        let (tx, rx) = oneshot::channel();
+    /// For the ones the ui polls itself, finishing doesn't wake up the runtime maps
+    pub fn spawn(fut: impl Future<Output = T> + Send + 'static) -> Self {
+        Self::spawn_inner(None, fut)
+    }
+
+    /// For the ones a runtime map polls, finishing wakes it up
+    pub fn spawn_notify(wakeup: &Wakeup, fut: impl Future<Output = T> + Send + 'static) -> Self {
+        Self::spawn_inner(Some(wakeup.clone()), fut)
+    }
+
+    fn spawn_inner(wakeup: Option<Wakeup>, fut: impl Future<Output = T> + Send + 'static) -> Self {
+        let (tx, rx) = Self::channel_inner(wakeup);
         tokio::spawn(async move {
             let result = fut.await;
             let _ = tx.send(result);
         });
-        Self { recv: rx }
+        rx
     }
 
     pub fn try_resolve(&mut self) -> Option<T> {
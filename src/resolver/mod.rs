@@ -11,3 +11,6 @@ pub use fut::Fut;
 
 mod ready;
 pub use ready::Ready;
+
+mod wakeup;
+pub use wakeup::Wakeup;
@@ -0,0 +1,28 @@
+use std::sync::{
+    atomic::{AtomicBool, Ordering},
+    Arc,
+};
+
+/// Something finished in the background, so the runtime maps have something to pick up
+///
+/// This is handed to whatever finishes work in the background, the clones share the flag
+#[derive(Clone)]
+pub struct Wakeup(Arc<AtomicBool>);
+
+// this starts out set so the first frame polls everything
+impl Default for Wakeup {
+    fn default() -> Self {
+        Self(Arc::new(AtomicBool::new(true)))
+    }
+}
+
+impl Wakeup {
+    pub fn notify(&self) {
+        self.0.store(true, Ordering::Release)
+    }
+
+    /// Whether anything finished since the last time this was called
+    pub fn take(&self) -> bool {
+        self.0.swap(false, Ordering::AcqRel)
+    }
+}
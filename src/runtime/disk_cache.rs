@@ -0,0 +1,223 @@
+use std::{
+    path::{Path, PathBuf},
+    sync::atomic::{AtomicU64, Ordering},
+    time::{Duration, SystemTime},
+};
+
+use hashbrown::HashMap;
+use reqwest::header::{HeaderMap, CACHE_CONTROL, ETAG, LAST_MODIFIED};
+
+use crate::task_error::{Source, TaskErrors};
+
+#[derive(Clone, Debug, serde::Serialize, serde::Deserialize)]
+pub struct Meta {
+    pub url: String,
+    pub etag: Option<String>,
+    pub last_modified: Option<String>,
+    #[serde(with = "time::serde::rfc3339")]
+    pub fetched: time::OffsetDateTime,
+    pub max_age: u64,
+}
+
+impl Meta {
+    const DEFAULT_MAX_AGE: u64 = 60 * 60 * 24;
+
+    pub fn from_headers(url: &str, headers: &HeaderMap) -> Self {
+        let get = |key| {
+            headers
+                .get(key)
+                .and_then(|val| val.to_str().ok())
+                .map(ToString::to_string)
+        };
+
+        let max_age = get(CACHE_CONTROL)
+            .and_then(|val| {
+                val.split(',')
+                    .find_map(|part| part.trim().strip_prefix("max-age=")?.parse().ok())
+            })
+            .unwrap_or(Self::DEFAULT_MAX_AGE);
+
+        Self {
+            url: url.to_string(),
+            etag: get(ETAG),
+            last_modified: get(LAST_MODIFIED),
+            fetched: time::OffsetDateTime::now_utc(),
+            max_age,
+        }
+    }
+
+    /// A 304 might not repeat all of the validators, so keep the ones we already had
+    pub fn revalidated(self, headers: &HeaderMap) -> Self {
+        let new = Self::from_headers(&self.url, headers);
+        Self {
+            etag: new.etag.or(self.etag),
+            last_modified: new.last_modified.or(self.last_modified),
+            ..new
+        }
+    }
+
+    pub fn is_fresh(&self) -> bool {
+        time::OffsetDateTime::now_utc() - self.fetched < Duration::from_secs(self.max_age)
+    }
+}
+
+pub struct Cached {
+    pub data: Vec<u8>,
+    pub meta: Meta,
+}
+
+pub struct DiskCache {
+    dir: PathBuf,
+    errors: TaskErrors,
+    // how much was stored since the last sweep
+    written: AtomicU64,
+}
+
+impl DiskCache {
+    /// How much the cache keeps, the entries used least recently are removed past this
+    const MAX_BYTES: u64 = 512 * 1024 * 1024;
+    // how much can be stored before it's swept again
+    const SWEEP_AFTER: u64 = Self::MAX_BYTES / 8;
+
+    pub fn new(dir: impl AsRef<Path>, errors: TaskErrors) -> Self {
+        let dir = dir.as_ref().to_path_buf();
+        if let Err(err) = std::fs::create_dir_all(&dir) {
+            let context = format!("cannot create cache directory {}", dir.display());
+            errors.report(Source::Cache, context, err)
+        }
+        let this = Self {
+            dir,
+            errors,
+            written: AtomicU64::new(0),
+        };
+        this.spawn_sweep();
+        this
+    }
+
+    pub fn load(&self, url: &str) -> Option<Cached> {
+        let (path, meta) = self.paths(url);
+        let meta = std::fs::read_to_string(meta).ok()?;
+        let meta = serde_json::from_str::<Meta>(&meta).ok()?;
+        // hash collisions are unlikely, but not impossible
+        if meta.url != url {
+            return None;
+        }
+        let data = std::fs::read(&path).ok()?;
+        // reading it counts as using it, so it outlasts the ones that weren't
+        let _ = std::fs::File::options()
+            .write(true)
+            .open(path)
+            .and_then(|file| file.set_modified(SystemTime::now()));
+        Some(Cached { data, meta })
+    }
+
+    pub fn store(&self, data: &[u8], meta: &Meta) {
+        let (path, _) = self.paths(&meta.url);
+        if let Err(err) = std::fs::write(&path, data) {
+            self.errors.report(
+                Source::Cache,
+                format!("cannot write {}", path.display()),
+                err,
+            );
+            return;
+        }
+        self.store_meta(meta);
+
+        let written = data.len() as u64;
+        if self.written.fetch_add(written, Ordering::Relaxed) + written >= Self::SWEEP_AFTER {
+            self.written.store(0, Ordering::Relaxed);
+            self.spawn_sweep();
+        }
+    }
+
+    pub fn store_meta(&self, meta: &Meta) {
+        let (_, path) = self.paths(&meta.url);
+        let Ok(data) = serde_json::to_string(meta) else { return };
+        if let Err(err) = std::fs::write(&path, data) {
+            self.errors.report(
+                Source::Cache,
+                format!("cannot write {}", path.display()),
+                err,
+            )
+        }
+    }
+
+    fn spawn_sweep(&self) {
+        let (dir, errors) = (self.dir.clone(), self.errors.clone());
+        tokio::task::spawn_blocking(move || {
+            if let Err(err) = Self::sweep(&dir, Self::MAX_BYTES) {
+                let context = format!("cannot clean up {}", dir.display());
+                errors.report(Source::Cache, context, err)
+            }
+        });
+    }
+
+    // the data and its meta are removed together, going by whichever was touched last
+    fn sweep(dir: &Path, max: u64) -> std::io::Result<()> {
+        let mut entries = HashMap::<_, (SystemTime, u64, Vec<PathBuf>)>::new();
+        for entry in std::fs::read_dir(dir)? {
+            let entry = entry?;
+            let meta = entry.metadata()?;
+            let path = entry.path();
+            let Some(key) = path.file_stem().map(ToOwned::to_owned) else { continue };
+            if !meta.is_file() {
+                continue;
+            }
+
+            let (used, size, paths) = entries
+                .entry(key)
+                .or_insert_with(|| (SystemTime::UNIX_EPOCH, 0, vec![]));
+            *used = (*used).max(meta.modified()?);
+            *size += meta.len();
+            paths.push(path);
+        }
+
+        let mut total = entries.values().map(|(_, size, _)| size).sum::<u64>();
+        if total <= max {
+            return Ok(());
+        }
+
+        let mut entries = entries.into_values().collect::<Vec<_>>();
+        entries.sort_unstable_by_key(|&(used, ..)| used);
+
+        let mut removed = 0;
+        for (_, size, paths) in entries {
+            if total <= max {
+                break;
+            }
+            for path in paths {
+                std::fs::remove_file(path)?;
+            }
+            total -= size;
+            removed += 1;
+        }
+        log!("removed {removed} entries from {}", dir.display());
+        Ok(())
+    }
+
+    fn paths(&self, url: &str) -> (PathBuf, PathBuf) {
+        let key = format!("{:016x}", Self::key(url));
+        (
+            self.dir.join(&key).with_extension("bin"),
+            self.dir.join(key).with_extension("json"),
+        )
+    }
+
+    // this has to be the same across runs and toolchains, so it's fnv-1a rather than std's hasher
+    fn key(url: &str) -> u64 {
+        url.bytes().fold(0xcbf2_9ce4_8422_2325, |hash, byte| {
+            (hash ^ u64::from(byte)).wrapping_mul(0x0100_0000_01b3)
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn key_is_stable() {
+        assert_eq!(DiskCache::key(""), 0xcbf2_9ce4_8422_2325);
+        assert_eq!(DiskCache::key("a"), 0xaf63_dc4c_8601_ec8c);
+    }
+}
@@ -3,22 +3,88 @@ use std::borrow::Cow;
 use hashbrown::HashSet;
 use tokio::sync::mpsc::{unbounded_channel, UnboundedReceiver, UnboundedSender};
 
-use crate::repaint::Repaint;
+use crate::{
+    repaint::{ErasedRepaint, Repaint},
+    resolver::Wakeup,
+    task_error::{Source, TaskErrors},
+};
+
+use super::{emote_map::Scale, Supervised, TaskExit};
 
 pub struct EmoteFetcher {
     seen: HashSet<Cow<'static, str>>,
-    sender: UnboundedSender<String>,
+    sender: UnboundedSender<(String, Scale)>,
     ready: UnboundedReceiver<(String, String)>,
+    scale: Scale,
+    task: Supervised,
+    // kept for restarting the task
+    repaint: ErasedRepaint,
+    wakeup: Wakeup,
+    errors: TaskErrors,
+    http: reqwest::Client,
 }
 
 impl EmoteFetcher {
-    pub fn create(repaint: impl Repaint, http: reqwest::Client) -> Self {
+    pub fn create(
+        repaint: impl Repaint,
+        wakeup: Wakeup,
+        errors: TaskErrors,
+        http: reqwest::Client,
+        scale: Scale,
+    ) -> Self {
+        let repaint = repaint.erased();
+        let (task, sender, ready) = Self::spawn(
+            repaint.clone(),
+            wakeup.clone(),
+            errors.clone(),
+            http.clone(),
+        );
+        Self {
+            seen: HashSet::new(),
+            ready,
+            sender,
+            scale,
+            task,
+            repaint,
+            wakeup,
+            errors,
+            http,
+        }
+    }
+
+    /// How the lookup task ended, if it did
+    pub fn exit(&self) -> Option<TaskExit> {
+        self.task.exit()
+    }
+
+    /// Start the lookup task again, everything has to be looked up again after this
+    pub fn restart(&mut self) {
+        let (task, sender, ready) = Self::spawn(
+            self.repaint.clone(),
+            self.wakeup.clone(),
+            self.errors.clone(),
+            self.http.clone(),
+        );
+        (self.task, self.sender, self.ready) = (task, sender, ready);
+        self.seen.clear();
+    }
+
+    fn spawn(
+        repaint: ErasedRepaint,
+        wakeup: Wakeup,
+        errors: TaskErrors,
+        http: reqwest::Client,
+    ) -> (
+        Supervised,
+        UnboundedSender<(String, Scale)>,
+        UnboundedReceiver<(String, String)>,
+    ) {
         let (tx, ready) = unbounded_channel();
         let (sender, mut rx) = unbounded_channel();
 
-        tokio::spawn(async move {
-            while let Some(id) = rx.recv().await {
-                struct Emote(String);
+        let task = Supervised::spawn(wakeup.clone(), async move {
+            while let Some((id, scale)) = rx.recv().await {
+                struct Emote(String, Scale);
 
                 impl Emote {
                     fn animated_url(&self) -> String {
@@ -27,7 +93,7 @@ impl EmoteFetcher {
                     id = self.0,
                     format = "animated",
                     theme_mode = "dark",
-                    scale = "1.0"
+                    scale = self.1.emote_scale()
                 )
                     }
                     fn static_url(&self) -> String {
@@ -36,7 +102,7 @@ impl EmoteFetcher {
                     id = self.0,
                     format = "static",
                     theme_mode = "dark",
-                    scale = "1.0"
+                    scale = self.1.emote_scale()
                 )
                     }
 
@@ -44,38 +110,45 @@ impl EmoteFetcher {
                         &mut self,
                         url: String,
                         http: &reqwest::Client,
+                        errors: &TaskErrors,
                         tx: &UnboundedSender<(String, String)>,
                     ) -> bool {
-                        if let Ok(resp) = http.get(&url).send().await {
-                            if let Ok(_resp) = resp.error_for_status() {
+                        match http.get(&url).send().await.and_then(|r| r.error_for_status()) {
+                            Ok(..) => {
                                 let _ = tx.send((std::mem::take(&mut self.0), url));
-                                return true;
+                                true
                             }
+                            // a bad status just means there's no such emote in that format
+                            Err(err) if err.status().is_none() => {
+                                let context = format!("cannot fetch {url}");
+                                errors.report(Source::Emotes, context, err);
+                                false
+                            }
+                            Err(..) => false,
                         }
-                        false
                     }
                 }
 
-                let mut emote = Emote(id);
-                if emote.try_get(emote.animated_url(), &http, &tx).await {
-                    repaint.repaint();
-                    continue;
-                }
-
-                if emote.try_get(emote.static_url(), &http, &tx).await {
-                    repaint.repaint();
+                let mut emote = Emote(id, scale);
+                if emote.try_get(emote.animated_url(), &http, &errors, &tx).await
+                    || emote.try_get(emote.static_url(), &http, &errors, &tx).await
+                {
+                    wakeup.notify();
+                    repaint();
                     continue;
                 }
 
-                eprintln!("unknown emote: {id}", id = emote.0);
+                log!("unknown emote: {id}", id = emote.0);
             }
         });
 
-        Self {
-            seen: HashSet::new(),
-            ready,
-            sender,
-        }
+        (task, sender, ready)
+    }
+
+    /// Everything has to be looked up again after this
+    pub fn set_scale(&mut self, scale: Scale) {
+        self.scale = scale;
+        self.seen.clear();
     }
 
     pub fn poll(&mut self) -> Option<(String, String)> {
@@ -88,6 +161,6 @@ impl EmoteFetcher {
             return;
         }
         self.seen.insert(Cow::from(id.to_string()));
-        let _ = self.sender.send(id.to_string());
+        let _ = self.sender.send((id.to_string(), self.scale));
     }
 }
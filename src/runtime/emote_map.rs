@@ -1,39 +1,157 @@
-use std::borrow::Cow;
+use std::time::{Duration, Instant};
 
-use hashbrown::HashMap;
+use hashbrown::{HashMap, HashSet};
 
-use crate::{helix, repaint::Repaint, resolver};
+use crate::{helix, repaint::Repaint, resolver, task_error::TaskErrors};
 
-use super::EmoteFetcher;
+use super::{
+    third_party::{Change, SevenTv},
+    EmoteFetcher, TaskExit,
+};
+
+/// Which resolution of emotes and badges to use
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub enum Scale {
+    #[default]
+    One,
+    Two,
+    Four,
+}
+
+impl Scale {
+    pub fn for_pixels_per_point(ppp: f32) -> Self {
+        match ppp {
+            ppp if ppp <= 2.0 => Self::One,
+            ppp if ppp <= 4.0 => Self::Two,
+            _ => Self::Four,
+        }
+    }
+
+    // emotes top out at 3.0
+    pub const fn emote_scale(&self) -> &'static str {
+        match self {
+            Self::One => "1.0",
+            Self::Two => "2.0",
+            Self::Four => "3.0",
+        }
+    }
+
+    fn badge_url(&self, version: &helix::data::BadgeVersion) -> String {
+        match self {
+            Self::One => &version.image_url_1x,
+            Self::Two => &version.image_url_2x,
+            Self::Four => &version.image_url_4x,
+        }
+        .clone()
+    }
+}
+
+/// Where an emote came from
+#[derive(Copy, Clone, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub enum EmoteSource {
+    Twitch,
+    SevenTv,
+}
+
+impl EmoteSource {
+    pub const fn label(&self) -> &'static str {
+        match self {
+            Self::Twitch => "Twitch",
+            Self::SevenTv => "7TV",
+        }
+    }
+}
+
+/// What an emote is known through, it's only forgotten once none of them have it anymore
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+enum Owner {
+    /// The global emotes, from twitch or 7tv
+    Global,
+    /// One of the user's emote sets
+    Set(String),
+    /// A channel's emotes, by its user id
+    Channel(String),
+}
+
+/// A badge version that was resolved, kept around so they can be listed
+#[derive(Clone, Debug)]
+pub struct BadgeEntry {
+    pub set_id: String,
+    pub version: String,
+    pub description: String,
+    pub url: String,
+}
 
 pub struct EmoteMap {
     name_to_id: HashMap<String, String>,
     // TODO id_to_name
     emote_map: HashMap<String, String>,
     emote_fetcher: EmoteFetcher,
-    emote_set_map: resolver::ResolverMap<String, String, Vec<helix::data::EmoteSet>>,
+    emote_set_map: resolver::ResolverMap<String, String, (Owner, Vec<helix::data::EmoteSet>)>,
+    // who has each emote, by its id
+    owners: HashMap<String, HashSet<Owner>>,
     badge_map: resolver::ResolverMap<u64, String, (Option<String>, Vec<helix::data::Badge>)>,
+    // things that only exist for a specific channel, keyed by its user id
+    channel_emotes: HashMap<String, Vec<(String, String)>>,
+    channel_badges: HashMap<String, Vec<u64>>,
+    // what was resolved, keyed by the channel's user id or `-` for the global ones
+    badge_sets: HashMap<String, Vec<BadgeEntry>>,
+    // channels that were populated, so a repeated room state doesn't fetch them again
+    channels: HashSet<String>,
+    emote_sets: HashSet<String>,
+    // emotes that twitch doesn't know about, so they're found by name in the text
+    //
+    // these are kept out of `name_to_id`, twitch's emotes win when the names are the same. a
+    // channel's emote can have the same name as a global one, the newest one is used
+    third_party: HashMap<String, Vec<String>>,
+    seven_tv: SevenTv,
+    last_refresh: Instant,
+    scale: Scale,
     helix: helix::Client,
 }
 
 impl EmoteMap {
+    const EMOTE_SET_REFRESH: Duration = Duration::from_secs(30 * 60);
+
     pub fn create(
         helix: helix::Client,
-        repaint: impl Repaint,
+        repaint: impl Repaint + Clone,
+        wakeup: resolver::Wakeup,
+        errors: TaskErrors,
         http_client: reqwest::Client,
+        scale: Scale,
     ) -> Self {
-        let mut this = Self {
+        // the globals are left for `populate_globals`, so they don't hold up the first frame
+        Self {
             name_to_id: HashMap::new(),
             emote_map: HashMap::new(),
-            emote_fetcher: EmoteFetcher::create(repaint, http_client),
+            seven_tv: SevenTv::create(
+                repaint.clone(),
+                wakeup.clone(),
+                errors.clone(),
+                http_client.clone(),
+                scale,
+            ),
+            emote_fetcher: EmoteFetcher::create(repaint, wakeup, errors, http_client, scale),
             emote_set_map: resolver::ResolverMap::new(),
+            owners: HashMap::new(),
             badge_map: resolver::ResolverMap::new(),
+            channel_emotes: HashMap::new(),
+            channel_badges: HashMap::new(),
+            badge_sets: HashMap::new(),
+            channels: HashSet::new(),
+            emote_sets: HashSet::new(),
+            third_party: HashMap::new(),
+            last_refresh: Instant::now(),
+            scale,
             helix,
-        };
+        }
+    }
 
-        this.populate_global_badges();
-        this.populate_global_emotes();
-        this
+    pub fn populate_globals(&mut self) {
+        self.populate_global_badges();
+        self.populate_global_emotes();
+        self.seven_tv.fetch_globals();
     }
 
     pub fn populate_global_badges(&mut self) {
@@ -48,16 +166,151 @@ impl EmoteMap {
         }))
     }
 
+    /// Fetch the badges and emotes for a channel, unless that already happened
+    pub fn populate_channel(&mut self, id: &str) {
+        if !self.channels.insert(id.to_string()) {
+            return;
+        }
+        self.populate_channel_badges(id);
+        self.populate_channel_emotes(id);
+        self.seven_tv.fetch_channel(id);
+    }
+
     pub fn populate_global_emotes(&mut self) {
-        self.emote_set_map.add(self.helix.get_global_emotes())
+        self.emote_set_map.add(
+            self.helix
+                .get_global_emotes()
+                .wrap(|list| (Owner::Global, list)),
+        )
     }
 
     pub fn populate_channel_emotes(&mut self, id: &str) {
-        self.emote_set_map.add(self.helix.get_channel_emotes(id))
+        self.emote_set_map
+            .add(self.helix.get_channel_emotes(id).wrap({
+                let id = id.to_string();
+                |list| (Owner::Channel(id), list)
+            }))
     }
 
     pub fn populate_emote_set(&mut self, id: &str) {
-        self.emote_set_map.add(self.helix.get_emote_set(id))
+        self.emote_sets.insert(id.to_string());
+        self.emote_set_map.add(self.helix.get_emote_set(id).wrap({
+            let id = id.to_string();
+            |list| (Owner::Set(id), list)
+        }))
+    }
+
+    /// Fetch any emote sets we haven't seen yet
+    pub fn sync_emote_sets<'a>(&mut self, sets: impl IntoIterator<Item = &'a str>) {
+        for set in sets {
+            if !self.emote_sets.contains(set) {
+                log!("populating new emote set: {set}");
+                self.populate_emote_set(set)
+            }
+        }
+    }
+
+    // emotes get added to existing sets (and globals) without the set ids changing
+    fn refresh_emote_sets(&mut self) {
+        let helix = self.helix.uncached();
+        self.emote_set_map
+            .add(helix.get_global_emotes().wrap(|list| (Owner::Global, list)));
+        for set in &self.emote_sets {
+            self.emote_set_map.add(helix.get_emote_set(set).wrap({
+                let set = set.clone();
+                |list| (Owner::Set(set), list)
+            }))
+        }
+    }
+
+    /// Switch to a different resolution, returns whether it changed
+    ///
+    /// The old urls are kept around until the new ones arrive, so nothing disappears in the meantime
+    pub fn set_scale(&mut self, scale: Scale) -> bool {
+        if self.scale == scale {
+            return false;
+        }
+
+        log!("switching emote scale to {scale:?}");
+        self.scale = scale;
+
+        self.emote_fetcher.set_scale(scale);
+        for id in self.emote_map.keys() {
+            self.emote_fetcher.lookup(id);
+        }
+
+        self.populate_global_badges();
+        self.populate_global_emotes();
+        for set in self.emote_sets.clone() {
+            self.populate_emote_set(&set);
+        }
+
+        self.seven_tv.set_scale(scale);
+        self.seven_tv.fetch_globals();
+
+        for id in std::mem::take(&mut self.channel_emotes).into_keys() {
+            self.populate_channel_emotes(&id);
+            self.seven_tv.fetch_channel(&id);
+        }
+        for id in std::mem::take(&mut self.channel_badges).into_keys() {
+            self.populate_channel_badges(&id);
+        }
+
+        true
+    }
+
+    /// Forget the emotes and badges that only belong to this channel
+    ///
+    /// Emotes that are also global or in one of the user's sets are kept
+    pub fn release_channel(&mut self, id: &str) {
+        self.channels.remove(id);
+        self.seven_tv.release_channel(id);
+        let owner = Owner::Channel(id.to_string());
+        for (emote_id, name) in self.channel_emotes.remove(id).into_iter().flatten() {
+            self.release_emote(&emote_id, &name, &owner);
+        }
+
+        for hash in self.channel_badges.remove(id).into_iter().flatten() {
+            self.badge_map.remove_by_key(&hash);
+        }
+        self.badge_sets.remove(id);
+    }
+
+    fn add_owner(&mut self, id: &str, owner: Owner) {
+        self.owners.entry_ref(id).or_default().insert(owner);
+    }
+
+    // this drops the emote once nothing else has it
+    fn release_emote(&mut self, id: &str, name: &str, owner: &Owner) {
+        if let Some(owners) = self.owners.get_mut(id) {
+            owners.remove(owner);
+            if !owners.is_empty() {
+                return;
+            }
+            self.owners.remove(id);
+        }
+
+        self.emote_set_map.remove_by_key(id);
+        self.emote_map.remove(id);
+        if self.name_to_id.get(name).map(String::as_str) == Some(id) {
+            self.name_to_id.remove(name);
+        }
+        self.remove_third_party(id, name);
+    }
+
+    fn remove_third_party(&mut self, id: &str, name: &str) {
+        let Some(ids) = self.third_party.get_mut(name) else { return };
+        ids.retain(|third_party| third_party != id);
+        if ids.is_empty() {
+            self.third_party.remove(name);
+        }
+    }
+
+    /// The badges resolved for a channel, or the global ones without a channel
+    pub fn badge_sets(&self, room_id: Option<&str>) -> &[BadgeEntry] {
+        self.badge_sets
+            .get(room_id.unwrap_or("-"))
+            .map_or(&[], Vec::as_slice)
     }
 
     pub fn get_badge_url(&self, set_id: &str, id: &str) -> Option<&str> {
@@ -76,6 +329,37 @@ impl EmoteMap {
             .map(<String>::as_str)
     }
 
+    /// How the emote lookups ended, if they did
+    pub fn fetcher_exit(&self) -> Option<TaskExit> {
+        self.emote_fetcher.exit()
+    }
+
+    /// Start the emote lookups again, anything that wasn't found yet is asked for again
+    pub fn restart_fetcher(&mut self) {
+        self.emote_fetcher.restart();
+        let missing = self
+            .name_to_id
+            .values()
+            .filter(|id| !self.emote_map.contains_key(*id));
+        for id in missing {
+            self.emote_fetcher.lookup(id);
+        }
+    }
+
+    /// How the 7TV event api task ended, if it did
+    pub fn seven_tv_exit(&self) -> Option<TaskExit> {
+        self.seven_tv.exit()
+    }
+
+    /// Start following the 7TV emote changes again, the sets are fetched again to be watched
+    pub fn restart_seven_tv(&mut self) {
+        self.seven_tv.restart();
+        self.seven_tv.fetch_globals();
+        for id in &self.channels {
+            self.seven_tv.fetch_channel(id);
+        }
+    }
+
     pub fn insert_emote(&mut self, id: &str, name: &str) {
         if !self.emote_map.contains_key(id) {
             self.emote_fetcher.lookup(id);
@@ -87,6 +371,70 @@ impl EmoteMap {
         self.name_to_id.get(name).map(<String>::as_str)
     }
 
+    /// Whether we can use the emote ourselves, it has to be global or in one of our sets
+    ///
+    /// Emotes from other people's messages are known by name, but we might not have them
+    pub fn can_use(&self, name: &str) -> bool {
+        self.get_emote_id(name)
+            .and_then(|id| self.owners.get(id))
+            .map_or(false, |owners| {
+                owners
+                    .iter()
+                    .any(|owner| matches!(owner, Owner::Global | Owner::Set(..)))
+            })
+    }
+
+    /// Names of the emotes we know about that start with `prefix`, in no particular order
+    ///
+    /// Twitch's come first, the others are only there when twitch doesn't have the name
+    pub fn names_starting_with<'a>(&'a self, prefix: &'a str) -> impl Iterator<Item = &'a str> {
+        let third_party = self
+            .third_party
+            .keys()
+            .map(String::as_str)
+            .filter(move |name| name.starts_with(prefix) && !self.name_to_id.contains_key(*name));
+        self.twitch_names_starting_with(prefix).chain(third_party)
+    }
+
+    /// Like [`Self::names_starting_with`], but only twitch's, e.g. for emote only mode
+    pub fn twitch_names_starting_with<'a>(
+        &'a self,
+        prefix: &'a str,
+    ) -> impl Iterator<Item = &'a str> {
+        self.name_to_id
+            .keys()
+            .map(String::as_str)
+            .filter(move |name| name.starts_with(prefix))
+    }
+
+    /// The id of an emote from outside of twitch, by its name
+    pub fn get_third_party_id(&self, name: &str) -> Option<&str> {
+        self.third_party
+            .get(name)
+            .and_then(|ids| ids.last())
+            .map(<String>::as_str)
+    }
+
+    /// The emotes that only belong to this channel, as their source, id and name
+    pub fn channel_emotes(&self, room_id: &str) -> impl Iterator<Item = (EmoteSource, &str, &str)> {
+        self.channel_emotes
+            .get(room_id)
+            .into_iter()
+            .flatten()
+            .map(|(id, name)| {
+                // 7tv is the only other source so far
+                let source = match self.third_party.get(name) {
+                    Some(ids) if ids.contains(id) => EmoteSource::SevenTv,
+                    _ => EmoteSource::Twitch,
+                };
+                (source, id.as_str(), name.as_str())
+            })
+    }
+
+    pub fn has_third_party(&self) -> bool {
+        !self.third_party.is_empty()
+    }
+
     pub fn get_emote_url(&self, id: &str) -> Option<&str> {
         self.emote_set_map
             .try_get(id)
@@ -94,6 +442,41 @@ impl EmoteMap {
             .map(<String>::as_str)
     }
 
+    // these are shown as soon as they're known, the urls don't need to be looked up
+    fn apply_third_party(&mut self, change: Change) {
+        match change {
+            Change::Added {
+                channel,
+                id,
+                name,
+                url,
+            } => {
+                self.emote_map.insert(id.clone(), url);
+                let ids = self.third_party.entry_ref(name.as_str()).or_default();
+                ids.retain(|third_party| *third_party != id);
+                ids.push(id.clone());
+
+                let owner = channel.clone().map_or(Owner::Global, Owner::Channel);
+                self.add_owner(&id, owner);
+                if let Some(channel) = channel {
+                    self.channel_emotes
+                        .entry(channel)
+                        .or_default()
+                        .push((id, name));
+                }
+            }
+
+            Change::Removed { channel, id, name } => {
+                if let Some(list) = channel.as_ref().and_then(|c| self.channel_emotes.get_mut(c)) {
+                    list.retain(|(emote_id, _)| *emote_id != id);
+                }
+                // the same emote can still be in another set, it keeps its name then
+                let owner = channel.map_or(Owner::Global, Owner::Channel);
+                self.release_emote(&id, &name, &owner);
+            }
+        }
+    }
+
     fn hash_badge(user_id: &str, set_id: &str, id: &str) -> u64 {
         use hashbrown::hash_map::DefaultHashBuilder as H;
         use std::hash::{BuildHasher, Hash, Hasher};
@@ -123,37 +506,77 @@ impl EmoteMap {
                 .unwrap_or_else(or_else)
         }
 
-        fn make_emote_url(set: &crate::helix::data::EmoteSet) -> String {
+        fn make_emote_url(set: &crate::helix::data::EmoteSet, scale: Scale) -> String {
             format!(
                 "https://static-cdn.jtvnw.net/emoticons/v2/{id}/{format}/{theme_mode}/{scale}",
                 id = set.id,
                 format = filter(&set.format, "animated", "static"),
                 theme_mode = filter(&set.theme_mode, "dark", "light"),
-                scale = filter(&set.scale, "1.0", None)
+                scale = filter(&set.scale, scale.emote_scale(), None)
             )
         }
 
+        if self.last_refresh.elapsed() >= Self::EMOTE_SET_REFRESH {
+            self.last_refresh = Instant::now();
+            self.refresh_emote_sets();
+        }
+
         while let Some((id, url)) = self.emote_fetcher.poll() {
             self.emote_map.insert(id, url);
         }
 
-        self.emote_set_map.poll(|entry, list| {
+        while let Some(change) = self.seven_tv.poll() {
+            self.apply_third_party(change);
+        }
+
+        let (channel_emotes, owners, name_to_id, scale) = (
+            &mut self.channel_emotes,
+            &mut self.owners,
+            &mut self.name_to_id,
+            self.scale,
+        );
+        self.emote_set_map.poll(|entry, (owner, list)| {
             for set in list {
-                let url = make_emote_url(&set);
+                let url = make_emote_url(&set, scale);
                 entry.set(set.id.clone(), url);
-                self.name_to_id.insert(set.name, set.id);
+                if let Owner::Channel(cid) = &owner {
+                    channel_emotes
+                        .entry_ref(cid.as_str())
+                        .or_default()
+                        .push((set.id.clone(), set.name.clone()));
+                }
+                owners
+                    .entry_ref(set.id.as_str())
+                    .or_default()
+                    .insert(owner.clone());
+                name_to_id.insert(set.name, set.id);
             }
         });
 
+        let (channel_badges, badge_sets) = (&mut self.channel_badges, &mut self.badge_sets);
         self.badge_map.poll(|entry, (cid, list)| {
-            let cid = cid.map_or_else(|| Cow::from("-"), Cow::from);
+            let mut owned = vec![];
+            let mut entries = vec![];
             for set in list {
                 for version in set.versions {
-                    let hash = Self::hash_badge(&cid, &set.set_id, &version.id);
-                    let url = version.image_url_1x;
+                    let hash =
+                        Self::hash_badge(cid.as_deref().unwrap_or("-"), &set.set_id, &version.id);
+                    let url = scale.badge_url(&version);
+                    owned.push(hash);
+                    entries.push(BadgeEntry {
+                        set_id: set.set_id.clone(),
+                        version: version.id,
+                        description: version.description,
+                        url: url.clone(),
+                    });
                     entry.set(hash, url)
                 }
             }
+
+            badge_sets.insert(cid.clone().unwrap_or_else(|| String::from("-")), entries);
+            if let Some(cid) = cid {
+                channel_badges.entry(cid).or_default().extend(owned);
+            }
         });
     }
 }
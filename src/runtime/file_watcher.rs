@@ -0,0 +1,60 @@
+use std::{
+    ffi::OsString,
+    path::{Path, PathBuf},
+};
+
+use hashbrown::HashSet;
+use notify::{EventKind, RecursiveMode, Watcher};
+use tokio::sync::mpsc::{unbounded_channel, UnboundedReceiver};
+
+use crate::{
+    repaint::Repaint,
+    task_error::{Source, TaskErrors},
+};
+
+pub struct FileWatcher {
+    // this has to be kept alive for events to keep coming in
+    _watcher: Option<notify::RecommendedWatcher>,
+    changed: UnboundedReceiver<PathBuf>,
+}
+
+impl FileWatcher {
+    pub fn create(files: &[&str], repaint: impl Repaint, errors: &TaskErrors) -> Self {
+        let (tx, changed) = unbounded_channel();
+        let files = files.iter().map(OsString::from).collect::<Vec<_>>();
+
+        let handler = move |event: notify::Result<notify::Event>| {
+            let Ok(event) = event else { return };
+            if !matches!(event.kind, EventKind::Create(..) | EventKind::Modify(..)) {
+                return;
+            }
+
+            for path in event.paths {
+                let Some(name) = path.file_name() else { continue };
+                if files.iter().any(|file| file == name) && tx.send(path).is_ok() {
+                    repaint.repaint();
+                }
+            }
+        };
+
+        // editors (and us) tend to replace files rather than writing to them,
+        // so watch the directory instead of the files themselves
+        let watcher = notify::recommended_watcher(handler)
+            .and_then(|mut watcher| {
+                watcher.watch(Path::new("."), RecursiveMode::NonRecursive)?;
+                Ok(watcher)
+            })
+            .map_err(|err| errors.report(Source::Files, "cannot watch files", err))
+            .ok();
+
+        Self {
+            _watcher: watcher,
+            changed,
+        }
+    }
+
+    /// Files that changed since the last poll
+    pub fn poll(&mut self) -> HashSet<PathBuf> {
+        std::iter::from_fn(|| self.changed.try_recv().ok()).collect()
+    }
+}
@@ -1,5 +1,10 @@
 use crate::{helix, resolver};
 
+/// The sizes box art is asked for in, so the same game doesn't get cached at every size
+///
+/// These are all 3:4, like the box art on twitch
+const BOX_ART_TIERS: [(u32, u32); 4] = [(54, 72), (108, 144), (216, 288), (432, 576)];
+
 pub struct GameMap {
     map: resolver::ResolverMap<String, helix::data::Game, Option<helix::data::Game>>,
     helix: helix::Client,
@@ -19,18 +24,25 @@ impl GameMap {
     }
 
     pub fn poll(&mut self) {
-        const WIDTH: &str = "144";
-        const HEIGHT: &str = "152";
-
         self.map.poll(|entry, game| {
-            if let Some(mut game) = game {
-                game.box_art_url = game
-                    .box_art_url
-                    .replace("{width}", WIDTH)
-                    .replace("{height}", HEIGHT);
-
+            if let Some(game) = game {
                 entry.set(game.id.clone(), game);
             }
         });
     }
+
+    /// The box art for a game, in the smallest size that covers `height` points on screen
+    ///
+    /// Each size has its own url, so they're cached apart from each other
+    pub fn box_art_url(game: &helix::data::Game, height: f32, pixels_per_point: f32) -> String {
+        let pixels = (height * pixels_per_point).ceil() as u32;
+        let (width, height) = BOX_ART_TIERS
+            .into_iter()
+            .find(|&(_, h)| h >= pixels)
+            .unwrap_or(BOX_ART_TIERS[BOX_ART_TIERS.len() - 1]);
+
+        game.box_art_url
+            .replace("{width}", &width.to_string())
+            .replace("{height}", &height.to_string())
+    }
 }
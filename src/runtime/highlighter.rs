@@ -0,0 +1,38 @@
+use crate::state::Message;
+
+/// Picks out the messages that mention us, or that have one of the keywords in them
+#[derive(Default)]
+pub struct Highlighter {
+    keywords: Vec<String>,
+}
+
+impl Highlighter {
+    pub fn new(keywords: &[String]) -> Self {
+        let mut this = Self::default();
+        this.set_keywords(keywords);
+        this
+    }
+
+    // the keywords are matched anywhere in the message, ignoring case
+    pub fn set_keywords(&mut self, keywords: &[String]) {
+        self.keywords = keywords
+            .iter()
+            .map(|keyword| keyword.trim().to_lowercase())
+            .filter(|keyword| !keyword.is_empty())
+            .collect();
+    }
+
+    pub fn is_highlight(&self, msg: &Message, our_name: &str) -> bool {
+        if msg.opts.local || msg.sender.eq_ignore_ascii_case(our_name) {
+            return false;
+        }
+
+        let data = msg.data.to_lowercase();
+        // the name has to be a whole word, with or without the @
+        let mentioned = data
+            .split(|c: char| !(c.is_ascii_alphanumeric() || c == '_'))
+            .any(|word| word.eq_ignore_ascii_case(our_name));
+
+        mentioned || self.keywords.iter().any(|keyword| data.contains(keyword))
+    }
+}
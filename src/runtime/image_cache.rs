@@ -1,17 +1,46 @@
-use crate::{image::Image, resolver};
+use std::time::{Duration, Instant};
+
+use hashbrown::{HashMap, HashSet};
+
+use crate::{image::Image, resolver, task_error::TaskErrors};
 
 use super::ImageFetcher;
 
+struct Failure {
+    attempts: u32,
+    retry_at: Option<Instant>,
+}
+
 pub struct ImageCache {
     images: resolver::ResolverMap<String, Image, (String, Option<Image>)>,
+    failed: HashMap<String, Failure>,
+    // which channels have displayed an image, so it can be freed once they're all gone
+    owners: HashMap<String, HashSet<String>>,
+    owner: Option<String>,
     fetcher: ImageFetcher,
+    animating: bool,
+    idle: bool,
 }
 
 impl ImageCache {
-    pub fn new(http: reqwest::Client, ctx: egui::Context) -> Self {
+    const MAX_ATTEMPTS: u32 = 5;
+    const BASE_BACKOFF: Duration = Duration::from_secs(2);
+    const MAX_BACKOFF: Duration = Duration::from_secs(5 * 60);
+
+    pub fn new(
+        http: reqwest::Client,
+        ctx: egui::Context,
+        wakeup: resolver::Wakeup,
+        errors: TaskErrors,
+    ) -> Self {
         Self {
             images: resolver::ResolverMap::new(),
-            fetcher: ImageFetcher::new(http, ctx),
+            failed: HashMap::new(),
+            owners: HashMap::new(),
+            owner: None,
+            fetcher: ImageFetcher::new(http, ctx, wakeup, errors),
+            animating: false,
+            idle: false,
         }
     }
 
@@ -19,20 +48,126 @@ impl ImageCache {
         self.images.update().set(url, image);
     }
 
+    /// Images fetched after this are attributed to `channel`
+    pub fn set_owner(&mut self, channel: Option<&str>) {
+        if self.owner.as_deref() != channel {
+            self.owner = channel.map(ToString::to_string)
+        }
+    }
+
+    pub fn release_channel(&mut self, channel: &str) {
+        let (images, failed) = (&mut self.images, &mut self.failed);
+        self.owners.retain(|url, owners| {
+            owners.remove(channel);
+            if !owners.is_empty() {
+                return true;
+            }
+
+            log!("evicting image: {url}");
+            images.remove_by_key(url);
+            failed.remove(url);
+            false
+        });
+    }
+
     pub fn get_image(&mut self, url: &str) -> Option<&Image> {
+        if let Some(owner) = &self.owner {
+            let owners = self.owners.entry_ref(url).or_default();
+            if !owners.contains(owner) {
+                owners.insert(owner.clone());
+            }
+        }
+
+        // new fetches wait until we're back, but anything already loaded is still shown
+        if self.idle {
+            return self.images.try_get(url);
+        }
+
         self.images
             .get_or_update(url, |url| self.fetcher.get_image(url))
     }
 
+    /// Drop every image, e.g. when they need to be fetched at a different resolution
+    pub fn clear(&mut self) {
+        self.images.retain(|_, _| false);
+        self.failed.clear();
+        self.owners.clear();
+    }
+
+    /// How many fetches crashed since the last restart
+    pub fn crashed_fetches(&self) -> usize {
+        self.fetcher.panics().count()
+    }
+
+    /// Ask again for everything that hasn't shown up, the crashed fetches never will
+    pub fn restart_fetches(&mut self) {
+        self.fetcher.panics().reset();
+        self.images.retain(|_, image| image.is_ready());
+    }
+
+    pub fn set_idle(&mut self, idle: bool) {
+        self.idle = idle;
+    }
+
+    pub fn is_idle(&self) -> bool {
+        self.idle
+    }
+
+    pub fn is_failed(&self, url: &str) -> bool {
+        self.failed.contains_key(url)
+    }
+
+    /// Forget about any previous failures and fetch the image again
+    pub fn retry(&mut self, url: &str) {
+        if self.failed.remove(url).is_some() {
+            self.images.remove_by_key(url);
+        }
+    }
+
+    pub fn mark_animating(&mut self) {
+        self.animating = true;
+    }
+
+    /// Whether a visible animation was drawn since the last time this was called
+    pub fn take_animating(&mut self) -> bool {
+        std::mem::take(&mut self.animating)
+    }
+
     pub fn poll(&mut self) {
+        let failed = &mut self.failed;
         self.images.poll(|entry, (k, v)| match v {
             Some(v) => {
-                eprintln!("fetched image: {k}");
+                log!("fetched image: {k}");
+                failed.remove(&k);
                 entry.set(k, v);
             }
             None => {
-                eprintln!("could not fetch image: {k}")
+                log!("could not fetch image: {k}");
+                let failure = failed.entry(k).or_insert(Failure {
+                    attempts: 0,
+                    retry_at: None,
+                });
+                failure.attempts += 1;
+                failure.retry_at = (failure.attempts < Self::MAX_ATTEMPTS).then(|| {
+                    let backoff = Self::BASE_BACKOFF * 2_u32.pow(failure.attempts - 1);
+                    Instant::now() + backoff.min(Self::MAX_BACKOFF)
+                });
             }
         });
+
+        let now = Instant::now();
+        for (url, failure) in &mut self.failed {
+            if failure.retry_at.filter(|&at| at <= now).is_some() {
+                log!("retrying image: {url}");
+                failure.retry_at.take();
+                // the next lookup will fetch it again
+                self.images.remove_by_key(url);
+            }
+        }
+
+        // animations that went off screen give back their frames
+        for (_, image) in self.images.ready_iter() {
+            image.unload_hidden();
+        }
     }
 }
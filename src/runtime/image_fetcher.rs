@@ -1,38 +1,149 @@
-use crate::{image::Image, resolver};
+use std::sync::Arc;
+
+use reqwest::{
+    header::{IF_MODIFIED_SINCE, IF_NONE_MATCH},
+    StatusCode,
+};
+
+use crate::{
+    image::Image,
+    resolver,
+    task_error::{Source, TaskErrors},
+};
+
+use super::{disk_cache::Meta, DiskCache, PanicCounter};
 
 #[derive(Clone)]
 pub struct ImageFetcher {
     http: reqwest::Client,
     ctx: egui::Context,
+    disk: Arc<DiskCache>,
+    panics: PanicCounter,
+    wakeup: resolver::Wakeup,
+    errors: TaskErrors,
 }
 
 impl ImageFetcher {
-    pub const fn new(http: reqwest::Client, ctx: egui::Context) -> Self {
-        Self { http, ctx }
+    const CACHE_DIR: &str = "image_cache";
+
+    pub fn new(
+        http: reqwest::Client,
+        ctx: egui::Context,
+        wakeup: resolver::Wakeup,
+        errors: TaskErrors,
+    ) -> Self {
+        Self {
+            http,
+            ctx,
+            disk: Arc::new(DiskCache::new(Self::CACHE_DIR, errors.clone())),
+            panics: PanicCounter::new(wakeup.clone()),
+            wakeup,
+            errors,
+        }
+    }
+
+    /// How many fetches crashed, their images never show up
+    pub fn panics(&self) -> &PanicCounter {
+        &self.panics
     }
 
     pub fn get_image(&self, url: &str) -> resolver::Fut<(String, Option<Image>)> {
+        enum Update {
+            Nothing,
+            Meta(Meta),
+            Data(Meta),
+        }
+
         let ctx = self.ctx.clone();
         let client = self.http.clone();
+        let disk = self.disk.clone();
+        let errors = self.errors.clone();
         let url = url.to_string();
 
-        let (tx, rx) = tokio::sync::oneshot::channel();
-        tokio::spawn(async move {
-            let Ok(resp) = client.get(&url).send().await else { return };
-            let true = resp.status().is_success() else {
-            let _ = tx.send((url, None));
-            return;
-        };
+        let (tx, rx) = resolver::Fut::channel(&self.wakeup);
+        self.panics.spawn(async move {
+            let cached = tokio::task::spawn_blocking({
+                let (disk, url) = (disk.clone(), url.clone());
+                move || disk.load(&url)
+            })
+            .await
+            .ok()
+            .flatten();
+
+            let (data, update) = match cached {
+                Some(cached) if cached.meta.is_fresh() => (cached.data, Update::Nothing),
+                cached => {
+                    let mut req = client.get(&url);
+                    if let Some(meta) = cached.as_ref().map(|cached| &cached.meta) {
+                        if let Some(etag) = &meta.etag {
+                            req = req.header(IF_NONE_MATCH, etag);
+                        }
+                        if let Some(last_modified) = &meta.last_modified {
+                            req = req.header(IF_MODIFIED_SINCE, last_modified);
+                        }
+                    }
 
-            let Ok(data) = resp.bytes().await.map(|data| data.to_vec()) else { return };
+                    let resp = match req.send().await {
+                        Ok(resp) => resp,
+                        Err(err) => {
+                            errors.report(Source::Images, format!("cannot fetch {url}"), err);
+                            let _ = tx.send((url, None));
+                            return;
+                        }
+                    };
+                    match (resp.status(), cached) {
+                        (StatusCode::NOT_MODIFIED, Some(cached)) => {
+                            let meta = cached.meta.revalidated(resp.headers());
+                            (cached.data, Update::Meta(meta))
+                        }
+                        (status, _) if status.is_success() => {
+                            let meta = Meta::from_headers(&url, resp.headers());
+                            let data = match resp.bytes().await {
+                                Ok(data) => data,
+                                Err(err) => {
+                                    let context = format!("cannot read {url}");
+                                    errors.report(Source::Images, context, err);
+                                    let _ = tx.send((url, None));
+                                    return;
+                                }
+                            };
+                            (data.to_vec(), Update::Data(meta))
+                        }
+                        (status, _) => {
+                            let context = format!("cannot fetch {url}");
+                            errors.report(Source::Images, context, status);
+                            let _ = tx.send((url, None));
+                            return;
+                        }
+                    }
+                }
+            };
 
-            tokio::task::spawn_blocking(move || {
-                let Ok(img) = Image::load_rgba_data(&ctx, &url, &data) else { return };
-                let _ = tx.send((url, Some(img)));
+            let loaded = tokio::task::spawn_blocking(move || {
+                match update {
+                    Update::Nothing => {}
+                    Update::Meta(meta) => disk.store_meta(&meta),
+                    Update::Data(meta) => disk.store(&data, &meta),
+                }
+
+                let img = Image::load_rgba_data(&ctx, &url, &data)
+                    .map_err(|err| {
+                        errors.report(Source::Images, format!("cannot load {url}"), err)
+                    })
+                    .ok();
+                let _ = tx.send((url, img));
                 ctx.request_repaint();
-            });
+            })
+            .await;
+
+            // a panic while decoding is counted like one while fetching
+            if let Err(err) = loaded {
+                if err.is_panic() {
+                    std::panic::resume_unwind(err.into_panic())
+                }
+            }
         });
 
-        resolver::Fut::new(rx)
+        rx
     }
 }
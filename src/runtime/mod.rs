@@ -10,8 +10,11 @@ pub use user_map::UserMap;
 mod stream_check;
 pub use stream_check::{Action, StreamCheck, StreamStatus};
 
+mod highlighter;
+pub use highlighter::Highlighter;
+
 mod emote_map;
-pub use emote_map::EmoteMap;
+pub use emote_map::{BadgeEntry, EmoteMap, EmoteSource, Scale};
 
 mod image_cache;
 pub use image_cache::ImageCache;
@@ -21,3 +24,14 @@ pub use emote_fetcher::EmoteFetcher;
 
 mod image_fetcher;
 pub use image_fetcher::ImageFetcher;
+
+mod disk_cache;
+pub use disk_cache::DiskCache;
+
+mod watchdog;
+pub use watchdog::{PanicCounter, Supervised, TaskExit};
+
+mod file_watcher;
+pub use file_watcher::FileWatcher;
+
+pub mod third_party;
@@ -1,15 +1,17 @@
-use std::time::Duration;
+use std::time::{Duration, Instant};
 
-use hashbrown::HashSet;
+use hashbrown::{HashMap, HashSet};
 use tokio::sync::mpsc::{unbounded_channel, UnboundedReceiver, UnboundedSender};
 
 use crate::{
     helix,
-    repaint::Repaint,
+    repaint::{Idle, Repaint},
     resolver,
     util::{select2, Either},
 };
 
+use super::{Supervised, TaskExit};
+
 pub enum Action<T> {
     Added(T),
     Removed(T),
@@ -20,6 +22,33 @@ pub struct StreamStatus {
     pub user_id: String,
 }
 
+// what the ui asks of the polling task
+enum Command {
+    Watch(Action<String>),
+    /// Check this one now, rather than waiting for its turn
+    Refresh(String),
+    Interval(Duration),
+}
+
+// what the polling task knows about a watched stream
+struct Watched {
+    last_checked: Option<Instant>,
+    offline_since: Option<Instant>,
+}
+
+impl Watched {
+    // streams that have been offline for a while are checked less often
+    fn is_due(&self, now: Instant) -> bool {
+        let long_offline = self
+            .offline_since
+            .map_or(false, |since| now - since >= StreamCheck::LONG_OFFLINE);
+        !long_offline
+            || self.last_checked.map_or(true, |last| {
+                now - last >= StreamCheck::LONG_OFFLINE_CHECK_DURATION
+            })
+    }
+}
+
 pub struct StreamCheck {
     map: resolver::ResolverMap<
         String,
@@ -27,22 +56,37 @@ pub struct StreamCheck {
         (String, Option<helix::data::Stream>),
     >,
 
-    watching: UnboundedSender<Action<String>>,
+    watching: UnboundedSender<Command>,
     update: UnboundedReceiver<(String, Option<helix::data::Stream>)>,
     send: UnboundedSender<Action<StreamStatus>>,
     events: UnboundedReceiver<Action<StreamStatus>>,
+    task: Supervised,
 }
 
 impl StreamCheck {
-    const STREAM_CHECK_DURATION: Duration = Duration::from_secs(30);
+    const IDLE_STREAM_CHECK_DURATION: Duration = Duration::from_secs(5 * 60);
     const BURST_WINDOW: Duration = Duration::from_secs(1);
 
-    pub fn create(helix: helix::Client, repaint: impl Repaint) -> Self {
+    // a stream that has been offline this long is only checked every so often
+    const LONG_OFFLINE: Duration = Duration::from_secs(60 * 60);
+    const LONG_OFFLINE_CHECK_DURATION: Duration = Duration::from_secs(10 * 60);
+
+    /// `interval` is how often the streams are checked while we aren't idle
+    pub fn create(
+        helix: helix::Client,
+        repaint: impl Repaint,
+        wakeup: resolver::Wakeup,
+        idle: Idle,
+        interval: Duration,
+    ) -> Self {
         let (watching, rx) = unbounded_channel();
         let (resp, update) = unbounded_channel();
         let (send, events) = unbounded_channel();
 
-        tokio::spawn(Self::poll_helix(helix, repaint, rx, resp));
+        let task = Supervised::spawn(
+            wakeup.clone(),
+            Self::poll_helix(helix, repaint, wakeup, idle, interval, rx, resp),
+        );
 
         Self {
             map: resolver::ResolverMap::new(),
@@ -50,9 +94,15 @@ impl StreamCheck {
             update,
             send,
             events,
+            task,
         }
     }
 
+    /// How the polling task ended, if it did
+    pub fn exit(&self) -> Option<TaskExit> {
+        self.task.exit()
+    }
+
     pub fn poll(&mut self) {
         while let Ok((id, stream)) = self.update.try_recv() {
             Self::update(&mut self.map.update(), &self.send, id, stream);
@@ -71,76 +121,141 @@ impl StreamCheck {
     pub fn get_or_subscribe(&mut self, user_id: &str) -> Option<&helix::data::Stream> {
         self.map
             .get_or_else(user_id, |user_id| {
-                eprintln!("subscribing to events for stream: {user_id}");
-                let _ = self.watching.send(Action::Added(user_id.to_string()));
+                log!("subscribing to events for stream: {user_id}");
+                let _ = self
+                    .watching
+                    .send(Command::Watch(Action::Added(user_id.to_string())));
             })?
             .as_ref()
     }
 
-    pub fn unsubscribe(&self, user_id: &str) {
-        let _ = self.watching.send(Action::Removed(user_id.to_string()));
+    /// The stream, without subscribing to it
+    pub fn try_get(&self, user_id: &str) -> Option<&helix::data::Stream> {
+        self.map.try_get(user_id)?.as_ref()
+    }
+
+    /// Stop checking the stream, subscribing again fetches it fresh
+    pub fn unsubscribe(&mut self, user_id: &str) {
+        self.map.remove_by_key(user_id);
+        let _ = self
+            .watching
+            .send(Command::Watch(Action::Removed(user_id.to_string())));
+    }
+
+    /// Check the stream right away, even if it's been offline for a while
+    pub fn refresh(&self, user_id: &str) {
+        let _ = self.watching.send(Command::Refresh(user_id.to_string()));
+    }
+
+    /// Change how often the streams are checked while we aren't idle
+    pub fn set_interval(&self, interval: Duration) {
+        let _ = self.watching.send(Command::Interval(interval));
     }
 
     async fn poll_helix(
         helix: helix::Client,
         repaint: impl Repaint,
-        mut recv: UnboundedReceiver<Action<String>>,
+        wakeup: resolver::Wakeup,
+        idle: Idle,
+        mut interval: Duration,
+        mut recv: UnboundedReceiver<Command>,
         send: UnboundedSender<(String, Option<helix::data::Stream>)>,
     ) {
-        let mut set = <HashSet<String>>::new();
+        let mut set = <HashMap<String, Watched>>::new();
         let mut queue = vec![];
 
         macro_rules! batch_send {
-            ($set:expr) => {
-                let mut delta = <HashSet<&str>>::from_iter($set.map(|s| &**s));
-                let Some(streams) = helix.get_many_streams($set).wait().await else { continue };
+            ($ids:expr) => {
+                let ids = $ids;
+                if ids.is_empty() {
+                    continue;
+                }
+
+                let now = Instant::now();
+                let mut delta = <HashSet<String>>::from_iter(ids.iter().cloned());
+                let Some(streams) = helix.get_many_streams(ids).wait().await else { continue };
                 for stream in streams {
                     delta.remove(&*stream.user_id);
+                    if let Some(watched) = set.get_mut(&*stream.user_id) {
+                        watched.last_checked.replace(now);
+                        watched.offline_since.take();
+                    }
                     if send.send((stream.user_id.clone(), Some(stream))).is_err() {
                         break;
                     }
                 }
 
                 for remaining in delta {
-                    if send.send((remaining.to_owned(), None)).is_err() {
+                    if let Some(watched) = set.get_mut(&remaining) {
+                        watched.last_checked.replace(now);
+                        watched.offline_since.get_or_insert(now);
+                    }
+                    if send.send((remaining, None)).is_err() {
                         break;
                     }
                 }
+                wakeup.notify();
             };
         }
 
+        // this is measured from the last check so the burst window doesn't keep pushing it back,
+        // and so coming back from idle catches up on the next tick
+        let mut last_check = Instant::now();
         loop {
-            let mut sleep = std::pin::pin!(tokio::time::sleep(Self::STREAM_CHECK_DURATION));
+            let period = if idle.get() {
+                interval.max(Self::IDLE_STREAM_CHECK_DURATION)
+            } else {
+                interval
+            };
+
+            let deadline = tokio::time::Instant::from_std(last_check + period);
+            let mut sleep = std::pin::pin!(tokio::time::sleep_until(deadline));
             let mut recv = std::pin::pin!(tokio::time::timeout(Self::BURST_WINDOW, recv.recv()));
 
             match select2(&mut sleep, &mut recv).await {
                 Either::Left(_) => {
-                    batch_send!(set.iter());
-                    if !set.is_empty() {
-                        repaint.repaint();
-                    }
+                    last_check = Instant::now();
+                    let due = set
+                        .iter()
+                        .filter(|(_, watched)| watched.is_due(last_check))
+                        .map(|(id, _)| id.clone())
+                        .collect::<Vec<_>>();
+                    batch_send!(due);
+                    repaint.repaint();
                 }
 
-                Either::Right(Ok(Some(action))) => {
-                    let channel = match action {
-                        Action::Added(channel) => channel,
-                        Action::Removed(channel) => {
+                Either::Right(Ok(Some(command))) => {
+                    let channel = match command {
+                        Command::Watch(Action::Added(channel)) => channel,
+                        Command::Watch(Action::Removed(channel)) => {
                             set.remove(&channel);
                             continue;
                         }
+                        Command::Refresh(channel) => {
+                            if set.contains_key(&channel) && !queue.contains(&channel) {
+                                queue.push(channel)
+                            }
+                            continue;
+                        }
+                        Command::Interval(new) => {
+                            interval = new;
+                            continue;
+                        }
                     };
 
-                    if set.insert(channel.clone()) {
+                    if !set.contains_key(&channel) {
+                        let watched = Watched {
+                            last_checked: None,
+                            offline_since: None,
+                        };
+                        set.insert(channel.clone(), watched);
                         queue.push(channel)
                     }
                 }
 
                 Either::Right(Err(..)) => {
-                    if !queue.is_empty() {
-                        batch_send!(queue.iter());
-                        queue.clear();
-                        repaint.repaint();
-                    }
+                    batch_send!(std::mem::take(&mut queue));
+                    repaint.repaint();
                 }
 
                 Either::Right(..) => break,
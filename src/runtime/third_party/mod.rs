@@ -0,0 +1,20 @@
+//! Emotes from outside of twitch, these are matched by name in the message text
+
+mod seven_tv;
+pub use seven_tv::SevenTv;
+
+/// An emote showing up, or going away
+pub enum Change {
+    Added {
+        /// The room id of the channel, or `None` for the global ones
+        channel: Option<String>,
+        id: String,
+        name: String,
+        url: String,
+    },
+    Removed {
+        channel: Option<String>,
+        id: String,
+        name: String,
+    },
+}
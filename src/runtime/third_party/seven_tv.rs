@@ -0,0 +1,483 @@
+use std::time::{Duration, Instant};
+
+use futures_util::{SinkExt, StreamExt};
+use hashbrown::HashMap;
+use tokio::sync::mpsc::{unbounded_channel, UnboundedReceiver, UnboundedSender};
+use tokio_tungstenite::tungstenite::Message;
+
+use crate::{
+    repaint::{ErasedRepaint, Repaint},
+    resolver::Wakeup,
+    runtime::{Scale, Supervised, TaskExit},
+    task_error::{Source, TaskErrors},
+};
+
+use super::Change;
+
+/// Global and per-channel emotes from 7TV, the channel sets are kept up to date over their event api
+pub struct SevenTv {
+    http: reqwest::Client,
+    repaint: ErasedRepaint,
+    wakeup: Wakeup,
+    errors: TaskErrors,
+    send: UnboundedSender<Update>,
+    updates: UnboundedReceiver<Update>,
+    watch: UnboundedSender<Watch>,
+    scale: Scale,
+    task: Supervised,
+}
+
+enum Update {
+    Added {
+        channel: Option<String>,
+        emote: Emote,
+    },
+    Removed {
+        channel: Option<String>,
+        id: String,
+        name: String,
+    },
+}
+
+enum Watch {
+    Add {
+        set_id: String,
+        channel: Option<String>,
+    },
+    Remove {
+        channel: String,
+    },
+}
+
+impl SevenTv {
+    const API: &str = "https://7tv.io/v3";
+    const EVENTS: &str = "wss://events.7tv.io/v3";
+
+    pub fn create(
+        repaint: impl Repaint,
+        wakeup: Wakeup,
+        errors: TaskErrors,
+        http: reqwest::Client,
+        scale: Scale,
+    ) -> Self {
+        let repaint = repaint.erased();
+        let (send, updates) = unbounded_channel();
+        let (watch, task) = Self::spawn(&send, &repaint, &wakeup, &errors);
+
+        Self {
+            http,
+            repaint,
+            wakeup,
+            errors,
+            send,
+            updates,
+            watch,
+            scale,
+            task,
+        }
+    }
+
+    /// How the event api task ended, if it did
+    pub fn exit(&self) -> Option<TaskExit> {
+        self.task.exit()
+    }
+
+    /// Start the event api task again
+    ///
+    /// It doesn't know about any of the sets after this, they're watched again as they're fetched
+    pub fn restart(&mut self) {
+        let (watch, task) = Self::spawn(&self.send, &self.repaint, &self.wakeup, &self.errors);
+        (self.watch, self.task) = (watch, task);
+    }
+
+    fn spawn(
+        send: &UnboundedSender<Update>,
+        repaint: &ErasedRepaint,
+        wakeup: &Wakeup,
+        errors: &TaskErrors,
+    ) -> (UnboundedSender<Watch>, Supervised) {
+        let (watch, rx) = unbounded_channel();
+        let listen = Self::listen(
+            send.clone(),
+            repaint.clone(),
+            wakeup.clone(),
+            errors.clone(),
+            rx,
+        );
+        (watch, Supervised::spawn(wakeup.clone(), listen))
+    }
+
+    /// Everything has to be fetched again after this, for the new urls
+    pub fn set_scale(&mut self, scale: Scale) {
+        self.scale = scale;
+    }
+
+    pub fn fetch_globals(&self) {
+        self.fetch(None, format!("{}/emote-sets/global", Self::API));
+    }
+
+    pub fn fetch_channel(&self, room_id: &str) {
+        let url = format!("{}/users/twitch/{room_id}", Self::API);
+        self.fetch(Some(room_id.to_string()), url);
+    }
+
+    /// Stop listening for changes to a channel's emotes
+    pub fn release_channel(&self, room_id: &str) {
+        let _ = self.watch.send(Watch::Remove {
+            channel: room_id.to_string(),
+        });
+    }
+
+    pub fn poll(&mut self) -> Option<Change> {
+        loop {
+            let (channel, emote) = match self.updates.try_recv().ok()? {
+                Update::Added { channel, emote } => (channel, emote),
+                Update::Removed { channel, id, name } => {
+                    return Some(Change::Removed { channel, id, name })
+                }
+            };
+
+            let Some(url) = emote.url(self.scale) else {
+                log!("no usable image for 7tv emote: {}", emote.name);
+                continue;
+            };
+            return Some(Change::Added {
+                channel,
+                id: emote.id,
+                name: emote.name,
+                url,
+            });
+        }
+    }
+
+    fn fetch(&self, channel: Option<String>, url: String) {
+        #[derive(serde::Deserialize)]
+        struct User {
+            emote_set: Option<EmoteSet>,
+        }
+
+        let (http, send, watch, repaint, wakeup, errors) = (
+            self.http.clone(),
+            self.send.clone(),
+            self.watch.clone(),
+            self.repaint.clone(),
+            self.wakeup.clone(),
+            self.errors.clone(),
+        );
+
+        tokio::spawn(async move {
+            let resp = http
+                .get(&url)
+                .send()
+                .await
+                .and_then(|r| r.error_for_status());
+            let resp = match resp {
+                Ok(resp) => resp,
+                // most channels don't use 7tv, so a 404 isn't worth mentioning
+                Err(err) if err.status() == Some(reqwest::StatusCode::NOT_FOUND) => return,
+                Err(err) => {
+                    errors.report(Source::SevenTv, format!("cannot fetch {url}"), err);
+                    return;
+                }
+            };
+
+            let set = match &channel {
+                Some(..) => resp.json::<User>().await.map(|user| user.emote_set),
+                None => resp.json::<EmoteSet>().await.map(Some),
+            };
+            let set = match set {
+                Ok(Some(set)) => set,
+                Ok(None) => return,
+                Err(err) => {
+                    errors.report(Source::SevenTv, format!("invalid emotes from {url}"), err);
+                    return;
+                }
+            };
+
+            for emote in set.emotes.into_iter().flatten() {
+                let channel = channel.clone();
+                if send.send(Update::Added { channel, emote }).is_err() {
+                    return;
+                }
+            }
+            let _ = watch.send(Watch::Add {
+                set_id: set.id,
+                channel,
+            });
+
+            wakeup.notify();
+            repaint();
+        });
+    }
+
+    // the subscriptions are sent again after a reconnect, and it stays disconnected while none of
+    // the channels we're in use 7tv. reconnecting backs off while it keeps failing
+    async fn listen(
+        send: UnboundedSender<Update>,
+        repaint: ErasedRepaint,
+        wakeup: Wakeup,
+        errors: TaskErrors,
+        mut watch: UnboundedReceiver<Watch>,
+    ) {
+        const BASE_BACKOFF: Duration = Duration::from_secs(5);
+        const MAX_BACKOFF: Duration = Duration::from_secs(5 * 60);
+        // a connection that lasted this long was working, so the backoff starts over
+        const STABLE: Duration = Duration::from_secs(60);
+
+        let mut sets = <HashMap<String, Option<String>>>::new();
+        let mut backoff = BASE_BACKOFF;
+        let reconnect = |backoff: &mut Duration| {
+            let wait = *backoff;
+            *backoff = (*backoff * 2).min(MAX_BACKOFF);
+            log!("reconnecting to the 7tv event api in {wait:?}");
+            tokio::time::sleep(wait)
+        };
+
+        'outer: loop {
+            while !Self::has_channel(&sets) {
+                match watch.recv().await {
+                    Some(watching) => {
+                        Self::apply_watch(&mut sets, watching);
+                    }
+                    None => return,
+                }
+            }
+
+            let mut ws = match tokio_tungstenite::connect_async(Self::EVENTS).await {
+                Ok((ws, _)) => ws,
+                Err(err) => {
+                    errors.report(Source::SevenTv, "cannot connect to the event api", err);
+                    reconnect(&mut backoff).await;
+                    continue;
+                }
+            };
+            let connected = Instant::now();
+
+            for set_id in sets.keys() {
+                if ws.send(Self::subscribe(35, set_id)).await.is_err() {
+                    reconnect(&mut backoff).await;
+                    continue 'outer;
+                }
+            }
+
+            loop {
+                let ok = tokio::select! {
+                    watching = watch.recv() => match watching {
+                        Some(watching) => {
+                            let mut ok = true;
+                            for msg in Self::apply_watch(&mut sets, watching) {
+                                ok &= ws.send(msg).await.is_ok();
+                            }
+                            ok
+                        }
+                        None => return,
+                    },
+
+                    msg = ws.next() => match msg {
+                        Some(Ok(Message::Text(text))) => {
+                            let ok = Self::dispatch(&text, &sets, &send, &errors);
+                            wakeup.notify();
+                            repaint();
+                            ok
+                        }
+                        Some(Ok(..)) => true,
+                        Some(Err(err)) => {
+                            errors.report(Source::SevenTv, "event api error", err);
+                            false
+                        }
+                        None => false,
+                    },
+                };
+
+                if !ok {
+                    break;
+                }
+
+                if !Self::has_channel(&sets) {
+                    log!("no channels use 7tv, leaving the event api");
+                    let _ = ws.close(None).await;
+                    continue 'outer;
+                }
+            }
+
+            if connected.elapsed() >= STABLE {
+                backoff = BASE_BACKOFF;
+            }
+            reconnect(&mut backoff).await;
+        }
+    }
+
+    // the global set alone isn't worth a connection, it rarely changes
+    fn has_channel(sets: &HashMap<String, Option<String>>) -> bool {
+        sets.values().any(Option::is_some)
+    }
+
+    // this hands back the subscriptions to send for the change
+    fn apply_watch(sets: &mut HashMap<String, Option<String>>, watching: Watch) -> Vec<Message> {
+        match watching {
+            Watch::Add { set_id, channel } => {
+                let msg = Self::subscribe(35, &set_id);
+                match sets.insert(set_id, channel) {
+                    Some(..) => vec![],
+                    None => vec![msg],
+                }
+            }
+            Watch::Remove { channel } => {
+                let removed = sets
+                    .iter()
+                    .filter(|(_, c)| c.as_deref() == Some(&*channel))
+                    .map(|(set_id, _)| set_id.clone())
+                    .collect::<Vec<_>>();
+                removed
+                    .into_iter()
+                    .map(|set_id| {
+                        sets.remove(&set_id);
+                        Self::subscribe(36, &set_id)
+                    })
+                    .collect()
+            }
+        }
+    }
+
+    // 35 subscribes, 36 unsubscribes
+    fn subscribe(op: u8, set_id: &str) -> Message {
+        let msg = serde_json::json!({
+            "op": op,
+            "d": {
+                "type": "emote_set.update",
+                "condition": { "object_id": set_id },
+            }
+        });
+        Message::Text(msg.to_string())
+    }
+
+    // this returns false when the server wants us to reconnect
+    fn dispatch(
+        text: &str,
+        sets: &HashMap<String, Option<String>>,
+        send: &UnboundedSender<Update>,
+        errors: &TaskErrors,
+    ) -> bool {
+        #[derive(serde::Deserialize)]
+        struct Frame {
+            op: u8,
+            #[serde(default)]
+            d: Option<Dispatch>,
+        }
+
+        #[derive(serde::Deserialize)]
+        struct Dispatch {
+            body: Option<ChangeMap>,
+        }
+
+        #[derive(serde::Deserialize)]
+        struct ChangeMap {
+            id: String,
+            #[serde(default)]
+            pushed: Vec<ChangeField>,
+            #[serde(default)]
+            pulled: Vec<ChangeField>,
+            #[serde(default)]
+            updated: Vec<ChangeField>,
+        }
+
+        #[derive(serde::Deserialize)]
+        struct ChangeField {
+            key: String,
+            value: Option<serde_json::Value>,
+            old_value: Option<serde_json::Value>,
+        }
+
+        #[derive(serde::Deserialize)]
+        struct Removed {
+            id: String,
+            name: String,
+        }
+
+        let frame = match serde_json::from_str::<Frame>(text) {
+            Ok(frame) => frame,
+            Err(err) => {
+                errors.report(Source::SevenTv, "invalid event", err);
+                return true;
+            }
+        };
+
+        // 4 is a reconnect, 7 is the end of the stream
+        match frame.op {
+            0 => {}
+            4 | 7 => return false,
+            _ => return true,
+        }
+
+        let Some(body) = frame.d.and_then(|d| d.body) else { return true };
+        let Some(channel) = sets.get(&body.id) else { return true };
+
+        let emotes = |field: &&ChangeField| field.key == "emotes";
+
+        // a renamed emote is in both
+        for field in body.pulled.iter().chain(&body.updated).filter(emotes) {
+            let Some(old) = field.old_value.clone() else { continue };
+            let Ok(Removed { id, name }) = serde_json::from_value(old) else { continue };
+            let channel = channel.clone();
+            let _ = send.send(Update::Removed { channel, id, name });
+        }
+
+        for field in body.pushed.iter().chain(&body.updated).filter(emotes) {
+            let Some(new) = field.value.clone() else { continue };
+            let Ok(emote) = serde_json::from_value(new) else { continue };
+            let channel = channel.clone();
+            let _ = send.send(Update::Added { channel, emote });
+        }
+
+        true
+    }
+}
+
+#[derive(serde::Deserialize)]
+struct EmoteSet {
+    id: String,
+    emotes: Option<Vec<Emote>>,
+}
+
+#[derive(serde::Deserialize)]
+struct Emote {
+    id: String,
+    name: String,
+    data: EmoteData,
+}
+
+#[derive(serde::Deserialize)]
+struct EmoteData {
+    host: Host,
+}
+
+#[derive(serde::Deserialize)]
+struct Host {
+    url: String,
+    files: Vec<File>,
+}
+
+#[derive(serde::Deserialize)]
+struct File {
+    name: String,
+    format: String,
+}
+
+impl Emote {
+    // webp is only used when there's nothing else, it loses the animation
+    fn url(&self, scale: Scale) -> Option<String> {
+        let size = match scale {
+            Scale::One => "1x",
+            Scale::Two => "2x",
+            Scale::Four => "4x",
+        };
+
+        let Host { url, files } = &self.data.host;
+        let file = ["GIF", "PNG", "WEBP"].into_iter().find_map(|format| {
+            files
+                .iter()
+                .find(|file| file.format == format && file.name.starts_with(size))
+        })?;
+        Some(format!("https:{url}/{name}", name = file.name))
+    }
+}
@@ -1,14 +1,49 @@
+use std::time::{Duration, Instant};
+
+use hashbrown::HashMap;
+
 use crate::{helix, resolver};
 
+// a single lookup or a batch of them, by the login they were asked for. a batch that failed
+// hands back its logins so they can be looked up again
+type Lookup = Result<Vec<(String, Option<helix::data::User>)>, Vec<String>>;
+
+struct Retry {
+    logins: Vec<String>,
+    attempts: u32,
+    retry_at: Option<Instant>,
+}
+
 pub struct UserMap {
-    map: resolver::ResolverMap<String, helix::data::User, Option<(String, helix::data::User)>>,
+    map: resolver::ResolverMap<String, helix::data::User, Lookup>,
+    fetched: HashMap<String, Instant>,
+    // logins that helix didn't know about, and when it said so
+    missing: HashMap<String, Instant>,
+    // these stay pending in the map until they're looked up again
+    retry: Retry,
+    last_check: Instant,
     helix: helix::Client,
 }
 
 impl UserMap {
+    const USER_TTL: Duration = Duration::from_secs(30 * 60);
+    // a user could be created or unbanned, so a miss doesn't last as long
+    const MISSING_TTL: Duration = Duration::from_secs(10 * 60);
+    const CHECK_INTERVAL: Duration = Duration::from_secs(60);
+    const BASE_BACKOFF: Duration = Duration::from_secs(2);
+    const MAX_BACKOFF: Duration = Duration::from_secs(5 * 60);
+
     pub fn create(helix: helix::Client) -> Self {
         Self {
             map: resolver::ResolverMap::new(),
+            fetched: HashMap::new(),
+            missing: HashMap::new(),
+            retry: Retry {
+                logins: vec![],
+                attempts: 0,
+                retry_at: None,
+            },
+            last_check: Instant::now(),
             helix,
         }
     }
@@ -16,14 +51,141 @@ impl UserMap {
     pub fn get(&mut self, login: &str) -> Option<&helix::data::User> {
         let login = login.strip_prefix('#').unwrap_or(login);
         self.map
-            .get_or_update(login, |login| self.helix.get_user(login))
+            .get_or_update(login, |login| Self::fetch(&self.helix, login))
+    }
+
+    /// Look up the users that aren't known yet with as few requests as possible
+    ///
+    /// This is for when many channels are opened at once, e.g. on startup
+    pub fn prefetch<'a>(&mut self, logins: impl IntoIterator<Item = &'a str>) {
+        let mut batch = vec![];
+        for login in logins {
+            let login = login.strip_prefix('#').unwrap_or(login);
+            if self.map.contains(login) || batch.iter().any(|l| l == login) {
+                continue;
+            }
+            // marked as pending so a `get` meanwhile doesn't look it up again
+            self.map.get_or_else(login, |_| {});
+            batch.push(login.to_string());
+        }
+
+        self.lookup(batch)
+    }
+
+    fn lookup(&mut self, batch: Vec<String>) {
+        if batch.is_empty() {
+            return;
+        }
+
+        log!("looking up {} users", batch.len());
+        let fut = self.helix.get_many_users(batch.clone()).wrap(move |users| {
+            let Ok(users) = users else { return Err(batch) };
+            let mut users = users
+                .into_iter()
+                .map(|user| (user.login.to_lowercase(), user))
+                .collect::<HashMap<_, _>>();
+            Ok(batch
+                .into_iter()
+                .map(|login| {
+                    let user = users.remove(&login.to_lowercase());
+                    (login, user)
+                })
+                .collect())
+        });
+        self.map.add(fut);
+    }
+
+    /// Whether the user was looked up and doesn't exist
+    pub fn is_missing(&self, login: &str) -> bool {
+        self.missing
+            .contains_key(login.strip_prefix('#').unwrap_or(login))
+    }
+
+    fn fetch(helix: &helix::Client, login: &str) -> resolver::Fut<Lookup> {
+        helix.get_user(login).wrap({
+            let login = login.to_string();
+            move |user| Ok(vec![(login, user.map(|(_, user)| user))])
+        })
+    }
+
+    pub fn try_get(&self, login: &str) -> Option<&helix::data::User> {
+        let login = login.strip_prefix('#').unwrap_or(login);
+        self.map.try_get(login)
+    }
+
+    pub fn refresh(&mut self, login: &str) {
+        let login = login.strip_prefix('#').unwrap_or(login);
+        if !self.map.contains(login) {
+            return;
+        }
+
+        // the old entry stays around until the new one arrives
+        self.fetched.insert(login.to_string(), Instant::now());
+        self.map.add(Self::fetch(&self.helix.uncached(), login));
     }
 
     pub fn poll(&mut self) {
-        self.map.poll(|entry, user| {
-            if let Some((_name, user)) = user {
+        let (fetched, missing, retry) = (&mut self.fetched, &mut self.missing, &mut self.retry);
+        self.map.poll(|entry, users| {
+            let users = match users {
+                // nothing is waiting on a retry, so the backoff starts over
+                Ok(users) if retry.logins.is_empty() => {
+                    retry.attempts = 0;
+                    users
+                }
+                Ok(users) => users,
+                Err(logins) => {
+                    retry.attempts += 1;
+                    let backoff = Self::BASE_BACKOFF * 2_u32.pow(retry.attempts.min(16) - 1);
+                    retry.retry_at = Some(Instant::now() + backoff.min(Self::MAX_BACKOFF));
+                    retry.logins.extend(logins);
+                    return;
+                }
+            };
+
+            for (login, user) in users {
+                let Some(user) = user else {
+                    missing.insert(login, Instant::now());
+                    continue;
+                };
+                missing.remove(&login);
+                fetched.insert(user.login.clone(), Instant::now());
                 entry.set(user.login.clone(), user);
             }
         });
+
+        let due = self.retry.retry_at.filter(|&at| at <= Instant::now());
+        if due.is_some() {
+            self.retry.retry_at.take();
+            let batch = std::mem::take(&mut self.retry.logins);
+            log!("retrying the lookup of {} users", batch.len());
+            self.lookup(batch);
+        }
+
+        if self.last_check.elapsed() < Self::CHECK_INTERVAL {
+            return;
+        }
+        self.last_check = Instant::now();
+
+        // the next lookup asks helix again
+        let map = &mut self.map;
+        self.missing.retain(|login, at| {
+            let keep = at.elapsed() < Self::MISSING_TTL;
+            if !keep {
+                map.remove_by_key(login);
+            }
+            keep
+        });
+
+        let stale = self
+            .fetched
+            .iter()
+            .filter_map(|(login, at)| (at.elapsed() >= Self::USER_TTL).then(|| login.clone()))
+            .collect::<Vec<_>>();
+
+        for login in stale {
+            log!("revalidating user: {login}");
+            self.refresh(&login);
+        }
     }
 }
@@ -0,0 +1,98 @@
+use std::{
+    future::Future,
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc, Mutex, PoisonError,
+    },
+};
+
+use tokio::task::JoinError;
+
+use crate::resolver::Wakeup;
+
+/// How a task that was meant to keep running ended
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum TaskExit {
+    Panicked,
+    Stopped,
+}
+
+impl TaskExit {
+    fn from_join(result: Result<(), JoinError>) -> Self {
+        match result {
+            Err(err) if err.is_panic() => Self::Panicked,
+            _ => Self::Stopped,
+        }
+    }
+
+    pub const fn label(&self) -> &'static str {
+        match self {
+            Self::Panicked => "crashed",
+            Self::Stopped => "stopped",
+        }
+    }
+}
+
+/// A task that runs for as long as we do, so it can be noticed when it doesn't
+///
+/// The end is picked up by a second task waiting on the first one, so a panic is told
+/// apart from the task just returning
+pub struct Supervised {
+    exit: Arc<Mutex<Option<TaskExit>>>,
+}
+
+impl Supervised {
+    pub fn spawn(wakeup: Wakeup, fut: impl Future<Output = ()> + Send + 'static) -> Self {
+        let exit = Arc::new(Mutex::new(None));
+        let handle = tokio::spawn(fut);
+        tokio::spawn({
+            let exit = exit.clone();
+            async move {
+                let how = TaskExit::from_join(handle.await);
+                *exit.lock().unwrap_or_else(PoisonError::into_inner) = Some(how);
+                wakeup.notify();
+            }
+        });
+        Self { exit }
+    }
+
+    /// How the task ended, if it did
+    pub fn exit(&self) -> Option<TaskExit> {
+        *self.exit.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+}
+
+/// Counts the panics of work that's spread over many short tasks
+#[derive(Clone)]
+pub struct PanicCounter {
+    count: Arc<AtomicUsize>,
+    wakeup: Wakeup,
+}
+
+impl PanicCounter {
+    pub fn new(wakeup: Wakeup) -> Self {
+        Self {
+            count: Arc::default(),
+            wakeup,
+        }
+    }
+
+    pub fn spawn(&self, fut: impl Future<Output = ()> + Send + 'static) {
+        let handle = tokio::spawn(fut);
+        let this = self.clone();
+        tokio::spawn(async move {
+            if TaskExit::from_join(handle.await) == TaskExit::Panicked {
+                this.count.fetch_add(1, Ordering::Relaxed);
+                this.wakeup.notify();
+            }
+        });
+    }
+
+    pub fn count(&self) -> usize {
+        self.count.load(Ordering::Relaxed)
+    }
+
+    pub fn reset(&self) {
+        self.count.store(0, Ordering::Relaxed)
+    }
+}
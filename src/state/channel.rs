@@ -1,32 +1,304 @@
+use std::time::{Duration, Instant};
+
+use egui::Color32;
 use uuid::Uuid;
 
-use crate::queue::Queue;
+use crate::{db, queue::Queue, resolver::Fut, runtime::EmoteMap, twitch::ChannelName};
 
-use super::Message;
+use super::{InputHistory, Message, MessageOpts};
 
 pub struct Channel {
-    pub name: String,
+    pub name: ChannelName,
+    /// The broadcaster's user id, this stays the same when the channel is renamed
+    pub room_id: Option<String>,
     pub buffer: String,
+    /// What was sent from the buffer, for going back through with the arrow keys
+    pub input_history: InputHistory,
     pub marker: Option<Uuid>,
     pub messages: Queue<Message>,
+    /// Whether the history was asked for, it arrives in the background
+    pub history_loaded: bool,
+    pending_history: Option<(Fut<Option<History>>, Instant)>,
+    // the page before the oldest stored message that's loaded, and whether there's more
+    pending_older: Option<Fut<Option<History>>>,
+    oldest_stored: Option<Uuid>,
+    history_exhausted: bool,
+    /// How many older messages were just put in front, the view keeps its place by skipping them
+    pub prepended: usize,
+    pub read_only: bool,
+    pub pinned: Vec<Pinned>,
+    pub jump_to: Option<Uuid>,
+    pub last_activity: Option<Instant>,
+    /// A notification rule wants this tab noticed
+    pub attention: bool,
+    /// Messages that came in while this wasn't the active tab
+    pub unread: usize,
+    /// Highlighted messages that came in while this wasn't the active tab
+    pub unread_highlights: usize,
+    /// Only emotes can be sent, unless we're a moderator
+    pub emote_only: bool,
+}
+
+// the stored messages, already parsed
+struct History {
+    first: Option<Uuid>,
+    last: Option<Uuid>,
+    // how many were read, some could've been left out when they couldn't be parsed
+    read: usize,
+    messages: Vec<Message>,
+}
+
+impl History {
+    fn parse(stored: Vec<db::Message>) -> Self {
+        let first = stored.first().map(|msg| msg.msg_id);
+        let last = stored.last().map(|msg| msg.msg_id);
+        let opts = || MessageOpts {
+            old: true,
+            local: false,
+        };
+
+        let messages = stored
+            .iter()
+            .filter_map(|msg| Message::from_history(msg, opts()))
+            .collect();
+        Self {
+            first,
+            last,
+            read: stored.len(),
+            messages,
+        }
+    }
+}
+
+// pins are copied out of the queue so they outlive the message scrolling away
+pub struct Pinned {
+    pub id: Uuid,
+    pub sender: String,
+    pub color: Color32,
+    pub data: String,
 }
 
 impl Channel {
+    pub const DEFAULT_SCROLLBACK: usize = 1000;
+    pub const MAX_SCROLLBACK: usize = 50_000;
+    // how many older messages are loaded at a time when scrolling back
+    const HISTORY_PAGE: usize = 200;
+    /// The name of the buffer whispers go into, this can't be a real login
+    pub const WHISPERS: &str = "*whispers";
+
     pub fn new(name: &str) -> Self {
         Self {
-            name: name.strip_prefix('#').unwrap_or(name).to_string(),
+            name: ChannelName::new(name),
+            room_id: None,
             marker: None,
             buffer: String::with_capacity(100),
-            messages: Queue::with_capacity(1000),
+            input_history: InputHistory::default(),
+            messages: Queue::with_capacity(Self::DEFAULT_SCROLLBACK),
+            history_loaded: false,
+            pending_history: None,
+            pending_older: None,
+            oldest_stored: None,
+            history_exhausted: false,
+            prepended: 0,
+            read_only: false,
+            pinned: Vec::new(),
+            jump_to: None,
+            last_activity: None,
+            attention: false,
+            unread: 0,
+            unread_highlights: 0,
+            emote_only: false,
+        }
+    }
+
+    /// The buffer for whispers, it isn't joined, saved or stored in the history
+    pub fn whispers() -> Self {
+        Self {
+            history_loaded: true,
+            ..Self::new(Self::WHISPERS)
         }
     }
 
+    pub fn is_whispers(&self) -> bool {
+        self.name == Self::WHISPERS
+    }
+
+    /// Looking at the tab is enough to have read it
+    pub fn mark_read(&mut self) {
+        self.attention = false;
+        self.unread = 0;
+        self.unread_highlights = 0;
+    }
+
     pub fn push(&mut self, message: Message) {
-        self.marker.take();
+        self.last_activity.replace(Instant::now());
         self.messages.push(message)
     }
 
+    pub fn pin(&mut self, id: Uuid) {
+        if self.is_pinned(id) {
+            return;
+        }
+
+        let Some(msg) = self.messages.iter().find(|msg| msg.id == Some(id)) else { return };
+        self.pinned.push(Pinned {
+            id,
+            sender: msg.sender.clone(),
+            color: msg.color,
+            data: msg.data.clone(),
+        });
+    }
+
+    pub fn unpin(&mut self, id: Uuid) {
+        self.pinned.retain(|pin| pin.id != id)
+    }
+
+    pub fn is_pinned(&self, id: Uuid) -> bool {
+        self.pinned.iter().any(|pin| pin.id == id)
+    }
+
+    /// Start loading up to `limit` of the most recent messages, unless that already happened
+    pub fn request_history(&mut self, db: &'static str, limit: usize) {
+        if std::mem::replace(&mut self.history_loaded, true) {
+            return;
+        }
+
+        let fut = db::Connection::recent_messages(
+            db,
+            self.room_id.clone(),
+            self.name.clone(),
+            limit,
+            History::parse,
+        );
+        self.pending_history.replace((fut, Instant::now()));
+    }
+
+    pub const fn is_loading_history(&self) -> bool {
+        self.pending_history.is_some()
+    }
+
+    /// Put the history in once it arrives, in front of anything that came in meanwhile
+    ///
+    /// This returns how long it took
+    pub fn poll_history(&mut self, emote_map: &mut EmoteMap) -> Option<Duration> {
+        let (fut, started) = self.pending_history.as_mut()?;
+        let history = fut.try_resolve()?;
+        let elapsed = started.elapsed();
+        self.pending_history.take();
+
+        let Some(History {
+            first,
+            last,
+            messages,
+            ..
+        }) = history
+        else {
+            log!("cannot load the history for #{}", self.name);
+            return Some(elapsed);
+        };
+
+        self.oldest_stored = first;
+        if let Some(last) = last {
+            self.mark_end_of_history(last);
+        }
+        for msg in &messages {
+            msg.register_emotes(emote_map);
+        }
+        self.messages.prepend(messages);
+        Some(elapsed)
+    }
+
+    /// Put a stored message in front of the others and scroll to it, for when it isn't loaded
+    pub fn jump_to_stored(&mut self, stored: db::Message, emote_map: &mut EmoteMap) {
+        let id = stored.msg_id;
+        for msg in History::parse(vec![stored]).messages {
+            msg.register_emotes(emote_map);
+            self.messages.push_front(msg);
+        }
+        self.jump_to.replace(id);
+    }
+
+    /// Start loading the page of history from before what's loaded, unless there's nothing left
+    ///
+    /// The scrollback grows to fit the page, up to [`Channel::MAX_SCROLLBACK`]
+    pub fn request_older_history(&mut self, db: &'static str) {
+        if self.pending_older.is_some() || self.is_loading_history() || self.history_exhausted {
+            return;
+        }
+        if self.is_whispers() {
+            return;
+        }
+        if self.messages.capacity() >= Self::MAX_SCROLLBACK {
+            return;
+        }
+
+        // without any history yet, it goes back from the oldest message that came in live
+        let before = self
+            .oldest_stored
+            .or_else(|| self.messages.iter().find_map(|msg| msg.id));
+        let Some(before) = before else { return };
+
+        let fut = db::Connection::messages_before(
+            db,
+            self.room_id.clone(),
+            self.name.clone(),
+            before,
+            Self::HISTORY_PAGE,
+            History::parse,
+        );
+        self.pending_older.replace(fut);
+    }
+
+    pub const fn is_loading_older(&self) -> bool {
+        self.pending_older.is_some()
+    }
+
+    /// Put the older page in front once it arrives, making room for it in the scrollback
+    pub fn poll_older_history(&mut self, emote_map: &mut EmoteMap) {
+        let Some(fut) = &mut self.pending_older else {
+            return;
+        };
+        let Some(history) = fut.try_resolve() else {
+            return;
+        };
+        self.pending_older.take();
+
+        let Some(History {
+            first,
+            read,
+            messages,
+            ..
+        }) = history
+        else {
+            log!("cannot load older history for #{}", self.name);
+            return;
+        };
+
+        self.history_exhausted = read < Self::HISTORY_PAGE;
+        if let Some(first) = first {
+            self.oldest_stored.replace(first);
+        }
+
+        for msg in &messages {
+            msg.register_emotes(emote_map);
+        }
+        let capacity = (self.messages.capacity() + messages.len()).min(Self::MAX_SCROLLBACK);
+        self.messages.set_capacity(capacity);
+
+        let before = self.messages.iter().len();
+        self.messages.prepend(messages);
+        self.prepended += self.messages.iter().len().saturating_sub(before);
+    }
+
     pub fn mark_end_of_history(&mut self, uuid: Uuid) {
         self.marker.replace(uuid);
     }
+
+    /// Once the history has been scrolled past, it doesn't need to stand out anymore
+    pub fn age_out_history(&mut self) {
+        self.marker.take();
+        for msg in self.messages.iter_mut() {
+            msg.opts.old = false;
+        }
+    }
 }
@@ -0,0 +1,91 @@
+use std::path::{Path, PathBuf};
+
+use indexmap::{IndexMap, IndexSet};
+use serde_json::Value;
+
+/// The parts of Chatterino's `settings.json` that have somewhere to go here
+///
+/// Regex patterns can't be matched by the highlighter, so those are counted and left out
+#[derive(Debug, Default)]
+pub struct ChatterinoImport {
+    pub keywords: Vec<String>,
+    pub ignored_users: IndexSet<String>,
+    /// Login to nickname
+    pub nicknames: IndexMap<String, String>,
+    pub skipped: usize,
+}
+
+impl ChatterinoImport {
+    /// Where Chatterino keeps its settings on this platform
+    pub fn default_path() -> Option<PathBuf> {
+        let base = if cfg!(windows) {
+            PathBuf::from(std::env::var_os("APPDATA")?).join("Chatterino2")
+        } else if cfg!(target_os = "macos") {
+            PathBuf::from(std::env::var_os("HOME")?).join("Library/Application Support/chatterino")
+        } else {
+            PathBuf::from(std::env::var_os("HOME")?).join(".local/share/chatterino")
+        };
+        Some(base.join("Settings/settings.json"))
+    }
+
+    pub fn load(path: impl AsRef<Path>) -> anyhow::Result<Self> {
+        let data = std::fs::read_to_string(path)?;
+        Self::parse(&serde_json::from_str(&data)?)
+    }
+
+    // e.g. `{"highlighting": {"highlights": [{"pattern": "foo", "regex": false}]}}`
+    fn parse(settings: &Value) -> anyhow::Result<Self> {
+        anyhow::ensure!(settings.is_object(), "these aren't chatterino's settings");
+
+        let mut this = Self::default();
+
+        for entry in Self::list(settings, "/highlighting/highlights") {
+            match Self::pattern(entry, "pattern", "regex") {
+                Some(pattern) if !this.keywords.contains(&pattern) => this.keywords.push(pattern),
+                Some(..) => {}
+                None => this.skipped += 1,
+            }
+        }
+
+        for entry in Self::list(settings, "/ignore/users") {
+            match Self::pattern(entry, "pattern", "regex") {
+                Some(login) => {
+                    this.ignored_users.insert(login.to_lowercase());
+                }
+                None => this.skipped += 1,
+            }
+        }
+
+        for entry in Self::list(settings, "/nicknames") {
+            let login = Self::pattern(entry, "name", "isRegex");
+            let nickname = entry.get("replace").and_then(Value::as_str);
+            match login.zip(nickname.filter(|s| !s.trim().is_empty())) {
+                Some((login, nickname)) => {
+                    this.nicknames
+                        .insert(login.to_lowercase(), nickname.trim().to_string());
+                }
+                None => this.skipped += 1,
+            }
+        }
+
+        Ok(this)
+    }
+
+    fn list<'a>(settings: &'a Value, pointer: &str) -> &'a [Value] {
+        settings
+            .pointer(pointer)
+            .and_then(Value::as_array)
+            .map_or(&[], Vec::as_slice)
+    }
+
+    // older versions have plain strings in the lists
+    fn pattern(entry: &Value, key: &str, regex: &str) -> Option<String> {
+        if let Some(pattern) = entry.as_str() {
+            return Some(pattern.trim().to_string()).filter(|s| !s.is_empty());
+        }
+
+        let is_regex = entry.get(regex).and_then(Value::as_bool).unwrap_or(false);
+        let pattern = entry.get(key)?.as_str()?.trim();
+        (!is_regex && !pattern.is_empty()).then(|| pattern.to_string())
+    }
+}
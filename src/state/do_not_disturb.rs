@@ -0,0 +1,44 @@
+/// Suppresses notifications and sounds, either on demand or during quiet hours
+#[derive(Clone, Debug, Default, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+#[serde(default)]
+pub struct DoNotDisturb {
+    pub enabled: bool,
+    pub quiet_hours: Option<QuietHours>,
+}
+
+/// From `start` up to `end`, in local hours. This wraps around midnight when `end` is before `start`
+#[derive(Copy, Clone, Debug, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+pub struct QuietHours {
+    pub start: u8,
+    pub end: u8,
+}
+
+impl Default for QuietHours {
+    fn default() -> Self {
+        Self { start: 23, end: 7 }
+    }
+}
+
+impl QuietHours {
+    pub const fn contains(&self, hour: u8) -> bool {
+        if self.start <= self.end {
+            self.start <= hour && hour < self.end
+        } else {
+            hour >= self.start || hour < self.end
+        }
+    }
+}
+
+impl DoNotDisturb {
+    /// The quiet hours are in local time, at `offset`
+    pub fn is_active(&self, offset: time::UtcOffset) -> bool {
+        if self.enabled {
+            return true;
+        }
+
+        let now = time::OffsetDateTime::now_utc().to_offset(offset);
+        self.quiet_hours
+            .filter(|hours| hours.contains(now.hour()))
+            .is_some()
+    }
+}
@@ -0,0 +1,59 @@
+use std::collections::VecDeque;
+
+/// The lines sent from a channel's input, so they can be recalled like in a shell
+#[derive(Default)]
+pub struct InputHistory {
+    lines: VecDeque<String>,
+    // the line being shown, there's none while on the draft
+    cursor: Option<usize>,
+    // what was typed before moving back through the lines
+    draft: String,
+}
+
+impl InputHistory {
+    const MAX_LINES: usize = 100;
+
+    pub fn push(&mut self, line: &str) {
+        self.cursor = None;
+        self.draft.clear();
+
+        if self.lines.back().map(String::as_str) == Some(line) {
+            return;
+        }
+        if self.lines.len() == Self::MAX_LINES {
+            self.lines.pop_front();
+        }
+        self.lines.push_back(line.to_string());
+    }
+
+    /// Replace the buffer with the line before the current one, returns whether it changed
+    pub fn older(&mut self, buffer: &mut String) -> bool {
+        let cursor = match self.cursor {
+            None if self.lines.is_empty() => return false,
+            None => {
+                self.draft = std::mem::take(buffer);
+                self.lines.len() - 1
+            }
+            Some(0) => return false,
+            Some(cursor) => cursor - 1,
+        };
+
+        self.cursor.replace(cursor);
+        buffer.clone_from(&self.lines[cursor]);
+        true
+    }
+
+    /// Replace the buffer with the line after the current one, or the draft after the newest line
+    pub fn newer(&mut self, buffer: &mut String) -> bool {
+        let Some(cursor) = self.cursor else { return false };
+
+        if cursor + 1 < self.lines.len() {
+            self.cursor.replace(cursor + 1);
+            buffer.clone_from(&self.lines[cursor + 1]);
+        } else {
+            self.cursor = None;
+            *buffer = std::mem::take(&mut self.draft);
+        }
+        true
+    }
+}
@@ -1,96 +1,253 @@
+use std::{cell::Cell, ops::Range};
+
 use egui::Color32;
 
-use twitch_message::{messages::Privmsg, IntoStatic};
+use twitch_message::messages::Privmsg;
 use uuid::Uuid;
 
-use crate::runtime::EmoteMap;
+use crate::{db, runtime::EmoteMap};
 
 pub struct Message {
     pub id: Option<Uuid>,
     pub sender: String,
+    pub user_id: Option<String>,
     pub color: Color32,
-    pub badges: Vec<twitch_message::Badge<'static>>,
+    /// The set id and version of each badge
+    pub badges: Vec<(String, String)>,
     pub data: String,
     pub spans: Vec<Span>,
     pub opts: MessageOpts,
+    /// A moderator removed it, or timed out the sender
+    pub deleted: bool,
+    /// What twitch said happened, the spans are the message the user attached to it
+    pub notice: Option<Notice>,
+    /// It mentions us, or has one of the highlight keywords
+    pub highlighted: bool,
+    /// When it came in, or when it was stored for ones from the history
+    pub received: time::OffsetDateTime,
+    /// The width it was last laid out at and how tall it was, so the view can skip over it
+    pub height: Cell<Option<(f32, f32)>>,
 }
 
 impl Message {
     pub fn from_pm(pm: &Privmsg<'_>, emote_map: &mut EmoteMap, opts: MessageOpts) -> Self {
-        fn parse_text(input: &str, spans: &mut Vec<Span>) {
-            fn check_for_url(input: &str) -> bool {
-                url::Url::parse(input)
-                    .ok()
-                    .filter(|url| matches!(url.scheme(), "http" | "https"))
-                    .is_some()
-            }
+        let this = Self::parse(pm, opts);
+        this.register_emotes(emote_map);
+        this
+    }
 
-            let (mut cursor, mut pos) = (0, 0);
-            let input = input.trim();
-            let mut iter = input.split_ascii_whitespace().peekable();
-            while let Some(el) = iter.next() {
-                if check_for_url(el) {
-                    pos += el.len() + 1;
-                    cursor = pos;
-                    spans.push(Span::Url(el.to_string()));
-                    continue;
-                }
-
-                let Some(next) = iter.peek() else { continue };
-
-                if check_for_url(next) {
-                    spans.push(Span::Text(input[cursor..pos + el.len()].to_string()));
-                    (cursor, pos) = (pos, pos + el.len() + 1);
-                    continue;
-                }
-                pos += el.len() + 1;
-            }
-
-            if cursor < input.len() {
-                spans.push(Span::Text(input[cursor..].to_string()));
-            }
-        }
+    /// Like [`Self::from_pm`], but the emotes aren't looked up, so this can be done on any thread
+    ///
+    /// The text is copied once and the spans point into it, each emote still has its own id
+    pub fn parse(pm: &Privmsg<'_>, opts: MessageOpts) -> Self {
+        let data = pm.data.to_string();
 
         let mut emotes = pm.emotes().collect::<Vec<_>>();
-        let data = &*pm.data;
-
         emotes.sort_unstable_by_key(|emote| emote.byte_pos);
 
         let mut spans = vec![];
         let mut cursor = 0;
 
-        for ((emote_id, emote_name), (start, end)) in emotes
-            .into_iter()
-            .map(|emote| ((emote.id, emote.name), emote.byte_pos))
-        {
-            if start != cursor {
-                let s = &data[cursor..start];
-                parse_text(s, &mut spans);
+        for (emote_id, (start, end)) in emotes.into_iter().map(|emote| (emote.id, emote.byte_pos)) {
+            let Some((start, end)) = Self::emote_range(&data, cursor, start, end) else {
+                log!("skipping an emote with a bad range: {start}..{end}");
+                continue;
+            };
+            if start > cursor {
+                Self::parse_text(&data, cursor..start, &mut spans);
             }
-
-            emote_map.insert_emote(emote_id.as_str(), &emote_name);
-
-            spans.push(Span::Emote((
-                emote_id.to_string(),
-                data[start..end].to_string(),
-            )));
-
+            spans.push(Span::Emote {
+                id: emote_id.to_string(),
+                range: start..end,
+            });
             cursor = end;
         }
 
-        if cursor != data.len() {
-            let s = &data[cursor..];
-            parse_text(s, &mut spans);
+        if cursor < data.len() {
+            Self::parse_text(&data, cursor..data.len(), &mut spans);
         }
 
         Self {
             id: pm.msg_id().and_then(|s| Uuid::parse_str(s.as_str()).ok()),
             sender: pm.sender.to_string(),
+            user_id: pm.user_id().map(|id| id.as_str().to_string()),
             color: Self::translate_color(pm.color()),
-            data: pm.data.to_string(),
-            badges: pm.badges().map(IntoStatic::into_static).collect(),
+            data,
+            badges: pm
+                .badges()
+                .map(|twitch_message::Badge { name, version }| {
+                    (name.to_string(), version.to_string())
+                })
+                .collect(),
             opts,
             spans,
+            deleted: false,
+            notice: None,
+            highlighted: false,
+            received: time::OffsetDateTime::now_utc(),
+            height: Cell::default(),
+        }
+    }
+
+    // the ranges come from the tags, which can be wrong. they're moved onto char boundaries, and
+    // ones that are empty or overlap the previous emote are skipped
+    fn emote_range(data: &str, cursor: usize, start: usize, end: usize) -> Option<(usize, usize)> {
+        let floor = |i: usize| {
+            (0..=i.min(data.len()))
+                .rev()
+                .find(|&i| data.is_char_boundary(i))
+                .unwrap_or_default()
+        };
+        let (start, end) = (floor(start), floor(end));
+        (cursor <= start && start < end).then_some((start, end))
+    }
+
+    // the text between urls is kept together, so most messages end up as a single span
+    fn parse_text(data: &str, range: Range<usize>, spans: &mut Vec<Span>) {
+        let mut text = range.start;
+        for word in data[range.clone()].split_ascii_whitespace() {
+            if !Self::looks_like_url(word) {
+                continue;
+            }
+
+            let start = word.as_ptr() as usize - data.as_ptr() as usize;
+            if let Some(range) = Self::trimmed(data, text..start) {
+                spans.push(Span::Text(range));
+            }
+            spans.push(Span::Url(start..start + word.len()));
+            text = start + word.len();
+        }
+
+        if let Some(range) = Self::trimmed(data, text..range.end) {
+            spans.push(Span::Text(range));
+        }
+    }
+
+    // only words with the right scheme are actually parsed
+    fn looks_like_url(word: &str) -> bool {
+        let Some((scheme, rest)) = word.split_once("://") else { return false };
+        let scheme_ok = scheme.eq_ignore_ascii_case("http")
+            || scheme.eq_ignore_ascii_case("https")
+            || scheme == super::Permalink::SCHEME;
+        scheme_ok && !rest.is_empty() && url::Url::parse(word).is_ok()
+    }
+
+    fn trimmed(data: &str, range: Range<usize>) -> Option<Range<usize>> {
+        let s = &data[range.clone()];
+        let start = range.start + (s.len() - s.trim_start().len());
+        let end = range.end - (s.len() - s.trim_end().len());
+        (start < end).then_some(start..end)
+    }
+
+    /// A line from the server about the channel, like why a message wasn't sent
+    pub fn server_notice(text: String) -> Self {
+        Self {
+            id: None,
+            sender: String::new(),
+            user_id: None,
+            color: Color32::GRAY,
+            badges: vec![],
+            data: String::new(),
+            spans: vec![],
+            opts: MessageOpts {
+                old: false,
+                local: true,
+            },
+            deleted: false,
+            notice: Some(Notice {
+                kind: NoticeKind::Server,
+                text,
+            }),
+            highlighted: false,
+            received: time::OffsetDateTime::now_utc(),
+            height: Cell::default(),
+        }
+    }
+
+    /// Make this a sub, raid or some other event
+    pub fn with_notice(self, kind: &str, text: String) -> Self {
+        Self {
+            notice: Some(Notice {
+                kind: NoticeKind::from_msg_id(kind),
+                text,
+            }),
+            ..self
+        }
+    }
+
+    /// The parsed parts of this, which are stored next to the raw line
+    pub fn to_stored(&self) -> String {
+        serde_json::to_string(&Stored {
+            spans: &self.spans,
+            color: self.color.to_array(),
+            badges: &self.badges,
+            notice: self.notice.as_ref(),
+        })
+        .expect("valid serialization")
+    }
+
+    /// Rebuild a message from the history without parsing the raw line again
+    ///
+    /// Messages stored before this existed don't have the parsed parts, or have them in an
+    /// older shape, so this gives up on those
+    pub fn from_stored(msg: &db::Message, opts: MessageOpts) -> Option<Self> {
+        #[derive(serde::Deserialize)]
+        struct Loaded {
+            spans: Vec<Span>,
+            color: [u8; 4],
+            badges: Vec<(String, String)>,
+            #[serde(default)]
+            notice: Option<Notice>,
+        }
+
+        let Loaded {
+            spans,
+            color: [r, g, b, a],
+            badges,
+            notice,
+        } = serde_json::from_str(msg.parsed.as_deref()?).ok()?;
+
+        Some(Self {
+            id: Some(msg.msg_id),
+            sender: msg.login.to_string(),
+            user_id: Some(msg.user_id.to_string()),
+            color: Color32::from_rgba_premultiplied(r, g, b, a),
+            badges,
+            data: msg.data.to_string(),
+            spans,
+            opts,
+            deleted: msg.deleted,
+            notice,
+            highlighted: false,
+            received: msg.timestamp,
+            height: Cell::default(),
+        })
+    }
+
+    /// Rebuild a message from the history, parsing the raw line again when it has to
+    pub fn from_history(msg: &db::Message, opts: MessageOpts) -> Option<Self> {
+        if let Some(this) = Self::from_stored(msg, opts) {
+            return Some(this);
+        }
+        match twitch_message::parse_as::<Privmsg>(&msg.raw) {
+            Ok(pm) => Some(Self {
+                deleted: msg.deleted,
+                received: msg.timestamp,
+                ..Self::parse(&pm, opts)
+            }),
+            Err(err) => {
+                log!("invalid message in history {}: {err}", msg.msg_id);
+                None
+            }
+        }
+    }
+
+    /// Make sure the emotes in this get fetched, and can be completed by name
+    pub fn register_emotes(&self, emote_map: &mut EmoteMap) {
+        for span in &self.spans {
+            if let Span::Emote { id, .. } = span {
+                emote_map.insert_emote(id, span.text(&self.data));
+            }
         }
     }
 
@@ -100,13 +257,79 @@ impl Message {
     }
 }
 
+#[derive(serde::Serialize)]
+struct Stored<'a> {
+    spans: &'a [Span],
+    color: [u8; 4],
+    badges: &'a [(String, String)],
+    #[serde(skip_serializing_if = "Option::is_none")]
+    notice: Option<&'a Notice>,
+}
+
+#[derive(Copy, Clone)]
 pub struct MessageOpts {
     pub old: bool,
     pub local: bool,
 }
 
+#[derive(serde::Serialize, serde::Deserialize)]
+pub struct Notice {
+    pub kind: NoticeKind,
+    pub text: String,
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum NoticeKind {
+    Sub,
+    Resub,
+    GiftSub,
+    GiftBomb,
+    Raid,
+    Other,
+    /// A NOTICE, these aren't from a user
+    Server,
+}
+
+impl NoticeKind {
+    // see https://dev.twitch.tv/docs/irc/tags/#usernotice-tags
+    pub fn from_msg_id(msg_id: &str) -> Self {
+        match msg_id {
+            "sub" => Self::Sub,
+            "resub" => Self::Resub,
+            "subgift" | "anonsubgift" => Self::GiftSub,
+            "submysterygift" | "anonsubmysterygift" => Self::GiftBomb,
+            "raid" => Self::Raid,
+            _ => Self::Other,
+        }
+    }
+
+    pub const fn label(&self) -> &'static str {
+        match self {
+            Self::Sub => "sub",
+            Self::Resub => "resub",
+            Self::GiftSub => "gift",
+            Self::GiftBomb => "gift bomb",
+            Self::Raid => "raid",
+            Self::Other => "notice",
+            Self::Server => "server",
+        }
+    }
+}
+
+/// The parts of a message, these are byte ranges into [`Message::data`]
+#[derive(serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "snake_case")]
 pub enum Span {
-    Text(String),
-    Emote((String, String)),
-    Url(String),
+    Text(Range<usize>),
+    Emote { id: String, range: Range<usize> },
+    Url(Range<usize>),
+}
+
+impl Span {
+    pub fn text<'a>(&self, data: &'a str) -> &'a str {
+        let (Self::Text(range) | Self::Emote { range, .. } | Self::Url(range)) = self;
+        // stored spans could be out of sync with the data, that shouldn't take anything down
+        data.get(range.clone()).unwrap_or_default()
+    }
 }
@@ -0,0 +1,154 @@
+use std::time::{Duration, Instant};
+
+use uuid::Uuid;
+
+use crate::{db, resolver::Fut, twitch::ChannelName};
+
+use super::{Channel, Message};
+
+/// Searches the active channel, both what's loaded and what's in the history
+#[derive(Default)]
+pub struct MessageSearch {
+    pub open: bool,
+    pub query: String,
+    /// The match being looked at
+    pub current: Option<Uuid>,
+    /// Matches from the history that aren't loaded, oldest first
+    pub older: Vec<db::Message>,
+    channel: Option<ChannelName>,
+    // the trimmed and lowercased query that was searched for
+    searched: String,
+    typed: Option<Instant>,
+    // the query box wants the focus once it's opened
+    focus: bool,
+    older_fut: Option<Fut<Option<Vec<db::Message>>>>,
+}
+
+impl MessageSearch {
+    // wait for the user to stop typing before going to the history
+    pub const SEARCH_DELAY: Duration = Duration::from_millis(300);
+    const HISTORY_LIMIT: usize = 500;
+
+    pub fn open(&mut self) {
+        self.open = true;
+        self.focus = true;
+    }
+
+    /// Whether the query box should grab the focus
+    pub fn take_focus(&mut self) -> bool {
+        std::mem::take(&mut self.focus)
+    }
+
+    pub fn close(&mut self) {
+        self.open = false;
+        self.query.clear();
+        self.reset();
+    }
+
+    fn reset(&mut self) {
+        self.searched.clear();
+        self.current.take();
+        self.older.clear();
+        self.older_fut.take();
+        self.typed.take();
+    }
+
+    pub fn is_searching(&self) -> bool {
+        self.open && !self.searched.is_empty()
+    }
+
+    pub fn is_loading(&self) -> bool {
+        self.typed.is_some() || self.older_fut.is_some()
+    }
+
+    pub fn matches(&self, msg: &Message) -> bool {
+        self.is_searching() && msg.data.to_lowercase().contains(&self.searched)
+    }
+
+    /// Keep up with the query and the channel, this returns whether a search is waiting on the delay
+    pub fn poll(&mut self, channel: &Channel, db: &'static str) -> bool {
+        if self.channel.as_ref() != Some(&channel.name) {
+            self.channel.replace(channel.name.clone());
+            self.reset();
+        }
+
+        if let Some(found) = self.older_fut.as_mut().and_then(Fut::try_resolve) {
+            self.older_fut.take();
+            let mut found = found.unwrap_or_default();
+            // the ones already loaded are found in the queue
+            found.retain(|stored| {
+                !channel
+                    .messages
+                    .iter()
+                    .any(|msg| msg.id == Some(stored.msg_id))
+            });
+            self.older = found;
+        }
+
+        let query = self.query.trim().to_lowercase();
+        if query != self.searched {
+            self.reset();
+            self.searched = query;
+            if !self.searched.is_empty() {
+                self.typed.replace(Instant::now());
+            }
+        }
+
+        let Some(typed) = self.typed else {
+            return false;
+        };
+        if typed.elapsed() < Self::SEARCH_DELAY {
+            return true;
+        }
+
+        self.typed.take();
+        self.older_fut.replace(db::Connection::search_messages(
+            db,
+            channel.room_id.clone(),
+            channel.name.clone(),
+            self.searched.clone(),
+            Self::HISTORY_LIMIT,
+        ));
+        false
+    }
+
+    /// Every match by id, oldest first
+    pub fn match_ids(&self, channel: &Channel) -> Vec<Uuid> {
+        let loaded = channel
+            .messages
+            .iter()
+            .filter(|msg| self.matches(msg))
+            .filter_map(|msg| msg.id);
+        self.older
+            .iter()
+            .map(|msg| msg.msg_id)
+            .chain(loaded)
+            .collect()
+    }
+
+    /// Move to the next newer match, or the next older one, staying put at the ends
+    ///
+    /// The older matches are put in front of the queue as they're reached, so going one
+    /// at a time keeps them in order
+    pub fn step(&mut self, channel: &Channel, newer: bool) -> Option<Uuid> {
+        let ids = self.match_ids(channel);
+        let last = ids.len().checked_sub(1)?;
+
+        let pos = self
+            .current
+            .and_then(|current| ids.iter().position(|&id| id == current));
+        let next = match (pos, newer) {
+            (None, _) => last,
+            (Some(pos), true) => (pos + 1).min(last),
+            (Some(pos), false) => pos.saturating_sub(1),
+        };
+        self.current.replace(ids[next]);
+        self.current
+    }
+
+    /// Take a match out of the older ones once it's been put in the queue
+    pub fn take_older(&mut self, id: Uuid) -> Option<db::Message> {
+        let pos = self.older.iter().position(|msg| msg.msg_id == id)?;
+        Some(self.older.remove(pos))
+    }
+}
@@ -1,13 +1,42 @@
-use crate::twitch::Identity;
+use indexmap::{IndexMap, IndexSet};
+
+use crate::twitch::{ChannelName, Identity};
 
 mod message;
-pub use message::{Message, MessageOpts, Span};
+pub use message::{Message, MessageOpts, Notice, NoticeKind, Span};
 
 mod channel;
 pub use channel::Channel;
 
+mod message_search;
+pub use message_search::MessageSearch;
+
+mod permalink;
+pub use permalink::Permalink;
+
+mod suggestions;
+pub use suggestions::{ChannelSearch, Suggestions};
+
+mod do_not_disturb;
+pub use do_not_disturb::{DoNotDisturb, QuietHours};
+
+mod notify_rules;
+pub use notify_rules::{actions_for, NotifyAction, NotifyEvent, Rule, Trigger};
+
+mod chatterino;
+pub use chatterino::ChatterinoImport;
+
 mod save_state;
-pub use save_state::SavedState;
+pub use save_state::{Autosave, SavedState};
+
+mod input_history;
+pub use input_history::InputHistory;
+
+mod preferences;
+pub use preferences::{AnimationRate, ImageScale, Preferences};
+
+mod toasts;
+pub use toasts::{Toast, ToastKind, ToastSender, Toasts};
 
 #[derive(Default, Debug)]
 pub enum Screen {
@@ -16,17 +45,122 @@ pub enum Screen {
     Connected {
         state: ViewState,
     },
+    /// The server rejected the credentials, this waits for new ones
+    InvalidCredentials,
 }
 
 #[derive(Debug)]
 pub enum ViewState {
-    Empty { buffer: String },
+    Empty {
+        buffer: String,
+        suggestions: Suggestions,
+    },
     MainView,
 }
 
+/// How the tabs are ordered, the saved order is always kept underneath
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum TabOrder {
+    #[default]
+    Manual,
+    Live,
+    Activity,
+}
+
+impl TabOrder {
+    pub const ALL: [Self; 3] = [Self::Manual, Self::Live, Self::Activity];
+
+    pub const fn label(&self) -> &'static str {
+        match self {
+            Self::Manual => "manual",
+            Self::Live => "live, then viewer count",
+            Self::Activity => "recent activity",
+        }
+    }
+}
+
+/// Which edge of the window the input is on
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum InputPosition {
+    #[default]
+    Bottom,
+    Top,
+}
+
+impl InputPosition {
+    pub const ALL: [Self; 2] = [Self::Bottom, Self::Top];
+
+    pub const fn label(&self) -> &'static str {
+        match self {
+            Self::Bottom => "input at the bottom",
+            Self::Top => "input at the top",
+        }
+    }
+}
+
+/// Which side of the input the tab bar is on
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum TabBarPosition {
+    #[default]
+    BelowInput,
+    AboveInput,
+}
+
+impl TabBarPosition {
+    pub const ALL: [Self; 2] = [Self::BelowInput, Self::AboveInput];
+
+    pub const fn label(&self) -> &'static str {
+        match self {
+            Self::BelowInput => "tabs below the input",
+            Self::AboveInput => "tabs above the input",
+        }
+    }
+}
+
 #[derive(Default)]
 pub struct State {
     pub channels: Vec<Channel>,
     pub active: usize,
     pub identity: Option<Identity>,
+    /// Hide anything private, for when the window is being shown to others
+    pub streamer_mode: bool,
+    /// Don't flash the task bar for mentions and whispers while the window is in the background
+    pub quiet_taskbar: bool,
+    /// Run with a token that can only read chat, nothing is sent and moderation is hidden
+    pub read_only_token: bool,
+    /// Channels that are always joined on startup, even if they were closed
+    pub favorites: IndexSet<ChannelName>,
+    /// Channels we want to know are live, without joining their chat
+    pub watchlist: IndexSet<ChannelName>,
+    /// Messages with any of these in them are highlighted, along with ones that mention us
+    pub highlight_keywords: Vec<String>,
+    /// Messages from these logins aren't shown, they're still kept in the history
+    pub ignored_users: IndexSet<String>,
+    /// Shown instead of the login, keyed by the login
+    pub nicknames: IndexMap<String, String>,
+    pub tab_order: TabOrder,
+    pub input_position: InputPosition,
+    pub tab_bar_position: TabBarPosition,
+    pub do_not_disturb: DoNotDisturb,
+    pub notify_rules: Vec<Rule>,
+    pub preferences: Preferences,
+    /// Who whispered to us last, or who we last whispered to
+    pub last_whisper: Option<String>,
+}
+
+impl State {
+    /// The whispers buffer, it's added as a tab the first time it's needed
+    pub fn whispers(&mut self) -> &mut Channel {
+        let pos = match self.channels.iter().position(Channel::is_whispers) {
+            Some(pos) => pos,
+            None => {
+                self.channels.push(Channel::whispers());
+                self.channels.len() - 1
+            }
+        };
+        &mut self.channels[pos]
+    }
 }
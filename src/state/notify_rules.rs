@@ -0,0 +1,154 @@
+/// What a rule reacts to
+#[derive(Clone, Debug, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Trigger {
+    /// Our name shows up in a message
+    Mention,
+    /// A message from this login
+    User(String),
+    GoLive,
+    /// A message containing this, ignoring case
+    Keyword(String),
+}
+
+impl Trigger {
+    pub const fn label(&self) -> &'static str {
+        match self {
+            Self::Mention => "mention",
+            Self::User(_) => "user",
+            Self::GoLive => "goes live",
+            Self::Keyword(_) => "keyword",
+        }
+    }
+
+    // these are what the rules table offers to pick from
+    pub fn defaults() -> [Self; 4] {
+        [
+            Self::Mention,
+            Self::User(String::new()),
+            Self::GoLive,
+            Self::Keyword(String::new()),
+        ]
+    }
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum NotifyAction {
+    Sound,
+    Desktop,
+    FlashTab,
+}
+
+impl NotifyAction {
+    pub const ALL: [Self; 3] = [Self::Sound, Self::Desktop, Self::FlashTab];
+
+    pub const fn label(&self) -> &'static str {
+        match self {
+            Self::Sound => "play a sound",
+            Self::Desktop => "desktop notification",
+            Self::FlashTab => "flash the tab",
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+pub struct Rule {
+    #[serde(default = "enabled")]
+    pub enabled: bool,
+    /// Only for this channel, otherwise it applies everywhere
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub channel: Option<String>,
+    pub trigger: Trigger,
+    pub action: NotifyAction,
+}
+
+const fn enabled() -> bool {
+    true
+}
+
+impl Default for Rule {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            channel: None,
+            trigger: Trigger::Mention,
+            action: NotifyAction::FlashTab,
+        }
+    }
+}
+
+/// Something that happened which the rules can match against
+pub enum NotifyEvent<'a> {
+    Message {
+        channel: &'a str,
+        sender: &'a str,
+        data: &'a str,
+        our_name: Option<&'a str>,
+    },
+    GoLive {
+        channel: &'a str,
+    },
+}
+
+impl<'a> NotifyEvent<'a> {
+    const fn channel(&self) -> &'a str {
+        match self {
+            Self::Message { channel, .. } | Self::GoLive { channel } => channel,
+        }
+    }
+}
+
+impl Rule {
+    pub fn matches(&self, event: &NotifyEvent<'_>) -> bool {
+        fn contains_word(data: &str, word: &str) -> bool {
+            data.split(|c: char| !c.is_alphanumeric() && c != '_')
+                .any(|part| part.eq_ignore_ascii_case(word))
+        }
+
+        if !self.enabled {
+            return false;
+        }
+
+        if let Some(channel) = &self.channel {
+            if !channel.eq_ignore_ascii_case(event.channel()) {
+                return false;
+            }
+        }
+
+        match (&self.trigger, event) {
+            (Trigger::GoLive, NotifyEvent::GoLive { .. }) => true,
+            (
+                Trigger::Mention,
+                NotifyEvent::Message {
+                    data,
+                    our_name: Some(name),
+                    ..
+                },
+            ) => contains_word(data, name),
+            (Trigger::User(user), NotifyEvent::Message { sender, .. }) => {
+                !user.is_empty() && user.eq_ignore_ascii_case(sender)
+            }
+            (Trigger::Keyword(keyword), NotifyEvent::Message { data, .. }) => {
+                !keyword.is_empty() && data.to_lowercase().contains(&keyword.to_lowercase())
+            }
+            _ => false,
+        }
+    }
+}
+
+/// The actions of every rule matching the event, each at most once
+pub fn actions_for<'r>(
+    rules: impl IntoIterator<Item = &'r Rule>,
+    event: &NotifyEvent<'_>,
+) -> Vec<NotifyAction> {
+    rules
+        .into_iter()
+        .filter(|rule| rule.matches(event))
+        .fold(vec![], |mut actions, rule| {
+            if !actions.contains(&rule.action) {
+                actions.push(rule.action)
+            }
+            actions
+        })
+}
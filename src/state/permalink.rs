@@ -0,0 +1,52 @@
+use uuid::Uuid;
+
+use crate::twitch::ChannelName;
+
+/// A link to a single message, e.g. `vohiyo://channel/museun/msg/<uuid>`
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Permalink {
+    pub channel: ChannelName,
+    pub id: Uuid,
+}
+
+impl Permalink {
+    pub const SCHEME: &str = "vohiyo";
+
+    pub fn new(channel: &str, id: Uuid) -> Self {
+        Self {
+            channel: ChannelName::new(channel),
+            id,
+        }
+    }
+
+    pub fn parse(input: &str) -> Option<Self> {
+        let url = url::Url::parse(input.trim()).ok()?;
+        if url.scheme() != Self::SCHEME || url.host_str()? != "channel" {
+            return None;
+        }
+
+        let mut parts = url.path_segments()?;
+        let channel = parts.next().filter(|s| !s.is_empty())?;
+        if parts.next()? != "msg" {
+            return None;
+        }
+        let id = Uuid::parse_str(parts.next()?).ok()?;
+        if parts.next().is_some() {
+            return None;
+        }
+
+        Some(Self::new(channel, id))
+    }
+}
+
+impl std::fmt::Display for Permalink {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{scheme}://channel/{channel}/msg/{id}",
+            scheme = Self::SCHEME,
+            channel = self.channel,
+            id = self.id
+        )
+    }
+}
@@ -0,0 +1,118 @@
+use crate::runtime::Scale;
+
+/// How often animated emotes are stepped, lower is easier on the cpu
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum AnimationRate {
+    Low,
+    Medium,
+    #[default]
+    Smooth,
+}
+
+impl AnimationRate {
+    pub const ALL: [Self; 3] = [Self::Low, Self::Medium, Self::Smooth];
+
+    pub const fn label(&self) -> &'static str {
+        match self {
+            Self::Low => "low (15 fps)",
+            Self::Medium => "medium (30 fps)",
+            Self::Smooth => "smooth (60 fps)",
+        }
+    }
+
+    pub const fn frame_time(&self) -> std::time::Duration {
+        let fps = match self {
+            Self::Low => 15,
+            Self::Medium => 30,
+            Self::Smooth => 60,
+        };
+        std::time::Duration::from_nanos(1_000_000_000 / fps)
+    }
+}
+
+/// Which resolution of emotes and badges to fetch
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ImageScale {
+    /// Whatever fits the monitor's scale factor
+    #[default]
+    Auto,
+    One,
+    Two,
+    Four,
+}
+
+impl ImageScale {
+    pub const ALL: [Self; 4] = [Self::Auto, Self::One, Self::Two, Self::Four];
+
+    pub const fn label(&self) -> &'static str {
+        match self {
+            Self::Auto => "match the display",
+            Self::One => "1x",
+            Self::Two => "2x",
+            Self::Four => "4x",
+        }
+    }
+
+    pub fn resolve(&self, pixels_per_point: f32) -> Scale {
+        match self {
+            Self::Auto => Scale::for_pixels_per_point(pixels_per_point),
+            Self::One => Scale::One,
+            Self::Two => Scale::Two,
+            Self::Four => Scale::Four,
+        }
+    }
+}
+
+/// How things are shown, from the settings screen
+#[derive(Clone, Debug, PartialEq, serde::Serialize, serde::Deserialize)]
+#[serde(default)]
+pub struct Preferences {
+    /// Show when each message came in
+    pub timestamps: bool,
+    pub animation_rate: AnimationRate,
+    pub image_scale: ImageScale,
+    /// How many stored messages are loaded when a channel is opened
+    pub history_limit: usize,
+    /// How many seconds between checking which streams are live
+    pub stream_check_secs: u64,
+    /// A font from the system, the bundled one is used when this is unset
+    pub font_path: Option<std::path::PathBuf>,
+    pub font_size: f32,
+}
+
+impl Default for Preferences {
+    fn default() -> Self {
+        Self {
+            timestamps: false,
+            animation_rate: AnimationRate::default(),
+            image_scale: ImageScale::default(),
+            history_limit: Self::DEFAULT_HISTORY_LIMIT,
+            stream_check_secs: Self::DEFAULT_STREAM_CHECK_SECS,
+            font_path: None,
+            font_size: Self::DEFAULT_FONT_SIZE,
+        }
+    }
+}
+
+impl Preferences {
+    pub const DEFAULT_HISTORY_LIMIT: usize = 250;
+    pub const MAX_HISTORY_LIMIT: usize = 5000;
+
+    // this is what egui uses for the body text
+    pub const DEFAULT_FONT_SIZE: f32 = 12.5;
+    pub const FONT_SIZES: std::ops::RangeInclusive<f32> = 8.0..=32.0;
+
+    pub const DEFAULT_STREAM_CHECK_SECS: u64 = 30;
+    // every check is a helix request per hundred channels
+    pub const STREAM_CHECK_SECS: std::ops::RangeInclusive<u64> = 15..=600;
+
+    pub fn stream_check_interval(&self) -> std::time::Duration {
+        let secs = self.stream_check_secs.clamp(
+            *Self::STREAM_CHECK_SECS.start(),
+            *Self::STREAM_CHECK_SECS.end(),
+        );
+        std::time::Duration::from_secs(secs)
+    }
+}
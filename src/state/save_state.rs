@@ -1,47 +1,227 @@
-use std::path::Path;
+use std::{
+    path::Path,
+    time::{Duration, Instant},
+};
 
-use indexmap::IndexSet;
+use indexmap::{IndexMap, IndexSet};
 
-use super::{Channel, State};
+use crate::twitch::ChannelName;
+
+use super::{
+    Channel, DoNotDisturb, InputPosition, Preferences, Rule, State, TabBarPosition, TabOrder,
+};
 
 pub struct SavedState<'a> {
     pub state: &'a State,
 }
 
 impl<'a> SavedState<'a> {
-    pub fn save(&self, path: impl AsRef<Path>) {
+    pub fn serialize(&self) -> String {
         #[derive(serde::Serialize)]
         struct Saved<'a> {
             channels: IndexSet<&'a str>,
             active: usize,
+            drafts: IndexMap<&'a str, &'a str>,
+            read_only: IndexSet<&'a str>,
+            streamer_mode: bool,
+            quiet_taskbar: bool,
+            read_only_token: bool,
+            scrollback: IndexMap<&'a str, usize>,
+            room_ids: IndexMap<&'a str, &'a str>,
+            favorites: &'a IndexSet<ChannelName>,
+            watchlist: &'a IndexSet<ChannelName>,
+            highlight_keywords: &'a [String],
+            ignored_users: &'a IndexSet<String>,
+            nicknames: &'a IndexMap<String, String>,
+            tab_order: TabOrder,
+            input_position: InputPosition,
+            tab_bar_position: TabBarPosition,
+            do_not_disturb: &'a DoNotDisturb,
+            notify_rules: &'a [Rule],
+            preferences: &'a Preferences,
         }
 
-        let s = toml::to_string_pretty(&Saved {
+        // the whispers buffer is only made once something is whispered
+        let channels = || self.state.channels.iter().filter(|s| !s.is_whispers());
+
+        toml::to_string_pretty(&Saved {
             active: self.state.active,
-            channels: self.state.channels.iter().map(|s| &*s.name).collect(),
+            channels: channels().map(|s| &*s.name).collect(),
+            drafts: channels()
+                .filter(|s| !s.buffer.is_empty())
+                .map(|s| (&*s.name, &*s.buffer))
+                .collect(),
+            read_only: channels()
+                .filter_map(|s| s.read_only.then_some(&*s.name))
+                .collect(),
+            streamer_mode: self.state.streamer_mode,
+            quiet_taskbar: self.state.quiet_taskbar,
+            read_only_token: self.state.read_only_token,
+            scrollback: channels()
+                .map(|s| (&*s.name, s.messages.capacity()))
+                .filter(|&(_, n)| n != Channel::DEFAULT_SCROLLBACK)
+                .collect(),
+            room_ids: channels()
+                .filter_map(|s| Some((&*s.name, s.room_id.as_deref()?)))
+                .collect(),
+            favorites: &self.state.favorites,
+            watchlist: &self.state.watchlist,
+            highlight_keywords: &self.state.highlight_keywords,
+            ignored_users: &self.state.ignored_users,
+            nicknames: &self.state.nicknames,
+            tab_order: self.state.tab_order,
+            input_position: self.state.input_position,
+            tab_bar_position: self.state.tab_bar_position,
+            do_not_disturb: &self.state.do_not_disturb,
+            notify_rules: &self.state.notify_rules,
+            preferences: &self.state.preferences,
         })
-        .expect("valid serialization");
+        .expect("valid serialization")
+    }
 
-        let _ = std::fs::write(path, s);
+    // write to a temporary file first so a crash mid-write doesn't leave a truncated file
+    fn write(path: impl AsRef<Path>, data: &str) {
+        let path = path.as_ref();
+        let tmp = path.with_extension("tmp");
+        if let Err(err) = std::fs::write(&tmp, data).and_then(|_| std::fs::rename(&tmp, path)) {
+            log!("cannot save state to {}: {err}", path.display())
+        }
     }
 
     pub fn load(path: impl AsRef<Path>) -> Option<State> {
         let data = std::fs::read_to_string(path).ok()?;
+        Self::parse(&data)
+    }
+
+    pub fn parse(data: &str) -> Option<State> {
         #[derive(serde::Deserialize)]
         struct Loaded {
             #[serde(default)]
-            channels: IndexSet<String>,
+            channels: IndexSet<ChannelName>,
             #[serde(default)]
             active: usize,
+            #[serde(default)]
+            drafts: IndexMap<ChannelName, String>,
+            #[serde(default)]
+            read_only: IndexSet<ChannelName>,
+            #[serde(default)]
+            streamer_mode: bool,
+            #[serde(default)]
+            quiet_taskbar: bool,
+            #[serde(default)]
+            read_only_token: bool,
+            #[serde(default)]
+            scrollback: IndexMap<ChannelName, usize>,
+            #[serde(default)]
+            room_ids: IndexMap<ChannelName, String>,
+            #[serde(default)]
+            favorites: IndexSet<ChannelName>,
+            #[serde(default)]
+            watchlist: IndexSet<ChannelName>,
+            #[serde(default)]
+            highlight_keywords: Vec<String>,
+            #[serde(default)]
+            ignored_users: IndexSet<String>,
+            #[serde(default)]
+            nicknames: IndexMap<String, String>,
+            #[serde(default)]
+            tab_order: TabOrder,
+            #[serde(default)]
+            input_position: InputPosition,
+            #[serde(default)]
+            tab_bar_position: TabBarPosition,
+            #[serde(default)]
+            do_not_disturb: DoNotDisturb,
+            #[serde(default)]
+            notify_rules: Vec<Rule>,
+            #[serde(default)]
+            preferences: Preferences,
         }
-        toml::from_str::<Loaded>(&data).ok().map(|loaded| State {
+
+        let mut loaded = toml::from_str::<Loaded>(data).ok()?;
+        Some(State {
             active: loaded.active.min(loaded.channels.len().saturating_sub(1)),
             channels: loaded
                 .channels
                 .into_iter()
-                .map(|ch| Channel::new(&ch))
+                .map(|ch| {
+                    let mut channel = Channel::new(&ch);
+                    if let Some(draft) = loaded.drafts.remove(&ch) {
+                        channel.buffer = draft;
+                    }
+                    channel.read_only = loaded.read_only.contains(&ch);
+                    channel.room_id = loaded.room_ids.remove(&ch);
+                    if let Some(&n) = loaded.scrollback.get(&ch) {
+                        channel
+                            .messages
+                            .set_capacity(n.clamp(1, Channel::MAX_SCROLLBACK));
+                    }
+                    channel
+                })
                 .collect(),
             identity: None,
+            streamer_mode: loaded.streamer_mode,
+            quiet_taskbar: loaded.quiet_taskbar,
+            read_only_token: loaded.read_only_token,
+            favorites: loaded.favorites,
+            watchlist: loaded.watchlist,
+            highlight_keywords: loaded.highlight_keywords,
+            ignored_users: loaded.ignored_users,
+            nicknames: loaded.nicknames,
+            tab_order: loaded.tab_order,
+            input_position: loaded.input_position,
+            tab_bar_position: loaded.tab_bar_position,
+            do_not_disturb: loaded.do_not_disturb,
+            notify_rules: loaded.notify_rules,
+            preferences: loaded.preferences,
+            last_whisper: None,
         })
     }
 }
+
+/// Writes the saved state every so often, or soon after something important changed
+pub struct Autosave {
+    last: Instant,
+    saved: String,
+    changed: bool,
+}
+
+impl Default for Autosave {
+    fn default() -> Self {
+        Self {
+            last: Instant::now(),
+            saved: String::new(),
+            changed: false,
+        }
+    }
+}
+
+impl Autosave {
+    const INTERVAL: Duration = Duration::from_secs(15);
+
+    pub fn mark_changed(&mut self) {
+        self.changed = true;
+    }
+
+    /// Whether this is what we last wrote out
+    pub fn is_ours(&self, data: &str) -> bool {
+        self.saved == data
+    }
+
+    pub fn maybe_save(&mut self, state: &State, path: impl AsRef<Path>) {
+        if self.changed || self.last.elapsed() >= Self::INTERVAL {
+            self.save(state, path)
+        }
+    }
+
+    pub fn save(&mut self, state: &State, path: impl AsRef<Path>) {
+        self.last = Instant::now();
+        self.changed = false;
+
+        let data = SavedState { state }.serialize();
+        if data != self.saved {
+            SavedState::write(path, &data);
+            self.saved = data;
+        }
+    }
+}
@@ -0,0 +1,138 @@
+use std::time::{Duration, Instant};
+
+use crate::{
+    db,
+    helix::{self, data},
+    resolver::Fut,
+};
+
+/// What the empty view offers to join
+pub struct Suggestions {
+    pub recent: Vec<db::RecentChannel>,
+    pub live: Vec<data::Stream>,
+    /// The live channels we follow, busiest first
+    pub followed: Vec<data::Stream>,
+    pub search: ChannelSearch,
+    live_fut: Option<Fut<Vec<data::Stream>>>,
+    followed_fut: Option<Fut<anyhow::Result<Vec<data::Stream>>>>,
+}
+
+impl std::fmt::Debug for Suggestions {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Suggestions")
+            .field("recent", &self.recent)
+            .field("followed", &self.followed.len())
+            .finish_non_exhaustive()
+    }
+}
+
+impl Suggestions {
+    const RECENT: usize = 10;
+
+    /// `followed` is the chat token and our user id, without it the followed channels are skipped
+    pub fn new(
+        history: db::History<'_>,
+        helix: &helix::Client,
+        followed: Option<(&str, &str)>,
+    ) -> Self {
+        let recent = history.get_recent_channels(Self::RECENT);
+        let live_fut = (!recent.is_empty())
+            .then(|| helix.get_many_streams(recent.iter().map(|c| &*c.room_id)));
+        let followed_fut =
+            followed.map(|(token, user_id)| helix.get_followed_streams(token, user_id));
+
+        Self {
+            recent,
+            live: vec![],
+            followed: vec![],
+            search: ChannelSearch::default(),
+            live_fut,
+            followed_fut,
+        }
+    }
+
+    pub fn is_live(&self, channel: &str) -> bool {
+        self.live.iter().any(|s| s.user_login == channel)
+    }
+
+    /// Returns whether a search is waiting on the delay
+    pub fn poll(&mut self, buffer: &str, helix: &helix::Client) -> bool {
+        if let Some(live) = self.live_fut.as_mut().and_then(Fut::try_resolve) {
+            self.live = live;
+            self.live_fut.take();
+        }
+
+        if let Some(followed) = self.followed_fut.as_mut().and_then(Fut::try_resolve) {
+            self.followed_fut.take();
+            match followed {
+                Ok(mut followed) => {
+                    followed.sort_unstable_by_key(|s| std::cmp::Reverse(s.viewer_count));
+                    self.followed = followed;
+                }
+                // most likely the token is missing the scope
+                Err(err) => log!("cannot get the followed channels: {err}"),
+            }
+        }
+        self.search.poll(buffer, helix)
+    }
+}
+
+/// Searches for channels as the user types
+#[derive(Default)]
+pub struct ChannelSearch {
+    pub results: Vec<data::SearchChannel>,
+    streams: Vec<data::Stream>,
+    search_fut: Option<Fut<Vec<data::SearchChannel>>>,
+    streams_fut: Option<Fut<Vec<data::Stream>>>,
+    query: String,
+    typed: Option<Instant>,
+}
+
+impl ChannelSearch {
+    // wait for the user to stop typing before searching
+    pub const SEARCH_DELAY: Duration = Duration::from_millis(300);
+
+    pub fn viewers(&self, id: &str) -> Option<i64> {
+        self.streams
+            .iter()
+            .find_map(|s| (s.user_id == id).then_some(s.viewer_count))
+    }
+
+    /// Returns whether a search is waiting on the delay
+    pub fn poll(&mut self, query: &str, helix: &helix::Client) -> bool {
+        if let Some(results) = self.search_fut.as_mut().and_then(Fut::try_resolve) {
+            self.search_fut.take();
+
+            // the search results only say whether they're live
+            let live = results.iter().filter(|c| c.is_live).map(|c| &*c.id);
+            self.streams_fut.replace(helix.get_many_streams(live));
+            self.results = results;
+        }
+
+        if let Some(streams) = self.streams_fut.as_mut().and_then(Fut::try_resolve) {
+            self.streams = streams;
+            self.streams_fut.take();
+        }
+
+        let query = query.trim();
+        if query != self.query {
+            self.query = query.to_string();
+            self.typed.replace(Instant::now());
+            if query.is_empty() {
+                self.results.clear();
+                self.streams.clear();
+                self.search_fut.take();
+                self.typed.take();
+            }
+        }
+
+        let Some(typed) = self.typed else { return false };
+        if typed.elapsed() < Self::SEARCH_DELAY {
+            return true;
+        }
+
+        self.typed.take();
+        self.search_fut.replace(helix.search_channels(&self.query));
+        false
+    }
+}
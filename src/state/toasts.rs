@@ -0,0 +1,165 @@
+use std::{
+    collections::VecDeque,
+    sync::mpsc::{Receiver, Sender},
+    time::{Duration, Instant},
+};
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum ToastKind {
+    Info,
+    Error,
+}
+
+#[derive(Clone, Debug)]
+pub struct Toast {
+    pub kind: ToastKind,
+    pub text: String,
+    pub when: time::OffsetDateTime,
+    shown: Instant,
+}
+
+/// Hands toasts to the ui from background tasks, they show up on the next frame
+#[derive(Clone)]
+pub struct ToastSender {
+    send: Sender<(ToastKind, String)>,
+    ctx: egui::Context,
+}
+
+impl ToastSender {
+    pub fn info(&self, text: impl ToString) {
+        self.send(ToastKind::Info, text.to_string())
+    }
+
+    pub fn error(&self, text: impl ToString) {
+        self.send(ToastKind::Error, text.to_string())
+    }
+
+    fn send(&self, kind: ToastKind, text: String) {
+        let _ = self.send.send((kind, text));
+        self.ctx.request_repaint();
+    }
+}
+
+/// Short lived messages about how something went, for things that'd otherwise only be logged
+///
+/// Everything that was shown is kept for the notification center
+pub struct Toasts {
+    active: Vec<Toast>,
+    history: VecDeque<Toast>,
+    /// Errors that came in since the notification center was last opened
+    pub unseen_errors: usize,
+    sender: ToastSender,
+    recv: Receiver<(ToastKind, String)>,
+    // when each one happened is shown in local time
+    offset: time::UtcOffset,
+}
+
+impl Toasts {
+    const INFO_DURATION: Duration = Duration::from_secs(4);
+    const ERROR_DURATION: Duration = Duration::from_secs(8);
+    const MAX_ACTIVE: usize = 5;
+    const MAX_HISTORY: usize = 100;
+
+    pub fn new(ctx: egui::Context, offset: time::UtcOffset) -> Self {
+        let (send, recv) = std::sync::mpsc::channel();
+        Self {
+            active: Vec::new(),
+            history: VecDeque::new(),
+            unseen_errors: 0,
+            sender: ToastSender { send, ctx },
+            recv,
+            offset,
+        }
+    }
+
+    pub fn sender(&self) -> ToastSender {
+        self.sender.clone()
+    }
+
+    pub fn info(&mut self, text: impl ToString) {
+        self.push(ToastKind::Info, text.to_string())
+    }
+
+    pub fn error(&mut self, text: impl ToString) {
+        self.push(ToastKind::Error, text.to_string())
+    }
+
+    /// Only put it in the notification center, e.g. for what do not disturb held back
+    pub fn record(&mut self, text: impl ToString) {
+        let text = text.to_string();
+        log!("recorded: {text}");
+        let toast = self.toast(ToastKind::Info, text);
+        self.remember(toast);
+    }
+
+    /// Pick up what was sent from the background and drop the toasts that have been up long enough
+    ///
+    /// This returns how long until the next one goes away
+    pub fn poll(&mut self) -> Option<Duration> {
+        while let Ok((kind, text)) = self.recv.try_recv() {
+            self.push(kind, text);
+        }
+
+        self.active
+            .retain(|toast| toast.shown.elapsed() < Self::duration(toast.kind));
+        self.active
+            .iter()
+            .map(|toast| Self::duration(toast.kind).saturating_sub(toast.shown.elapsed()))
+            .min()
+    }
+
+    pub fn active(&self) -> &[Toast] {
+        &self.active
+    }
+
+    pub fn dismiss(&mut self, index: usize) {
+        if index < self.active.len() {
+            self.active.remove(index);
+        }
+    }
+
+    /// Newest first
+    pub fn history(&self) -> impl Iterator<Item = &Toast> {
+        self.history.iter().rev()
+    }
+
+    pub fn clear_history(&mut self) {
+        self.history.clear();
+        self.unseen_errors = 0;
+    }
+
+    fn push(&mut self, kind: ToastKind, text: String) {
+        log!("toast ({kind:?}): {text}");
+        let toast = self.toast(kind, text);
+        self.remember(toast.clone());
+
+        if self.active.len() == Self::MAX_ACTIVE {
+            self.active.remove(0);
+        }
+        self.active.push(toast);
+    }
+
+    fn toast(&self, kind: ToastKind, text: String) -> Toast {
+        Toast {
+            kind,
+            text,
+            when: time::OffsetDateTime::now_utc().to_offset(self.offset),
+            shown: Instant::now(),
+        }
+    }
+
+    fn remember(&mut self, toast: Toast) {
+        if self.history.len() == Self::MAX_HISTORY {
+            self.history.pop_front();
+        }
+        self.unseen_errors += usize::from(toast.kind == ToastKind::Error);
+        self.history.push_back(toast);
+    }
+
+    const fn duration(kind: ToastKind) -> Duration {
+        match kind {
+            ToastKind::Info => Self::INFO_DURATION,
+            ToastKind::Error => Self::ERROR_DURATION,
+        }
+    }
+}
@@ -0,0 +1,118 @@
+use std::{
+    collections::VecDeque,
+    sync::{Arc, Mutex, PoisonError},
+    time::{Duration, Instant},
+};
+
+/// Which part of the background work a failure came from
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub enum Source {
+    Helix,
+    Images,
+    Emotes,
+    SevenTv,
+    Cache,
+    Files,
+}
+
+impl Source {
+    const ALL: [Self; 6] = [
+        Self::Helix,
+        Self::Images,
+        Self::Emotes,
+        Self::SevenTv,
+        Self::Cache,
+        Self::Files,
+    ];
+
+    pub const fn label(&self) -> &'static str {
+        match self {
+            Self::Helix => "helix",
+            Self::Images => "images",
+            Self::Emotes => "emotes",
+            Self::SevenTv => "7tv",
+            Self::Cache => "cache",
+            Self::Files => "files",
+        }
+    }
+}
+
+/// Something that went wrong in a task nobody is waiting on
+#[derive(Clone, Debug)]
+pub struct TaskError {
+    pub source: Source,
+    /// What was being done, e.g. "cannot fetch https://..."
+    pub context: String,
+    pub error: String,
+    /// How many from the same source were held back before this one
+    pub suppressed: usize,
+}
+
+impl std::fmt::Display for TaskError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let Self {
+            source,
+            context,
+            error,
+            suppressed,
+        } = self;
+        write!(f, "{}: {context}: {error}", source.label())?;
+        match suppressed {
+            0 => Ok(()),
+            n => write!(f, " (and {n} more)"),
+        }
+    }
+}
+
+#[derive(Default)]
+struct Pending {
+    queue: VecDeque<TaskError>,
+    // per source, when one was last let through and how many were held back since
+    last: [(Option<Instant>, usize); Source::ALL.len()],
+}
+
+/// Where the background tasks report their failures, the clones share the queue
+#[derive(Clone, Default)]
+pub struct TaskErrors(Arc<Mutex<Pending>>);
+
+impl TaskErrors {
+    // a source that keeps failing (e.g. every image while offline) only shows up this often
+    const THROTTLE: Duration = Duration::from_secs(30);
+    const MAX_PENDING: usize = 50;
+
+    /// Log a failure, and queue it up to be shown unless its source was shown recently
+    pub fn report(&self, source: Source, context: impl ToString, error: impl std::fmt::Display) {
+        let (context, error) = (context.to_string(), error.to_string());
+        log!("{}: {context}: {error}", source.label());
+
+        let mut pending = self.0.lock().unwrap_or_else(PoisonError::into_inner);
+        let index = Source::ALL
+            .iter()
+            .position(|s| *s == source)
+            .expect("every source is listed");
+
+        let (last, suppressed) = &mut pending.last[index];
+        if last.map_or(false, |last| last.elapsed() < Self::THROTTLE) {
+            *suppressed += 1;
+            return;
+        }
+        last.replace(Instant::now());
+        let suppressed = std::mem::take(suppressed);
+
+        if pending.queue.len() == Self::MAX_PENDING {
+            pending.queue.pop_front();
+        }
+        pending.queue.push_back(TaskError {
+            source,
+            context,
+            error,
+            suppressed,
+        });
+    }
+
+    /// Everything reported since the last time this was called
+    pub fn drain(&self) -> Vec<TaskError> {
+        let mut pending = self.0.lock().unwrap_or_else(PoisonError::into_inner);
+        pending.queue.drain(..).collect()
+    }
+}
@@ -0,0 +1,66 @@
+use serde::{Deserialize, Serialize};
+
+/// A channel's name, lowercase and without the `#` in front
+///
+/// Twitch sends them both ways, so they're normalized once here and compared directly after that
+#[derive(Clone, Debug, Default, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(from = "String", into = "String")]
+pub struct ChannelName(String);
+
+impl ChannelName {
+    pub fn new(name: &str) -> Self {
+        let name = name.strip_prefix('#').unwrap_or(name);
+        Self(name.to_lowercase())
+    }
+
+    pub fn as_str(&self) -> &str {
+        &self.0
+    }
+}
+
+impl std::ops::Deref for ChannelName {
+    type Target = str;
+    fn deref(&self) -> &Self::Target {
+        &self.0
+    }
+}
+
+impl std::fmt::Display for ChannelName {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
+impl From<&str> for ChannelName {
+    fn from(name: &str) -> Self {
+        Self::new(name)
+    }
+}
+
+impl From<String> for ChannelName {
+    fn from(name: String) -> Self {
+        Self::new(&name)
+    }
+}
+
+impl From<ChannelName> for String {
+    fn from(name: ChannelName) -> Self {
+        name.0
+    }
+}
+
+// so a name straight from twitch can be checked without normalizing it first
+impl PartialEq<str> for ChannelName {
+    fn eq(&self, other: &str) -> bool {
+        other
+            .strip_prefix('#')
+            .unwrap_or(other)
+            .eq_ignore_ascii_case(&self.0)
+    }
+}
+
+impl PartialEq<&str> for ChannelName {
+    fn eq(&self, other: &&str) -> bool {
+        self == *other
+    }
+}
@@ -1,11 +1,18 @@
-use std::time::Instant;
+use std::{
+    collections::VecDeque,
+    time::{Duration, Instant},
+};
 
 use tokio::sync::{mpsc::unbounded_channel, oneshot};
 use twitch_message::builders::{PrivmsgBuilder, TagsBuilder};
 
-use crate::repaint::Repaint;
+use crate::{
+    repaint::Repaint,
+    resolver::Wakeup,
+    runtime::{Supervised, TaskExit},
+};
 
-use super::{Config, Event, Events, Identity, Message, Signal, Status, Writer};
+use super::{ChannelName, Config, Event, Events, Identity, Message, Signal, Status, Writer};
 
 pub struct Client {
     events: Events,
@@ -13,16 +20,19 @@ pub struct Client {
     signal: Option<oneshot::Sender<Signal>>,
     status: Status,
     config: Config,
+    latency: Option<Duration>,
+    queued: usize,
+    task: Supervised,
 }
 
 impl Client {
-    pub fn create(config: Config, repaint: impl Repaint) -> Self {
+    pub fn create(config: Config, repaint: impl Repaint, wakeup: Wakeup) -> Self {
         let (read, recv) = unbounded_channel();
         let (send, write) = unbounded_channel();
 
         let (signal_tx, signal_rx) = oneshot::channel();
 
-        tokio::spawn({
+        let task = Supervised::spawn(wakeup, {
             let config = config.clone();
             async move {
                 let wait = async move { signal_rx.await.unwrap_or(Signal::Ignore) };
@@ -36,13 +46,32 @@ impl Client {
             signal: Some(signal_tx),
             status: Status::default(),
             config,
+            latency: None,
+            queued: 0,
+            task,
         }
     }
 
+    /// How the connection task ended, if it did
+    ///
+    /// It only ends on its own when something went wrong, a shutdown replaces the client
+    pub fn exit(&self) -> Option<TaskExit> {
+        self.task.exit()
+    }
+
     pub fn user_name(&self) -> &str {
         &self.config.name
     }
 
+    pub const fn config(&self) -> &Config {
+        &self.config
+    }
+
+    /// The chat token, which is also good for acting as us on helix
+    pub(crate) fn token(&self) -> &str {
+        &self.config.token
+    }
+
     pub fn connect(&mut self) {
         if let Some(signal) = self.signal.take() {
             let _ = signal.send(Signal::Start);
@@ -53,6 +82,16 @@ impl Client {
         self.status
     }
 
+    /// The last measured round trip to the server
+    pub const fn latency(&self) -> Option<Duration> {
+        self.latency
+    }
+
+    /// How many sent messages are waiting on the rate limit
+    pub const fn queued(&self) -> usize {
+        self.queued
+    }
+
     pub const fn writer(&self) -> &Writer {
         &self.writer
     }
@@ -60,62 +99,129 @@ impl Client {
     pub(crate) fn poll(
         &mut self,
         identity: &mut Option<Identity>,
-        last: &mut Option<(PrivmsgBuilder, TagsBuilder)>,
+        last: &mut VecDeque<(ChannelName, PrivmsgBuilder, TagsBuilder)>,
     ) -> Option<Message> {
         self.status = match self.events.poll()? {
             Event::Connecting => {
-                eprintln!("status: connecting");
+                log!("status: connecting");
                 Status::Connecting
             }
 
             Event::Connected { identity: new } => {
-                eprintln!("status: connected: {new:#?}");
+                log!("status: connected: {new:#?}");
                 let _ = identity.replace(new);
                 Status::Connected
             }
 
             Event::Reconnecting { duration } => {
-                eprintln!("status: reconnecting: {duration:.2?}");
+                log!("status: reconnecting: {duration:.2?}");
+                self.latency.take();
                 Status::Reconnecting {
                     when: Instant::now(),
                     after: duration,
                 }
             }
 
+            Event::Contested => {
+                log!("status: contested");
+                self.latency.take();
+                Status::Contested
+            }
+
+            Event::InvalidCredentials => {
+                log!("status: invalid credentials");
+                self.latency.take();
+                Status::InvalidCredentials
+            }
+
             Event::UserState { msg } => {
-                let identity = identity
-                    .as_mut()
-                    .expect("we should have an identity at this point");
-                identity.append_badges(&msg.channel, msg.badges());
-
-                // app.state.channels[app.state.active].messages.push(msg);
-
-                if let Some((pm, tags)) = last.take() {
-                    let tags = tags
-                        .add(
-                            "id",
-                            msg.msg_id()
-                                .map(<twitch_message::messages::MsgIdRef>::to_string)
-                                .expect("msg-id attached"),
-                        )
-                        .finish();
-
-                    let pm = pm.tags(tags).finish_privmsg().expect("valid pm");
-                    return Some(Message::Finished { msg: pm });
+                match identity.as_mut() {
+                    Some(identity) => {
+                        identity.append_badges(&ChannelName::new(&msg.channel), msg.badges());
+                        // subscribing grants new sets without a reconnect
+                        identity.update_emote_sets(msg.emote_sets().map(ToString::to_string));
+                    }
+                    None => log!("got a USERSTATE before we have an identity"),
                 }
 
-                return None;
+                // the one after a JOIN has no id, only the ones for our messages do
+                let id = msg.msg_id()?;
+                let channel = ChannelName::new(&msg.channel);
+                let Some(pos) = last.iter().position(|(name, ..)| *name == channel) else {
+                    log!("USERSTATE for a message in #{channel} we aren't waiting on");
+                    return None;
+                };
+
+                let (_, pm, tags) = last.remove(pos).expect("valid position");
+                let tags = tags.add("id", id.to_string()).finish();
+                let pm = pm.tags(tags).finish_privmsg().expect("valid pm");
+                return Some(Message::Finished { msg: pm });
             }
 
-            Event::ChannelId {
-                channel: _,
-                room_id: _,
-            } => {
-                return None;
+            Event::ChannelId { channel, room_id } => {
+                return Some(Message::ChannelId { channel, room_id })
             }
 
             Event::Join { channel } => return Some(Message::Join { channel }),
             Event::Privmsg { msg } => return Some(Message::Privmsg { msg }),
+            Event::Whisper { msg } => return Some(Message::Whisper { msg }),
+            Event::UserTimedOut {
+                channel,
+                user_id,
+                duration,
+            } => {
+                return Some(Message::UserTimedOut {
+                    channel,
+                    user_id,
+                    duration,
+                })
+            }
+            Event::MessageDeleted { channel, msg_id } => {
+                return Some(Message::MessageDeleted { channel, msg_id })
+            }
+            Event::UserNotice {
+                msg,
+                kind,
+                system,
+                raw,
+            } => {
+                return Some(Message::UserNotice {
+                    msg,
+                    kind,
+                    system,
+                    raw,
+                })
+            }
+            Event::Notice {
+                channel,
+                kind,
+                text,
+            } => {
+                // a refused message never gets a USERSTATE, so it stops waiting on one
+                if kind.as_deref().map_or(false, |kind| kind.starts_with("msg_")) {
+                    if let Some(pos) = last.iter().position(|(name, ..)| *name == channel) {
+                        log!("our message in #{channel} was refused");
+                        last.remove(pos);
+                    }
+                }
+                return Some(Message::Notice {
+                    channel,
+                    kind,
+                    text,
+                })
+            }
+            Event::Unsent { messages } => return Some(Message::Unsent { messages }),
+            Event::EmoteOnly { channel, enabled } => {
+                return Some(Message::EmoteOnly { channel, enabled })
+            }
+            Event::Latency { rtt } => {
+                self.latency.replace(rtt);
+                return None;
+            }
+            Event::Queued { depth } => {
+                self.queued = depth;
+                return None;
+            }
         };
 
         None
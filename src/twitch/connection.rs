@@ -0,0 +1,56 @@
+use std::sync::Arc;
+
+use once_cell::sync::Lazy;
+use tokio::{
+    io::{AsyncRead, AsyncWrite},
+    net::TcpStream,
+};
+use tokio_rustls::{
+    rustls::{ClientConfig, OwnedTrustAnchor, RootCertStore, ServerName},
+    TlsConnector,
+};
+
+const TWITCH_IRC_TLS_ADDRESS: &str = "irc.chat.twitch.tv:6697";
+const TWITCH_IRC_DOMAIN: &str = "irc.chat.twitch.tv";
+
+pub trait Stream: AsyncRead + AsyncWrite + Send + Unpin {}
+impl<T> Stream for T where T: AsyncRead + AsyncWrite + Send + Unpin {}
+
+pub const fn address(tls: bool) -> &'static str {
+    if tls {
+        TWITCH_IRC_TLS_ADDRESS
+    } else {
+        twitch_message::TWITCH_IRC_ADDRESS
+    }
+}
+
+/// Connects to the irc server, over TLS if `tls` is set
+pub async fn connect(tls: bool) -> std::io::Result<Box<dyn Stream>> {
+    if !tls {
+        let stream = TcpStream::connect(address(tls)).await?;
+        return Ok(Box::new(stream));
+    }
+
+    // building the root store is somewhat expensive, so its only done once
+    static CONNECTOR: Lazy<TlsConnector> = Lazy::new(|| {
+        let mut roots = RootCertStore::empty();
+        roots.add_trust_anchors(webpki_roots::TLS_SERVER_ROOTS.iter().map(|ta| {
+            OwnedTrustAnchor::from_subject_spki_name_constraints(
+                ta.subject,
+                ta.spki,
+                ta.name_constraints,
+            )
+        }));
+
+        let config = ClientConfig::builder()
+            .with_safe_defaults()
+            .with_root_certificates(roots)
+            .with_no_client_auth();
+        TlsConnector::from(Arc::new(config))
+    });
+
+    let domain = ServerName::try_from(TWITCH_IRC_DOMAIN).expect("valid domain");
+    let stream = TcpStream::connect(address(tls)).await?;
+    let stream = CONNECTOR.connect(domain, stream).await?;
+    Ok(Box::new(stream))
+}
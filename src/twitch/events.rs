@@ -3,14 +3,73 @@ use std::time::Duration;
 use tokio::sync::mpsc::UnboundedReceiver;
 use twitch_message::messages::{Privmsg, UserState};
 
+use super::ChannelName;
+
 pub enum Event {
     Connecting,
-    Connected { identity: super::Identity },
-    Privmsg { msg: Privmsg<'static> },
-    Join { channel: String },
-    ChannelId { channel: String, room_id: String },
-    UserState { msg: UserState<'static> },
-    Reconnecting { duration: Duration },
+    Connected {
+        identity: super::Identity,
+    },
+    Privmsg {
+        msg: Privmsg<'static>,
+    },
+    Join {
+        channel: ChannelName,
+    },
+    ChannelId {
+        channel: ChannelName,
+        room_id: String,
+    },
+    UserState {
+        msg: UserState<'static>,
+    },
+    Reconnecting {
+        duration: Duration,
+    },
+    Unsent {
+        messages: Vec<(String, String)>,
+    },
+    Latency {
+        rtt: Duration,
+    },
+    /// How many messages are waiting on the rate limit
+    Queued {
+        depth: usize,
+    },
+    Contested,
+    /// The server didn't accept the name and token
+    InvalidCredentials,
+    EmoteOnly {
+        channel: ChannelName,
+        enabled: bool,
+    },
+    Whisper {
+        msg: Privmsg<'static>,
+    },
+    /// No duration is a ban
+    UserTimedOut {
+        channel: ChannelName,
+        user_id: String,
+        duration: Option<Duration>,
+    },
+    MessageDeleted {
+        channel: ChannelName,
+        msg_id: String,
+    },
+    /// The server telling us something about a channel, `kind` is the `msg-id`
+    Notice {
+        channel: ChannelName,
+        kind: Option<String>,
+        text: String,
+    },
+    /// Subs, raids and the like, `kind` is the `msg-id` and `system` is twitch's description of it
+    UserNotice {
+        msg: Privmsg<'static>,
+        kind: String,
+        system: String,
+        /// The line as it came in, `msg` is only the part the user wrote
+        raw: String,
+    },
 }
 
 pub struct Events {
@@ -2,6 +2,8 @@ use hashbrown::HashMap;
 
 use twitch_message::Color;
 
+use super::ChannelName;
+
 #[derive(Clone, Debug)]
 pub struct Identity {
     pub name: String,
@@ -9,20 +11,18 @@ pub struct Identity {
     pub user_id: String,
     pub color: Option<Color>,
     pub emote_sets: Vec<String>,
-    pub(in crate::twitch) badge_map: HashMap<String, HashMap<String, String>>,
+    pub(in crate::twitch) badge_map: HashMap<ChannelName, HashMap<String, String>>,
 }
 
 impl Identity {
     pub fn append_badges<'a>(
         &mut self,
-        channel: &str,
+        channel: &ChannelName,
         badges: impl IntoIterator<Item = twitch_message::Badge<'a>>,
     ) {
         use hashbrown::hash_map::RawEntryMut::*;
-        let channel = channel.strip_prefix('#').unwrap_or(channel);
-
         // TODO keep track of insertions so we can just .first() / .last() to get the best badge
-        let map = self.badge_map.entry(channel.to_string()).or_default();
+        let map = self.badge_map.entry(channel.clone()).or_default();
         for (set_id, id) in badges
             .into_iter()
             .map(|twitch_message::Badge { name, version }| (name, version))
@@ -38,9 +38,22 @@ impl Identity {
         }
     }
 
-    pub fn get_badges_for(&self, channel: &str) -> impl Iterator<Item = (&str, &str)> {
-        let channel = channel.strip_prefix('#').unwrap_or(channel);
+    /// USERSTATE has every set we have, so this replaces the list rather than adding to it
+    pub fn update_emote_sets(&mut self, sets: impl IntoIterator<Item = String>) {
+        let sets = sets.into_iter().collect::<Vec<_>>();
+        // an empty list is more likely a missing tag than losing every set
+        if !sets.is_empty() {
+            self.emote_sets = sets;
+        }
+    }
+
+    /// Whether we can moderate the channel, which the broadcaster always can
+    pub fn is_moderator(&self, channel: &ChannelName) -> bool {
+        self.get_badges_for(channel)
+            .any(|(set_id, _)| matches!(set_id, "moderator" | "broadcaster"))
+    }
 
+    pub fn get_badges_for(&self, channel: &ChannelName) -> impl Iterator<Item = (&str, &str)> {
         self.badge_map
             .get(channel)
             .into_iter()
@@ -0,0 +1,80 @@
+use std::{
+    collections::VecDeque,
+    time::{Duration, Instant},
+};
+
+use super::ChannelName;
+
+/// Twitch allows 20 join attempts every 10 seconds, anything past that waits its turn
+#[derive(Default)]
+pub struct JoinLimiter {
+    queued: VecDeque<ChannelName>,
+    sent: VecDeque<Instant>,
+}
+
+impl JoinLimiter {
+    const LIMIT: usize = 20;
+    const WINDOW: Duration = Duration::from_secs(10);
+    // lines can be 512 bytes, including the tags we don't send
+    const MAX_LINE: usize = 500;
+
+    pub fn push(&mut self, channel: ChannelName) {
+        if !self.queued.contains(&channel) {
+            self.queued.push_back(channel);
+        }
+    }
+
+    pub fn remove(&mut self, channel: &ChannelName) {
+        self.queued.retain(|queued| queued != channel);
+    }
+
+    /// Replace the queue with these channels, the attempts already made still count
+    pub fn reset(&mut self, channels: impl IntoIterator<Item = ChannelName>) {
+        self.queued.clear();
+        channels.into_iter().for_each(|channel| self.push(channel));
+    }
+
+    /// A single JOIN for as many of the queued channels as are allowed right now
+    pub fn next_batch(&mut self) -> Option<String> {
+        self.expire();
+
+        let mut line = String::from("JOIN ");
+        while self.sent.len() < Self::LIMIT {
+            let Some(channel) = self.queued.front() else { break };
+            if line.len() + channel.len() + 2 > Self::MAX_LINE {
+                break;
+            }
+
+            if !line.ends_with(' ') {
+                line.push(',');
+            }
+            line.push('#');
+            line.push_str(channel);
+
+            self.queued.pop_front();
+            self.sent.push_back(Instant::now());
+        }
+
+        (!line.ends_with(' ')).then(|| line + "\r\n")
+    }
+
+    /// How long until more channels can be joined, if any are waiting
+    pub fn wait(&mut self) -> Option<Duration> {
+        if self.queued.is_empty() {
+            return None;
+        }
+
+        self.expire();
+        if self.sent.len() < Self::LIMIT {
+            return Some(Duration::ZERO);
+        }
+        let oldest = *self.sent.front()?;
+        Some((oldest + Self::WINDOW).saturating_duration_since(Instant::now()))
+    }
+
+    fn expire(&mut self) {
+        while matches!(self.sent.front(), Some(sent) if sent.elapsed() >= Self::WINDOW) {
+            self.sent.pop_front();
+        }
+    }
+}
@@ -0,0 +1,90 @@
+use std::{
+    collections::{HashSet, VecDeque},
+    time::{Duration, Instant},
+};
+
+use super::{writer::Outgoing, ChannelName};
+
+/// Twitch allows 20 messages every 30 seconds, anything past that waits its turn
+///
+/// Moderators and broadcasters get 100 in their own channels, the ones sent elsewhere still count
+#[derive(Default)]
+pub(in crate::twitch) struct MessageLimiter {
+    queued: VecDeque<Outgoing>,
+    sent: VecDeque<Instant>,
+    moderated: HashSet<ChannelName>,
+}
+
+impl MessageLimiter {
+    const LIMIT: usize = 20;
+    const MODERATOR_LIMIT: usize = 100;
+    const WINDOW: Duration = Duration::from_secs(30);
+
+    /// Whether we're a moderator (or the broadcaster) in the channel, from its USERSTATE
+    pub(in crate::twitch) fn set_moderator(&mut self, channel: ChannelName, moderator: bool) {
+        if moderator {
+            self.moderated.insert(channel);
+        } else {
+            self.moderated.remove(&channel);
+        }
+    }
+
+    /// How many messages are waiting on the limit
+    pub(in crate::twitch) fn len(&self) -> usize {
+        self.queued.len()
+    }
+
+    pub(in crate::twitch) fn push(&mut self, msg: Outgoing) {
+        self.queued.push_back(msg);
+    }
+
+    /// The next message, if one is allowed right now
+    pub(in crate::twitch) fn pop(&mut self) -> Option<Outgoing> {
+        self.expire();
+        if self.sent.len() >= self.limit()? {
+            return None;
+        }
+
+        let msg = self.queued.pop_front()?;
+        self.sent.push_back(Instant::now());
+        Some(msg)
+    }
+
+    /// Everything still waiting, without regard for the limit
+    pub(in crate::twitch) fn drain(&mut self) -> impl Iterator<Item = Outgoing> + '_ {
+        self.queued.drain(..)
+    }
+
+    /// How long until another message can be sent, if any are waiting
+    pub(in crate::twitch) fn wait(&mut self) -> Option<Duration> {
+        if self.queued.is_empty() {
+            return None;
+        }
+
+        self.expire();
+        let limit = self.limit()?;
+        if self.sent.len() < limit {
+            return Some(Duration::ZERO);
+        }
+        // the one that has to expire for the next message to fit
+        let oldest = *self.sent.get(self.sent.len() - limit)?;
+        Some((oldest + Self::WINDOW).saturating_duration_since(Instant::now()))
+    }
+
+    // this goes by the next message in line, so they're still sent in order
+    fn limit(&self) -> Option<usize> {
+        let next = self.queued.front()?;
+        let moderated = self.moderated.contains(&ChannelName::new(&next.target));
+        Some(if moderated {
+            Self::MODERATOR_LIMIT
+        } else {
+            Self::LIMIT
+        })
+    }
+
+    fn expire(&mut self) {
+        while matches!(self.sent.front(), Some(sent) if sent.elapsed() >= Self::WINDOW) {
+            self.sent.pop_front();
+        }
+    }
+}
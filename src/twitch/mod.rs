@@ -1,5 +1,5 @@
 use std::{
-    collections::HashSet,
+    collections::{HashMap, HashSet, VecDeque},
     future::Future,
     time::{Duration, Instant},
 };
@@ -9,14 +9,15 @@ use tokio::{
     sync::mpsc::{UnboundedReceiver, UnboundedSender},
 };
 use twitch_message::{
-    encode::{join, part, ping, privmsg, register, ALL_CAPABILITIES},
+    builders::TagsBuilder,
+    encode::{part, ping, register, ALL_CAPABILITIES},
     messages::{Privmsg, TwitchMessage},
-    IntoStatic, ParseResult, PingTracker,
+    IntoStatic, ParseResult, PingTracker, Tags,
 };
 
 use crate::{
     repaint::Repaint,
-    twitch::writer::WriteKind,
+    twitch::writer::{Outgoing, WriteKind},
     util::{select2, Either},
 };
 
@@ -30,6 +31,10 @@ pub enum Status {
         when: Instant,
         after: Duration,
     },
+    /// The server keeps dropping us, probably for another session on the same account
+    Contested,
+    /// The server rejected the token, nothing is retried until the credentials change
+    InvalidCredentials,
 }
 
 #[derive(Copy, Clone, Debug, Default)]
@@ -42,15 +47,176 @@ pub enum Signal {
 #[non_exhaustive]
 #[derive(Debug, Clone)]
 pub enum Message {
-    Join { channel: String },
-    Privmsg { msg: Privmsg<'static> },
-    Finished { msg: Privmsg<'static> },
+    Join {
+        channel: ChannelName,
+    },
+    Privmsg {
+        msg: Privmsg<'static>,
+    },
+    Finished {
+        msg: Privmsg<'static>,
+    },
+    Unsent {
+        messages: Vec<(String, String)>,
+    },
+    EmoteOnly {
+        channel: ChannelName,
+        enabled: bool,
+    },
+    ChannelId {
+        channel: ChannelName,
+        room_id: String,
+    },
+    Whisper {
+        msg: Privmsg<'static>,
+    },
+    UserTimedOut {
+        channel: ChannelName,
+        user_id: String,
+        duration: Option<Duration>,
+    },
+    MessageDeleted {
+        channel: ChannelName,
+        msg_id: String,
+    },
+    UserNotice {
+        msg: Privmsg<'static>,
+        kind: String,
+        system: String,
+        raw: String,
+    },
+    Notice {
+        channel: ChannelName,
+        kind: Option<String>,
+        text: String,
+    },
 }
 
 #[derive(Clone)]
 pub struct Config {
     pub name: String,
     pub token: String,
+    /// For getting a new token before this one expires, if the token came with one
+    pub refresh_token: Option<String>,
+    /// Connect over TLS, otherwise plain TCP is used
+    pub tls: bool,
+}
+
+impl Config {
+    pub const ENV_FILES: [&str; 2] = [".dev.env", ".secrets.env"];
+
+    /// Loads the environment files and reads the credentials from the environment
+    pub fn load() -> anyhow::Result<Self> {
+        simple_env_load::load_env_from(Self::ENV_FILES);
+        Self::from_lookup(|key| std::env::var(key).ok())
+    }
+
+    /// Read the credentials from the environment files again, what's in them wins
+    ///
+    /// The environment is only filled in at startup, so this is how edits to the files are seen
+    pub fn reload() -> anyhow::Result<Self> {
+        let files = Self::read_files();
+        Self::from_lookup(|key| files.get(key).cloned().or_else(|| std::env::var(key).ok()))
+    }
+
+    /// The `key=value` lines in the environment files, the later files win
+    pub fn read_files() -> HashMap<String, String> {
+        let mut values = HashMap::new();
+        for path in Self::ENV_FILES {
+            let Ok(data) = std::fs::read_to_string(path) else { continue };
+            for line in data.lines().map(str::trim) {
+                if line.is_empty() || line.starts_with('#') {
+                    continue;
+                }
+                let line = line.strip_prefix("export ").unwrap_or(line);
+                let Some((key, value)) = line.split_once('=') else { continue };
+                let value = value.trim();
+                let value = ['"', '\'']
+                    .into_iter()
+                    .find_map(|q| value.strip_prefix(q)?.strip_suffix(q))
+                    .unwrap_or(value);
+                values.insert(key.trim().to_string(), value.to_string());
+            }
+        }
+        values
+    }
+
+    fn from_lookup(get: impl Fn(&str) -> Option<String>) -> anyhow::Result<Self> {
+        let required = |key| get(key).ok_or_else(|| anyhow::anyhow!("'{key}' must be set"));
+        Ok(Self {
+            name: required("TWITCH_NAME")?,
+            token: required("TWITCH_OAUTH")?,
+            refresh_token: get("TWITCH_REFRESH_TOKEN").filter(|token| !token.trim().is_empty()),
+            // this is opt-out, plain TCP is only there for networks that won't allow 6697
+            tls: get("TWITCH_TLS")
+                .map_or(true, |tls| !matches!(&*tls, "0" | "false" | "no" | "off")),
+        })
+    }
+
+    /// The irc server this connects to
+    pub const fn address(&self) -> &'static str {
+        connection::address(self.tls)
+    }
+
+    /// Write credentials into the secrets file, keeping any other lines that are in it
+    ///
+    /// Only we can read the file afterwards, on platforms where that can be done
+    pub fn store_secrets<'a>(
+        secrets: impl IntoIterator<Item = (&'a str, &'a str)>,
+    ) -> anyhow::Result<()> {
+        let secrets = secrets.into_iter().collect::<Vec<_>>();
+        if secrets.is_empty() {
+            return Ok(());
+        }
+
+        // a line break in either would add lines of its own to the file
+        for &(key, value) in &secrets {
+            anyhow::ensure!(
+                !key.is_empty() && key.chars().all(|c| c.is_ascii_alphanumeric() || c == '_'),
+                "invalid secret name: {key:?}"
+            );
+            anyhow::ensure!(
+                !value.chars().any(char::is_control),
+                "the value for {key} has control characters in it"
+            );
+        }
+
+        let path = Self::ENV_FILES[1];
+        let existing = std::fs::read_to_string(path).unwrap_or_default();
+
+        let mut out = String::new();
+        for line in existing.lines() {
+            let key = line.split_once('=').map_or(line, |(key, _)| key).trim();
+            if !secrets.iter().any(|&(k, _)| k == key) {
+                out.push_str(line);
+                out.push('\n');
+            }
+        }
+        for (key, value) in secrets {
+            out.push_str(&format!("{key}={value}\n"));
+        }
+
+        // the file is only ever readable by us, even while it's being written
+        let mut options = std::fs::OpenOptions::new();
+        options.write(true).create(true).truncate(true);
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::OpenOptionsExt as _;
+            options.mode(0o600);
+        }
+        let mut file = options.open(path)?;
+
+        // the mode only applies to new files, one that was already there is tightened first
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt as _;
+            file.set_permissions(std::fs::Permissions::from_mode(0o600))?;
+        }
+
+        // tokio's AsyncWriteExt is in scope here, so this one is spelled out
+        std::io::Write::write_all(&mut file, out.as_bytes())?;
+        Ok(())
+    }
 }
 
 async fn run(
@@ -61,14 +227,28 @@ async fn run(
     mut write: UnboundedReceiver<WriteKind>,
 ) {
     const RECONNECT: Duration = Duration::from_secs(5);
-
-    let mut active_channels = <HashSet<String>>::new();
-
-    eprintln!("waiting for the start signal");
+    // anything queued for longer than this is handed back to the user instead of being sent
+    const STALE: Duration = Duration::from_secs(30);
+    // busy channels never hit the ping timeout, so latency is measured on its own schedule
+    const LATENCY_INTERVAL: Duration = Duration::from_secs(15);
+    // the server closing this many short sessions in the window looks like someone else is using the account
+    const SHORT_SESSION: Duration = Duration::from_secs(60);
+    const CONTESTED_WINDOW: Duration = Duration::from_secs(5 * 60);
+    const CONTESTED_DROPS: usize = 3;
+
+    let mut active_channels = <HashSet<ChannelName>>::new();
+    let mut joins = JoinLimiter::default();
+    let mut messages = MessageLimiter::default();
+    let mut pending = <VecDeque<(Instant, Outgoing)>>::new();
+    let mut drops = <VecDeque<Instant>>::new();
+    // what the ui was last told is waiting to be sent
+    let mut queued = 0;
+
+    log!("waiting for the start signal");
     if matches!(signal.await, Signal::Ignore) {
         return;
     }
-    eprintln!("got start signal");
+    log!("got start signal");
 
     'outer: loop {
         #[rustfmt::skip]
@@ -85,30 +265,41 @@ async fn run(
 
         while let Ok(msg) = write.try_recv() {
             match msg {
-                WriteKind::Join { channel } => active_channels.insert(channel),
-                WriteKind::Part { channel } => active_channels.remove(&channel),
-                _ => continue 'outer,
-            };
+                WriteKind::Join { channels } => {
+                    active_channels.extend(channels);
+                }
+                WriteKind::Part { channel, .. } => {
+                    active_channels.remove(&channel);
+                }
+                WriteKind::Privmsg(msg) => {
+                    pending.push_back((Instant::now(), msg));
+                }
+                WriteKind::TakeOver => {}
+                // there's no connection to flush anything to
+                WriteKind::Shutdown { done, .. } => {
+                    let _ = done.send(());
+                    break 'outer;
+                }
+            }
         }
 
         if read.send(Event::Connecting).is_err() {
             break;
         }
 
-        let mut stream =
-            match tokio::net::TcpStream::connect(twitch_message::TWITCH_IRC_ADDRESS).await {
-                Ok(stream) => stream,
-                Err(err) => {
-                    eprintln!("cannot connect: {err}");
-                    reconnect!();
-                }
-            };
+        let stream = match connection::connect(config.tls).await {
+            Ok(stream) => stream,
+            Err(err) => {
+                log!("cannot connect (tls: {}): {err}", config.tls);
+                reconnect!();
+            }
+        };
 
-        let (stream_read, mut stream_write) = stream.split();
+        let (stream_read, mut stream_write) = tokio::io::split(stream);
 
         let register = register(&config.name, &config.token, ALL_CAPABILITIES).to_string();
         if let Err(err) = write_all(register, &mut stream_write).await {
-            eprintln!("cannot write: {err}");
+            log!("cannot write: {err}");
             reconnect!();
         }
 
@@ -118,65 +309,158 @@ async fn run(
         let pt = PingTracker::new(ping_timeout * 2);
 
         let mut our_name = <Option<String>>::None;
+        // joins wait until the server knows who we are
+        let mut registered = false;
         let start = Instant::now();
+        let mut last_ping = start;
 
         'inner: loop {
-            let mut write_fut = std::pin::pin!(write.recv());
-            let mut read_fut = std::pin::pin!(reader.next_line());
+            // the token is when it was sent, so the pong tells us the round trip
+            if last_ping.elapsed() >= LATENCY_INTERVAL {
+                last_ping = Instant::now();
+                let ping = ping(&start.elapsed().as_millis().to_string()).to_string();
+                if write_all(ping, &mut stream_write).await.is_err() {
+                    log!("cannot write");
+                    reconnect!();
+                }
+            }
+
+            if let Some(line) = registered.then(|| joins.next_batch()).flatten() {
+                if let Err(err) = write_all(line, &mut stream_write).await {
+                    log!("cannot write: {err}");
+                    reconnect!();
+                }
+            }
+
+            while let Some(msg) = registered.then(|| messages.pop()).flatten() {
+                if let Err(err) = write_all(&msg.line, &mut stream_write).await {
+                    log!("cannot write: {err}");
+                    pending.push_back((Instant::now(), msg));
+                    reconnect!();
+                }
+            }
+
+            // anything that couldn't be sent yet is waiting on the rate limit
+            if messages.len() != queued {
+                queued = messages.len();
+                if read.send(Event::Queued { depth: queued }).is_err() {
+                    break 'outer;
+                }
+                repaint.repaint();
+            }
+
+            // wake up early if there are joins or messages waiting on the rate limit
+            let timeout = match joins.wait().into_iter().chain(messages.wait()).min() {
+                Some(wait) if registered => wait.min(ping_timeout),
+                _ => ping_timeout,
+            };
+            let event = {
+                let mut write_fut = std::pin::pin!(write.recv());
+                let mut read_fut = std::pin::pin!(reader.next_line());
+                tokio::time::timeout(timeout, select2(&mut write_fut, &mut read_fut)).await
+            };
 
-            let timeout =
-                tokio::time::timeout(ping_timeout, select2(&mut write_fut, &mut read_fut));
-            match if let Ok(ev) = timeout.await {
+            match if let Ok(ev) = event {
                 ev
             } else {
+                if timeout < ping_timeout {
+                    continue 'inner;
+                }
+
                 if pt.probably_timed_out() {
-                    eprintln!("connection timed out");
+                    log!("connection timed out");
                     reconnect!();
                 }
 
-                let ping = ping(&start.elapsed().as_secs().to_string()).to_string();
+                last_ping = Instant::now();
+                let ping = ping(&start.elapsed().as_millis().to_string()).to_string();
                 if write_all(ping, &mut stream_write).await.is_err() {
-                    eprintln!("cannot write");
+                    log!("cannot write");
                     reconnect!();
                 }
                 continue 'inner;
             } {
-                Either::Left(Some(write)) => match write {
-                    WriteKind::Join { channel } => {
-                        active_channels.insert(channel.clone());
-                        if let Err(err) =
-                            write_all(join(&channel).to_string(), &mut stream_write).await
-                        {
-                            eprintln!("cannot write: {err}");
-                            reconnect!();
+                Either::Left(Some(kind)) => match kind {
+                    // these are sent at the top of the loop
+                    WriteKind::Join { channels } => {
+                        for channel in channels {
+                            joins.push(channel.clone());
+                            active_channels.insert(channel);
                         }
                     }
 
-                    WriteKind::Part { channel } => {
+                    WriteKind::Part { channel, line } => {
                         active_channels.remove(&channel);
-                        if let Err(err) =
-                            write_all(part(&channel).to_string(), &mut stream_write).await
-                        {
-                            eprintln!("cannot write: {err}");
+                        joins.remove(&channel);
+                        if let Err(err) = write_all(line, &mut stream_write).await {
+                            log!("cannot write: {err}");
                             reconnect!();
                         }
                     }
 
-                    WriteKind::Privmsg { target, data } => {
-                        if let Err(err) =
-                            write_all(privmsg(&target, &data).to_string(), &mut stream_write).await
-                        {
-                            eprintln!("cannot write: {err}");
-                            reconnect!();
+                    // these are sent at the top of the loop too
+                    WriteKind::Privmsg(msg) => messages.push(msg),
+
+                    WriteKind::Shutdown {
+                        part: should_part,
+                        done,
+                    } => {
+                        log!("shutting down the connection");
+                        let mut parts = String::new();
+                        while let Ok(kind) = write.try_recv() {
+                            match kind {
+                                // there's no point in joining anything now
+                                WriteKind::Join { .. } => {}
+                                WriteKind::Part { line, .. } => parts.push_str(&line),
+                                WriteKind::Privmsg(msg) => messages.push(msg),
+                                WriteKind::Shutdown { done, .. } => {
+                                    let _ = done.send(());
+                                }
+                                WriteKind::TakeOver => {}
+                            }
+                        }
+
+                        // only what the rate limit allows right now is sent, waiting on the rest
+                        // would hold up the shutdown
+                        let mut out = String::new();
+                        while let Some(msg) = registered.then(|| messages.pop()).flatten() {
+                            out.push_str(&msg.line);
+                        }
+                        let unsent = messages.drain().count();
+                        if unsent > 0 {
+                            log!("dropping {unsent} messages that were waiting on the rate limit");
+                        }
+                        out.push_str(&parts);
+
+                        if should_part {
+                            for channel in &active_channels {
+                                out.push_str(&part(channel.as_str()).to_string());
+                            }
+                        }
+
+                        if let Err(err) = write_all(out, &mut stream_write).await {
+                            log!("cannot flush writes: {err}");
                         }
+                        let _ = done.send(());
+                        break 'outer;
                     }
+
+                    WriteKind::TakeOver => {}
                 },
 
                 Either::Right(Ok(Some(line))) => {
+                    if let Some(sent) = pong_token(&line).and_then(|token| token.parse().ok()) {
+                        let rtt = start.elapsed().saturating_sub(Duration::from_millis(sent));
+                        if read.send(Event::Latency { rtt }).is_err() {
+                            break 'outer;
+                        }
+                        repaint.repaint();
+                    }
+
                     let msg = match twitch_message::parse(&line) {
                         Ok(ParseResult { message, .. }) => message,
                         Err(err) => {
-                            eprintln!("cannot parse '{}' : {err}", line.escape_debug());
+                            log!("cannot parse '{}' : {err}", line.escape_debug());
                             reconnect!();
                         }
                     };
@@ -189,7 +473,7 @@ async fn run(
                             .await
                             .is_err()
                         {
-                            eprintln!("cannot write");
+                            log!("cannot write");
                             reconnect!();
                         }
                     }
@@ -203,7 +487,50 @@ async fn run(
                         };
                     }
 
-                    eprintln!(">{msg}", msg = msg.raw.escape_debug());
+                    log!(">{msg}", msg = msg.raw.escape_debug());
+
+                    // these are checked on the line, so it doesn't matter how they were parsed
+                    match command(&line) {
+                        // the server is going away, the channels are joined again on the next one
+                        Some(("RECONNECT", _)) => {
+                            log!("server asked us to reconnect");
+                            let event = Event::Reconnecting {
+                                duration: Duration::ZERO,
+                            };
+                            if read.send(event).is_err() {
+                                break 'outer;
+                            }
+                            repaint.repaint();
+                            continue 'outer;
+                        }
+
+                        Some(("NOTICE", channel)) if channel.starts_with('#') => {
+                            send_event!(Event::Notice {
+                                channel: ChannelName::new(channel),
+                                kind: raw_tag(&line, "msg-id").map(ToString::to_string),
+                                text: notice_text(&line).to_string(),
+                            });
+                            continue 'inner;
+                        }
+
+                        // the server closes the connection after this, and retrying won't help
+                        Some(("NOTICE", _)) if is_auth_failure(notice_text(&line)) => {
+                            log!("login failed: {}", notice_text(&line));
+                            if read.send(Event::InvalidCredentials).is_err() {
+                                break 'outer;
+                            }
+                            repaint.repaint_now();
+
+                            if wait_for_take_over(&mut write, &mut active_channels, &mut pending)
+                                .await
+                            {
+                                continue 'outer;
+                            }
+                            break 'outer;
+                        }
+
+                        _ => {}
+                    }
 
                     match msg.as_enum() {
                         TwitchMessage::Privmsg(msg) => {
@@ -218,20 +545,36 @@ async fn run(
                             let _ = our_name.replace(msg.name.to_string());
                         }
 
+                        // this is usually the user waiting on a join
                         TwitchMessage::Join(msg) if Some(&*msg.user) == our_name.as_deref() => {
-                            send_event!(Event::Join {
-                                channel: msg.channel.to_string()
-                            });
+                            let channel = ChannelName::new(&msg.channel);
+                            if read.send(Event::Join { channel }).is_err() {
+                                break 'outer;
+                            }
+                            repaint.repaint_now();
                         }
 
                         TwitchMessage::RoomState(msg) => {
                             send_event!(Event::ChannelId {
-                                channel: msg.channel.to_string(),
+                                channel: ChannelName::new(&msg.channel),
                                 room_id: msg.room_id().expect("room-id attached").to_string(),
                             });
+
+                            // this is only there on join, and when it changes
+                            if let Some(enabled) = raw_tag(&line, "emote-only") {
+                                send_event!(Event::EmoteOnly {
+                                    channel: ChannelName::new(&msg.channel),
+                                    enabled: enabled == "1",
+                                });
+                            }
                         }
 
                         TwitchMessage::UserState(msg) => {
+                            let moderator = msg.badges().any(|badge| {
+                                matches!(badge.name.as_str(), "moderator" | "broadcaster")
+                            });
+                            messages.set_moderator(ChannelName::new(&msg.channel), moderator);
+
                             send_event!(Event::UserState {
                                 msg: msg.into_static(),
                             });
@@ -249,7 +592,7 @@ async fn run(
                                 color: msg.color(),
                                 emote_sets: msg.emote_sets().map(ToString::to_string).collect(),
                                 badge_map: std::iter::once((
-                                    our_name,
+                                    ChannelName::new(&our_name),
                                     msg.badges()
                                         .map(|twitch_message::Badge { name, version }| {
                                             (name.to_string(), version.to_string())
@@ -261,16 +604,78 @@ async fn run(
 
                             send_event!(Event::Connected { identity });
 
-                            for channel in &active_channels {
-                                eprintln!("joining: {channel}");
-                                let join = join(channel).to_string();
-                                if let Err(err) = write_all(join, &mut stream_write).await {
-                                    eprintln!("cannot write: {err}");
+                            log!("joining {} channels", active_channels.len());
+                            joins.reset(active_channels.iter().cloned());
+                            registered = true;
+
+                            let mut stale = vec![];
+                            while let Some((queued, msg)) = pending.pop_front() {
+                                if queued.elapsed() >= STALE {
+                                    stale.push((queued, msg));
+                                    continue;
+                                }
+
+                                log!("replaying message to: {}", msg.target);
+                                if let Err(err) = write_all(&msg.line, &mut stream_write).await {
+                                    log!("cannot write: {err}");
+                                    // the stale ones are still handed back after the next connect
+                                    pending.push_front((queued, msg));
+                                    for entry in stale.drain(..).rev() {
+                                        pending.push_front(entry);
+                                    }
                                     reconnect!();
                                 }
                             }
+
+                            if !stale.is_empty() {
+                                let messages = stale
+                                    .into_iter()
+                                    .map(|(_, msg)| (msg.target, msg.data))
+                                    .collect();
+                                send_event!(Event::Unsent { messages });
+                            }
                         }
-                        _ => {}
+
+                        _ => match command(&line) {
+                            // these don't have a channel, so they're turned into a message to us
+                            Some(("WHISPER", _)) => {
+                                if let Some(msg) = whisper(&line) {
+                                    send_event!(Event::Whisper { msg });
+                                }
+                            }
+
+                            // without a user the whole chat was cleared, the messages are kept then
+                            Some(("CLEARCHAT", channel)) => {
+                                if let Some(user_id) = raw_tag(&line, "target-user-id") {
+                                    send_event!(Event::UserTimedOut {
+                                        channel: ChannelName::new(channel),
+                                        user_id: user_id.to_string(),
+                                        duration: raw_tag(&line, "ban-duration")
+                                            .and_then(|secs| secs.parse().ok())
+                                            .map(Duration::from_secs),
+                                    });
+                                }
+                            }
+
+                            Some(("USERNOTICE", channel)) => match user_notice(&line, channel) {
+                                Some((msg, kind, system)) => {
+                                    let raw = line.clone();
+                                    send_event!(Event::UserNotice { msg, kind, system, raw });
+                                }
+                                None => log!("invalid notice: {}", line.escape_debug()),
+                            },
+
+                            Some(("CLEARMSG", channel)) => {
+                                if let Some(msg_id) = raw_tag(&line, "target-msg-id") {
+                                    send_event!(Event::MessageDeleted {
+                                        channel: ChannelName::new(channel),
+                                        msg_id: msg_id.to_string(),
+                                    });
+                                }
+                            }
+
+                            _ => {}
+                        },
                     }
                 }
 
@@ -278,6 +683,31 @@ async fn run(
                     break 'outer;
                 }
 
+                Either::Right(Ok(None)) => {
+                    log!("connection closed by the server");
+                    if start.elapsed() < SHORT_SESSION {
+                        drops.push_back(Instant::now());
+                    }
+                    drops.retain(|dropped| dropped.elapsed() < CONTESTED_WINDOW);
+
+                    if drops.len() < CONTESTED_DROPS {
+                        reconnect!();
+                    }
+
+                    // stop fighting over the account until the user decides to take it back
+                    log!("connection looks contested, waiting to take over");
+                    drops.clear();
+                    if read.send(Event::Contested).is_err() {
+                        break 'outer;
+                    }
+                    repaint.repaint();
+
+                    if wait_for_take_over(&mut write, &mut active_channels, &mut pending).await {
+                        continue 'outer;
+                    }
+                    break 'outer;
+                }
+
                 Either::Right(..) => {
                     reconnect!();
                 }
@@ -286,6 +716,170 @@ async fn run(
     }
 }
 
+/// Keep track of what the user does while we're disconnected on purpose
+///
+/// Returns `true` if the user wants to try connecting again, `false` on shutdown
+async fn wait_for_take_over(
+    write: &mut UnboundedReceiver<WriteKind>,
+    active_channels: &mut HashSet<ChannelName>,
+    pending: &mut VecDeque<(Instant, Outgoing)>,
+) -> bool {
+    while let Some(msg) = write.recv().await {
+        match msg {
+            WriteKind::Join { channels } => {
+                active_channels.extend(channels);
+            }
+            WriteKind::Part { channel, .. } => {
+                active_channels.remove(&channel);
+            }
+            WriteKind::Privmsg(msg) => {
+                pending.push_back((Instant::now(), msg));
+            }
+            WriteKind::Shutdown { done, .. } => {
+                let _ = done.send(());
+                return false;
+            }
+            WriteKind::TakeOver => return true,
+        }
+    }
+    false
+}
+
+// e.g. `:tmi.twitch.tv NOTICE * :Login authentication failed`
+fn notice_text(line: &str) -> &str {
+    line.split_once(" NOTICE ")
+        .and_then(|(_, rest)| rest.split_once(" :"))
+        .map_or("", |(_, text)| text)
+}
+
+fn is_auth_failure(text: &str) -> bool {
+    ["Login authentication failed", "Improperly formatted auth"]
+        .iter()
+        .any(|prefix| text.starts_with(prefix))
+}
+
+// not every tag has an accessor, so these are read out of the line
+fn raw_tag<'a>(line: &'a str, key: &str) -> Option<&'a str> {
+    let (tags, _) = line.strip_prefix('@')?.split_once(' ')?;
+    tags.split(';')
+        .filter_map(|tag| tag.split_once('='))
+        .find_map(|(k, v)| (k == key).then_some(v))
+}
+
+/// Undo the escaping of a tag value, e.g. `\s` is a space
+pub fn unescape_tag(value: &str) -> String {
+    let mut out = String::with_capacity(value.len());
+    let mut iter = value.chars();
+    while let Some(c) = iter.next() {
+        if c != '\\' {
+            out.push(c);
+            continue;
+        }
+        match iter.next() {
+            Some(':') => out.push(';'),
+            Some('s') => out.push(' '),
+            Some('r') => out.push('\r'),
+            Some('n') => out.push('\n'),
+            Some(c) => out.push(c),
+            None => {}
+        }
+    }
+    out
+}
+
+// the command and its first argument, e.g. `@tags :tmi.twitch.tv CLEARMSG #channel :data`
+fn command(line: &str) -> Option<(&str, &str)> {
+    let line = match line.strip_prefix('@') {
+        Some(line) => line.split_once(' ')?.1,
+        None => line,
+    };
+    let line = match line.strip_prefix(':') {
+        Some(line) => line.split_once(' ')?.1,
+        None => line,
+    };
+    let (command, args) = line.split_once(' ').unwrap_or((line, ""));
+    let arg = args.split(' ').next().unwrap_or_default();
+    Some((command, arg))
+}
+
+// e.g. `@color=#FF0000;display-name=Foo;user-id=1234 :foo!foo@foo.tmi.twitch.tv WHISPER bar :hello`
+fn whisper(line: &str) -> Option<Privmsg<'static>> {
+    let prefix = match line.strip_prefix('@') {
+        Some(line) => line.split_once(' ')?.1,
+        None => line,
+    };
+    let (sender, rest) = prefix.strip_prefix(':')?.split_once('!')?;
+    let (_, rest) = rest.split_once(" WHISPER ")?;
+    let (target, data) = rest.split_once(" :")?;
+
+    let tags = copy_tags(
+        line,
+        &["color", "display-name", "user-id", "emotes", "badges"],
+    );
+    Privmsg::builder()
+        .sender(sender)
+        .channel(target)
+        .data(data)
+        .tags(tags.finish())
+        .finish_privmsg()
+        .ok()
+}
+
+// e.g. `@login=foo;msg-id=resub;system-msg=foo\ssubscribed :tmi.twitch.tv USERNOTICE #bar :hello`
+//
+// the user's own message (if there is one) is turned into a privmsg
+fn user_notice(line: &str, channel: &str) -> Option<(Privmsg<'static>, String, String)> {
+    let data = line
+        .split_once(" USERNOTICE ")?
+        .1
+        .split_once(" :")
+        .map_or("", |(_, data)| data);
+
+    let tags = copy_tags(
+        line,
+        &[
+            "color",
+            "display-name",
+            "user-id",
+            "emotes",
+            "badges",
+            "id",
+            "room-id",
+        ],
+    );
+    let msg = Privmsg::builder()
+        .sender(raw_tag(line, "login")?)
+        .channel(channel)
+        .data(data)
+        .tags(tags.finish())
+        .finish_privmsg()
+        .ok()?;
+
+    let kind = raw_tag(line, "msg-id")?.to_string();
+    let system = raw_tag(line, "system-msg").map(unescape_tag)?;
+    Some((msg, kind, system))
+}
+
+// the tags that are there, and not empty
+fn copy_tags(line: &str, keys: &[&str]) -> TagsBuilder {
+    keys.iter().fold(Tags::builder(), |tags, &key| {
+        match raw_tag(line, key).filter(|v| !v.is_empty()) {
+            Some(value) => tags.add(key, value),
+            None => tags,
+        }
+    })
+}
+
+// e.g. `:tmi.twitch.tv PONG tmi.twitch.tv :1234`
+fn pong_token(line: &str) -> Option<&str> {
+    let line = match line.strip_prefix(':') {
+        Some(line) => line.split_once(' ')?.1,
+        None => line,
+    };
+    let (_, token) = line.strip_prefix("PONG ")?.split_once(':')?;
+    Some(token.trim())
+}
+
 async fn write_all(
     s: impl AsRef<[u8]> + Send + Sync,
     w: &mut (impl AsyncWrite + Unpin + Send + Sync),
@@ -294,6 +888,8 @@ async fn write_all(
     w.flush().await
 }
 
+mod connection;
+
 mod identity;
 pub use identity::Identity;
 
@@ -301,7 +897,16 @@ mod events;
 pub use events::{Event, Events};
 
 mod writer;
-pub use writer::Writer;
+pub use writer::{is_login, WriteError, Writer};
+
+mod channel_name;
+pub use channel_name::ChannelName;
+
+mod join_limiter;
+use join_limiter::JoinLimiter;
+
+mod message_limiter;
+use message_limiter::MessageLimiter;
 
 mod client;
 pub use client::Client;
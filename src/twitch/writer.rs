@@ -1,33 +1,160 @@
 use tokio::sync::mpsc::UnboundedSender;
+use twitch_message::encode::{part, privmsg};
+
+use super::ChannelName;
 
 #[derive(Clone)]
 pub struct Writer {
     pub(in crate::twitch) send: UnboundedSender<WriteKind>,
 }
 
+/// Why something couldn't be sent, these are checked before anything is queued
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum WriteError {
+    InvalidChannel,
+    EmptyMessage,
+    /// Messages can't span multiple lines
+    LineBreak,
+    TooLong {
+        len: usize,
+    },
+    /// The connection is gone for good
+    Closed,
+}
+
+impl WriteError {
+    // twitch counts characters, not bytes
+    pub const MAX_LENGTH: usize = 500;
+}
+
+impl std::fmt::Display for WriteError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::InvalidChannel => f.write_str("that isn't a valid channel name"),
+            Self::EmptyMessage => f.write_str("there's nothing to send"),
+            Self::LineBreak => f.write_str("messages can't have line breaks"),
+            Self::TooLong { len } => write!(
+                f,
+                "messages can be {} characters at most, this is {len}",
+                Self::MAX_LENGTH
+            ),
+            Self::Closed => f.write_str("the connection is closed"),
+        }
+    }
+}
+
+impl std::error::Error for WriteError {}
+
+/// A message that's already encoded, the target and data are kept around in case it can't be sent
+pub(in crate::twitch) struct Outgoing {
+    pub target: String,
+    pub data: String,
+    pub line: String,
+}
+
 pub(in crate::twitch) enum WriteKind {
-    Join { channel: String },
-    Part { channel: String },
-    Privmsg { target: String, data: String },
+    // these are encoded together once the rate limit allows it
+    Join {
+        channels: Vec<ChannelName>,
+    },
+    Part {
+        channel: ChannelName,
+        line: String,
+    },
+    Privmsg(Outgoing),
+    Shutdown {
+        part: bool,
+        done: std::sync::mpsc::Sender<()>,
+    },
+    TakeOver,
+}
+
+/// Logins are at most 25 word characters, this is without a `#` or `@` in front
+pub fn is_login(login: &str) -> bool {
+    (1..=25).contains(&login.len()) && login.chars().all(|c| c.is_ascii_alphanumeric() || c == '_')
 }
 
 impl Writer {
-    pub fn privmsg(&self, target: impl ToString, data: impl ToString) {
-        let _ = self.send.send(WriteKind::Privmsg {
-            target: target.to_string(),
-            data: data.to_string(),
-        });
+    pub fn privmsg(&self, target: impl ToString, data: impl ToString) -> Result<(), WriteError> {
+        let (target, data) = (target.to_string(), data.to_string());
+        Self::check_channel(&target)?;
+        Self::check_message(&data)?;
+
+        let line = privmsg(&target, &data).to_string();
+        self.send(WriteKind::Privmsg(Outgoing { target, data, line }))
+    }
+
+    /// Check a whisper the same way as a message, they're sent through helix rather than chat
+    pub fn check_whisper(user: &str, data: &str) -> Result<(), WriteError> {
+        Self::check_channel(user)?;
+        Self::check_message(data)
+    }
+
+    pub fn join(&self, channel: impl ToString) -> Result<(), WriteError> {
+        self.join_many([channel])
+    }
+
+    /// Join several channels, nothing is joined if any of them aren't valid
+    pub fn join_many<T: ToString>(
+        &self,
+        channels: impl IntoIterator<Item = T>,
+    ) -> Result<(), WriteError> {
+        let channels = channels
+            .into_iter()
+            .map(|channel| Self::canonical(&channel.to_string()))
+            .collect::<Result<Vec<_>, _>>()?;
+
+        self.send(WriteKind::Join { channels })
+    }
+
+    pub fn part(&self, channel: impl ToString) -> Result<(), WriteError> {
+        let channel = Self::canonical(&channel.to_string())?;
+
+        let line = part(channel.as_str()).to_string();
+        self.send(WriteKind::Part { channel, line })
+    }
+
+    /// Reconnect after backing off from a contested connection
+    pub fn take_over(&self) {
+        let _ = self.send.send(WriteKind::TakeOver);
+    }
+
+    /// Flush any pending writes and close the connection.
+    ///
+    /// Queued messages are only sent if the rate limit allows it right now, the rest are
+    /// dropped. The returned receiver is notified once everything has been written
+    pub fn shutdown(&self, part: bool) -> std::sync::mpsc::Receiver<()> {
+        let (done, rx) = std::sync::mpsc::channel();
+        let _ = self.send.send(WriteKind::Shutdown { part, done });
+        rx
+    }
+
+    fn send(&self, kind: WriteKind) -> Result<(), WriteError> {
+        self.send.send(kind).map_err(|_| WriteError::Closed)
+    }
+
+    // the server only knows about the lowercase name, anything else joins an empty channel
+    fn canonical(channel: &str) -> Result<ChannelName, WriteError> {
+        Self::check_channel(channel)?;
+        Ok(ChannelName::new(channel))
     }
 
-    pub fn join(&self, channel: impl ToString) {
-        let _ = self.send.send(WriteKind::Join {
-            channel: channel.to_string(),
-        });
+    // the `#` in front is optional
+    fn check_channel(channel: &str) -> Result<(), WriteError> {
+        let name = channel.strip_prefix('#').unwrap_or(channel);
+        is_login(name).then_some(()).ok_or(WriteError::InvalidChannel)
     }
 
-    pub fn part(&self, channel: impl ToString) {
-        let _ = self.send.send(WriteKind::Part {
-            channel: channel.to_string(),
-        });
+    fn check_message(data: &str) -> Result<(), WriteError> {
+        if data.trim().is_empty() {
+            return Err(WriteError::EmptyMessage);
+        }
+        if data.contains(['\r', '\n']) {
+            return Err(WriteError::LineBreak);
+        }
+        match data.chars().count() {
+            len if len > WriteError::MAX_LENGTH => Err(WriteError::TooLong { len }),
+            _ => Ok(()),
+        }
     }
 }
@@ -0,0 +1,183 @@
+use std::{
+    io::{BufRead as _, Write as _},
+    net::{Ipv4Addr, SocketAddr, TcpStream},
+    path::{Path, PathBuf},
+    time::Duration,
+};
+
+use tokio::io::{AsyncBufReadExt as _, AsyncWriteExt as _};
+
+use crate::state::Permalink;
+
+// the running instance writes the port it takes links on here, next to the state
+const LINK_PORT: &str = ".vohiyo.port";
+
+/// Register this executable as the handler for `vohiyo://` links for the current user
+///
+/// Links are opened from the current directory, because that is where the state lives
+pub fn register() -> anyhow::Result<()> {
+    let exe = std::env::current_exe()?;
+    let cwd = std::env::current_dir()?;
+    std::fs::write(state_dir_file(&exe), cwd.to_string_lossy().as_bytes())?;
+    register_for(&exe, &cwd)?;
+    log!(
+        "registered {}:// links to {}",
+        Permalink::SCHEME,
+        exe.display()
+    );
+    Ok(())
+}
+
+// the handler can be started from anywhere, so where the state lives is kept beside the executable
+fn state_dir_file(exe: &Path) -> PathBuf {
+    exe.with_extension("dir")
+}
+
+/// Move to the directory the link handler was registered from, when started for a link
+///
+/// This falls back to the directory the executable is in
+pub fn enter_state_dir() -> anyhow::Result<()> {
+    let exe = std::env::current_exe()?;
+    let dir = match std::fs::read_to_string(state_dir_file(&exe)) {
+        Ok(dir) => PathBuf::from(dir.trim()),
+        Err(..) => exe
+            .parent()
+            .map(ToOwned::to_owned)
+            .ok_or_else(|| anyhow::anyhow!("cannot find the directory of {}", exe.display()))?,
+    };
+    std::env::set_current_dir(dir)?;
+    Ok(())
+}
+
+/// Hand the link to the instance that's already running, this returns whether one took it
+pub fn forward(link: &Permalink) -> bool {
+    let Ok(port) = std::fs::read_to_string(LINK_PORT) else { return false };
+    let Ok(port) = port.trim().parse::<u16>() else { return false };
+
+    let addr = SocketAddr::from((Ipv4Addr::LOCALHOST, port));
+    let Ok(mut stream) = TcpStream::connect_timeout(&addr, Duration::from_millis(500)) else {
+        return false;
+    };
+    if writeln!(stream, "{link}").is_err() {
+        return false;
+    }
+
+    // the port file could be left over from a crash, with something else on the port now
+    let _ = stream.set_read_timeout(Some(Duration::from_secs(1)));
+    let mut reply = String::new();
+    let _ = std::io::BufReader::new(stream).read_line(&mut reply);
+    reply.trim() == "ok"
+}
+
+/// Takes the links other instances were started with, so only one client is connected
+pub struct LinkListener {
+    recv: tokio::sync::mpsc::UnboundedReceiver<Permalink>,
+}
+
+impl LinkListener {
+    pub fn bind(ctx: egui::Context) -> anyhow::Result<Self> {
+        let listener = std::net::TcpListener::bind((Ipv4Addr::LOCALHOST, 0))?;
+        listener.set_nonblocking(true)?;
+        std::fs::write(LINK_PORT, listener.local_addr()?.port().to_string())?;
+        let listener = tokio::net::TcpListener::from_std(listener)?;
+
+        let (send, recv) = tokio::sync::mpsc::unbounded_channel();
+        tokio::spawn(async move {
+            while let Ok((stream, _)) = listener.accept().await {
+                let (read, mut write) = stream.into_split();
+                let mut line = String::new();
+                let read = tokio::io::BufReader::new(read).read_line(&mut line);
+                if !matches!(
+                    tokio::time::timeout(Duration::from_secs(1), read).await,
+                    Ok(Ok(_))
+                ) {
+                    continue;
+                }
+                let Some(link) = Permalink::parse(line.trim()) else { continue };
+
+                let _ = write.write_all(b"ok\n").await;
+                if send.send(link).is_err() {
+                    break;
+                }
+                ctx.request_repaint();
+            }
+        });
+
+        Ok(Self { recv })
+    }
+
+    pub fn poll(&mut self) -> Option<Permalink> {
+        self.recv.try_recv().ok()
+    }
+}
+
+impl Drop for LinkListener {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_file(LINK_PORT);
+    }
+}
+
+#[cfg(target_os = "linux")]
+fn register_for(exe: &std::path::Path, cwd: &std::path::Path) -> anyhow::Result<()> {
+    let dir = std::env::var_os("XDG_DATA_HOME")
+        .map(std::path::PathBuf::from)
+        .or_else(|| {
+            std::env::var_os("HOME").map(|home| std::path::Path::new(&home).join(".local/share"))
+        })
+        .ok_or_else(|| anyhow::anyhow!("cannot find the data directory"))?
+        .join("applications");
+    std::fs::create_dir_all(&dir)?;
+
+    let name = format!("{}.desktop", env!("CARGO_PKG_NAME"));
+    std::fs::write(
+        dir.join(&name),
+        format!(
+            "[Desktop Entry]\n\
+            Type=Application\n\
+            Name=VoHiYo\n\
+            Exec=\"{exe}\" %u\n\
+            Path={cwd}\n\
+            Terminal=false\n\
+            NoDisplay=true\n\
+            MimeType=x-scheme-handler/{scheme};\n",
+            exe = exe.display(),
+            cwd = cwd.display(),
+            scheme = Permalink::SCHEME,
+        ),
+    )?;
+
+    let status = std::process::Command::new("xdg-mime")
+        .args(["default", &name])
+        .arg(format!("x-scheme-handler/{}", Permalink::SCHEME))
+        .status()?;
+    anyhow::ensure!(status.success(), "xdg-mime failed: {status}");
+    Ok(())
+}
+
+// the registry has no notion of a working directory, the app finds it with `enter_state_dir`
+//
+// the link is passed straight to the executable, a shell would interpret what's in it
+#[cfg(windows)]
+fn register_for(exe: &std::path::Path, _cwd: &std::path::Path) -> anyhow::Result<()> {
+    let key = format!(r"HKCU\Software\Classes\{}", Permalink::SCHEME);
+    let command = format!(r#""{exe}" "%1""#, exe = exe.display());
+
+    let open = format!(r"{key}\shell\open\command");
+    for args in [
+        vec![&*key, "/ve", "/d", "URL:VoHiYo", "/f"],
+        vec![&*key, "/v", "URL Protocol", "/d", "", "/f"],
+        vec![&*open, "/ve", "/d", &*command, "/f"],
+    ] {
+        let status = std::process::Command::new("reg")
+            .arg("add")
+            .args(args)
+            .status()?;
+        anyhow::ensure!(status.success(), "reg add failed: {status}");
+    }
+    Ok(())
+}
+
+#[cfg(not(any(target_os = "linux", windows)))]
+fn register_for(_exe: &std::path::Path, _cwd: &std::path::Path) -> anyhow::Result<()> {
+    anyhow::bail!("registering a link handler isn't supported on this platform")
+}
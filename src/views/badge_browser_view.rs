@@ -0,0 +1,76 @@
+use egui::{Grid, RichText, ScrollArea, Vec2};
+
+use crate::{
+    runtime::{BadgeEntry, EmoteMap, ImageCache},
+    twitch::ChannelName,
+};
+
+/// Every badge that was resolved for the channel, along with the global ones
+pub struct BadgeBrowserView<'a> {
+    pub open: &'a mut bool,
+    pub channel: &'a ChannelName,
+    pub room_id: Option<&'a str>,
+    pub emote_map: &'a EmoteMap,
+    pub cache: &'a mut ImageCache,
+}
+
+impl<'a> BadgeBrowserView<'a> {
+    pub fn display(self, ctx: &egui::Context) {
+        if !*self.open {
+            return;
+        }
+
+        let (emote_map, cache) = (self.emote_map, self.cache);
+        let channel = self.room_id.map(|id| emote_map.badge_sets(Some(id)));
+
+        egui::Window::new(format!("badges for #{}", self.channel))
+            .id(egui::Id::new("badge-browser"))
+            .open(self.open)
+            .collapsible(false)
+            .show(ctx, |ui| {
+                ScrollArea::vertical().max_height(400.0).show(ui, |ui| {
+                    match channel {
+                        Some(badges) => Self::display_badges(ui, "channel", badges, cache),
+                        None => {
+                            ui.label(RichText::new("the channel isn't known yet").weak());
+                        }
+                    }
+                    ui.separator();
+                    Self::display_badges(ui, "global", emote_map.badge_sets(None), cache);
+                });
+            });
+    }
+
+    fn display_badges(
+        ui: &mut egui::Ui,
+        label: &str,
+        badges: &[BadgeEntry],
+        cache: &mut ImageCache,
+    ) {
+        ui.strong(format!("{label} ({})", badges.len()));
+        if badges.is_empty() {
+            ui.label(RichText::new("none resolved").weak());
+            return;
+        }
+
+        Grid::new(("badge-browser", label))
+            .num_columns(4)
+            .striped(true)
+            .show(ui, |ui| {
+                for badge in badges {
+                    match cache.get_image(&badge.url) {
+                        Some(image) => {
+                            let frame = ui.ctx().frame_nr();
+                            ui.add(image.as_egui_image(Vec2::splat(18.0), frame))
+                        }
+                        None => ui.label(RichText::new("missing").small().weak()),
+                    }
+                    .on_hover_text(&badge.url);
+                    ui.monospace(&badge.set_id);
+                    ui.monospace(&badge.version);
+                    ui.label(&badge.description);
+                    ui.end_row();
+                }
+            });
+    }
+}
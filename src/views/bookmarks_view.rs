@@ -0,0 +1,111 @@
+use egui::{Align2, RichText, ScrollArea, Spinner, Vec2};
+use uuid::Uuid;
+
+use crate::{db, resolver::Fut, state::Permalink};
+
+/// The bookmarked messages, read from the history when the list is opened
+pub struct BookmarkList {
+    messages: Option<Vec<db::Message>>,
+    fut: Option<Fut<Option<Vec<db::Message>>>>,
+}
+
+impl BookmarkList {
+    /// Read the bookmarks off the ui thread, see [`crate::App::history_path`]
+    pub fn new(db: &'static str) -> Self {
+        Self {
+            messages: None,
+            fut: Some(db::Connection::bookmarked_messages(db)),
+        }
+    }
+
+    /// Keep only the messages that are still bookmarked
+    pub fn retain(&mut self, keep: impl FnMut(&db::Message) -> bool) {
+        if let Some(messages) = &mut self.messages {
+            messages.retain(keep)
+        }
+    }
+
+    fn poll(&mut self) {
+        let Some(found) = self.fut.as_mut().and_then(Fut::try_resolve) else { return };
+        self.fut.take();
+        self.messages.replace(found.unwrap_or_default());
+    }
+}
+
+/// What was picked in the bookmarks list
+pub enum BookmarkAction {
+    Jump(Permalink),
+    Remove(Uuid),
+}
+
+pub struct BookmarksView<'a> {
+    pub bookmarks: &'a mut Option<BookmarkList>,
+}
+
+impl<'a> BookmarksView<'a> {
+    pub fn display(self, ctx: &egui::Context) -> Option<BookmarkAction> {
+        let list = self.bookmarks.as_mut()?;
+        list.poll();
+
+        let mut open = true;
+        let mut action = None;
+        egui::Window::new("bookmarks")
+            .id(egui::Id::new("bookmarks"))
+            .open(&mut open)
+            .collapsible(false)
+            .anchor(Align2::CENTER_CENTER, Vec2::ZERO)
+            .show(ctx, |ui| {
+                let Some(bookmarks) = &list.messages else {
+                    ui.add(Spinner::new());
+                    return;
+                };
+                if bookmarks.is_empty() {
+                    ui.label("nothing is bookmarked");
+                    ui.label(
+                        RichText::new("messages can be bookmarked from their context menu").weak(),
+                    );
+                    return;
+                }
+
+                let format =
+                    time::macros::format_description!("[year]-[month]-[day] [hour]:[minute]");
+                ScrollArea::vertical().max_height(400.0).show(ui, |ui| {
+                    for msg in bookmarks {
+                        ui.horizontal(|ui| {
+                            ui.label(RichText::new(&*msg.channel).small().weak());
+                            ui.label(
+                                RichText::new(msg.timestamp.format(format).unwrap_or_default())
+                                    .small()
+                                    .monospace()
+                                    .weak(),
+                            );
+                            if ui.small_button("jump").clicked() {
+                                action.replace(BookmarkAction::Jump(Permalink::new(
+                                    &msg.channel,
+                                    msg.msg_id,
+                                )));
+                            }
+                            if ui.small_button("remove").clicked() {
+                                action.replace(BookmarkAction::Remove(msg.msg_id));
+                            }
+                        });
+
+                        ui.horizontal_wrapped(|ui| {
+                            ui.strong(&*msg.login);
+                            if msg.deleted {
+                                ui.label(RichText::new(&*msg.data).strikethrough().weak());
+                            } else {
+                                ui.label(&*msg.data);
+                            }
+                        });
+                        ui.separator();
+                    }
+                });
+            });
+
+        if !open || matches!(action, Some(BookmarkAction::Jump(..))) {
+            self.bookmarks.take();
+        }
+        action
+    }
+}
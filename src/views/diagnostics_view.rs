@@ -0,0 +1,47 @@
+use egui::Grid;
+
+use crate::app::Startup;
+
+pub struct DiagnosticsView<'a> {
+    pub open: &'a mut bool,
+    pub startup: &'a Startup,
+}
+
+impl<'a> DiagnosticsView<'a> {
+    pub fn display(self, ctx: &egui::Context) {
+        if !*self.open {
+            return;
+        }
+
+        let startup = self.startup;
+        egui::Window::new("Diagnostics")
+            .open(self.open)
+            .collapsible(false)
+            .show(ctx, |ui| {
+                ui.strong("Startup");
+                Grid::new("diagnostics-startup")
+                    .num_columns(2)
+                    .striped(true)
+                    .show(ui, |ui| {
+                        for (name, dt) in startup.phases() {
+                            ui.label(name);
+                            ui.monospace(format!("{dt:.2?}"));
+                            ui.end_row();
+                        }
+
+                        ui.strong("first frame");
+                        match startup.time_to_first_frame() {
+                            Some(dt) => ui.monospace(format!("{dt:.2?}")),
+                            None => ui.monospace("-"),
+                        };
+                        ui.end_row();
+                    });
+
+                ui.label(
+                    egui::RichText::new("the history for each tab loads in the background")
+                        .small()
+                        .weak(),
+                );
+            });
+    }
+}
@@ -0,0 +1,86 @@
+use egui::{RichText, ScrollArea, TextEdit};
+
+use crate::{
+    runtime::{EmoteMap, EmoteSource, ImageCache},
+    twitch::ChannelName,
+    widgets::EmoteGrid,
+};
+
+/// The channel whose emotes are being browsed
+pub struct EmoteBrowser {
+    pub channel: ChannelName,
+    pub query: String,
+}
+
+pub struct EmoteBrowserView<'a> {
+    pub browser: &'a mut Option<EmoteBrowser>,
+    /// The channel's id, once it's known
+    pub room_id: Option<&'a str>,
+    pub emote_map: &'a EmoteMap,
+    pub cache: &'a mut ImageCache,
+}
+
+impl<'a> EmoteBrowserView<'a> {
+    const EMOTE_SIZE: f32 = 28.0;
+
+    /// Returns the channel and the name of the emote that was clicked
+    pub fn display(self, ctx: &egui::Context) -> Option<(ChannelName, String)> {
+        let browser = self.browser.as_mut()?;
+        let (emote_map, cache) = (self.emote_map, self.cache);
+
+        let mut open = true;
+        let mut picked = None;
+        egui::Window::new(format!("emotes for #{}", browser.channel))
+            .id(egui::Id::new("emote-browser"))
+            .open(&mut open)
+            .collapsible(false)
+            .default_width(320.0)
+            .show(ctx, |ui| {
+                ui.add(TextEdit::singleline(&mut browser.query).hint_text("search"));
+
+                let Some(room_id) = self.room_id else {
+                    ui.label(RichText::new("the channel isn't known yet").weak());
+                    return;
+                };
+
+                let query = browser.query.to_lowercase();
+                let mut emotes = emote_map
+                    .channel_emotes(room_id)
+                    .filter(|(_, _, name)| name.to_lowercase().contains(&query))
+                    .collect::<Vec<_>>();
+                emotes.sort_unstable();
+                if emotes.is_empty() {
+                    ui.label(RichText::new("no emotes").weak());
+                    return;
+                }
+
+                ScrollArea::vertical().max_height(400.0).show(ui, |ui| {
+                    for source in [EmoteSource::Twitch, EmoteSource::SevenTv] {
+                        let group = emotes
+                            .iter()
+                            .filter(|(s, ..)| *s == source)
+                            .map(|&(_, id, name)| (id, name))
+                            .collect::<Vec<_>>();
+                        if group.is_empty() {
+                            continue;
+                        }
+
+                        ui.strong(format!("{} ({})", source.label(), group.len()));
+                        let grid = EmoteGrid {
+                            emotes: &group,
+                            size: Self::EMOTE_SIZE,
+                        };
+                        if let Some(name) = grid.display(ui, emote_map, cache) {
+                            picked.replace(name);
+                        }
+                    }
+                });
+            });
+
+        let channel = browser.channel.clone();
+        if !open {
+            self.browser.take();
+        }
+        picked.map(|name| (channel, name))
+    }
+}
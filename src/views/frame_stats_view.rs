@@ -0,0 +1,64 @@
+use egui::{Align2, Grid, RichText, ScrollArea, Vec2};
+
+use crate::app::FrameStats;
+
+/// An overlay with the recent frame times, for reporting jank
+pub struct FrameStatsView<'a> {
+    pub stats: &'a FrameStats,
+}
+
+impl<'a> FrameStatsView<'a> {
+    pub fn display(self, ctx: &egui::Context) {
+        let stats = self.stats;
+        let percentile = |p| {
+            stats
+                .percentile(p)
+                .map_or_else(|| String::from("-"), |dt| format!("{dt:.1?}"))
+        };
+
+        egui::Window::new("frame times")
+            .id(egui::Id::new("frame-stats"))
+            .anchor(Align2::LEFT_TOP, Vec2::splat(8.0))
+            .collapsible(true)
+            .resizable(false)
+            .show(ctx, |ui| {
+                Grid::new("frame-stats-grid").num_columns(2).show(ui, |ui| {
+                    for (label, p) in [("p50", 0.5), ("p95", 0.95), ("p99", 0.99), ("max", 1.0)] {
+                        ui.label(label);
+                        ui.monospace(percentile(p));
+                        ui.end_row();
+                    }
+
+                    ui.label("message rows");
+                    ui.monospace(stats.rows().to_string());
+                    ui.end_row();
+
+                    ui.label("textures");
+                    ui.monospace(format!("{} (+{})", stats.textures(), stats.uploads()));
+                    ui.end_row();
+                });
+
+                ui.separator();
+                ui.label(
+                    RichText::new(format!("frames over {:?}", FrameStats::LONG_FRAME)).small(),
+                );
+
+                let format = time::macros::format_description!("[hour]:[minute]:[second]");
+                ScrollArea::vertical().max_height(150.0).show(ui, |ui| {
+                    let mut any = false;
+                    for frame in stats.long_frames().rev() {
+                        any = true;
+                        ui.monospace(format!(
+                            "{} {:>8.1?} {}",
+                            frame.when.format(format).unwrap_or_default(),
+                            frame.took,
+                            frame.channel.as_deref().unwrap_or("-"),
+                        ));
+                    }
+                    if !any {
+                        ui.label(RichText::new("none yet").small().weak());
+                    }
+                });
+            });
+    }
+}
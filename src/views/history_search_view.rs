@@ -0,0 +1,195 @@
+use egui::{Grid, RichText, ScrollArea, Spinner, TextEdit, TextStyle, Vec2};
+
+use crate::{
+    db::{self, HistoryQuery},
+    resolver::Fut,
+    runtime::{EmoteMap, ImageCache},
+    state::{Message, MessageOpts, Permalink},
+    twitch::ChannelName,
+};
+
+use super::MainView;
+
+/// A search over everything in the history, not just the open channels
+#[derive(Default)]
+pub struct HistorySearch {
+    pub query: HistoryQuery,
+    page: usize,
+    // what the results are for, they stay up while the query is edited
+    searched: Option<HistoryQuery>,
+    fut: Option<Fut<Option<Vec<db::Message>>>>,
+    results: Vec<(ChannelName, Message)>,
+}
+
+impl HistorySearch {
+    const PER_PAGE: usize = 50;
+
+    /// Start out already searching for the query
+    pub fn with_query(db: &'static str, query: HistoryQuery) -> Self {
+        let mut this = Self {
+            query,
+            ..Self::default()
+        };
+        this.search(db, 0);
+        this
+    }
+
+    fn search(&mut self, db: &'static str, page: usize) {
+        self.page = page;
+        self.searched.replace(self.query.clone());
+        self.fut.replace(db::Connection::search_history(
+            db,
+            self.query.clone(),
+            page,
+            Self::PER_PAGE,
+        ));
+    }
+
+    fn poll(&mut self, emote_map: &mut EmoteMap) {
+        let Some(found) = self.fut.as_mut().and_then(Fut::try_resolve) else { return };
+        self.fut.take();
+
+        let opts = MessageOpts {
+            old: true,
+            local: false,
+        };
+        self.results = found
+            .unwrap_or_default()
+            .iter()
+            .filter_map(|stored| {
+                let msg = Message::from_history(stored, opts)?;
+                msg.register_emotes(emote_map);
+                Some((stored.channel.clone(), msg))
+            })
+            .collect();
+    }
+
+    // a full page means there could be more after it
+    fn has_more(&self) -> bool {
+        self.results.len() == Self::PER_PAGE
+    }
+}
+
+pub struct HistorySearchView<'a> {
+    /// Where the history is, see [`crate::App::history_path`]
+    pub db: &'static str,
+    pub search: &'a mut Option<HistorySearch>,
+    pub emote_map: &'a mut EmoteMap,
+    pub cache: &'a mut ImageCache,
+}
+
+impl<'a> HistorySearchView<'a> {
+    /// Returns a link to the message the user wants to see in its channel
+    pub fn display(self, ctx: &egui::Context) -> Option<Permalink> {
+        let search = self.search.as_mut()?;
+        search.poll(self.emote_map);
+
+        let mut open = true;
+        let mut link = None;
+        egui::Window::new("search history")
+            .id(egui::Id::new("history-search"))
+            .open(&mut open)
+            .collapsible(false)
+            .default_width(500.0)
+            .show(ctx, |ui| {
+                let mut submit = false;
+                Grid::new("history-search-query")
+                    .num_columns(2)
+                    .show(ui, |ui| {
+                        let query = &mut search.query;
+                        for (label, value, hint) in [
+                            ("text", &mut query.text, "words in the message"),
+                            ("user", &mut query.login, "any user"),
+                            ("channel", &mut query.channel, "any channel"),
+                        ] {
+                            ui.label(label);
+                            let resp = ui.add(TextEdit::singleline(value).hint_text(hint));
+                            submit |=
+                                resp.lost_focus() && ui.input(|i| i.key_pressed(egui::Key::Enter));
+                            ui.end_row();
+                        }
+                    });
+
+                ui.horizontal(|ui| {
+                    let searchable = !search.query.is_empty();
+                    submit |= ui
+                        .add_enabled(searchable, egui::Button::new("Search"))
+                        .clicked();
+                    if submit && searchable {
+                        search.search(self.db, 0);
+                    }
+
+                    if search.fut.is_some() {
+                        ui.add(Spinner::new());
+                    }
+
+                    if search.searched.is_none() {
+                        return;
+                    }
+                    ui.separator();
+                    if ui
+                        .add_enabled(search.page > 0, egui::Button::new("newer"))
+                        .clicked()
+                    {
+                        search.query = search.searched.clone().unwrap_or_default();
+                        search.search(self.db, search.page - 1);
+                    }
+                    ui.label(format!("page {}", search.page + 1));
+                    if ui
+                        .add_enabled(search.has_more(), egui::Button::new("older"))
+                        .clicked()
+                    {
+                        search.query = search.searched.clone().unwrap_or_default();
+                        search.search(self.db, search.page + 1);
+                    }
+                });
+
+                if search.searched.is_some() && search.fut.is_none() && search.results.is_empty() {
+                    ui.label(RichText::new("nothing matched").weak());
+                    return;
+                }
+
+                let fid = TextStyle::Body.resolve(ui.style());
+                let (w, h) = ui.fonts(|f| (f.glyph_width(&fid, ' '), f.row_height(&fid)));
+                let format =
+                    time::macros::format_description!("[year]-[month]-[day] [hour]:[minute]");
+
+                ui.separator();
+                ScrollArea::vertical().max_height(400.0).show(ui, |ui| {
+                    for (channel, msg) in &search.results {
+                        ui.horizontal(|ui| {
+                            ui.label(RichText::new(&**channel).small().weak());
+                            ui.label(
+                                RichText::new(msg.received.format(format).unwrap_or_default())
+                                    .small()
+                                    .monospace()
+                                    .weak(),
+                            );
+                            if ui.small_button("jump").clicked() {
+                                link = msg.id.map(|id| Permalink::new(channel, id));
+                            }
+                        });
+
+                        ui.horizontal_wrapped(|ui| {
+                            ui.spacing_mut().item_spacing.x = w;
+                            ui.label(RichText::new(&msg.sender).color(msg.color).strong());
+                            MainView::display_fragments(
+                                ui,
+                                Vec2::splat(h),
+                                msg,
+                                self.emote_map,
+                                self.cache,
+                                &mut link,
+                            );
+                        });
+                        ui.separator();
+                    }
+                });
+            });
+
+        if !open || link.is_some() {
+            self.search.take();
+        }
+        link
+    }
+}
@@ -0,0 +1,288 @@
+use egui::{Color32, RichText, Sense, Vec2};
+
+use crate::{app::App, helix::data::TokenInfo, resolver::Fut, twitch};
+
+/// What the chat token was granted, it's checked when the credentials are loaded
+///
+/// The app polls this, since refreshing the token is scheduled off of it
+pub enum TokenCheck {
+    Checking(Fut<anyhow::Result<TokenInfo>>),
+    Checked(TokenInfo),
+    Failed(String),
+}
+
+impl TokenCheck {
+    /// Chat doesn't work at all without these
+    pub const REQUIRED_SCOPES: [&str; 2] = ["chat:read", "chat:edit"];
+    /// Enough to watch chat, without sending anything
+    pub const READ_ONLY_SCOPES: [&str; 1] = ["chat:read"];
+
+    pub fn poll(&mut self, read_only: bool) {
+        let Self::Checking(fut) = self else { return };
+        *self = match fut.try_resolve() {
+            Some(Ok(info)) => {
+                let missing = Self::missing(&info, read_only);
+                if !missing.is_empty() {
+                    log!("the token is missing scopes: {}", missing.join(", "));
+                } else if Self::can_only_read(&info) {
+                    log!("the token can only read chat, so nothing will be sent");
+                }
+                Self::Checked(info)
+            }
+            Some(Err(err)) => {
+                log!("cannot validate the token: {err}");
+                Self::Failed(err.to_string())
+            }
+            None => return,
+        };
+    }
+
+    /// The required scopes that weren't granted, nothing until the check is done
+    pub fn missing_scopes(&self, read_only: bool) -> Vec<&'static str> {
+        match self {
+            Self::Checked(info) => Self::missing(info, read_only),
+            _ => vec![],
+        }
+    }
+
+    /// The token can read chat but was never allowed to send to it
+    pub fn is_read_only(&self) -> bool {
+        matches!(self, Self::Checked(info) if Self::can_only_read(info))
+    }
+
+    fn can_only_read(info: &TokenInfo) -> bool {
+        Self::missing(info, true).is_empty() && !Self::missing(info, false).is_empty()
+    }
+
+    fn missing(info: &TokenInfo, read_only: bool) -> Vec<&'static str> {
+        let required = if read_only {
+            &Self::READ_ONLY_SCOPES[..]
+        } else {
+            &Self::REQUIRED_SCOPES[..]
+        };
+
+        let scopes = info.scopes.as_deref().unwrap_or_default();
+        required
+            .iter()
+            .copied()
+            .filter(|required| !scopes.iter().any(|scope| scope == required))
+            .collect()
+    }
+}
+
+/// Our avatar, name and color, with a menu for the account
+pub struct IdentityChip<'a> {
+    pub app: &'a mut App,
+}
+
+impl<'a> IdentityChip<'a> {
+    // the colors that don't need turbo or prime, by the names helix takes
+    const COLORS: [(&'static str, Color32); 15] = [
+        ("blue", Color32::from_rgb(0x00, 0x00, 0xFF)),
+        ("blue_violet", Color32::from_rgb(0x8A, 0x2B, 0xE2)),
+        ("cadet_blue", Color32::from_rgb(0x5F, 0x9E, 0xA0)),
+        ("chocolate", Color32::from_rgb(0xD2, 0x69, 0x1E)),
+        ("coral", Color32::from_rgb(0xFF, 0x7F, 0x50)),
+        ("dodger_blue", Color32::from_rgb(0x1E, 0x90, 0xFF)),
+        ("firebrick", Color32::from_rgb(0xB2, 0x22, 0x22)),
+        ("golden_rod", Color32::from_rgb(0xDA, 0xA5, 0x20)),
+        ("green", Color32::from_rgb(0x00, 0x80, 0x00)),
+        ("hot_pink", Color32::from_rgb(0xFF, 0x69, 0xB4)),
+        ("orange_red", Color32::from_rgb(0xFF, 0x45, 0x00)),
+        ("red", Color32::from_rgb(0xFF, 0x00, 0x00)),
+        ("sea_green", Color32::from_rgb(0x2E, 0x8B, 0x57)),
+        ("spring_green", Color32::from_rgb(0x00, 0xFF, 0x7F)),
+        ("yellow_green", Color32::from_rgb(0x9A, 0xCD, 0x32)),
+    ];
+
+    pub fn display(self, ui: &mut egui::Ui) {
+        let app = self.app;
+        let size = Vec2::splat(ui.text_style_height(&egui::TextStyle::Body));
+
+        let login = app.twitch.user_name().to_string();
+        if let Some(image) = app
+            .user_map
+            .get(&login)
+            .map(|user| user.profile_image_url.clone())
+            .and_then(|url| app.cache.get_image(&url))
+        {
+            ui.add(image.as_paused_egui_image(size));
+        }
+
+        let identity = app.state.identity.as_ref();
+        let name = identity
+            .and_then(|identity| identity.display_name.as_deref())
+            .unwrap_or(&login)
+            .to_string();
+        let color = identity
+            .and_then(|identity| identity.color)
+            .map(|twitch_message::Color(r, g, b)| Color32::from_rgb(r, g, b));
+
+        let mut text = RichText::new(name);
+        if let Some(color) = color {
+            text = text.color(color);
+        }
+
+        let missing = app.token_check.missing_scopes(app.chat_read_only());
+        if !missing.is_empty() {
+            ui.label(
+                RichText::new("!")
+                    .strong()
+                    .color(ui.visuals().warn_fg_color),
+            )
+            .on_hover_text(format!(
+                "the token is missing {}, chat won't work without them",
+                missing.join(" and ")
+            ));
+        }
+
+        ui.menu_button(text, |ui| {
+            Self::display_connection(ui, app);
+            ui.separator();
+
+            ui.add_enabled_ui(app.state.identity.is_some(), |ui| {
+                ui.menu_button("Change color", |ui| {
+                    for (name, color) in Self::COLORS {
+                        ui.horizontal(|ui| {
+                            let (rect, _) = ui.allocate_exact_size(size, Sense::hover());
+                            ui.painter().rect_filled(rect, 2.0, color);
+                            if ui.button(name.replace('_', " ")).clicked() {
+                                app.change_color(name, color);
+                                ui.close_menu();
+                            }
+                        });
+                    }
+                })
+                .response
+                .on_disabled_hover_text("this needs a connection");
+            });
+
+            if ui
+                .button("Switch account")
+                .on_hover_text(format!(
+                    "put the other account's credentials in {}, then click this to use them",
+                    twitch::Config::ENV_FILES.join(" or ")
+                ))
+                .clicked()
+            {
+                app.reload_credentials(ui.ctx());
+                ui.close_menu();
+            }
+
+            if ui.button("Disconnect").clicked() {
+                app.disconnect(ui.ctx());
+                ui.close_menu();
+            }
+        });
+    }
+
+    fn display_connection(ui: &mut egui::Ui, app: &App) {
+        let status = match app.twitch.status() {
+            twitch::Status::NotConnected => "not connected",
+            twitch::Status::Connecting => "connecting",
+            twitch::Status::Connected => "connected",
+            twitch::Status::Reconnecting { .. } => "reconnecting",
+            twitch::Status::Contested => "disconnected by another session",
+            twitch::Status::InvalidCredentials => "login failed",
+        };
+
+        egui::Grid::new("identity-connection")
+            .num_columns(2)
+            .show(ui, |ui| {
+                ui.label("status");
+                ui.label(status);
+                ui.end_row();
+
+                ui.label("server");
+                ui.monospace(app.twitch.config().address())
+                    .on_hover_text(if app.twitch.config().tls {
+                        "connected over TLS"
+                    } else {
+                        "connected over plain TCP"
+                    });
+                ui.end_row();
+
+                if let Some(rtt) = app.twitch.latency() {
+                    ui.label("latency");
+                    ui.monospace(format!("{} ms", rtt.as_millis()));
+                    ui.end_row();
+                }
+
+                ui.label("login");
+                ui.monospace(app.twitch.user_name());
+                ui.end_row();
+
+                if let Some(identity) = &app.state.identity {
+                    ui.label("user id");
+                    if app.state.streamer_mode {
+                        ui.monospace("(hidden in streamer mode)");
+                    } else {
+                        ui.monospace(&identity.user_id);
+                    }
+                    ui.end_row();
+                }
+
+                ui.label("token");
+                Self::display_secret(ui, app.state.streamer_mode, app.twitch.token());
+                ui.end_row();
+
+                if let TokenCheck::Checked(info) = &app.token_check {
+                    ui.label("client id");
+                    Self::display_secret(ui, app.state.streamer_mode, &info.client_id);
+                    ui.end_row();
+                }
+
+                ui.label("scopes");
+                match &app.token_check {
+                    TokenCheck::Checking(..) => {
+                        ui.label("checking...");
+                    }
+                    TokenCheck::Failed(err) => {
+                        ui.colored_label(ui.visuals().warn_fg_color, err);
+                    }
+                    TokenCheck::Checked(info) => {
+                        ui.vertical(|ui| {
+                            for scope in info.scopes.iter().flatten() {
+                                ui.monospace(scope);
+                            }
+                            for scope in app.token_check.missing_scopes(app.chat_read_only()) {
+                                ui.label(
+                                    RichText::new(format!("{scope} (missing)"))
+                                        .monospace()
+                                        .color(ui.visuals().warn_fg_color),
+                                );
+                            }
+                        });
+                    }
+                }
+                ui.end_row();
+
+                if app.chat_read_only() {
+                    ui.label("mode");
+                    ui.label("read only").on_hover_text("nothing can be sent with this token");
+                    ui.end_row();
+                }
+
+                if let TokenCheck::Checked(info) = &app.token_check {
+                    if info.expires_in > 0 {
+                        ui.label("expires in");
+                        ui.monospace(format!("{} hours", info.expires_in / 3600));
+                        ui.end_row();
+                    }
+                }
+            });
+    }
+
+    // masked, and left out entirely in streamer mode since even the length says something
+    fn display_secret(ui: &mut egui::Ui, streamer_mode: bool, mut value: &str) {
+        if streamer_mode {
+            ui.monospace("(hidden in streamer mode)");
+            return;
+        }
+        ui.add(
+            egui::TextEdit::singleline(&mut value)
+                .password(true)
+                .font(egui::TextStyle::Monospace),
+        );
+    }
+}
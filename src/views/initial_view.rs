@@ -1,34 +1,115 @@
-use egui::{Align2, Area, CentralPanel, Vec2};
+use egui::{Align2, Area, CentralPanel, Color32, RichText, ScrollArea, Vec2};
 
-use crate::twitch;
+use crate::{
+    app::Theme,
+    helix,
+    state::{ChannelSearch, Suggestions},
+    twitch,
+    widgets::SearchResults,
+};
 
 pub struct InitialView<'a> {
     pub buffer: &'a mut String,
+    pub suggestions: &'a mut Suggestions,
     pub twitch: &'a twitch::Client,
+    pub helix: &'a helix::Client,
+    pub theme: &'a Theme,
 }
 
 impl<'a> InitialView<'a> {
     pub fn display(self, ctx: &egui::Context) {
+        if self.suggestions.poll(self.buffer, self.helix) {
+            ctx.request_repaint_after(ChannelSearch::SEARCH_DELAY);
+        }
+
+        let mut join = None;
+
         Area::new(egui::Id::new("initial-join"))
             .anchor(Align2::CENTER_CENTER, Vec2::ZERO)
             .show(ctx, |ui| {
                 ui.horizontal(|ui| {
                     let resp = ui.text_edit_singleline(self.buffer);
-                    if resp.lost_focus()
-                        || ui.input(|i| i.key_pressed(egui::Key::Enter))
+                    // clicking a suggestion takes the focus away too, so only enter counts
+                    if (resp.lost_focus() && ui.input(|i| i.key_pressed(egui::Key::Enter)))
                         || ui.button("Join").clicked()
                     {
                         let buf = std::mem::take(self.buffer);
                         let buf = buf.trim();
                         if !buf.is_empty() {
-                            self.twitch.writer().join(buf);
+                            join.replace(buf.to_string());
                         }
                     }
                     resp.request_focus();
                 });
+
+                ScrollArea::vertical().max_height(300.0).show(ui, |ui| {
+                    if !self.suggestions.search.results.is_empty() {
+                        ui.label(RichText::new("search results").weak());
+                        let results = SearchResults {
+                            search: &self.suggestions.search,
+                            live: self.theme.live(),
+                        };
+                        if let Some(channel) = results.display(ui) {
+                            join.replace(channel);
+                        }
+                        ui.separator();
+                    }
+
+                    if !self.suggestions.followed.is_empty() {
+                        ui.label(RichText::new("live now").weak());
+                        for stream in &self.suggestions.followed {
+                            let resp = ui
+                                .horizontal(|ui| {
+                                    let live = RichText::new("live").small();
+                                    ui.label(live.color(self.theme.live()));
+                                    let resp = ui.link(&stream.user_login);
+                                    let viewers = format!("{} viewers", stream.viewer_count);
+                                    ui.label(RichText::new(viewers).small().weak());
+                                    resp
+                                })
+                                .inner
+                                .on_hover_text(format!("{}\n{}", stream.title, stream.game_name));
+                            if resp.clicked() {
+                                join.replace(stream.user_login.clone());
+                            }
+                        }
+                        ui.separator();
+                    }
+
+                    if !self.suggestions.recent.is_empty() {
+                        ui.label(RichText::new("recent channels").weak());
+                        for recent in &self.suggestions.recent {
+                            let live = self
+                                .suggestions
+                                .is_live(&recent.channel)
+                                .then_some(self.theme.live());
+                            if Self::suggestion(ui, &recent.channel, live).clicked() {
+                                join.replace(recent.channel.to_string());
+                            }
+                        }
+                    }
+                });
             });
 
+        if let Some(channel) = join {
+            if let Err(err) = self.twitch.writer().join(&channel) {
+                log!("cannot join {channel}: {err}");
+            }
+        }
+
         // fill in the window
         CentralPanel::default().show(ctx, |_ui| {});
     }
+
+    fn suggestion(ui: &mut egui::Ui, channel: &str, live: Option<Color32>) -> egui::Response {
+        ui.horizontal(|ui| {
+            let text = RichText::new("live").small();
+            ui.label(match live {
+                Some(color) => text.color(color),
+                None => text.weak(),
+            });
+            ui.link(channel)
+        })
+        .inner
+    }
 }
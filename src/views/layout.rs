@@ -0,0 +1,240 @@
+use egui::{vec2, Rect};
+
+use crate::twitch::ChannelName;
+
+/// Which way a split divides its space
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum SplitDirection {
+    /// Side by side
+    Horizontal,
+    /// One above the other
+    Vertical,
+}
+
+enum Node {
+    Pane(Option<ChannelName>),
+    Split {
+        direction: SplitDirection,
+        children: Vec<Node>,
+    },
+}
+
+/// The chat area divided up into panes, each showing a channel
+///
+/// Panes are numbered in the order they're shown. The focused one follows the active tab,
+/// so the input always goes to the channel in it
+pub struct SplitLayout {
+    root: Node,
+    focused: usize,
+}
+
+impl Default for SplitLayout {
+    fn default() -> Self {
+        Self {
+            root: Node::Pane(None),
+            focused: 0,
+        }
+    }
+}
+
+impl SplitLayout {
+    pub fn pane_count(&self) -> usize {
+        self.channels().count()
+    }
+
+    pub fn is_split(&self) -> bool {
+        matches!(self.root, Node::Split { .. })
+    }
+
+    pub const fn focused(&self) -> usize {
+        self.focused
+    }
+
+    pub fn focus(&mut self, pane: usize) {
+        if pane < self.pane_count() {
+            self.focused = pane;
+        }
+    }
+
+    /// What each pane is showing, in order
+    pub fn channels(&self) -> impl Iterator<Item = Option<&ChannelName>> {
+        let mut out = vec![];
+        Self::leaves(&self.root, &mut out);
+        out.into_iter()
+    }
+
+    pub fn channel(&self, pane: usize) -> Option<&ChannelName> {
+        self.channels().nth(pane).flatten()
+    }
+
+    /// Show the active channel in the focused pane, and empty the panes of closed channels
+    pub fn sync(&mut self, active: &ChannelName, exists: impl Fn(&ChannelName) -> bool) {
+        let mut index = 0;
+        Self::for_each(&mut self.root, &mut |pane| {
+            if index == self.focused {
+                pane.replace(active.clone());
+            } else if pane.as_ref().map_or(false, |channel| !exists(channel)) {
+                pane.take();
+            }
+            index += 1;
+        });
+    }
+
+    /// Open a channel in a new pane after the focused one, and focus it
+    pub fn split(&mut self, channel: ChannelName, direction: SplitDirection) {
+        let mut target = self.focused;
+        if Self::insert(&mut self.root, &mut target, &channel, direction) {
+            self.focused += 1;
+        }
+    }
+
+    /// Close a pane, there's always at least one left
+    pub fn close(&mut self, pane: usize) {
+        let mut target = pane;
+        if !Self::remove(&mut self.root, &mut target) {
+            return;
+        }
+        if self.focused > pane || self.focused == self.pane_count() {
+            self.focused = self.focused.saturating_sub(1);
+        }
+    }
+
+    /// Each pane with its share of `rect`, splits divide their space evenly
+    pub fn panes(&self, rect: Rect, gap: f32) -> Vec<(Option<&ChannelName>, Rect)> {
+        let mut out = vec![];
+        Self::layout(&self.root, rect, gap, &mut out);
+        out
+    }
+
+    fn leaves<'a>(node: &'a Node, out: &mut Vec<Option<&'a ChannelName>>) {
+        match node {
+            Node::Pane(channel) => out.push(channel.as_ref()),
+            Node::Split { children, .. } => {
+                for child in children {
+                    Self::leaves(child, out)
+                }
+            }
+        }
+    }
+
+    fn for_each(node: &mut Node, func: &mut impl FnMut(&mut Option<ChannelName>)) {
+        match node {
+            Node::Pane(channel) => func(channel),
+            Node::Split { children, .. } => {
+                for child in children {
+                    Self::for_each(child, func)
+                }
+            }
+        }
+    }
+
+    // `target` counts down the panes, the new one goes in once it reaches the right one
+    fn insert(
+        node: &mut Node,
+        target: &mut usize,
+        channel: &ChannelName,
+        direction: SplitDirection,
+    ) -> bool {
+        match node {
+            Node::Pane(..) if *target == 0 => {
+                let old = std::mem::replace(node, Node::Pane(None));
+                *node = Node::Split {
+                    direction,
+                    children: vec![old, Node::Pane(Some(channel.clone()))],
+                };
+                true
+            }
+            Node::Pane(..) => {
+                *target -= 1;
+                false
+            }
+            // a split going the same way gets another pane, rather than nesting
+            Node::Split {
+                direction: existing,
+                children,
+            } => {
+                for i in 0..children.len() {
+                    if *existing == direction
+                        && *target == 0
+                        && matches!(children[i], Node::Pane(..))
+                    {
+                        children.insert(i + 1, Node::Pane(Some(channel.clone())));
+                        return true;
+                    }
+                    if Self::insert(&mut children[i], target, channel, direction) {
+                        return true;
+                    }
+                }
+                false
+            }
+        }
+    }
+
+    fn remove(node: &mut Node, target: &mut usize) -> bool {
+        let Node::Split { children, .. } = node else {
+            return false;
+        };
+
+        let mut removed = false;
+        for i in 0..children.len() {
+            let pane = matches!(children[i], Node::Pane(..));
+            if pane && *target == 0 {
+                children.remove(i);
+                removed = true;
+                break;
+            }
+            if pane {
+                *target -= 1;
+            } else if Self::remove(&mut children[i], target) {
+                removed = true;
+                break;
+            }
+        }
+
+        // a split of one is just that one
+        if removed && children.len() == 1 {
+            *node = children.pop().expect("one child");
+        }
+        removed
+    }
+
+    fn layout<'a>(
+        node: &'a Node,
+        rect: Rect,
+        gap: f32,
+        out: &mut Vec<(Option<&'a ChannelName>, Rect)>,
+    ) {
+        let (direction, children) = match node {
+            Node::Pane(channel) => {
+                out.push((channel.as_ref(), rect));
+                return;
+            }
+            Node::Split {
+                direction,
+                children,
+            } => (direction, children),
+        };
+
+        let n = children.len() as f32;
+        for (i, child) in children.iter().enumerate() {
+            let i = i as f32;
+            let rect = match direction {
+                SplitDirection::Horizontal => {
+                    let width = (rect.width() - gap * (n - 1.0)) / n;
+                    Rect::from_min_size(
+                        rect.min + vec2(i * (width + gap), 0.0),
+                        vec2(width, rect.height()),
+                    )
+                }
+                SplitDirection::Vertical => {
+                    let height = (rect.height() - gap * (n - 1.0)) / n;
+                    Rect::from_min_size(
+                        rect.min + vec2(0.0, i * (height + gap)),
+                        vec2(rect.width(), height),
+                    )
+                }
+            };
+            Self::layout(child, rect, gap, out);
+        }
+    }
+}
@@ -1,6 +1,6 @@
 use egui::{
-    vec2, Button, CentralPanel, Color32, Grid, Key, Label, Layout, RichText, Rounding, ScrollArea,
-    Sense, TextEdit, TextStyle, TopBottomPanel, Vec2,
+    vec2, Button, CentralPanel, Color32, Grid, Key, Label, Layout, Modifiers, Rect, RichText,
+    Rounding, ScrollArea, Sense, Shape, Spinner, TextEdit, TextStyle, TopBottomPanel, Vec2,
 };
 use hashbrown::HashMap;
 use twitch_message::{
@@ -8,279 +8,2074 @@ use twitch_message::{
     messages::Privmsg,
     Tags,
 };
+use uuid::Uuid;
 
 use crate::{
-    app::App,
-    input::Input,
-    runtime::{EmoteMap, ImageCache},
-    state::{MessageOpts, Span},
+    app::{App, ImportMode, LiveShape, Palette},
+    automod::Automod,
+    input::{Command, Input},
+    runtime::{EmoteMap, GameMap, ImageCache},
+    state::{
+        Channel, ChannelSearch, ChatterinoImport, InputPosition, MessageOpts, MessageSearch,
+        Notice, Permalink, QuietHours, Span, TabBarPosition, TabOrder,
+    },
+    twitch::{self, ChannelName},
+    views::{
+        EmoteBrowser, IdentityChip, SplitDirection, TokenCheck, WatchlistAction, WatchlistView,
+    },
+    widgets::{BrokenImage, SearchResults},
 };
 
+enum PinAction {
+    Pin(Uuid),
+    Unpin(Uuid),
+    Jump(Uuid),
+}
+
+// what was done in a pane, it's applied once the panes are drawn
+#[derive(Default)]
+struct PaneActions {
+    pin: Option<PinAction>,
+    open_link: Option<Permalink>,
+    find_user: Option<(String, String)>,
+    open_card: Option<(String, String)>,
+    history_seen: bool,
+    edit: Option<Uuid>,
+    inspect: Option<Uuid>,
+    bookmark: Option<Uuid>,
+}
+
 pub struct MainView<'a> {
     pub app: &'a mut App,
 }
 
-impl<'a> MainView<'a> {
-    const INACTIVE_GAMMA: f32 = 0.6;
+impl<'a> MainView<'a> {
+    const INACTIVE_GAMMA: f32 = 0.6;
+    const SLOW_CONNECTION: std::time::Duration = std::time::Duration::from_millis(500);
+    const FLASH_INTERVAL: f32 = 0.5;
+    // the input grows with the draft up to this, then it scrolls
+    const MAX_INPUT_LINES: usize = 6;
+    // these are the ids of the list editors in the settings menu
+    const KEYWORDS_ID: &str = "highlight-keywords";
+    const IGNORED_ID: &str = "ignored-users";
+    const NICKNAMES_ID: &str = "nicknames";
+    // in characters, so a quote leaves room for something to be said about it
+    const MAX_QUOTE: usize = 200;
+    const LOOKUP_DELAY: f64 = 0.5;
+    const PANE_GAP: f32 = 4.0;
+    // how tall the box art is in the game popup
+    const BOX_ART_POPUP: f32 = 160.0;
+
+    pub fn display(self, ctx: &egui::Context) {
+        let app = &mut *self.app;
+        let active = app.state.channels[app.state.active].name.clone();
+        app.layout.sync(&active, |name| {
+            app.state.channels.iter().any(|c| c.name == *name)
+        });
+
+        let visible = app
+            .layout
+            .channels()
+            .flatten()
+            .filter_map(|name| app.state.channels.iter().position(|c| c.name == *name))
+            .collect::<Vec<_>>();
+        for index in visible {
+            app.ensure_history(index);
+            // looking at the channel is enough to acknowledge it
+            app.state.channels[index].mark_read();
+        }
+
+        let active = &self.app.state.channels[self.app.state.active].name;
+        self.app.cache.set_owner(Some(active.as_str()));
+
+        // the panel added first is the one closest to the edge of the window
+        let state = &self.app.state;
+        let input_first = matches!(
+            (state.input_position, state.tab_bar_position),
+            (InputPosition::Bottom, TabBarPosition::AboveInput)
+                | (InputPosition::Top, TabBarPosition::BelowInput)
+        );
+        if input_first {
+            Self::display_input(ctx, self.app);
+            Self::display_tab_bar(ctx, self.app);
+        } else {
+            Self::display_tab_bar(ctx, self.app);
+            Self::display_input(ctx, self.app);
+        }
+        Self::display_topic_bar(ctx, self.app);
+        Self::display_watchlist(ctx, self.app);
+        Self::display_message_search(ctx, self.app);
+
+        let mut actions = vec![];
+        let (mut focus, mut close) = (None, None);
+
+        CentralPanel::default().show(ctx, |ui| {
+            let app = &mut *self.app;
+            let split = app.layout.is_split();
+            let panes = app
+                .layout
+                .panes(ui.available_rect_before_wrap(), Self::PANE_GAP)
+                .into_iter()
+                .map(|(name, rect)| {
+                    let index = name
+                        .and_then(|name| app.state.channels.iter().position(|c| c.name == *name));
+                    (index, rect)
+                })
+                .collect::<Vec<_>>();
+
+            for (pane, (index, rect)) in panes.into_iter().enumerate() {
+                // clicking anywhere in a pane is enough to type into it
+                let pressed = ui.input(|i| {
+                    i.pointer.primary_pressed()
+                        && i.pointer
+                            .interact_pos()
+                            .map_or(false, |pos| rect.contains(pos))
+                });
+                if split && pressed {
+                    focus.replace(pane);
+                }
+
+                ui.push_id(pane, |ui| {
+                    let mut ui = ui.child_ui(rect, *ui.layout());
+                    ui.set_clip_rect(rect.intersect(ui.clip_rect()));
+
+                    if split {
+                        ui.horizontal(|ui| {
+                            let name =
+                                index.map_or("(empty)", |i| app.state.channels[i].name.as_str());
+                            ui.label(RichText::new(name).small().strong());
+                            ui.with_layout(Layout::right_to_left(egui::Align::Center), |ui| {
+                                if ui
+                                    .small_button("x")
+                                    .on_hover_text("close this pane")
+                                    .clicked()
+                                {
+                                    close.replace(pane);
+                                }
+                            });
+                        });
+                        ui.separator();
+                    }
+
+                    match index {
+                        Some(index) => {
+                            let mut pane_actions = PaneActions::default();
+                            Self::display_channel(&mut ui, app, index, &mut pane_actions);
+                            actions.push((index, pane_actions));
+                        }
+                        None => {
+                            ui.weak("pick a tab to show it here");
+                        }
+                    }
+                });
+
+                if split && pane == app.layout.focused() {
+                    ui.painter().rect_stroke(
+                        rect.expand(Self::PANE_GAP * 0.5),
+                        Rounding::none(),
+                        (1.0, app.theme.accent()),
+                    );
+                }
+            }
+        });
+
+        for (index, actions) in actions {
+            Self::apply_pane_actions(self.app, index, actions);
+        }
+
+        if let Some(pane) = close {
+            self.app.layout.close(pane);
+            Self::focus_pane(self.app, self.app.layout.focused());
+        } else if let Some(pane) = focus {
+            Self::focus_pane(self.app, pane);
+        }
+    }
+
+    // the active tab follows the focused pane, so that's where the input goes
+    fn focus_pane(app: &mut App, pane: usize) {
+        app.layout.focus(pane);
+        let Some(name) = app.layout.channel(pane) else {
+            return;
+        };
+        if let Some(index) = app.state.channels.iter().position(|c| c.name == *name) {
+            if index != app.state.active {
+                app.state.active = index;
+                app.send_to.take();
+            }
+        }
+    }
+
+    fn display_channel(ui: &mut egui::Ui, app: &mut App, index: usize, actions: &mut PaneActions) {
+        let jump_to = app.state.channels[index].jump_to.take();
+        let prepended = std::mem::take(&mut app.state.channels[index].prepended);
+        let channel = &app.state.channels[index];
+
+        let fid = TextStyle::Body.resolve(ui.style());
+        let (w, h) = ui.fonts(|f| (f.glyph_width(&fid, ' '), f.row_height(&fid)));
+
+        Self::display_pinned(ui, channel, &mut actions.pin);
+
+        // the offset is only looked up once a frame, rather than for every message
+        let timestamps = app.state.preferences.timestamps.then_some(app.local_offset);
+
+        let our_name = app.state.identity.as_ref().map(|identity| &*identity.name);
+        let search = (index == app.state.active).then_some(&app.message_search);
+        // who sent them is still shown, just not what they said
+        let hidden = app.state.streamer_mode && channel.is_whispers();
+
+        // only the messages in view are laid out, the rest are skipped over by their last height
+        let mut rows = 0;
+        let output = ScrollArea::vertical()
+            .id_source("messages")
+            .drag_to_scroll(false)
+            .stick_to_bottom(true)
+            .show_viewport(ui, |ui, viewport| {
+                let width = ui.available_width();
+                let spacing = ui.spacing().item_spacing.y;
+                let offsets = Self::row_offsets(channel, width, h + spacing);
+                let header = if channel.is_loading_older() {
+                    ui.spacing().interact_size.y + spacing
+                } else {
+                    0.0
+                };
+                let total = header + offsets.last().copied().unwrap_or_default();
+                ui.set_width(width);
+                ui.set_height(total);
+
+                let (origin, x_range) = (ui.max_rect().top(), ui.max_rect().x_range());
+                let row_rect = |i: usize| {
+                    let y = origin + header;
+                    egui::Rect::from_x_y_ranges(x_range, y + offsets[i]..=y + offsets[i + 1])
+                };
+
+                if header > 0.0 && viewport.min.y < header {
+                    let rect = egui::Rect::from_x_y_ranges(x_range, origin..=origin + header);
+                    ui.put(rect, Spinner::new());
+                }
+
+                // there's nothing to divide when nothing new came in yet
+                let marker = channel.marker.filter(|&marker| {
+                    channel.messages.iter().next_back().and_then(|msg| msg.id) != Some(marker)
+                });
+                let position = |id| channel.messages.iter().position(|msg| msg.id == Some(id));
+                // only scrolling the divider off the top counts, not switching away
+                if let Some(i) = marker.and_then(position) {
+                    if header + offsets[i + 1] < viewport.min.y {
+                        actions.history_seen = true;
+                    }
+                }
+                // it's likely not laid out, so this goes by where it would be
+                if let Some(i) = jump_to.and_then(position) {
+                    ui.scroll_to_rect(row_rect(i), Some(egui::Align::Center));
+                }
+                // the older page went in above what was being looked at, so the view follows it
+                if let Some(&shifted) = offsets.get(prepended).filter(|_| prepended > 0) {
+                    ui.scroll_with_delta(vec2(0.0, -shifted));
+                }
+
+                let first = offsets
+                    .partition_point(|&y| header + y <= viewport.min.y)
+                    .saturating_sub(1);
+                let bottom = origin + viewport.max.y;
+                let rect = egui::Rect::from_x_y_ranges(
+                    x_range,
+                    origin + header + offsets[first]..=origin + total.max(viewport.max.y),
+                );
+                ui.allocate_ui_at_rect(rect, |ui| {
+                    // each row is measured once the next one starts
+                    let mut measuring: Option<(&crate::state::Message, f32)> = None;
+                    for msg in channel.messages.iter().skip(first) {
+                        let top = ui.cursor().top();
+                        if let Some((msg, start)) = measuring.take() {
+                            msg.height.set(Some((width, top - start)));
+                        }
+                        if top > bottom {
+                            break;
+                        }
+                        measuring.replace((msg, top));
+                        rows += 1;
+
+                        let notice = msg
+                            .notice
+                            .as_ref()
+                            .map(|notice| Self::display_notice(ui, notice, app.theme.accent()));
+                        // raids and gift bombs don't come with a message
+                        let bare = notice.is_some() && msg.spans.is_empty();
+
+                        // put down first so it ends up behind the row, once its size is known
+                        let ours =
+                            our_name.map_or(false, |name| msg.sender.eq_ignore_ascii_case(name));
+                        let found = search.filter(|search| search.matches(msg));
+                        let background = if let Some(search) = found {
+                            let current = search.current.is_some() && search.current == msg.id;
+                            let alpha = if current { 0.5 } else { 0.2 };
+                            Some(app.theme.accent().linear_multiply(alpha))
+                        } else if msg.highlighted {
+                            Some(app.theme.mention())
+                        } else {
+                            app.theme.own_message.filter(|_| ours)
+                        }
+                        .map(|color| (ui.painter().add(Shape::Noop), color));
+
+                        let resp = Self::message_row(ui, bare, |ui| {
+                            ui.scope(|ui| {
+                                ui.spacing_mut().item_spacing.x = 1.0;
+                                // TODO fix this alignment
+                                ui.with_layout(Layout::left_to_right(egui::Align::Center), |ui| {
+                                    if let Some(offset) = timestamps {
+                                        Self::display_timestamp(ui, msg, offset);
+                                    }
+
+                                    if let Some((name, version)) = msg.badges.first() {
+                                        if let Some(url) = app
+                                            .emote_map
+                                            .get_badge_url(name.as_str(), version.as_str())
+                                        {
+                                            if let Some(mut image) = Self::image_for(
+                                                ui,
+                                                &mut app.cache,
+                                                url,
+                                                Vec2::splat(h * 0.6),
+                                            ) {
+                                                if msg.opts.old {
+                                                    image = image.tint(
+                                                        Color32::WHITE
+                                                            .gamma_multiply(Self::INACTIVE_GAMMA),
+                                                    )
+                                                }
+
+                                                ui.add(image).on_hover_text(name.as_str());
+                                            } else {
+                                                Self::display_broken_image(
+                                                    ui,
+                                                    &mut app.cache,
+                                                    url,
+                                                    Vec2::splat(h * 0.6),
+                                                );
+                                            }
+                                        }
+                                    }
+
+                                    let color = if msg.opts.old {
+                                        msg.color.gamma_multiply(Self::INACTIVE_GAMMA)
+                                    } else {
+                                        msg.color
+                                    };
+                                    let nickname = app.state.nicknames.get(&msg.sender);
+                                    let name = nickname.unwrap_or(&msg.sender);
+                                    let mut resp = ui.add(
+                                        Label::new(RichText::new(name).color(color))
+                                            .sense(Sense::click()),
+                                    );
+                                    if nickname.is_some() {
+                                        resp = resp.on_hover_text(&msg.sender);
+                                    }
+
+                                    if let Some(user_id) =
+                                        msg.user_id.as_ref().filter(|_| resp.clicked())
+                                    {
+                                        actions
+                                            .open_card
+                                            .replace((msg.sender.clone(), user_id.clone()));
+                                    }
+
+                                    if let Some(id) = msg.id {
+                                        resp.context_menu(|ui| {
+                                            if msg.opts.local && ui.button("Edit message").clicked()
+                                            {
+                                                actions.edit.replace(id);
+                                                ui.close_menu();
+                                            }
+
+                                            if channel.is_pinned(id) {
+                                                if ui.button("Unpin message").clicked() {
+                                                    actions.pin.replace(PinAction::Unpin(id));
+                                                    ui.close_menu();
+                                                }
+                                            } else if ui.button("Pin message").clicked() {
+                                                actions.pin.replace(PinAction::Pin(id));
+                                                ui.close_menu();
+                                            }
+
+                                            if let Some(user_id) = &msg.user_id {
+                                                if ui.button("Find user").clicked() {
+                                                    actions.find_user.replace((
+                                                        msg.sender.clone(),
+                                                        user_id.clone(),
+                                                    ));
+                                                    ui.close_menu();
+                                                }
+                                            }
+
+                                            let bookmarked = app.bookmarked.contains(&id);
+                                            let resp = ui.button(if bookmarked {
+                                                "Remove bookmark"
+                                            } else {
+                                                "Bookmark message"
+                                            });
+                                            let size = resp.rect.height() * 0.5;
+                                            Self::paint_star(
+                                                ui.painter(),
+                                                resp.rect.right_center() - vec2(size, 0.0),
+                                                size * 0.5,
+                                                if bookmarked {
+                                                    app.theme.marker()
+                                                } else {
+                                                    ui.visuals().weak_text_color()
+                                                },
+                                            );
+                                            if resp.clicked() {
+                                                actions.bookmark.replace(id);
+                                                ui.close_menu();
+                                            }
+
+                                            if ui.button("Copy message link").clicked() {
+                                                let link = Permalink::new(&channel.name, id);
+                                                ui.output_mut(|o| o.copied_text = link.to_string());
+                                                ui.close_menu();
+                                            }
+
+                                            ui.separator();
+                                            if ui.button("Inspect raw message").clicked() {
+                                                actions.inspect.replace(id);
+                                                ui.close_menu();
+                                            }
+                                        });
+                                    }
+                                });
+                            });
+
+                            ui.scope(|ui| {
+                                ui.spacing_mut().item_spacing.x = w;
+                                if hidden {
+                                    ui.weak("(hidden in streamer mode)");
+                                    return;
+                                }
+
+                                Self::display_fragments(
+                                    ui,
+                                    Vec2::splat(h),
+                                    msg,
+                                    &mut app.emote_map,
+                                    &mut app.cache,
+                                    &mut actions.open_link,
+                                )
+                            });
+                        });
+                        let Some(resp) = resp.or(notice) else {
+                            continue;
+                        };
+
+                        if let Some((background, color)) = background {
+                            ui.painter().set(
+                                background,
+                                Shape::rect_filled(resp.rect, Rounding::none(), color),
+                            );
+                        }
+
+                        if jump_to.is_some() && jump_to == msg.id {
+                            resp.scroll_to_me(Some(egui::Align::Center));
+                        }
+
+                        if let Some(marker) = marker {
+                            if Some(marker) == msg.id {
+                                let color = app.theme.marker().gamma_multiply(Self::INACTIVE_GAMMA);
+
+                                ui.horizontal(|ui| {
+                                    let label = RichText::new("new messages since last session")
+                                        .small()
+                                        .color(color);
+                                    let label = ui.label(label);
+
+                                    let rect = ui.available_rect_before_wrap();
+                                    let y = label.rect.center().y;
+                                    ui.painter().rect_filled(
+                                        egui::Rect::from_x_y_ranges(
+                                            rect.left()..=rect.right() - 2.0,
+                                            y..=y + 1.0,
+                                        ),
+                                        Rounding::none(),
+                                        color,
+                                    );
+                                });
+                            }
+                        }
+                    }
+                    if let Some((msg, start)) = measuring {
+                        msg.height.set(Some((width, ui.cursor().top() - start)));
+                    }
+                });
+            });
+        app.frame_stats.add_rows(rows);
+
+        // scrolling all the way up loads the page before it
+        let scrollable = output.content_size.y > output.inner_rect.height();
+        if scrollable && output.state.offset.y <= 0.0 {
+            let db = app.history_path();
+            app.state.channels[index].request_older_history(db);
+        }
+    }
+
+    fn apply_pane_actions(app: &mut App, index: usize, actions: PaneActions) {
+        let channel = &mut app.state.channels[index];
+        if actions.history_seen {
+            channel.age_out_history();
+        }
+
+        match actions.pin {
+            Some(PinAction::Pin(id)) => channel.pin(id),
+            Some(PinAction::Unpin(id)) => channel.unpin(id),
+            Some(PinAction::Jump(id)) => channel.jump_to = Some(id),
+            None => {}
+        }
+
+        if let Some(link) = actions.open_link {
+            app.open_link(link);
+        }
+
+        if let Some((login, user_id)) = actions.find_user {
+            app.find_user(&login, &user_id);
+        }
+
+        if let Some((login, user_id)) = actions.open_card {
+            app.open_user_card(&login, &user_id);
+        }
+
+        if let Some(id) = actions.edit {
+            app.edit_message(index, id);
+        }
+
+        if let Some(id) = actions.inspect {
+            app.inspect_message(id);
+        }
+
+        if let Some(id) = actions.bookmark {
+            app.toggle_bookmark(id);
+        }
+    }
+
+    fn display_timestamp(
+        ui: &mut egui::Ui,
+        msg: &crate::state::Message,
+        offset: time::UtcOffset,
+    ) {
+        let format = time::macros::format_description!("[hour]:[minute]");
+        let when = msg.received.to_offset(offset);
+        ui.label(
+            RichText::new(when.format(format).unwrap_or_default())
+                .small()
+                .weak(),
+        )
+        .on_hover_text(when.date().to_string());
+        ui.add_space(4.0);
+    }
+
+    // where each message starts, going by how tall it was the last time it was laid out at this
+    // width. the ones that haven't been are guessed from the ones that have
+    fn row_offsets(channel: &Channel, width: f32, fallback: f32) -> Vec<f32> {
+        let measured = |msg: &crate::state::Message| {
+            msg.height
+                .get()
+                .filter(|&(w, _)| w == width)
+                .map(|(_, h)| h)
+        };
+        let (sum, count) = channel
+            .messages
+            .iter()
+            .filter_map(measured)
+            .fold((0.0, 0), |(sum, count), h| (sum + h, count + 1));
+        let guess = if count == 0 {
+            fallback
+        } else {
+            sum / count as f32
+        };
+
+        let ends = channel.messages.iter().scan(0.0, |y, msg| {
+            *y += measured(msg).unwrap_or(guess);
+            Some(*y)
+        });
+        std::iter::once(0.0).chain(ends).collect()
+    }
+
+    fn message_row(
+        ui: &mut egui::Ui,
+        skip: bool,
+        add_contents: impl FnOnce(&mut egui::Ui),
+    ) -> Option<egui::Response> {
+        (!skip).then(|| ui.horizontal_wrapped(add_contents).response)
+    }
+
+    // a stripe down the side sets these apart from the chat around them
+    fn display_notice(ui: &mut egui::Ui, notice: &Notice, color: Color32) -> egui::Response {
+        let resp = ui
+            .horizontal_wrapped(|ui| {
+                ui.add_space(6.0);
+                ui.label(
+                    RichText::new(notice.kind.label())
+                        .small()
+                        .strong()
+                        .color(color),
+                );
+                ui.label(RichText::new(&notice.text).italics().color(color));
+            })
+            .response;
+
+        let rect = resp.rect;
+        ui.painter().rect_filled(
+            egui::Rect::from_x_y_ranges(rect.left()..=rect.left() + 2.0, rect.y_range()),
+            Rounding::none(),
+            color,
+        );
+        resp
+    }
+
+    fn display_pinned(ui: &mut egui::Ui, channel: &Channel, pin_action: &mut Option<PinAction>) {
+        if channel.pinned.is_empty() {
+            return;
+        }
+
+        for pin in &channel.pinned {
+            ui.horizontal_wrapped(|ui| {
+                if ui.small_button("unpin").clicked() {
+                    pin_action.replace(PinAction::Unpin(pin.id));
+                }
+
+                let exists = channel.messages.iter().any(|msg| msg.id == Some(pin.id));
+                if ui
+                    .add_enabled(exists, Button::new("jump").small())
+                    .on_disabled_hover_text("this message is no longer in the buffer")
+                    .clicked()
+                {
+                    pin_action.replace(PinAction::Jump(pin.id));
+                }
+
+                ui.label(RichText::new(&pin.sender).color(pin.color));
+                ui.label(&pin.data);
+            });
+        }
+
+        ui.separator();
+    }
+
+    fn display_input(ctx: &egui::Context, app: &mut App) {
+        let style = ctx.style();
+
+        let fid = TextStyle::Body.resolve(&style);
+        let height = ctx.fonts(|f| f.row_height(&fid));
+
+        Self::panel("input", app.state.input_position == InputPosition::Top)
+            .show_separator_line(true)
+            .show(ctx, |ui| {
+                let is_empty = app.state.channels.is_empty();
+                let read_only_token = app.chat_read_only();
+                let read_only = read_only_token
+                    || (!is_empty && app.state.channels[app.state.active].read_only);
+                let reply = (!is_empty && app.state.channels[app.send_target()].is_whispers())
+                    .then(|| match &app.state.last_whisper {
+                        Some(user) => format!("reply to {user}"),
+                        None => String::from("/w user message"),
+                    });
+                let hint = match () {
+                    _ if read_only_token => "read only, the token can't send messages",
+                    _ if read_only => "read only",
+                    _ if !is_empty && Self::is_emote_only(app) => "emote only",
+                    _ => reply.as_deref().unwrap_or_default(),
+                };
+
+                // tab would move the focus away otherwise
+                if !is_empty
+                    && !read_only
+                    && ui.input_mut(|i| i.consume_key(Modifiers::NONE, Key::Tab))
+                {
+                    Self::complete_input(ctx, app);
+                }
+
+                if !is_empty && !read_only {
+                    Self::recall_input(ctx, app);
+                }
+
+                // shift+enter is left to the text edit, which starts a new line
+                let id = egui::Id::new("input_buffer").with(app.state.active);
+                let send = !read_only
+                    && ui.memory(|mem| mem.has_focus(id))
+                    && ui.input_mut(|i| i.consume_key(Modifiers::NONE, Key::Enter));
+
+                if !is_empty {
+                    Self::display_quote_preview(ui, app);
+                }
+
+                let resp = ui
+                    .horizontal(|ui| {
+                        if !is_empty && app.state.channels.len() > 1 {
+                            Self::display_send_to(ui, app);
+                        }
+
+                        let default = "";
+                        let (mut a, b);
+                        let buf: &mut dyn egui::TextBuffer = if is_empty {
+                            a = default;
+                            &mut a as _
+                        } else {
+                            b = &mut app.state.channels[app.state.active].buffer;
+                            b as _
+                        };
+
+                        let edit = TextEdit::multiline(buf)
+                            // TODO this should use the buffer name
+                            .id(id)
+                            .font(egui::TextStyle::Body)
+                            .frame(false)
+                            .margin(vec2(0.0, 1.0))
+                            .desired_width(f32::INFINITY)
+                            .desired_rows(1)
+                            .interactive(!read_only)
+                            .lock_focus(true)
+                            .hint_text(hint);
+
+                        ScrollArea::vertical()
+                            .max_height(height * Self::MAX_INPUT_LINES as f32)
+                            .show(ui, |ui| ui.add(edit))
+                            .inner
+                    })
+                    .inner;
+
+                if let Some(error) = Self::input_error(ctx, app) {
+                    ui.label(
+                        RichText::new(error)
+                            .small()
+                            .color(ui.visuals().warn_fg_color),
+                    );
+                }
+
+                Self::display_join_search(ctx, app, resp.rect.left_bottom(), is_empty);
+                Self::display_command_palette(ctx, app, resp.rect.left_bottom(), is_empty);
+                Self::display_emote_completions(ctx, app, resp.rect.left_bottom(), is_empty);
+                Self::display_completer(ctx, app, resp.rect.left_bottom(), is_empty);
+
+                'ret: {
+                    if send {
+                        let buf = std::mem::take(&mut app.state.channels[app.state.active].buffer);
+
+                        let buf = buf.trim();
+                        if buf.is_empty() {
+                            break 'ret;
+                        }
+                        app.state.channels[app.state.active].input_history.push(buf);
+
+                        match Input::parse(buf) {
+                            // keep it around so the name can be fixed
+                            Input::Join { channels }
+                                if channels
+                                    .iter()
+                                    .any(|c| app.user_map.is_missing(&c.to_lowercase())) =>
+                            {
+                                app.state.channels[app.state.active].buffer = buf.to_string();
+                            }
+                            Input::Join { channels } => {
+                                if let Err(err) = app.twitch.writer().join_many(channels) {
+                                    Self::write_failed(app, buf, err);
+                                }
+                            }
+                            Input::Timeout {
+                                user,
+                                duration,
+                                reason,
+                            } => {
+                                app.timeout_user(user, duration, reason);
+                            }
+                            Input::Part { channel } => {
+                                if let Err(err) = app.twitch.writer().part(channel) {
+                                    Self::write_failed(app, buf, err);
+                                    break 'ret;
+                                }
+                                app.remove_channel(&ChannelName::new(channel));
+                            }
+                            Input::Watch { channel }
+                                if app.user_map.is_missing(&channel.to_lowercase()) =>
+                            {
+                                app.state.channels[app.state.active].buffer = buf.to_string();
+                            }
+                            Input::Watch { channel } => app.watch(ChannelName::new(channel)),
+                            Input::Unwatch { channel } => {
+                                app.unwatch(&ChannelName::new(channel));
+                            }
+                            // the server would just drop it
+                            Input::Send { data } if !Self::non_emotes(app, data).is_empty() => {
+                                app.state.channels[app.state.active].buffer = buf.to_string();
+                            }
+                            Input::Whisper { user, data } => {
+                                if let Err(err) = app.whisper(user, data) {
+                                    Self::write_failed(app, buf, err);
+                                }
+                            }
+                            // keep it around until there's someone to reply to
+                            Input::Send { .. }
+                                if app.state.channels[app.send_target()].is_whispers()
+                                    && app.state.last_whisper.is_none() =>
+                            {
+                                app.state.channels[app.state.active].buffer = buf.to_string();
+                            }
+                            Input::Send { data }
+                                if app.state.channels[app.send_target()].is_whispers() =>
+                            {
+                                let user = app.state.last_whisper.clone().expect("checked");
+                                if let Err(err) = app.whisper(&user, data) {
+                                    Self::write_failed(app, buf, err);
+                                }
+                            }
+                            // each line of a draft goes out as its own message
+                            Input::Send { data } => {
+                                let data = Self::expand_quotes(app, data);
+                                let lines = data
+                                    .lines()
+                                    .map(str::trim)
+                                    .filter(|line| !line.is_empty())
+                                    .collect::<Vec<_>>();
+                                for (i, line) in lines.iter().enumerate() {
+                                    if let Err(err) = Self::send_message(app, line) {
+                                        // the lines that already went out aren't put back
+                                        Self::write_failed(app, &lines[i..].join("\n"), err);
+                                        break 'ret;
+                                    }
+                                }
+                            }
+                            // keep it around so it can be fixed, the palette says what's wrong
+                            Input::Usage { .. } | Input::Unknown { .. } => {
+                                app.state.channels[app.state.active].buffer = buf.to_string();
+                            }
+                        }
+                    }
+                }
+
+                resp.request_focus();
+
+                ui.painter().line_segment(
+                    [resp.rect.left_bottom(), resp.rect.right_bottom()],
+                    (0.5, Color32::WHITE),
+                );
+            });
+    }
+
+    fn display_tab_bar(ctx: &egui::Context, app: &mut App) {
+        let style = ctx.style();
+
+        let fid = TextStyle::Body.resolve(&style);
+        let height = ctx.fonts(|f| f.row_height(&fid));
+
+        Self::panel("tab_bar", app.state.input_position == InputPosition::Top)
+            .show_separator_line(true)
+            .show(ctx, |ui| {
+                // if let Some(img) = app.cache.get_image(&user.profile_image_url) {
+                //     let resp = ui.add(img.as_egui_image(Vec2::splat(ui.available_height()), 0.0));
+                //     if let Some(desc) = user.description.as_ref().filter(|c| !c.trim().is_empty()) {
+                //         resp.on_hover_ui(|ui| {
+                //             ui.label(&*desc);
+                //         });
+                //     }
+                // }
+
+                // TODO channel icon
+
+                ui.horizontal_wrapped(|ui| {
+                    ui.scope(|ui| {
+                        ui.spacing_mut().item_spacing = Vec2::splat(2.0);
+
+                        let (mut toggle_read_only, mut set_scrollback) = (None, None);
+                        let (mut toggle_favorite, mut close) = (None, None);
+                        let mut split = None;
+                        // tabs can only be dragged around when they're in the manual order
+                        let draggable = app.state.tab_order == TabOrder::Manual;
+                        let (mut dragging, mut tab_rects) = (None, vec![]);
+                        for i in app.tab_order() {
+                            let channel = &app.state.channels[i];
+                            let active = i == app.state.active;
+
+                            // the tab alternates with the marker color until it's looked at
+                            let phase = ui.input(|i| i.time) as f32 / Self::FLASH_INTERVAL;
+                            let flash = channel.attention && phase as u64 % 2 == 0;
+                            if channel.attention {
+                                ui.ctx()
+                                    .request_repaint_after(std::time::Duration::from_secs_f32(
+                                        Self::FLASH_INTERVAL,
+                                    ));
+                            }
+
+                            let button = Button::new(channel.name.as_str()).small().fill(if active {
+                                ui.visuals().widgets.active.bg_fill
+                            } else if flash {
+                                app.theme.marker().linear_multiply(0.6)
+                            } else {
+                                ui.visuals()
+                                    .widgets
+                                    .active
+                                    .weak_bg_fill
+                                    .linear_multiply(0.2)
+                            });
+
+                            // the id follows the channel, so a drag survives the tab moving
+                            let resp = ui.push_id(&channel.name, |ui| ui.add(button)).inner;
+                            let resp = if draggable {
+                                resp.interact(Sense::click_and_drag())
+                            } else {
+                                resp
+                            };
+                            if resp.dragged() {
+                                dragging.replace(i);
+                            }
+                            tab_rects.push((i, resp.rect));
+
+                            let favorite = app.state.favorites.contains(&channel.name);
+                            if favorite {
+                                Self::paint_star(
+                                    ui.painter(),
+                                    resp.rect.right_top() + vec2(-3.0, 3.0),
+                                    3.0,
+                                    app.theme.marker(),
+                                );
+                            }
+
+                            if active {
+                                ui.painter().rect_stroke(
+                                    resp.rect,
+                                    ui.visuals().widgets.active.rounding,
+                                    (0.5, app.theme.accent()),
+                                )
+                            }
+
+                            if channel.is_loading_history() {
+                                ui.add(Spinner::new().size(height * 0.6))
+                                    .on_hover_text("loading history");
+                            }
+
+                            if channel.unread > 0 {
+                                ui.label(
+                                    RichText::new(Self::counter(channel.unread)).small().weak(),
+                                )
+                                .on_hover_text(format!("{} unread messages", channel.unread));
+                            }
+                            if channel.unread_highlights > 0 {
+                                ui.label(
+                                    RichText::new(Self::counter(channel.unread_highlights))
+                                        .small()
+                                        .strong()
+                                        .color(app.theme.marker()),
+                                )
+                                .on_hover_text(format!(
+                                    "{} unread mentions",
+                                    channel.unread_highlights
+                                ));
+                            }
+
+                            let resp_close = ui
+                                .add(
+                                    Label::new(RichText::new("x").small().weak())
+                                        .sense(Sense::click()),
+                                )
+                                .on_hover_text(format!("close {}", channel.name));
+                            if resp_close.clicked() {
+                                close.replace(channel.name.clone());
+                            }
+
+                            if resp.clicked() {
+                                app.state.active = i;
+                                app.send_to.take();
+                            }
+
+                            resp.context_menu(|ui| {
+                                let mut checked = favorite;
+                                if ui.checkbox(&mut checked, "Favorite").changed() {
+                                    toggle_favorite.replace(i);
+                                    ui.close_menu();
+                                }
+
+                                let mut read_only = channel.read_only;
+                                if ui.checkbox(&mut read_only, "Read only").changed() {
+                                    toggle_read_only.replace(i);
+                                    ui.close_menu();
+                                }
+
+                                ui.horizontal(|ui| {
+                                    let mut n = channel.messages.capacity();
+                                    ui.label("Scrollback");
+                                    let resp = ui.add(
+                                        egui::DragValue::new(&mut n)
+                                            .clamp_range(100..=Channel::MAX_SCROLLBACK)
+                                            .speed(100),
+                                    );
+                                    if resp.changed() {
+                                        set_scrollback.replace((i, n));
+                                    }
+                                });
+
+                                ui.menu_button("Open in a split", |ui| {
+                                    if ui.button("Side by side").clicked() {
+                                        split.replace((i, SplitDirection::Horizontal));
+                                        ui.close_menu();
+                                    }
+                                    if ui.button("Stacked").clicked() {
+                                        split.replace((i, SplitDirection::Vertical));
+                                        ui.close_menu();
+                                    }
+                                });
+
+                                if ui.button("Browse emotes").clicked() {
+                                    app.emote_browser.replace(EmoteBrowser {
+                                        channel: channel.name.clone(),
+                                        query: String::new(),
+                                    });
+                                    ui.close_menu();
+                                }
+
+                                if ui.button("Refresh channel info").clicked() {
+                                    app.user_map.refresh(&channel.name);
+                                    if let Some(user) = app.user_map.get(&channel.name) {
+                                        app.stream_check.refresh(&user.id);
+                                    }
+                                    ui.close_menu();
+                                }
+                            });
+                        }
+
+                        if let Some(i) = toggle_read_only {
+                            let channel = &mut app.state.channels[i];
+                            channel.read_only = !channel.read_only;
+                            app.autosave.mark_changed();
+                        }
+
+                        if let Some(i) = toggle_favorite {
+                            let name = app.state.channels[i].name.clone();
+                            app.toggle_favorite(&name);
+                        }
+
+                        if let Some((i, n)) = set_scrollback {
+                            app.state.channels[i].messages.set_capacity(n);
+                            app.autosave.mark_changed();
+                        }
+
+                        // the dragged tab takes the place of the one it's over once it's past
+                        // the middle, otherwise tabs of different widths would keep swapping
+                        let pointer = ui.input(|i| i.pointer.interact_pos());
+                        if let Some((from, pos)) = dragging.zip(pointer) {
+                            ui.output_mut(|o| o.cursor_icon = egui::CursorIcon::Grabbing);
+                            let target = tab_rects.iter().find(|(to, rect)| {
+                                rect.contains(pos)
+                                    && match to.cmp(&from) {
+                                        std::cmp::Ordering::Less => pos.x < rect.center().x,
+                                        std::cmp::Ordering::Greater => pos.x > rect.center().x,
+                                        std::cmp::Ordering::Equal => false,
+                                    }
+                            });
+                            if let Some(&(to, _)) = target {
+                                app.move_channel(from, to);
+                            }
+                        }
+
+                        if let Some((i, direction)) = split {
+                            app.layout
+                                .split(app.state.channels[i].name.clone(), direction);
+                            app.state.active = i;
+                            app.send_to.take();
+                        }
+
+                        if let Some(name) = close {
+                            app.close_channel(&name);
+                        }
+                    });
+
+                    ui.with_layout(Layout::right_to_left(egui::Align::Center), |ui| {
+                        Self::display_settings_menu(ui, app);
+                    });
+                });
+            });
+    }
+
+    // keeps a busy channel's tab from growing without bound
+    fn counter(n: usize) -> String {
+        match n {
+            ..=99 => n.to_string(),
+            _ => String::from("99+"),
+        }
+    }
+
+    fn panel(id: &str, top: bool) -> TopBottomPanel {
+        if top {
+            TopBottomPanel::top(id)
+        } else {
+            TopBottomPanel::bottom(id)
+        }
+    }
+
+    // suggests channels while typing out a /join
+    fn display_join_search(ctx: &egui::Context, app: &mut App, pos: egui::Pos2, is_empty: bool) {
+        let buffer = if is_empty {
+            ""
+        } else {
+            &app.state.channels[app.state.active].buffer
+        };
+
+        // only a single channel is searched for
+        let (query, watch) = match Input::parse(buffer) {
+            Input::Join { channels } if channels.len() == 1 => (channels[0], false),
+            Input::Watch { channel } => (channel, true),
+            _ => ("", false),
+        };
+
+        if app.join_search.poll(query, &app.helix) {
+            ctx.request_repaint_after(ChannelSearch::SEARCH_DELAY);
+        }
+
+        if app.join_search.results.is_empty() {
+            return;
+        }
+
+        egui::Area::new("join-search")
+            .order(egui::Order::Foreground)
+            .fixed_pos(pos)
+            .show(ctx, |ui| {
+                egui::Frame::popup(ui.style()).show(ui, |ui| {
+                    let results = SearchResults {
+                        search: &app.join_search,
+                        live: app.theme.live(),
+                    };
+                    let Some(channel) = results.display(ui) else { return };
+                    if watch {
+                        app.watch(ChannelName::new(&channel));
+                        app.state.channels[app.state.active].buffer.clear();
+                        return;
+                    }
+                    match app.twitch.writer().join(&channel) {
+                        Ok(()) => app.state.channels[app.state.active].buffer.clear(),
+                        Err(err) => log!("cannot join {channel}: {err}"),
+                    }
+                });
+            });
+    }
+
+    // read only channels can't be picked, so whatever is picked here can be sent to
+    fn display_send_to(ui: &mut egui::Ui, app: &mut App) {
+        let target = app.send_target();
+        let mut picked = None;
+
+        egui::ComboBox::from_id_source("send-to")
+            .selected_text(RichText::new(&*app.state.channels[target].name).small())
+            .width(0.0)
+            .show_ui(ui, |ui| {
+                for (i, channel) in app.state.channels.iter().enumerate() {
+                    if (channel.read_only || channel.is_whispers()) && i != app.state.active {
+                        continue;
+                    }
+                    if ui.selectable_label(i == target, &*channel.name).clicked() {
+                        picked.replace(i);
+                    }
+                }
+            })
+            .response
+            .on_hover_text("the channel messages are sent to");
+
+        if let Some(i) = picked {
+            app.send_to = (i != app.state.active).then(|| app.state.channels[i].name.clone());
+        }
+    }
+
+    // the input is kept around so it can be fixed
+    fn display_quote_preview(ui: &mut egui::Ui, app: &mut App) {
+        let buffer = &app.state.channels[app.state.active].buffer;
+        let Some(link) = buffer.split_whitespace().find_map(Permalink::parse) else { return };
+
+        let color = app.theme.accent();
+        ui.horizontal_wrapped(|ui| match app.quoted_message(link.id) {
+            Some(msg) => {
+                let author = format!("quoting {}", msg.login);
+                ui.label(RichText::new(author).small().strong().color(color));
+                ui.label(RichText::new(&*msg.data).small().italics());
+            }
+            None => {
+                ui.label(
+                    RichText::new("that message isn't in the history")
+                        .small()
+                        .weak(),
+                );
+            }
+        });
+    }
+
+    // a link would only mean something to us, so the message it points at is sent instead
+    fn expand_quotes(app: &mut App, data: &str) -> String {
+        data.split(' ')
+            .map(|word| {
+                let quote = Permalink::parse(word)
+                    .and_then(|link| app.quoted_message(link.id))
+                    .map(|msg| Self::format_quote(&msg.login, &msg.data));
+                quote.unwrap_or_else(|| word.to_string())
+            })
+            .collect::<Vec<_>>()
+            .join(" ")
+    }
+
+    fn format_quote(login: &str, data: &str) -> String {
+        match data.char_indices().nth(Self::MAX_QUOTE) {
+            Some((end, _)) => format!("@{login} said: \"{}...\"", &data[..end]),
+            None => format!("@{login} said: \"{data}\""),
+        }
+    }
+
+    fn send_message(app: &mut App, data: &str) -> Result<(), twitch::WriteError> {
+        let target = app.send_target();
+        let channel = &app.state.channels[target].name;
+        app.twitch.writer().privmsg(channel, data)?;
+
+        let (msg, tags) = Self::create_self_message(app, target, data);
+        let pm = msg
+            .clone()
+            .tags(tags.clone().finish())
+            .finish_privmsg()
+            .expect("valid privmsg");
+
+        let send = crate::state::Message::from_pm(
+            &pm,
+            &mut app.emote_map,
+            MessageOpts {
+                old: false,
+                local: true,
+            },
+        );
+        let channel = app.state.channels[target].name.clone();
+        app.state.channels[target].push(send);
+
+        app.last.push_back((channel, msg, tags));
+        Ok(())
+    }
+
+    fn write_failed(app: &mut App, input: &str, err: twitch::WriteError) {
+        app.toasts.error(format!("cannot send the message: {err}"));
+        app.state.channels[app.state.active].buffer = input.to_string();
+        app.input_error
+            .replace((err.to_string(), input.to_string()));
+    }
+
+    // names are only looked up once typing pauses, otherwise every prefix of them would be
+    fn input_error(ctx: &egui::Context, app: &mut App) -> Option<String> {
+        if app.state.channels.is_empty() {
+            return None;
+        }
+        let buffer = &app.state.channels[app.state.active].buffer;
+
+        match &app.input_error {
+            Some((err, input)) if input == buffer => return Some(err.clone()),
+            Some(..) => app.input_error = None,
+            None => {}
+        }
+
+        let id = egui::Id::new("input-validation");
+        let now = ctx.input(|i| i.time);
+        let settled = ctx.data_mut(|data| {
+            let (last, changed) = data.get_temp_mut_or_insert_with(id, || (String::new(), now));
+            if last != buffer {
+                *last = buffer.clone();
+                *changed = now;
+            }
+            now - *changed >= Self::LOOKUP_DELAY
+        });
+
+        let logins = match Input::parse(buffer) {
+            Input::Usage { error, .. } => return error.map(ToString::to_string),
+            Input::Send { .. }
+                if app.state.channels[app.send_target()].is_whispers()
+                    && app.state.last_whisper.is_none() =>
+            {
+                return Some(String::from("nobody to reply to yet, use /w user message"));
+            }
+            Input::Send { data } => {
+                let words = Self::non_emotes(app, data);
+                return (!words.is_empty())
+                    .then(|| format!("only emotes can be sent here: {}", words.join(" ")));
+            }
+            Input::Join { channels } => channels,
+            Input::Watch { channel } => vec![channel],
+            Input::Timeout { user, .. } | Input::Whisper { user, .. } => vec![user],
+            _ => return None,
+        };
+
+        if !settled {
+            ctx.request_repaint_after(std::time::Duration::from_secs_f64(Self::LOOKUP_DELAY));
+            return None;
+        }
+
+        let mut missing = vec![];
+        for login in logins {
+            let login = login.trim_start_matches(['#', '@']).to_lowercase();
+            app.user_map.get(&login);
+            if app.user_map.is_missing(&login) {
+                missing.push(login);
+            }
+        }
+        (!missing.is_empty()).then(|| format!("there's nobody named {}", missing.join(", ")))
+    }
+
+    // moderators can still say whatever they want
+    fn is_emote_only(app: &App) -> bool {
+        let channel = &app.state.channels[app.send_target()];
+        channel.emote_only
+            && !app
+                .state
+                .identity
+                .as_ref()
+                .map_or(false, |identity| identity.is_moderator(&channel.name))
+    }
+
+    // the words that'd get the message rejected in emote only mode
+    fn non_emotes<'b>(app: &App, data: &'b str) -> Vec<&'b str> {
+        if !Self::is_emote_only(app) {
+            return vec![];
+        }
+
+        data.split_whitespace()
+            .filter(|word| !app.emote_map.can_use(word))
+            .collect()
+    }
+
+    // in emote only mode, the word being typed is completed to the emotes it could be
+    fn display_emote_completions(
+        ctx: &egui::Context,
+        app: &mut App,
+        pos: egui::Pos2,
+        is_empty: bool,
+    ) {
+        const MAX_COMPLETIONS: usize = 10;
+
+        if is_empty || !Self::is_emote_only(app) {
+            return;
+        }
+
+        let buffer = &app.state.channels[app.state.active].buffer;
+        let (head, word) = buffer.rsplit_once(' ').unwrap_or(("", buffer));
+        if word.is_empty() || buffer.starts_with('/') {
+            return;
+        }
+        // tab completion has its own list
+        if app.completer.candidates(buffer).is_some() {
+            return;
+        }
+        if app.emote_map.can_use(word) {
+            return;
+        }
+
+        // emote only mode is twitch's, it doesn't count anything else as an emote
+        let mut names = app
+            .emote_map
+            .twitch_names_starting_with(word)
+            .filter(|name| app.emote_map.can_use(name))
+            .collect::<Vec<_>>();
+        names.sort_unstable();
+        names.truncate(MAX_COMPLETIONS);
+        if names.is_empty() {
+            return;
+        }
+
+        let mut picked = None;
+        egui::Area::new("emote-completions")
+            .order(egui::Order::Foreground)
+            .fixed_pos(pos)
+            .show(ctx, |ui| {
+                egui::Frame::popup(ui.style()).show(ui, |ui| {
+                    for name in names {
+                        if ui.selectable_label(false, name).clicked() {
+                            picked.replace(if head.is_empty() {
+                                format!("{name} ")
+                            } else {
+                                format!("{head} {name} ")
+                            });
+                        }
+                    }
+                });
+            });
+
+        if let Some(buffer) = picked {
+            app.state.channels[app.state.active].buffer = buffer;
+        }
+    }
 
-    pub fn display(self, ctx: &egui::Context) {
-        Self::display_tab_bar(ctx, self.app);
-        Self::display_topic_bar(ctx, self.app);
+    fn complete_input(ctx: &egui::Context, app: &mut App) {
+        let channel = &mut app.state.channels[app.state.active];
+        let mut buffer = std::mem::take(&mut channel.buffer);
+        let changed = app.completer.complete(&mut buffer, &app.emote_map, channel);
+        channel.buffer = buffer;
+        if changed {
+            Self::move_cursor_to_end(ctx, app);
+        }
+    }
 
-        let channel = &self.app.state.channels[self.app.state.active];
+    // up and down go through what was sent before, like a shell
+    fn recall_input(ctx: &egui::Context, app: &mut App) {
+        // the arrows move between the lines of a draft instead
+        if app.state.channels[app.state.active].buffer.contains('\n') {
+            return;
+        }
 
-        // TODO vertical and horizontal splits
-        // TODO refactor this
+        let (up, down) = ctx.input_mut(|i| {
+            (
+                i.consume_key(Modifiers::NONE, Key::ArrowUp),
+                i.consume_key(Modifiers::NONE, Key::ArrowDown),
+            )
+        });
 
-        CentralPanel::default().show(ctx, |ui| {
-            let fid = TextStyle::Body.resolve(ui.style());
-            let (w, h) = ui.fonts(|f| (f.glyph_width(&fid, ' '), f.row_height(&fid)));
-
-            ScrollArea::vertical()
-                .drag_to_scroll(false)
-                .stick_to_bottom(true)
-                .show(ui, |ui| {
-                    let dt = ui.input(|i| i.stable_dt.min(0.1));
-                    let marker = channel.marker;
-
-                    for msg in channel.messages.iter() {
-                        ui.horizontal_wrapped(|ui| {
-                            ui.scope(|ui| {
-                                ui.spacing_mut().item_spacing.x = 1.0;
-                                // TODO fix this alignment
-                                ui.with_layout(Layout::left_to_right(egui::Align::Center), |ui| {
-                                    if let Some(twitch_message::Badge { name, version }) =
-                                        msg.badges.first()
-                                    {
-                                        if let Some(url) = self
-                                            .app
-                                            .emote_map
-                                            .get_badge_url(name.as_str(), version.as_str())
-                                        {
-                                            if let Some(image) = self.app.cache.get_image(url) {
-                                                let mut image =
-                                                    image.as_egui_image(Vec2::splat(h * 0.6), dt);
-                                                if msg.opts.old {
-                                                    image = image.tint(
-                                                        Color32::WHITE
-                                                            .gamma_multiply(Self::INACTIVE_GAMMA),
-                                                    )
-                                                }
+        let channel = &mut app.state.channels[app.state.active];
+        let changed = match (up, down) {
+            (true, false) => channel.input_history.older(&mut channel.buffer),
+            (false, true) => channel.input_history.newer(&mut channel.buffer),
+            _ => false,
+        };
+        if changed {
+            Self::move_cursor_to_end(ctx, app);
+        }
+    }
 
-                                                ui.add(image).on_hover_text(name.as_str());
-                                            }
-                                        }
-                                    }
+    // the text edit keeps its cursor where it was when the buffer is replaced
+    fn move_cursor_to_end(ctx: &egui::Context, app: &App) {
+        use egui::text::{CCursor, CCursorRange};
 
-                                    ui.add(Label::new(RichText::new(&msg.sender).color(
-                                        if msg.opts.old {
-                                            msg.color.gamma_multiply(Self::INACTIVE_GAMMA)
-                                        } else {
-                                            msg.color
-                                        },
-                                    )));
-                                });
-                            });
+        let id = egui::Id::new("input_buffer").with(app.state.active);
+        let Some(mut state) = egui::TextEdit::load_state(ctx, id) else { return };
+        let end = app.state.channels[app.state.active].buffer.chars().count();
+        state.set_ccursor_range(Some(CCursorRange::one(CCursor::new(end))));
+        state.store(ctx, id);
+    }
 
-                            ui.scope(|ui| {
-                                ui.spacing_mut().item_spacing.x = w;
+    // the candidates while tab is cycling through them
+    fn display_completer(ctx: &egui::Context, app: &mut App, pos: egui::Pos2, is_empty: bool) {
+        if is_empty {
+            return;
+        }
 
-                                Self::display_fragments(
-                                    ui,
-                                    Vec2::splat(h),
-                                    dt,
-                                    msg,
-                                    &mut self.app.emote_map,
-                                    &mut self.app.cache,
-                                )
-                            });
-                        });
+        let buffer = &app.state.channels[app.state.active].buffer;
+        let Some((candidates, index)) = app.completer.candidates(buffer) else { return };
 
-                        if let Some(marker) = marker {
-                            if Some(marker) == msg.id {
-                                let rect = ui.available_rect_before_wrap();
-                                let mut rect = rect.shrink2(vec2(2.0, h));
-                                rect.set_height(1.0);
-                                let (rect, response) =
-                                    ui.allocate_exact_size(rect.size(), Sense::hover());
+        let mut picked = None;
+        egui::Area::new("tab-completions")
+            .order(egui::Order::Foreground)
+            .fixed_pos(pos)
+            .show(ctx, |ui| {
+                egui::Frame::popup(ui.style()).show(ui, |ui| {
+                    for (i, candidate) in candidates.iter().enumerate() {
+                        if ui.selectable_label(i == index, candidate).clicked() {
+                            picked.replace(i);
+                        }
+                    }
+                });
+            });
 
-                                ui.add(|ui: &mut egui::Ui| {
-                                    ui.painter().rect_filled(
-                                        rect,
-                                        Rounding::none(),
-                                        Color32::RED.gamma_multiply(Self::INACTIVE_GAMMA),
-                                    );
+        if let Some(i) = picked {
+            let buffer = &mut app.state.channels[app.state.active].buffer;
+            app.completer.pick(i, buffer);
+            Self::move_cursor_to_end(ctx, app);
+        }
+    }
+
+    // lists the commands while the name is being typed, then the help for the one that was picked
+    fn display_command_palette(
+        ctx: &egui::Context,
+        app: &mut App,
+        pos: egui::Pos2,
+        is_empty: bool,
+    ) {
+        if is_empty || !app.join_search.results.is_empty() {
+            return;
+        }
 
-                                    response
+        let buffer = &app.state.channels[app.state.active].buffer;
+        let Some(tail) = buffer.strip_prefix('/') else { return };
+
+        let mut picked = None;
+        egui::Area::new("command-palette")
+            .order(egui::Order::Foreground)
+            .fixed_pos(pos)
+            .show(ctx, |ui| {
+                egui::Frame::popup(ui.style()).show(ui, |ui| {
+                    if let Some((head, _)) = tail.split_once(' ') {
+                        match Command::find(head) {
+                            Some(cmd) => {
+                                ui.horizontal(|ui| {
+                                    ui.monospace(cmd.syntax());
+                                    ui.label(RichText::new(cmd.description).weak());
                                 });
                             }
+                            None => {
+                                ui.colored_label(
+                                    ui.visuals().warn_fg_color,
+                                    format!("unknown command: /{head}"),
+                                );
+                            }
                         }
+                        return;
                     }
 
-                    ui.allocate_space(ui.available_size_before_wrap());
+                    let mut any = false;
+                    Grid::new("command-palette-grid")
+                        .num_columns(2)
+                        .show(ui, |ui| {
+                            for cmd in Command::matching(tail) {
+                                any = true;
+                                if ui.selectable_label(false, cmd.syntax()).clicked() {
+                                    picked.replace(cmd);
+                                }
+                                ui.label(RichText::new(cmd.description).weak());
+                                ui.end_row();
+                            }
+                        });
+
+                    if !any {
+                        ui.colored_label(
+                            ui.visuals().warn_fg_color,
+                            format!("unknown command: /{tail}"),
+                        );
+                    }
                 });
+            });
+
+        if let Some(cmd) = picked {
+            app.state.channels[app.state.active].buffer = format!("/{} ", cmd.name);
+        }
+    }
+
+    // this is drawn as an outline, the filled shape isn't convex
+    fn paint_star(painter: &egui::Painter, center: egui::Pos2, radius: f32, color: Color32) {
+        let points = (0..10)
+            .map(|i| {
+                let r = if i % 2 == 0 { radius } else { radius * 0.45 };
+                let angle = std::f32::consts::PI * (i as f32 / 5.0 - 0.5);
+                center + r * vec2(angle.cos(), angle.sin())
+            })
+            .collect();
+        painter.add(egui::Shape::closed_line(points, (1.0, color)));
+    }
+
+    // the text is kept around so blank lines can be typed before the next entry
+    fn edit_lines(
+        ui: &mut egui::Ui,
+        id: &str,
+        current: impl FnOnce() -> String,
+    ) -> Option<Vec<String>> {
+        let id = egui::Id::new(id);
+        let mut text = ui
+            .data_mut(|data| data.get_temp::<String>(id))
+            .unwrap_or_else(current);
+
+        let changed = ui
+            .add(TextEdit::multiline(&mut text).desired_rows(4))
+            .changed();
+        let lines = changed.then(|| {
+            text.lines()
+                .map(str::trim)
+                .filter(|line| !line.is_empty())
+                .map(ToString::to_string)
+                .collect()
         });
+        ui.data_mut(|data| data.insert_temp(id, text));
+        lines
     }
 
-    fn display_tab_bar(ctx: &egui::Context, app: &mut App) {
-        let style = ctx.style();
+    fn display_settings_menu(ui: &mut egui::Ui, app: &mut App) {
+        ui.menu_button("settings", |ui| {
+            if ui.button("Preferences").clicked() {
+                app.show_settings = true;
+                ui.close_menu();
+            }
 
-        let fid = TextStyle::Body.resolve(&style);
-        let height = ctx.fonts(|f| f.row_height(&fid));
+            if ui
+                .checkbox(&mut app.state.streamer_mode, "Streamer mode")
+                .on_hover_text("hide private information while sharing the screen")
+                .changed()
+            {
+                app.autosave.mark_changed();
+            }
 
-        // TODO redo this
-        // TODO why is the edit box here?
+            let mut flash = !app.state.quiet_taskbar;
+            if ui
+                .checkbox(&mut flash, "Flash the task bar")
+                .on_hover_text("for mentions and whispers while the window is in the background")
+                .changed()
+            {
+                app.state.quiet_taskbar = !flash;
+                app.autosave.mark_changed();
+            }
 
-        TopBottomPanel::bottom("tab_bar")
-            .height_range(height * 2.0..=f32::INFINITY)
-            .show_separator_line(true)
-            .show(ctx, |ui| {
-                ui.vertical(|ui| {
-                    let size = vec2(ui.available_size().x, height);
+            if ui
+                .checkbox(&mut app.state.read_only_token, "Read-only token")
+                .on_hover_text(format!(
+                    "only {} is needed, sending and moderating are turned off",
+                    TokenCheck::READ_ONLY_SCOPES.join(" and ")
+                ))
+                .changed()
+            {
+                app.autosave.mark_changed();
+            }
 
-                    let is_empty = app.state.channels.is_empty();
+            ui.menu_button("Do not disturb", |ui| {
+                let dnd = &mut app.state.do_not_disturb;
+                let before = dnd.clone();
 
-                    let resp = ui.add(|ui: &mut egui::Ui| {
-                        let default = "";
-                        let (mut a, b);
-                        ui.add_sized(size, {
-                            let buf: &mut dyn egui::TextBuffer = if is_empty {
-                                a = default;
-                                &mut a as _
-                            } else {
-                                b = &mut app.state.channels[app.state.active].buffer;
-                                b as _
-                            };
+                ui.checkbox(&mut dnd.enabled, "Mute notifications");
 
-                            TextEdit::singleline(buf)
-                                // TODO this should use the buffer name
-                                .id(egui::Id::new("input_buffer").with(app.state.active))
-                                .font(egui::TextStyle::Body)
-                                .frame(false)
-                                .margin(vec2(0.0, 1.0))
-                        })
+                let mut scheduled = dnd.quiet_hours.is_some();
+                if ui.checkbox(&mut scheduled, "Quiet hours").changed() {
+                    dnd.quiet_hours = scheduled.then(QuietHours::default);
+                }
+
+                if let Some(hours) = &mut dnd.quiet_hours {
+                    ui.horizontal(|ui| {
+                        ui.label("from");
+                        ui.add(egui::DragValue::new(&mut hours.start).clamp_range(0..=23));
+                        ui.label("to");
+                        ui.add(egui::DragValue::new(&mut hours.end).clamp_range(0..=23));
                     });
+                }
 
-                    'ret: {
-                        if ui.input(|i| i.key_released(Key::Enter)) {
-                            let buf =
-                                std::mem::take(&mut app.state.channels[app.state.active].buffer);
+                if *dnd != before {
+                    app.autosave.mark_changed();
+                }
+            });
 
-                            let buf = buf.trim();
-                            if buf.is_empty() {
-                                break 'ret;
-                            }
+            ui.menu_button("Auto-moderation", |ui| {
+                let automod = &mut app.automod;
+                ui.label(format!(
+                    "{} rules in {}",
+                    automod.config.rules.len(),
+                    Automod::PATH
+                ));
+                if ui
+                    .checkbox(&mut automod.config.dry_run, "Dry run")
+                    .on_hover_text("only write what would happen to the audit log")
+                    .changed()
+                {
+                    automod.save(Automod::PATH);
+                }
+            });
 
-                            match Input::parse(buf) {
-                                Input::Join { channel } => {
-                                    app.twitch.writer().join(channel);
-                                }
-                                Input::Part { channel } => {
-                                    app.twitch.writer().part(channel);
-                                    // TODO leave the channel
-                                    // TODO shift the buffer over
-                                    // TODO change the 'active'
-                                }
-                                Input::Send { data } => {
-                                    let (msg, tags) = Self::create_self_message(app, data);
-                                    let pm = msg
-                                        .clone()
-                                        .tags(tags.clone().finish())
-                                        .finish_privmsg()
-                                        .expect("valid privmsg");
-
-                                    let send = crate::state::Message::from_pm(
-                                        &pm,
-                                        &mut app.emote_map,
-                                        MessageOpts {
-                                            old: false,
-                                            local: true,
-                                        },
-                                    );
-                                    app.state.channels[app.state.active].push(send);
+            ui.menu_button("Highlights", |ui| {
+                ui.label(RichText::new("one per line, mentions are always highlighted").small());
+                let keywords = &app.state.highlight_keywords;
+                let text = || keywords.join("\n");
+                if let Some(lines) = Self::edit_lines(ui, Self::KEYWORDS_ID, text) {
+                    app.state.highlight_keywords = lines;
+                    app.highlighter.set_keywords(&app.state.highlight_keywords);
+                    app.autosave.mark_changed();
+                }
+            });
 
-                                    app.last.replace((msg, tags));
+            ui.menu_button("Ignored users", |ui| {
+                ui.label(RichText::new("one login per line, their messages aren't shown").small());
+                let users = &app.state.ignored_users;
+                let text = || users.iter().cloned().collect::<Vec<_>>().join("\n");
+                if let Some(lines) = Self::edit_lines(ui, Self::IGNORED_ID, text) {
+                    app.state.ignored_users = lines.iter().map(|s| s.to_lowercase()).collect();
+                    app.autosave.mark_changed();
+                }
+            });
 
-                                    app.twitch
-                                        .writer()
-                                        .privmsg(&app.state.channels[app.state.active].name, data)
-                                }
-                                _ => {}
+            ui.menu_button("Nicknames", |ui| {
+                ui.label(RichText::new("one per line, as: login nickname").small());
+                let nicknames = &app.state.nicknames;
+                let text = || {
+                    (nicknames.iter())
+                        .map(|(login, nickname)| format!("{login} {nickname}"))
+                        .collect::<Vec<_>>()
+                        .join("\n")
+                };
+                if let Some(lines) = Self::edit_lines(ui, Self::NICKNAMES_ID, text) {
+                    app.state.nicknames = lines
+                        .iter()
+                        .filter_map(|line| line.split_once(' '))
+                        .map(|(login, nickname)| {
+                            (login.to_lowercase(), nickname.trim().to_string())
+                        })
+                        .collect();
+                    app.autosave.mark_changed();
+                }
+            });
+
+            ui.menu_button("Import / export", |ui| {
+                // the last result is kept around so errors can be read
+                let id = egui::Id::new("settings-export");
+                let (mut path, mut include_secrets, mut status) = ui
+                    .data_mut(|data| data.get_temp::<(String, bool, String)>(id))
+                    .unwrap_or_else(|| {
+                        (String::from("vohiyo-settings.json"), false, String::new())
+                    });
+
+                ui.add(TextEdit::singleline(&mut path).hint_text("file"));
+                ui.checkbox(&mut include_secrets, "Include credentials")
+                    .on_hover_text("the oauth token and client secret, keep the file private");
+
+                let mut result = ui
+                    .horizontal(|ui| {
+                        if ui.button("Export").clicked() {
+                            let result = app.export_settings(&path, include_secrets);
+                            return Some(result.map(|_| format!("exported to {path}")));
+                        }
+                        for (label, hint, mode) in [
+                            ("Merge", "add to the current settings", ImportMode::Merge),
+                            ("Replace", "use only the settings in the file", ImportMode::Replace),
+                        ] {
+                            if ui.button(label).on_hover_text(hint).clicked() {
+                                let result = app.import_settings(ui.ctx(), &path, mode);
+                                return Some(result.map(|_| format!("imported from {path}")));
                             }
                         }
+                        None
+                    })
+                    .inner;
+
+                ui.separator();
+                let chatterino_id = egui::Id::new("chatterino-path");
+                let mut chatterino = ui
+                    .data_mut(|data| data.get_temp::<String>(chatterino_id))
+                    .or_else(|| {
+                        let path = ChatterinoImport::default_path()?;
+                        Some(path.display().to_string())
+                    })
+                    .unwrap_or_default();
+                ui.add(
+                    TextEdit::singleline(&mut chatterino).hint_text("chatterino's settings.json"),
+                );
+                if ui
+                    .button("Import from Chatterino")
+                    .on_hover_text("adds its highlights, ignored users and nicknames to ours")
+                    .clicked()
+                {
+                    result = Some(app.import_chatterino(&chatterino));
+                }
+                ui.data_mut(|data| data.insert_temp(chatterino_id, chatterino));
+
+                match result {
+                    Some(Ok(msg)) => {
+                        app.toasts.info(&msg);
+                        // the list editors keep their own text around
+                        ui.data_mut(|data| {
+                            for id in [Self::KEYWORDS_ID, Self::IGNORED_ID, Self::NICKNAMES_ID] {
+                                data.remove::<String>(egui::Id::new(id));
+                            }
+                        });
+                        status = msg;
+                    }
+                    Some(Err(err)) => {
+                        app.toasts.error(&err);
+                        status = err.to_string()
+                    }
+                    None => {}
+                }
+
+                if !status.is_empty() {
+                    ui.label(RichText::new(&status).small().weak());
+                }
+                ui.data_mut(|data| data.insert_temp(id, (path, include_secrets, status)));
+            });
+
+            if ui.button("Notification rules").clicked() {
+                app.show_rules = true;
+                ui.close_menu();
+            }
+
+            ui.menu_button("Tab order", |ui| {
+                for option in TabOrder::ALL {
+                    if ui
+                        .radio_value(&mut app.state.tab_order, option, option.label())
+                        .changed()
+                    {
+                        app.autosave.mark_changed();
                     }
+                }
+            });
 
-                    resp.request_focus();
+            ui.menu_button("Layout", |ui| {
+                let before = (app.state.input_position, app.state.tab_bar_position);
+                for option in InputPosition::ALL {
+                    ui.radio_value(&mut app.state.input_position, option, option.label());
+                }
+                ui.separator();
+                for option in TabBarPosition::ALL {
+                    ui.radio_value(&mut app.state.tab_bar_position, option, option.label());
+                }
+                if (app.state.input_position, app.state.tab_bar_position) != before {
+                    app.autosave.mark_changed();
+                }
+            });
 
-                    ui.painter().line_segment(
-                        [resp.rect.left_bottom(), resp.rect.right_bottom()],
-                        (0.5, Color32::WHITE),
-                    );
+            ui.menu_button("Accessibility", |ui| {
+                let (mut palette, mut shape) = (app.theme.palette, app.theme.live_shape);
 
-                    // if let Some(img) = app.cache.get_image(&user.profile_image_url) {
-                    //     let resp = ui.add(img.as_egui_image(Vec2::splat(ui.available_height()), 0.0));
-                    //     if let Some(desc) = user.description.as_ref().filter(|c| !c.trim().is_empty()) {
-                    //         resp.on_hover_ui(|ui| {
-                    //             ui.label(&*desc);
-                    //         });
-                    //     }
-                    // }
-
-                    // TODO a close button on the button
-                    // TODO channel icon
-
-                    ui.horizontal_wrapped(|ui| {
-                        ui.scope(|ui| {
-                            ui.spacing_mut().item_spacing = Vec2::splat(2.0);
-
-                            for (i, channel) in app.state.channels.iter().enumerate() {
-                                let active = i == app.state.active;
-
-                                let button = Button::new(&channel.name).small().fill(if active {
-                                    ui.visuals().widgets.active.bg_fill
-                                } else {
-                                    ui.visuals()
-                                        .widgets
-                                        .active
-                                        .weak_bg_fill
-                                        .linear_multiply(0.2)
-                                });
+                ui.label("Colors");
+                for option in Palette::ALL {
+                    ui.radio_value(&mut palette, option, option.label());
+                }
 
-                                let resp = ui.add(button);
+                ui.separator();
+                ui.label("Live indicator");
+                for option in LiveShape::ALL {
+                    ui.radio_value(&mut shape, option, option.label());
+                }
 
-                                if active {
-                                    ui.painter().rect_stroke(
-                                        resp.rect,
-                                        ui.visuals().widgets.active.rounding,
-                                        (0.5, Color32::BLUE),
-                                    )
-                                }
+                if (palette, shape) != (app.theme.palette, app.theme.live_shape) {
+                    app.theme.palette = palette;
+                    app.theme.live_shape = shape;
+                    app.theme.save(App::THEME_PATH);
+                }
+            });
 
-                                if resp.clicked() {
-                                    app.state.active = i;
-                                }
-                            }
-                        });
-                    });
+            if ui.button("Diagnostics").clicked() {
+                app.show_diagnostics = true;
+                ui.close_menu();
+            }
+
+            let notifications = match app.toasts.unseen_errors {
+                0 => String::from("Notifications"),
+                n => format!("Notifications ({n})"),
+            };
+            if ui.button(notifications).clicked() {
+                app.show_notifications = true;
+                ui.close_menu();
+            }
+
+            if ui.button("Bookmarks").clicked() {
+                app.show_bookmarks();
+                ui.close_menu();
+            }
+
+            if ui
+                .button("Switch channel")
+                .on_hover_text("or press ctrl+k, favorites are managed here too")
+                .clicked()
+            {
+                app.quick_switcher.get_or_insert_with(Default::default);
+                ui.close_menu();
+            }
+
+            if ui.button("Search history").clicked() {
+                app.history_search.get_or_insert_with(Default::default);
+                ui.close_menu();
+            }
+
+            if ui.button("Badges").clicked() {
+                app.show_badges = true;
+                ui.close_menu();
+            }
+
+            ui.checkbox(&mut app.show_watchlist, "Watchlist panel")
+                .on_hover_text("channels added with /watch, and whether they're live");
+
+            ui.checkbox(&mut app.show_frame_stats, "Frame time overlay")
+                .on_hover_text("frame times and slow frames, for reporting stutters");
+
+            if ui.button("Open vohiyo:// links with this").clicked() {
+                match crate::url_handler::register() {
+                    Ok(()) => app.toasts.info("vohiyo:// links now open here"),
+                    Err(err) => app.toasts.error(format!("cannot register link handler: {err}")),
+                }
+                ui.close_menu();
+            }
+        });
+
+        IdentityChip { app }.display(ui);
+
+        if matches!(app.twitch.status(), twitch::Status::Contested) {
+            let resp = ui
+                .add(
+                    Label::new(
+                        RichText::new("disconnected")
+                            .small()
+                            .color(ui.visuals().warn_fg_color),
+                    )
+                    .sense(Sense::click()),
+                )
+                .on_hover_text("another session may be using this account, click to take over");
+            if resp.clicked() {
+                app.take_over();
+            }
+        }
+
+        match app.twitch.queued() {
+            0 => {}
+            n => {
+                let text = if n == 1 {
+                    String::from("1 message queued")
+                } else {
+                    format!("{n} messages queued")
+                };
+                ui.label(
+                    RichText::new(text)
+                        .small()
+                        .color(ui.visuals().warn_fg_color),
+                )
+                .on_hover_text("waiting on the message rate limit, these are sent in order");
+            }
+        }
+
+        if let Some(rtt) = app.twitch.latency() {
+            let text = RichText::new(format!("{} ms", rtt.as_millis())).small();
+            ui.label(if rtt >= Self::SLOW_CONNECTION {
+                text.color(ui.visuals().warn_fg_color)
+            } else {
+                text.weak()
+            })
+            .on_hover_text("round trip time to the chat server");
+        }
+
+        if app.is_history_postponed() {
+            let resp = ui
+                .add(
+                    Label::new(
+                        RichText::new("history not saved")
+                            .small()
+                            .color(ui.visuals().warn_fg_color),
+                    )
+                    .sense(Sense::click()),
+                )
+                .on_hover_text("the history is damaged, click to rebuild it");
+            if resp.clicked() {
+                app.review_history_damage();
+            }
+        }
+
+        // this is in a right-to-left layout, so it ends up next to the menu
+        if app.state.do_not_disturb.is_active(app.local_offset) {
+            ui.label(RichText::new("do not disturb").small().weak());
+        }
+    }
+
+    fn display_watchlist(ctx: &egui::Context, app: &mut App) {
+        if !app.show_watchlist || app.state.watchlist.is_empty() {
+            return;
+        }
+
+        let view = WatchlistView {
+            watchlist: &app.state.watchlist,
+            user_map: &app.user_map,
+            stream_check: &app.stream_check,
+            live_shape: app.theme.live_shape,
+            live: app.theme.live(),
+        };
+        match view.display(ctx) {
+            Some(WatchlistAction::Join(channel)) => {
+                if let Err(err) = app.twitch.writer().join(&channel) {
+                    log!("cannot join {channel}: {err}");
+                }
+            }
+            Some(WatchlistAction::Remove(channel)) => app.unwatch(&channel),
+            None => {}
+        }
+    }
+
+    fn display_message_search(ctx: &egui::Context, app: &mut App) {
+        if !app.message_search.open {
+            return;
+        }
+
+        let db = app.history_path();
+        let channel = &app.state.channels[app.state.active];
+        if app.message_search.poll(channel, db) {
+            ctx.request_repaint_after(MessageSearch::SEARCH_DELAY);
+        }
+
+        let (mut step, mut close) = (None, false);
+        egui::Window::new("message-search")
+            .title_bar(false)
+            .resizable(false)
+            .anchor(egui::Align2::RIGHT_TOP, vec2(-8.0, 40.0))
+            .show(ctx, |ui| {
+                ui.horizontal(|ui| {
+                    let search = &mut app.message_search;
+                    let resp = ui.add(
+                        TextEdit::singleline(&mut search.query)
+                            .hint_text(format!("search #{}", channel.name))
+                            .desired_width(180.0),
+                    );
+                    if search.take_focus() {
+                        resp.request_focus();
+                    }
+
+                    // enter goes back through the chat, shift+enter comes forward again
+                    if resp.lost_focus() && ui.input(|i| i.key_pressed(Key::Enter)) {
+                        step.replace(ui.input(|i| i.modifiers.shift));
+                        resp.request_focus();
+                    }
+                    if ui.input(|i| i.key_pressed(Key::Escape)) {
+                        close = true;
+                    }
+
+                    let ids = search.match_ids(channel);
+                    let pos = search
+                        .current
+                        .and_then(|current| ids.iter().position(|&id| id == current));
+                    match (pos, ids.len()) {
+                        _ if search.is_loading() => {
+                            ui.add(Spinner::new());
+                        }
+                        (_, 0) if search.is_searching() => {
+                            ui.weak("no matches");
+                        }
+                        (Some(pos), n) => {
+                            ui.monospace(format!("{} of {n}", pos + 1));
+                        }
+                        (None, n) if n > 0 => {
+                            ui.monospace(format!("{n} found"));
+                        }
+                        _ => {}
+                    }
+
+                    if ui.small_button("older").clicked() {
+                        step.replace(false);
+                    }
+                    if ui.small_button("newer").clicked() {
+                        step.replace(true);
+                    }
+                    if ui.small_button("x").on_hover_text("close").clicked() {
+                        close = true;
+                    }
                 });
             });
+
+        if close {
+            app.message_search.close();
+            return;
+        }
+
+        let Some(newer) = step else { return };
+        let channel = &mut app.state.channels[app.state.active];
+        let Some(id) = app.message_search.step(channel, newer) else {
+            return;
+        };
+        match app.message_search.take_older(id) {
+            Some(stored) => channel.jump_to_stored(stored, &mut app.emote_map),
+            None => {
+                channel.jump_to.replace(id);
+            }
+        }
+        ctx.request_repaint();
     }
 
     fn display_topic_bar(ctx: &egui::Context, app: &mut App) {
         let channel = &app.state.channels[app.state.active];
+        if channel.is_whispers() {
+            return;
+        }
 
         let Some(user) = app.user_map.get(&channel.name) else { return };
         let Some(stream) = app.stream_check.get_or_subscribe(&user.id) else { return };
 
+        let mut refresh = false;
         TopBottomPanel::top(egui::Id::new(&user.id).with("topic-bar")).show(ctx, |ui| {
             // views [img] topic
             ui.horizontal(|ui| {
+                refresh = ui
+                    .small_button("refresh")
+                    .on_hover_text("Check the stream now")
+                    .clicked();
+
                 let (rect, resp) = ui.allocate_exact_size(Vec2::splat(12.0), Sense::hover());
 
-                ui.painter().circle(
-                    rect.center(),
-                    rect.width() * 0.5,
-                    Color32::RED,
+                app.theme.live_shape.paint(
+                    ui.painter(),
+                    rect,
+                    app.theme.live(),
                     (1.5, Color32::BLACK),
                 );
 
@@ -315,62 +2110,190 @@ impl<'a> MainView<'a> {
                 }
 
                 if let Some(game) = app.game_map.get(&stream.game_id) {
-                    if let Some(image) = app.cache.get_image(&game.box_art_url) {
-                        ui.add(image.as_egui_image(Vec2::splat(ui.available_height()), 0.0))
-                            .on_hover_text(&game.name);
+                    let ppp = ctx.pixels_per_point();
+                    let height = ui.available_height();
+                    let size = vec2(height * 0.75, height);
+                    let url = GameMap::box_art_url(game, height, ppp);
+                    if let Some(image) = Self::image_for(ui, &mut app.cache, &url, size) {
+                        let cache = &mut app.cache;
+                        ui.add(image).on_hover_ui(|ui| {
+                            // the popup gets its own, larger, box art
+                            let size = vec2(Self::BOX_ART_POPUP * 0.75, Self::BOX_ART_POPUP);
+                            let url = GameMap::box_art_url(game, Self::BOX_ART_POPUP, ppp);
+                            match Self::image_for(ui, cache, &url, size) {
+                                Some(image) => ui.add(image),
+                                None => ui.add_sized(size, Spinner::new()),
+                            };
+                            ui.strong(&game.name);
+                        });
+                    } else {
+                        Self::display_broken_image(ui, &mut app.cache, &url, size);
                     }
                 }
 
                 ui.add(Label::new(&stream.title).wrap(true));
             });
         });
+
+        if refresh {
+            app.stream_check.refresh(&user.id);
+        }
     }
 
-    fn display_fragments(
+    pub(crate) fn display_fragments(
         ui: &mut egui::Ui,
         image_size: Vec2,
-        dt: f32,
         msg: &crate::state::Message,
         emote_map: &mut EmoteMap,
         cache: &mut ImageCache,
+        open_link: &mut Option<Permalink>,
     ) {
         ui.scope(|ui| {
             if msg.opts.local {
                 ui.visuals_mut().override_text_color = Some(Color32::WHITE);
             }
+            // it's still readable, for the moderators who want to know what happened
+            if msg.deleted {
+                ui.visuals_mut().override_text_color = Some(ui.visuals().weak_text_color());
+            }
+            let text = |text: &str| {
+                let text = RichText::new(text);
+                if msg.deleted {
+                    return text.strikethrough();
+                }
+                text
+            };
 
             for span in &msg.spans {
+                let data = span.text(&msg.data);
                 match span {
-                    Span::Text(text) => {
-                        ui.label(text);
+                    // 7tv emotes aren't in the tags, so they're picked out of the text
+                    Span::Text(..) if emote_map.has_third_party() => {
+                        let mut start = 0;
+                        for word in data.split_ascii_whitespace() {
+                            let Some(id) = emote_map.get_third_party_id(word) else { continue };
+
+                            let pos = word.as_ptr() as usize - data.as_ptr() as usize;
+                            let before = data[start..pos].trim();
+                            if !before.is_empty() {
+                                ui.label(text(before));
+                            }
+                            start = pos + word.len();
+
+                            let shown = Self::display_emote(
+                                ui, msg, id, word, emote_map, cache, image_size,
+                            );
+                            if !shown {
+                                ui.label(text(word));
+                            }
+                        }
+
+                        let rest = data[start..].trim();
+                        if !rest.is_empty() {
+                            ui.label(text(rest));
+                        }
                     }
 
-                    Span::Emote((id, name)) => {
-                        if let Some(url) = emote_map.get_emote_url(id) {
-                            if let Some(image) = cache.get_image(url) {
-                                let mut image = image.as_egui_image(image_size, dt);
-                                if msg.opts.old {
-                                    image = image
-                                        .tint(Color32::WHITE.gamma_multiply(Self::INACTIVE_GAMMA));
-                                }
+                    Span::Text(..) => {
+                        ui.label(text(data));
+                    }
 
-                                ui.add(image).on_hover_text(name);
-                                continue;
-                            }
+                    Span::Emote { id, .. } => {
+                        if !Self::display_emote(ui, msg, id, data, emote_map, cache, image_size) {
+                            ui.label(text(data));
                         }
-                        ui.label(name);
                     }
 
-                    Span::Url(url) => {
-                        ui.hyperlink(url);
+                    Span::Url(..) if msg.deleted => {
+                        ui.label(text(data));
                     }
+
+                    Span::Url(..) => match Permalink::parse(data) {
+                        Some(link) => {
+                            if ui.link(data).clicked() {
+                                open_link.replace(link);
+                            }
+                        }
+                        None => {
+                            ui.hyperlink(data);
+                        }
+                    },
                 }
             }
         });
     }
 
-    fn create_self_message(app: &mut App, data: &str) -> (PrivmsgBuilder, TagsBuilder) {
-        let channel = &app.state.channels[app.state.active].name;
+    // this returns false when there's no image to show yet
+    fn display_emote(
+        ui: &mut egui::Ui,
+        msg: &crate::state::Message,
+        id: &str,
+        name: &str,
+        emote_map: &EmoteMap,
+        cache: &mut ImageCache,
+        image_size: Vec2,
+    ) -> bool {
+        let Some(url) = emote_map.get_emote_url(id) else { return false };
+        if let Some(mut image) = Self::image_for(ui, cache, url, image_size) {
+            if msg.opts.old || msg.deleted {
+                image = image.tint(Color32::WHITE.gamma_multiply(Self::INACTIVE_GAMMA));
+            }
+
+            ui.add(image).on_hover_text(name);
+            return true;
+        }
+        Self::display_broken_image(ui, cache, url, image_size)
+    }
+
+    // animations only advance while they're actually on screen and we're not idle
+    fn image_for(
+        ui: &egui::Ui,
+        cache: &mut ImageCache,
+        url: &str,
+        size: Vec2,
+    ) -> Option<egui::Image> {
+        let visible =
+            !cache.is_idle() && ui.is_rect_visible(Rect::from_min_size(ui.cursor().min, size));
+        let image = cache.get_image(url)?;
+        let animated = visible && image.is_animated();
+
+        let image = if visible {
+            image.as_egui_image(size, ui.ctx().frame_nr())
+        } else {
+            image.as_paused_egui_image(size)
+        };
+
+        if animated {
+            cache.mark_animating();
+        }
+        Some(image)
+    }
+
+    fn display_broken_image(
+        ui: &mut egui::Ui,
+        cache: &mut ImageCache,
+        url: &str,
+        size: Vec2,
+    ) -> bool {
+        if !cache.is_failed(url) {
+            return false;
+        }
+
+        let resp = BrokenImage { size }
+            .display(ui)
+            .on_hover_text("failed to load image, click to retry");
+        if resp.clicked() {
+            cache.retry(url);
+        }
+        true
+    }
+
+    fn create_self_message(
+        app: &mut App,
+        target: usize,
+        data: &str,
+    ) -> (PrivmsgBuilder, TagsBuilder) {
+        let channel = &app.state.channels[target].name;
         let identity = app.state.identity.as_ref().expect("we should be connected");
 
         let mut tags = Tags::builder() //
@@ -396,7 +2319,7 @@ impl<'a> MainView<'a> {
 
         let pm = Privmsg::builder()
             .sender(&identity.name)
-            .channel(channel)
+            .channel(channel.as_str())
             .data(data);
         (pm, tags)
     }
@@ -1,8 +1,56 @@
+mod badge_browser_view;
+pub use badge_browser_view::BadgeBrowserView;
+
+mod bookmarks_view;
+pub use bookmarks_view::{BookmarkAction, BookmarkList, BookmarksView};
+
+mod diagnostics_view;
+pub use diagnostics_view::DiagnosticsView;
+
+mod emote_browser_view;
+pub use emote_browser_view::{EmoteBrowser, EmoteBrowserView};
+
+mod frame_stats_view;
+pub use frame_stats_view::FrameStatsView;
+
+mod history_search_view;
+pub use history_search_view::{HistorySearch, HistorySearchView};
+
+mod identity_chip;
+pub use identity_chip::{IdentityChip, TokenCheck};
+
 mod initial_view;
 pub use initial_view::InitialView;
 
+mod layout;
+pub use layout::{SplitDirection, SplitLayout};
+
 mod main_view;
 pub use main_view::MainView;
 
+mod notify_rules_view;
+pub use notify_rules_view::NotifyRulesView;
+
+mod quick_switcher_view;
+pub use quick_switcher_view::{QuickSwitch, QuickSwitcher, QuickSwitcherView};
+
+mod raw_message_view;
+pub use raw_message_view::{RawMessage, RawMessageView};
+
+mod settings_view;
+pub use settings_view::SettingsView;
+
 mod start_view;
 pub use start_view::StartView;
+
+mod toasts_view;
+pub use toasts_view::{NotificationsView, ToastsView};
+
+mod user_card_view;
+pub use user_card_view::{FollowStatus, UserCard, UserCardAction, UserCardView};
+
+mod user_search_view;
+pub use user_search_view::{UserSearch, UserSearchView};
+
+mod watchlist_view;
+pub use watchlist_view::{WatchlistAction, WatchlistView};
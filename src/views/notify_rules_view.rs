@@ -0,0 +1,116 @@
+use egui::{ComboBox, Grid, TextEdit};
+
+use crate::state::{NotifyAction, Rule, Trigger};
+
+pub struct NotifyRulesView<'a> {
+    pub open: &'a mut bool,
+    pub rules: &'a mut Vec<Rule>,
+    pub channels: Vec<&'a str>,
+}
+
+impl<'a> NotifyRulesView<'a> {
+    /// Returns whether any of the rules were changed
+    pub fn display(self, ctx: &egui::Context) -> bool {
+        if !*self.open {
+            return false;
+        }
+
+        let before = self.rules.clone();
+        let (rules, channels) = (self.rules, self.channels);
+
+        egui::Window::new("Notification rules")
+            .open(self.open)
+            .collapsible(false)
+            .show(ctx, |ui| {
+                let mut remove = None;
+
+                Grid::new("notify-rules")
+                    .num_columns(6)
+                    .striped(true)
+                    .show(ui, |ui| {
+                        ui.label("");
+                        ui.strong("channel");
+                        ui.strong("when");
+                        ui.label("");
+                        ui.strong("then");
+                        ui.end_row();
+
+                        for (i, rule) in rules.iter_mut().enumerate() {
+                            ui.checkbox(&mut rule.enabled, "");
+
+                            ComboBox::from_id_source(("rule-channel", i))
+                                .selected_text(rule.channel.as_deref().unwrap_or("any channel"))
+                                .show_ui(ui, |ui| {
+                                    ui.selectable_value(&mut rule.channel, None, "any channel");
+                                    for &channel in &channels {
+                                        let value = Some(channel.to_string());
+                                        ui.selectable_value(&mut rule.channel, value, channel);
+                                    }
+                                });
+
+                            ComboBox::from_id_source(("rule-trigger", i))
+                                .selected_text(rule.trigger.label())
+                                .show_ui(ui, |ui| {
+                                    for trigger in Trigger::defaults() {
+                                        let label = trigger.label();
+                                        let selected = std::mem::discriminant(&trigger)
+                                            == std::mem::discriminant(&rule.trigger);
+                                        if ui.selectable_label(selected, label).clicked()
+                                            && !selected
+                                        {
+                                            rule.trigger = trigger;
+                                        }
+                                    }
+                                });
+
+                            match &mut rule.trigger {
+                                Trigger::User(text) => ui.add(
+                                    TextEdit::singleline(text)
+                                        .hint_text("login")
+                                        .desired_width(100.0),
+                                ),
+                                Trigger::Keyword(text) => ui.add(
+                                    TextEdit::singleline(text)
+                                        .hint_text("text")
+                                        .desired_width(100.0),
+                                ),
+                                Trigger::Mention | Trigger::GoLive => ui.label(""),
+                            };
+
+                            ComboBox::from_id_source(("rule-action", i))
+                                .selected_text(rule.action.label())
+                                .show_ui(ui, |ui| {
+                                    for action in NotifyAction::ALL {
+                                        ui.selectable_value(
+                                            &mut rule.action,
+                                            action,
+                                            action.label(),
+                                        );
+                                    }
+                                });
+
+                            if ui.small_button("remove").clicked() {
+                                remove.replace(i);
+                            }
+                            ui.end_row();
+                        }
+                    });
+
+                if let Some(i) = remove {
+                    rules.remove(i);
+                }
+
+                if ui.button("Add rule").clicked() {
+                    rules.push(Rule::default());
+                }
+
+                ui.label(
+                    egui::RichText::new("sounds and notifications are muted by do not disturb")
+                        .small()
+                        .weak(),
+                );
+            });
+
+        *rules != before
+    }
+}
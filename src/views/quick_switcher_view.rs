@@ -0,0 +1,129 @@
+use egui::{Align2, Color32, Key, RichText, ScrollArea, TextEdit, Vec2};
+use indexmap::IndexSet;
+
+use crate::{
+    helix,
+    state::{Channel, ChannelSearch},
+    twitch::ChannelName,
+    widgets::SearchResults,
+};
+
+/// Jump to an open channel by typing some of its name, or join one that isn't open
+#[derive(Default)]
+pub struct QuickSwitcher {
+    pub query: String,
+    /// Channels on twitch that match, for the ones that have never been joined
+    search: ChannelSearch,
+}
+
+/// What was picked in the switcher
+pub enum QuickSwitch {
+    /// The index of an open channel
+    Show(usize),
+    Join(ChannelName),
+    ToggleFavorite(ChannelName),
+}
+
+pub struct QuickSwitcherView<'a> {
+    pub switcher: &'a mut Option<QuickSwitcher>,
+    pub channels: &'a [Channel],
+    pub favorites: &'a IndexSet<ChannelName>,
+    pub helix: &'a helix::Client,
+    pub live: Color32,
+}
+
+impl<'a> QuickSwitcherView<'a> {
+    pub fn display(self, ctx: &egui::Context) -> Option<QuickSwitch> {
+        let switcher = self.switcher.as_mut()?;
+        if switcher.search.poll(&switcher.query, self.helix) {
+            ctx.request_repaint_after(ChannelSearch::SEARCH_DELAY);
+        }
+
+        let mut picked = None;
+        egui::Window::new("switch channel")
+            .id(egui::Id::new("quick-switcher"))
+            .collapsible(false)
+            .resizable(false)
+            .title_bar(false)
+            .anchor(Align2::CENTER_TOP, Vec2::new(0.0, 60.0))
+            .show(ctx, |ui| {
+                let resp = ui.add(
+                    TextEdit::singleline(&mut switcher.query)
+                        .hint_text("channel")
+                        .desired_width(300.0),
+                );
+                resp.request_focus();
+                let enter = ui.input(|i| i.key_pressed(Key::Enter));
+
+                // the favorites come first, then the open channels in the order of the tabs
+                let query = ChannelName::new(switcher.query.trim());
+                let names = self
+                    .favorites
+                    .iter()
+                    .chain(self.channels.iter().map(|c| &c.name))
+                    .filter(|name| name.contains(&*query))
+                    .collect::<IndexSet<_>>();
+
+                // the first one is what enter picks
+                let mut first = None;
+                ScrollArea::vertical().max_height(300.0).show(ui, |ui| {
+                    for name in &names {
+                        let index = self.channels.iter().position(|c| &c.name == *name);
+                        let pick = match index {
+                            Some(i) => QuickSwitch::Show(i),
+                            None => QuickSwitch::Join((*name).clone()),
+                        };
+
+                        ui.horizontal(|ui| {
+                            let favorite = self.favorites.contains(*name);
+                            if ui
+                                .selectable_label(favorite, "favorite")
+                                .on_hover_text("always join this on startup")
+                                .clicked()
+                            {
+                                picked.replace(QuickSwitch::ToggleFavorite((*name).clone()));
+                            }
+                            if ui.link(name.as_str()).clicked() {
+                                picked.replace(pick);
+                            } else if first.is_none() {
+                                first.replace(pick);
+                            }
+                            if index.is_none() {
+                                ui.label(RichText::new("not joined").small().weak());
+                            }
+                        });
+                    }
+
+                    if !query.is_empty() && !names.contains(&query) {
+                        if ui.link(format!("join #{query}")).clicked() {
+                            picked.replace(QuickSwitch::Join(query.clone()));
+                        }
+                        first.get_or_insert(QuickSwitch::Join(query));
+                    }
+
+                    if !switcher.search.results.is_empty() {
+                        ui.separator();
+                        ui.label(RichText::new("search results").weak());
+                        let results = SearchResults {
+                            search: &switcher.search,
+                            live: self.live,
+                        };
+                        if let Some(channel) = results.display(ui) {
+                            picked.replace(QuickSwitch::Join(ChannelName::new(&channel)));
+                        }
+                    }
+                });
+
+                if enter && picked.is_none() {
+                    picked = first;
+                }
+            });
+
+        // the favorites can be changed a few at a time
+        let done = matches!(picked, Some(QuickSwitch::Show(..) | QuickSwitch::Join(..)));
+        if done || ctx.input(|i| i.key_pressed(Key::Escape)) {
+            self.switcher.take();
+        }
+        picked
+    }
+}
@@ -0,0 +1,134 @@
+use egui::{Align2, Grid, ScrollArea, Spinner, TextEdit, Vec2};
+use uuid::Uuid;
+
+use crate::{db, resolver::Fut};
+
+pub struct RawMessage {
+    pub id: Uuid,
+    /// This is `None` when the message never made it into the history
+    pub raw: Option<Box<str>>,
+    fut: Option<Fut<Option<Option<db::Message>>>>,
+}
+
+impl RawMessage {
+    /// Look up the message off the ui thread, see [`crate::App::history_path`]
+    pub fn new(db: &'static str, id: Uuid) -> Self {
+        Self {
+            id,
+            raw: None,
+            fut: Some(db::Connection::message_by_id(db, id)),
+        }
+    }
+
+    fn poll(&mut self) {
+        let Some(found) = self.fut.as_mut().and_then(Fut::try_resolve) else { return };
+        self.fut.take();
+        self.raw = found.flatten().map(|msg| msg.raw);
+    }
+}
+
+pub struct RawMessageView<'a> {
+    pub inspect: &'a mut Option<RawMessage>,
+}
+
+impl<'a> RawMessageView<'a> {
+    pub fn display(self, ctx: &egui::Context) {
+        let Some(inspect) = self.inspect.as_mut() else { return };
+        inspect.poll();
+
+        let mut open = true;
+        egui::Window::new("raw message")
+            .id(egui::Id::new("raw-message"))
+            .open(&mut open)
+            .collapsible(false)
+            .anchor(Align2::CENTER_CENTER, Vec2::ZERO)
+            .show(ctx, |ui| {
+                ui.horizontal(|ui| {
+                    ui.label("id");
+                    ui.monospace(inspect.id.to_string());
+                });
+
+                if inspect.fut.is_some() {
+                    ui.add(Spinner::new());
+                    return;
+                }
+                let Some(raw) = inspect.raw.as_deref() else {
+                    ui.label("this message isn't in the history");
+                    return;
+                };
+
+                ui.separator();
+                ui.horizontal(|ui| {
+                    ui.strong("line");
+                    if ui.small_button("copy").clicked() {
+                        ui.output_mut(|o| o.copied_text = raw.to_string());
+                    }
+                });
+
+                let mut line = raw;
+                ui.add(
+                    TextEdit::multiline(&mut line)
+                        .code_editor()
+                        .desired_rows(2)
+                        .desired_width(f32::INFINITY),
+                );
+
+                let tags = tags(raw).collect::<Vec<_>>();
+
+                ui.separator();
+                ui.strong("badges");
+                let badges = tags
+                    .iter()
+                    .find_map(|(key, value)| (*key == "badges").then_some(value.as_str()))
+                    .unwrap_or_default();
+                if badges.is_empty() {
+                    ui.label("none");
+                } else {
+                    Grid::new("raw-message-badges")
+                        .num_columns(2)
+                        .striped(true)
+                        .show(ui, |ui| {
+                            for badge in badges.split(',') {
+                                let (name, version) = badge.split_once('/').unwrap_or((badge, ""));
+                                ui.monospace(name);
+                                ui.monospace(version);
+                                ui.end_row();
+                            }
+                        });
+                }
+
+                ui.separator();
+                ui.strong("tags");
+                ScrollArea::vertical().max_height(300.0).show(ui, |ui| {
+                    Grid::new("raw-message-tags")
+                        .num_columns(2)
+                        .striped(true)
+                        .show(ui, |ui| {
+                            for (key, value) in &tags {
+                                ui.monospace(*key);
+                                ui.monospace(value);
+                                ui.end_row();
+                            }
+                        });
+                });
+            });
+
+        if !open {
+            self.inspect.take();
+        }
+    }
+}
+
+// the tags with their values unescaped, in the order they were sent
+fn tags(line: &str) -> impl Iterator<Item = (&str, String)> {
+    line.strip_prefix('@')
+        .and_then(|line| line.split_once(' '))
+        .map(|(tags, _)| tags)
+        .unwrap_or_default()
+        .split(';')
+        .filter(|tag| !tag.is_empty())
+        .map(|tag| {
+            let (key, value) = tag.split_once('=').unwrap_or((tag, ""));
+            (key, crate::twitch::unescape_tag(value))
+        })
+}
@@ -0,0 +1,193 @@
+use egui::{
+    color_picker::{color_edit_button_srgba, Alpha},
+    Color32, ComboBox, DragValue, Grid,
+};
+
+use crate::{
+    app::{FontManager, Theme},
+    state::{AnimationRate, ImageScale, Preferences},
+};
+
+pub struct SettingsView<'a> {
+    pub open: &'a mut bool,
+    pub preferences: &'a mut Preferences,
+    pub theme: &'a mut Theme,
+    pub fonts: &'a mut FontManager,
+    pub show_rules: &'a mut bool,
+}
+
+impl<'a> SettingsView<'a> {
+    /// Returns whether any of the preferences were changed, the theme is compared by the caller
+    pub fn display(self, ctx: &egui::Context) -> bool {
+        if !*self.open {
+            return false;
+        }
+
+        let before = self.preferences.clone();
+        let (preferences, theme, show_rules) = (self.preferences, self.theme, self.show_rules);
+        let fonts = self.fonts;
+
+        egui::Window::new("Settings")
+            .open(self.open)
+            .collapsible(false)
+            .resizable(false)
+            .show(ctx, |ui| {
+                Grid::new("settings-grid")
+                    .num_columns(2)
+                    .spacing([16.0, 6.0])
+                    .show(ui, |ui| {
+                        ui.label("Timestamps");
+                        ui.checkbox(&mut preferences.timestamps, "show when messages came in");
+                        ui.end_row();
+
+                        ui.label("Animations");
+                        ui.vertical(|ui| {
+                            for option in AnimationRate::ALL {
+                                ui.radio_value(
+                                    &mut preferences.animation_rate,
+                                    option,
+                                    option.label(),
+                                );
+                            }
+                        });
+                        ui.end_row();
+
+                        ui.label("Image scale");
+                        ui.vertical(|ui| {
+                            for option in ImageScale::ALL {
+                                ui.radio_value(
+                                    &mut preferences.image_scale,
+                                    option,
+                                    option.label(),
+                                );
+                            }
+                        });
+                        ui.end_row();
+
+                        ui.label("History");
+                        ui.horizontal(|ui| {
+                            ui.add(
+                                DragValue::new(&mut preferences.history_limit)
+                                    .clamp_range(0..=Preferences::MAX_HISTORY_LIMIT)
+                                    .speed(10),
+                            );
+                            ui.label("messages loaded when a channel is opened");
+                        });
+                        ui.end_row();
+
+                        ui.label("Live checks");
+                        ui.horizontal(|ui| {
+                            ui.label("every");
+                            ui.add(
+                                DragValue::new(&mut preferences.stream_check_secs)
+                                    .clamp_range(Preferences::STREAM_CHECK_SECS)
+                                    .suffix("s"),
+                            );
+                            ui.label("channels offline for a while are checked less often");
+                        });
+                        ui.end_row();
+
+                        ui.label("Font");
+                        ui.horizontal(|ui| {
+                            Self::font_picker(ui, fonts, &mut preferences.font_path);
+                            ui.add(
+                                DragValue::new(&mut preferences.font_size)
+                                    .clamp_range(Preferences::FONT_SIZES)
+                                    .speed(0.25)
+                                    .suffix("pt"),
+                            );
+                        });
+                        ui.end_row();
+
+                        ui.label("Theme");
+                        ui.horizontal(|ui| {
+                            for (dark, label) in [(true, "dark"), (false, "light")] {
+                                let selected = theme.dark == dark;
+                                if ui.radio(selected, label).clicked() && !selected {
+                                    *theme = theme.preset(dark);
+                                }
+                            }
+                        });
+                        ui.end_row();
+
+                        let fixed = [
+                            ("Accent", &mut theme.accent),
+                            ("Unread marker", &mut theme.marker),
+                        ];
+                        for (label, color) in fixed {
+                            ui.label(label);
+                            color_edit_button_srgba(ui, color, Alpha::Opaque);
+                            ui.end_row();
+                        }
+
+                        let defaults = (theme.background(), theme.text(), theme.mention());
+                        Self::color_role(ui, "Background", &mut theme.background, defaults.0);
+                        Self::color_role(ui, "Message text", &mut theme.text, defaults.1);
+                        Self::color_role(ui, "Mentions", &mut theme.mention, defaults.2);
+                        Self::color_role(
+                            ui,
+                            "Own messages",
+                            &mut theme.own_message,
+                            Color32::TRANSPARENT,
+                        );
+
+                        ui.label("Notifications");
+                        if ui.button("Edit notification rules").clicked() {
+                            *show_rules = true;
+                        }
+                        ui.end_row();
+                    });
+
+                ui.separator();
+                if ui.button("Reset to defaults").clicked() {
+                    *preferences = Preferences::default();
+                }
+            });
+
+        *preferences != before
+    }
+
+    fn font_picker(
+        ui: &mut egui::Ui,
+        fonts: &mut FontManager,
+        picked: &mut Option<std::path::PathBuf>,
+    ) {
+        let name = picked
+            .as_deref()
+            .and_then(|path| path.file_stem())
+            .map_or_else(
+                || "Roboto (bundled)".to_string(),
+                |name| name.to_string_lossy().into_owned(),
+            );
+
+        ComboBox::from_id_source("font-family")
+            .selected_text(name)
+            .width(200.0)
+            .show_ui(ui, |ui| {
+                ui.selectable_value(picked, None, "Roboto (bundled)");
+                let Some(system) = fonts.system_fonts() else {
+                    ui.weak("looking for fonts..");
+                    return;
+                };
+                for font in system {
+                    let value = Some(font.path.clone());
+                    ui.selectable_value(picked, value, &font.name);
+                }
+            });
+    }
+
+    // an unset role shows what it falls back to, picking a color sets it
+    fn color_role(ui: &mut egui::Ui, label: &str, role: &mut Option<Color32>, default: Color32) {
+        ui.label(label);
+        ui.horizontal(|ui| {
+            let mut color = role.unwrap_or(default);
+            if color_edit_button_srgba(ui, &mut color, Alpha::Opaque).changed() {
+                role.replace(color);
+            }
+            if role.is_some() && ui.small_button("reset").clicked() {
+                role.take();
+            }
+        });
+        ui.end_row();
+    }
+}
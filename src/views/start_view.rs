@@ -4,8 +4,7 @@ use std::{
 };
 
 use egui::{
-    pos2, vec2, Align2, Area, CentralPanel, Color32, Frame, Margin, Rect, Sense, Spinner,
-    TextStyle, Vec2,
+    pos2, vec2, Align2, Area, CentralPanel, Frame, Margin, Rect, Sense, Spinner, TextStyle, Vec2,
 };
 
 use crate::{
@@ -49,6 +48,10 @@ impl<'a> StartView<'a> {
             twitch::Status::Reconnecting { when, after } => {
                 self.display_reconnecting(ctx, when, after);
             }
+            // the app asks what to do about these
+            twitch::Status::Contested | twitch::Status::InvalidCredentials => {
+                CentralPanel::default().show(ctx, |_ui| {});
+            }
         }
     }
 
@@ -59,28 +62,6 @@ impl<'a> StartView<'a> {
         let size = ctx.screen_rect().size() * 0.2;
         let center = ctx.screen_rect().center() - pos2(0.0, size.y * 0.5);
 
-        let image_frame = |ui: &mut egui::Ui| {
-            Frame::none()
-                .inner_margin(Margin::symmetric(10.0, 0.0))
-                .show(ui, |ui| {
-                    ui.horizontal(|ui| {
-                        let fid = TextStyle::Body.resolve(ui.style());
-                        let w = ui.fonts(|fonts| fonts.glyph_width(&fid, ' '));
-                        ui.scope(|ui| {
-                            ui.spacing_mut().item_spacing.x = w;
-                            ui.colored_label(Color32::from_rgb(0x64, 0x41, 0xA5), "Twitch");
-                            ui.label("name:");
-                            ui.monospace(self.twitch.user_name())
-                        });
-                    });
-                });
-        };
-
-        Area::new("start-inlay")
-            .anchor(Align2::RIGHT_TOP, Vec2::ZERO)
-            .movable(false)
-            .show(ctx, image_frame);
-
         if connecting {
             Area::new("connecting-inlay")
                 .anchor(Align2::CENTER_CENTER, vec2(10.0, 0.0))
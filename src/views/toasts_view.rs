@@ -0,0 +1,94 @@
+use egui::{Align2, Color32, RichText, ScrollArea, Vec2};
+
+use crate::state::{Toast, ToastKind, Toasts};
+
+fn color(ui: &egui::Ui, kind: ToastKind) -> Color32 {
+    match kind {
+        ToastKind::Info => ui.visuals().text_color(),
+        ToastKind::Error => ui.visuals().error_fg_color,
+    }
+}
+
+fn timestamp(toast: &Toast) -> String {
+    let format = time::macros::format_description!("[hour]:[minute]:[second]");
+    toast.when.format(format).unwrap_or_default()
+}
+
+/// The toasts that are still up, stacked in the bottom right corner
+pub struct ToastsView<'a> {
+    pub toasts: &'a mut Toasts,
+}
+
+impl<'a> ToastsView<'a> {
+    pub fn display(self, ctx: &egui::Context) {
+        if let Some(next) = self.toasts.poll() {
+            ctx.request_repaint_after(next);
+        }
+        if self.toasts.active().is_empty() {
+            return;
+        }
+
+        let mut dismiss = None;
+        egui::Area::new("toasts")
+            .anchor(Align2::RIGHT_BOTTOM, Vec2::new(-8.0, -48.0))
+            .order(egui::Order::Foreground)
+            .show(ctx, |ui| {
+                for (i, toast) in self.toasts.active().iter().enumerate() {
+                    egui::Frame::popup(ui.style()).show(ui, |ui| {
+                        ui.set_max_width(320.0);
+                        ui.horizontal(|ui| {
+                            ui.label(RichText::new(&toast.text).color(color(ui, toast.kind)));
+                            if ui.small_button("x").on_hover_text("dismiss").clicked() {
+                                dismiss = Some(i);
+                            }
+                        });
+                    });
+                }
+            });
+
+        if let Some(i) = dismiss {
+            self.toasts.dismiss(i);
+        }
+    }
+}
+
+/// Every toast that was shown, newest first
+pub struct NotificationsView<'a> {
+    pub open: &'a mut bool,
+    pub toasts: &'a mut Toasts,
+}
+
+impl<'a> NotificationsView<'a> {
+    pub fn display(self, ctx: &egui::Context) {
+        if !*self.open {
+            return;
+        }
+        // opening this counts as having seen them
+        self.toasts.unseen_errors = 0;
+
+        let toasts = self.toasts;
+        egui::Window::new("Notifications")
+            .open(self.open)
+            .collapsible(false)
+            .show(ctx, |ui| {
+                ScrollArea::vertical().max_height(300.0).show(ui, |ui| {
+                    let mut any = false;
+                    for toast in toasts.history() {
+                        any = true;
+                        ui.horizontal_wrapped(|ui| {
+                            ui.monospace(timestamp(toast));
+                            ui.label(RichText::new(&toast.text).color(color(ui, toast.kind)));
+                        });
+                    }
+                    if !any {
+                        ui.label(RichText::new("nothing yet").small().weak());
+                    }
+                });
+
+                ui.separator();
+                if ui.button("Clear").clicked() {
+                    toasts.clear_history();
+                }
+            });
+    }
+}
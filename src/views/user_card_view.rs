@@ -0,0 +1,205 @@
+use std::time::Duration;
+
+use egui::{Align2, Grid, RichText, ScrollArea, Vec2};
+use time::OffsetDateTime;
+
+use crate::{
+    db,
+    resolver::Fut,
+    runtime::{ImageCache, UserMap},
+    twitch::ChannelName,
+};
+
+/// Whether the user follows the channel, only moderators can find this out
+pub enum FollowStatus {
+    Loading(Fut<anyhow::Result<Option<OffsetDateTime>>>),
+    Following(OffsetDateTime),
+    NotFollowing,
+    Unknown,
+}
+
+pub struct UserCard {
+    pub login: String,
+    pub user_id: String,
+    pub channel: ChannelName,
+    /// Whether we can time out or ban them in the channel
+    pub moderator: bool,
+    pub follow: FollowStatus,
+    /// What they said in the channel, according to the history, once it's been read
+    pub messages: Option<Vec<db::Message>>,
+    pub pending_messages: Option<Fut<Option<Vec<db::Message>>>>,
+}
+
+impl UserCard {
+    pub const RECENT_MESSAGES: usize = 50;
+
+    fn poll_follow(&mut self) {
+        let FollowStatus::Loading(fut) = &mut self.follow else { return };
+        self.follow = match fut.try_resolve() {
+            Some(Ok(Some(followed_at))) => FollowStatus::Following(followed_at),
+            Some(Ok(None)) => FollowStatus::NotFollowing,
+            Some(Err(err)) => {
+                log!("cannot get the follow status for {}: {err}", self.login);
+                FollowStatus::Unknown
+            }
+            None => return,
+        };
+    }
+
+    fn poll_messages(&mut self) {
+        let Some(found) = self.pending_messages.as_mut().and_then(Fut::try_resolve) else { return };
+        self.pending_messages.take();
+        self.messages.replace(found.unwrap_or_default());
+    }
+}
+
+/// What was picked on the card, these are done in the card's channel
+pub enum UserCardAction {
+    Timeout {
+        login: String,
+        channel: ChannelName,
+        duration: Duration,
+    },
+    Ban {
+        login: String,
+        channel: ChannelName,
+    },
+}
+
+pub struct UserCardView<'a> {
+    pub card: &'a mut Option<UserCard>,
+    pub user_map: &'a mut UserMap,
+    pub cache: &'a mut ImageCache,
+}
+
+impl<'a> UserCardView<'a> {
+    const TIMEOUTS: [(&str, u64); 3] = [("1m", 60), ("10m", 10 * 60), ("1h", 60 * 60)];
+
+    pub fn display(self, ctx: &egui::Context) -> Option<UserCardAction> {
+        let card = self.card.as_mut()?;
+        card.poll_follow();
+        card.poll_messages();
+
+        let mut open = true;
+        let mut action = None;
+        egui::Window::new(&card.login)
+            .id(egui::Id::new("user-card"))
+            .open(&mut open)
+            .collapsible(false)
+            .resizable(false)
+            .anchor(Align2::CENTER_CENTER, Vec2::ZERO)
+            .show(ctx, |ui| {
+                ui.horizontal(|ui| {
+                    let user = self.user_map.get(&card.login);
+                    if let Some(image) = user
+                        .and_then(|user| self.cache.get_image(&user.profile_image_url))
+                        .map(|image| image.as_egui_image(Vec2::splat(64.0), ui.ctx().frame_nr()))
+                    {
+                        ui.add(image);
+                    }
+
+                    Grid::new("user-card-info").num_columns(2).show(ui, |ui| {
+                        if let Some(user) = user {
+                            ui.label("name");
+                            ui.label(&user.display_name);
+                            ui.end_row();
+
+                            ui.label("created");
+                            ui.label(format!(
+                                "{} ({} ago)",
+                                user.created_at.date(),
+                                Self::format_age(user.created_at)
+                            ));
+                            ui.end_row();
+                        }
+
+                        ui.label("follows");
+                        ui.label(match card.follow {
+                            FollowStatus::Loading(..) => String::from("..."),
+                            FollowStatus::Following(since) => {
+                                format!("for {}", Self::format_age(since))
+                            }
+                            FollowStatus::NotFollowing => String::from("no"),
+                            FollowStatus::Unknown => String::from("unknown"),
+                        });
+                        ui.end_row();
+                    });
+
+                    // the name and picture are kept for a while, this gets them now
+                    if ui
+                        .small_button("refresh")
+                        .on_hover_text("fetch their name and picture again")
+                        .clicked()
+                    {
+                        self.user_map.refresh(&card.login);
+                    }
+                });
+
+                if card.moderator {
+                    ui.horizontal(|ui| {
+                        ui.label("time out");
+                        for (label, secs) in Self::TIMEOUTS {
+                            if ui.small_button(label).clicked() {
+                                action.replace(UserCardAction::Timeout {
+                                    login: card.login.clone(),
+                                    channel: card.channel.clone(),
+                                    duration: Duration::from_secs(secs),
+                                });
+                            }
+                        }
+                        ui.separator();
+                        if ui.small_button("ban").clicked() {
+                            action.replace(UserCardAction::Ban {
+                                login: card.login.clone(),
+                                channel: card.channel.clone(),
+                            });
+                        }
+                    });
+                }
+
+                ui.separator();
+                ui.label(RichText::new(format!("recent messages in #{}", card.channel)).small());
+                let Some(messages) = &card.messages else {
+                    ui.add(egui::Spinner::new());
+                    return;
+                };
+                if messages.is_empty() {
+                    ui.label(RichText::new("none in the history").weak());
+                    return;
+                }
+
+                let format = time::macros::format_description!("[hour]:[minute]");
+                ScrollArea::vertical()
+                    .max_height(200.0)
+                    .stick_to_bottom(true)
+                    .show(ui, |ui| {
+                        for msg in messages {
+                            ui.horizontal_wrapped(|ui| {
+                                ui.label(
+                                    RichText::new(msg.timestamp.format(format).unwrap_or_default())
+                                        .monospace()
+                                        .weak(),
+                                );
+                                ui.label(&*msg.data);
+                            });
+                        }
+                    });
+            });
+
+        if !open {
+            self.card.take();
+        }
+        action
+    }
+
+    fn format_age(since: OffsetDateTime) -> String {
+        let days = (OffsetDateTime::now_utc() - since).whole_days();
+        match days {
+            ..=0 => String::from("less than a day"),
+            1 => String::from("1 day"),
+            2..=364 => format!("{days} days"),
+            _ if days < 2 * 365 => String::from("1 year"),
+            _ => format!("{} years", days / 365),
+        }
+    }
+}
@@ -0,0 +1,83 @@
+use egui::{Align2, Grid, Spinner, Vec2};
+
+use crate::{
+    db::{self, HistoryQuery, UserChannel},
+    resolver::Fut,
+};
+
+pub struct UserSearch {
+    pub login: String,
+    channels: Option<Vec<UserChannel>>,
+    fut: Option<Fut<Option<Vec<UserChannel>>>>,
+}
+
+impl UserSearch {
+    /// Look up the channels off the ui thread, see [`crate::App::history_path`]
+    pub fn new(db: &'static str, login: &str, user_id: &str) -> Self {
+        Self {
+            login: login.to_string(),
+            channels: None,
+            fut: Some(db::Connection::channels_for_user(db, user_id.to_string())),
+        }
+    }
+
+    fn poll(&mut self) {
+        let Some(found) = self.fut.as_mut().and_then(Fut::try_resolve) else { return };
+        self.fut.take();
+        self.channels.replace(found.unwrap_or_default());
+    }
+}
+
+pub struct UserSearchView<'a> {
+    pub search: &'a mut Option<UserSearch>,
+}
+
+impl<'a> UserSearchView<'a> {
+    /// Returns a search for the user's messages in the channel they picked
+    pub fn display(self, ctx: &egui::Context) -> Option<HistoryQuery> {
+        let search = self.search.as_mut()?;
+        search.poll();
+
+        let mut open = true;
+        let mut query = None;
+        egui::Window::new(format!("channels for {}", search.login))
+            .id(egui::Id::new("user-search"))
+            .open(&mut open)
+            .collapsible(false)
+            .anchor(Align2::CENTER_CENTER, Vec2::ZERO)
+            .show(ctx, |ui| {
+                let Some(channels) = &search.channels else {
+                    ui.add(Spinner::new());
+                    return;
+                };
+
+                if channels.is_empty() {
+                    ui.label("no messages from this user");
+                    return;
+                }
+
+                Grid::new("user-search-channels")
+                    .num_columns(3)
+                    .striped(true)
+                    .show(ui, |ui| {
+                        for channel in channels {
+                            ui.label(&*channel.channel);
+                            ui.label(format!("{} messages", channel.count));
+                            if ui.small_button("history").clicked() {
+                                query.replace(HistoryQuery {
+                                    login: search.login.clone(),
+                                    channel: channel.channel.to_string(),
+                                    ..HistoryQuery::default()
+                                });
+                            }
+                            ui.end_row();
+                        }
+                    });
+            });
+
+        if !open || query.is_some() {
+            self.search.take();
+        }
+        query
+    }
+}
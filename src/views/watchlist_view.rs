@@ -0,0 +1,84 @@
+use std::cmp::Reverse;
+
+use egui::{Color32, RichText, Sense, Vec2};
+use indexmap::IndexSet;
+
+use crate::{
+    app::LiveShape,
+    runtime::{StreamCheck, UserMap},
+    twitch::ChannelName,
+};
+
+/// What was clicked in the watchlist
+pub enum WatchlistAction {
+    Join(ChannelName),
+    Remove(ChannelName),
+}
+
+/// A compact list of the watched channels, with the live ones first
+pub struct WatchlistView<'a> {
+    pub watchlist: &'a IndexSet<ChannelName>,
+    pub user_map: &'a UserMap,
+    pub stream_check: &'a StreamCheck,
+    pub live_shape: LiveShape,
+    pub live: Color32,
+}
+
+impl<'a> WatchlistView<'a> {
+    pub fn display(self, ctx: &egui::Context) -> Option<WatchlistAction> {
+        let mut entries = self
+            .watchlist
+            .iter()
+            .map(|name| {
+                let stream = self
+                    .user_map
+                    .try_get(name)
+                    .and_then(|user| self.stream_check.try_get(&user.id));
+                (name, stream)
+            })
+            .collect::<Vec<_>>();
+        // this is stable, so the offline ones keep the order they were added in
+        entries.sort_by_key(|(_, stream)| Reverse(stream.map(|stream| stream.viewer_count)));
+
+        let mut action = None;
+        egui::SidePanel::right("watchlist")
+            .resizable(false)
+            .show(ctx, |ui| {
+                ui.label(RichText::new("watching").small().weak());
+
+                for (name, stream) in entries {
+                    ui.horizontal(|ui| {
+                        let (rect, _) = ui.allocate_exact_size(Vec2::splat(8.0), Sense::hover());
+                        let resp = match stream {
+                            Some(stream) => {
+                                self.live_shape.paint(
+                                    ui.painter(),
+                                    rect,
+                                    self.live,
+                                    (1.0, Color32::BLACK),
+                                );
+                                ui.label(&**name).on_hover_text(format!(
+                                    "{} viewers\n{}",
+                                    stream.viewer_count, stream.title
+                                ))
+                            }
+                            None => ui.label(RichText::new(&**name).weak()),
+                        };
+
+                        resp.context_menu(|ui| {
+                            if ui.button("join").clicked() {
+                                action.replace(WatchlistAction::Join(name.clone()));
+                                ui.close_menu();
+                            }
+                            if ui.button("stop watching").clicked() {
+                                action.replace(WatchlistAction::Remove(name.clone()));
+                                ui.close_menu();
+                            }
+                        });
+                    });
+                }
+            });
+
+        action
+    }
+}
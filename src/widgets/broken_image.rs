@@ -0,0 +1,24 @@
+use egui::{Rounding, Sense, Stroke, Vec2};
+
+pub struct BrokenImage {
+    pub size: Vec2,
+}
+
+impl BrokenImage {
+    pub fn display(self, ui: &mut egui::Ui) -> egui::Response {
+        let (rect, resp) = ui.allocate_exact_size(self.size, Sense::click());
+        if !ui.is_rect_visible(rect) {
+            return resp;
+        }
+
+        let stroke = Stroke::new(1.0, ui.visuals().weak_text_color());
+        let rect = rect.shrink(1.0);
+        ui.painter().rect_stroke(rect, Rounding::same(2.0), stroke);
+        ui.painter()
+            .line_segment([rect.left_top(), rect.right_bottom()], stroke);
+        ui.painter()
+            .line_segment([rect.right_top(), rect.left_bottom()], stroke);
+
+        resp
+    }
+}
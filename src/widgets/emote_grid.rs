@@ -0,0 +1,39 @@
+use egui::{Button, RichText, Sense, Vec2};
+
+use crate::runtime::{EmoteMap, ImageCache};
+
+/// Emotes laid out in wrapping rows, returns the name of the clicked one
+pub struct EmoteGrid<'a> {
+    /// The id and name of each emote
+    pub emotes: &'a [(&'a str, &'a str)],
+    pub size: f32,
+}
+
+impl<'a> EmoteGrid<'a> {
+    pub fn display(
+        self,
+        ui: &mut egui::Ui,
+        emote_map: &EmoteMap,
+        cache: &mut ImageCache,
+    ) -> Option<String> {
+        let mut clicked = None;
+        ui.horizontal_wrapped(|ui| {
+            for &(id, name) in self.emotes {
+                let image = emote_map
+                    .get_emote_url(id)
+                    .and_then(|url| cache.get_image(url))
+                    .map(|image| image.as_egui_image(Vec2::splat(self.size), ui.ctx().frame_nr()));
+
+                // the name stands in until the image shows up
+                let resp = match image {
+                    Some(image) => ui.add(image.sense(Sense::click())),
+                    None => ui.add(Button::new(RichText::new(name).small()).frame(false)),
+                };
+                if resp.on_hover_text(name).clicked() {
+                    clicked.replace(name.to_string());
+                }
+            }
+        });
+        clicked
+    }
+}
@@ -1,2 +1,11 @@
 mod progress;
 pub use progress::Progress;
+
+mod broken_image;
+pub use broken_image::BrokenImage;
+
+mod search_results;
+pub use search_results::SearchResults;
+
+mod emote_grid;
+pub use emote_grid::EmoteGrid;
@@ -0,0 +1,35 @@
+use egui::{Color32, RichText};
+
+use crate::state::ChannelSearch;
+
+/// The results of a [`ChannelSearch`], returns the login of the clicked one
+pub struct SearchResults<'a> {
+    pub search: &'a ChannelSearch,
+    pub live: Color32,
+}
+
+impl<'a> SearchResults<'a> {
+    pub fn display(self, ui: &mut egui::Ui) -> Option<String> {
+        let mut clicked = None;
+        for result in &self.search.results {
+            let resp = ui
+                .horizontal(|ui| {
+                    if result.is_live {
+                        ui.label(RichText::new("live").small().color(self.live));
+                    }
+                    let resp = ui.link(&result.broadcaster_login);
+                    if let Some(viewers) = self.search.viewers(&result.id) {
+                        ui.label(RichText::new(format!("{viewers} viewers")).small().weak());
+                    }
+                    resp
+                })
+                .inner
+                .on_hover_text(&result.title);
+
+            if resp.clicked() {
+                clicked.replace(result.broadcaster_login.clone());
+            }
+        }
+        clicked
+    }
+}